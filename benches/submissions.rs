@@ -0,0 +1,78 @@
+use chrono::{Duration, Utc};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use murahdahla::{
+    discord::{
+        submission_parser::parse_variable_time,
+        submissions::{build_submission, leaderboard_text, RunTime, Submission},
+    },
+    games::{AsyncRaceData, GameName},
+    testing,
+};
+
+fn test_race(game: GameName) -> AsyncRaceData {
+    AsyncRaceData {
+        race_active: true,
+        race_game: game,
+        race_info: String::from("Standard Open Ganon 7/7 Defeat Ganon"),
+        ..testing::test_race()
+    }
+}
+
+fn test_leaderboard(size: u32) -> Vec<Submission> {
+    let now = Utc::now().naive_utc();
+    (0..size)
+        .map(|i| Submission {
+            submission_id: i,
+            runner_id: u64::from(i),
+            // a few recent submissions so some lines get highlighted
+            submission_datetime: now - Duration::minutes(i64::from(i) * 10),
+            runner_name: format!("runner{}", i),
+            runner_time: Some(RunTime::from_millis(5_400_000 + u64::from(i) * 1000)),
+            runner_collection: Some(216),
+            ..testing::test_submission()
+        })
+        .collect()
+}
+
+fn bench_parse_variable_time(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_variable_time");
+    for time in ["45", "1:23", "1:23:45"] {
+        group.bench_function(time, |b| b.iter(|| parse_variable_time(black_box(time))));
+    }
+    group.finish();
+}
+
+fn bench_build_submission(c: &mut Criterion) {
+    let z3r_race = test_race(GameName::ALTTPR);
+    let other_race = test_race(GameName::Other);
+    c.bench_function("build_submission alttpr", |b| {
+        b.iter(|| build_submission(1, "runner", black_box("1:23:45 216"), &z3r_race, &[], None))
+    });
+    c.bench_function("build_submission other", |b| {
+        b.iter(|| build_submission(1, "runner", black_box("1:23:45"), &other_race, &[], None))
+    });
+    c.bench_function("build_submission forfeit", |b| {
+        b.iter(|| build_submission(1, "runner", black_box("ff"), &other_race, &[], None))
+    });
+}
+
+fn bench_leaderboard_text(c: &mut Criterion) {
+    let cutoff = Some(Utc::now().naive_utc() - Duration::hours(6));
+    let mut group = c.benchmark_group("leaderboard_text");
+    for size in [10, 100, 500] {
+        let leaderboard = test_leaderboard(size);
+        group.bench_function(size.to_string(), |b| {
+            b.iter(|| leaderboard_text("Leaderboard", black_box(&leaderboard), cutoff))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse_variable_time,
+    bench_build_submission,
+    bench_leaderboard_text
+);
+criterion_main!(benches);
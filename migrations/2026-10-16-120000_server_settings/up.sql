@@ -0,0 +1,9 @@
+CREATE TABLE server_settings(
+    server_id BIGINT(20) UNSIGNED NOT NULL,
+    setting_key VARCHAR(64) NOT NULL,
+    setting_value TEXT NOT NULL,
+    PRIMARY KEY (server_id, setting_key),
+    FOREIGN KEY (server_id)
+        REFERENCES servers(server_id)
+        ON DELETE CASCADE
+);
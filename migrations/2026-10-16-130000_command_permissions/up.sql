@@ -0,0 +1,9 @@
+CREATE TABLE command_permissions(
+    server_id BIGINT(20) UNSIGNED NOT NULL,
+    command_name VARCHAR(32) NOT NULL,
+    role_id BIGINT(20) UNSIGNED NOT NULL,
+    PRIMARY KEY (server_id, command_name, role_id),
+    FOREIGN KEY (server_id)
+        REFERENCES servers(server_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE channels
+    DROP COLUMN ascii_names,
+    DROP COLUMN max_name_length;
@@ -0,0 +1,3 @@
+ALTER TABLE channels
+    ADD COLUMN ascii_names TINYINT(1) NOT NULL DEFAULT 0,
+    ADD COLUMN max_name_length SMALLINT(3) UNSIGNED;
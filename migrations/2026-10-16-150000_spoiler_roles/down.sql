@@ -0,0 +1 @@
+DROP TABLE spoiler_roles;
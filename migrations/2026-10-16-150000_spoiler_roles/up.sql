@@ -0,0 +1,10 @@
+CREATE TABLE spoiler_roles(
+    channel_group_id BINARY(16) NOT NULL,
+    role_id BIGINT(20) UNSIGNED NOT NULL,
+    race_game TINYTEXT,
+    division_role_id BIGINT(20) UNSIGNED,
+    PRIMARY KEY (channel_group_id, role_id),
+    FOREIGN KEY (channel_group_id)
+        REFERENCES channels(channel_group_id)
+        ON DELETE CASCADE
+);
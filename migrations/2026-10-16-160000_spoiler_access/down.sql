@@ -0,0 +1,11 @@
+-- Overwrite mode groups have no spoiler role and can't be kept, so refuse to roll back
+-- while any exist. The second insert fails with a duplicate key if there are any.
+DROP TEMPORARY TABLE IF EXISTS remove_overwrite_groups_before_rollback;
+CREATE TEMPORARY TABLE remove_overwrite_groups_before_rollback (id INT PRIMARY KEY);
+INSERT INTO remove_overwrite_groups_before_rollback VALUES (0);
+INSERT INTO remove_overwrite_groups_before_rollback
+    SELECT 0 FROM channels WHERE spoiler_role_id IS NULL LIMIT 1;
+DROP TEMPORARY TABLE remove_overwrite_groups_before_rollback;
+ALTER TABLE channels
+    MODIFY spoiler_role_id BIGINT(20) UNSIGNED NOT NULL,
+    DROP COLUMN spoiler_access;
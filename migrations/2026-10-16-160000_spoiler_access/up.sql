@@ -0,0 +1,3 @@
+ALTER TABLE channels
+    MODIFY spoiler_role_id BIGINT(20) UNSIGNED,
+    ADD COLUMN spoiler_access TINYTEXT NOT NULL DEFAULT 'role';
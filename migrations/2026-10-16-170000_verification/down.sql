@@ -0,0 +1,5 @@
+ALTER TABLE submissions
+    DROP COLUMN pending;
+ALTER TABLE channels
+    DROP COLUMN verify_under,
+    DROP COLUMN verification_channel;
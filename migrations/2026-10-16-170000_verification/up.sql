@@ -0,0 +1,5 @@
+ALTER TABLE channels
+    ADD COLUMN verify_under TIME,
+    ADD COLUMN verification_channel BIGINT(20) UNSIGNED;
+ALTER TABLE submissions
+    ADD COLUMN pending TINYINT(1) NOT NULL DEFAULT 0;
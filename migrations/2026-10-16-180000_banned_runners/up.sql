@@ -0,0 +1,8 @@
+CREATE TABLE banned_runners(
+    channel_group_id BINARY(16) NOT NULL,
+    runner_id BIGINT(20) UNSIGNED NOT NULL,
+    PRIMARY KEY (channel_group_id, runner_id),
+    FOREIGN KEY (channel_group_id)
+        REFERENCES channels(channel_group_id)
+        ON DELETE CASCADE
+);
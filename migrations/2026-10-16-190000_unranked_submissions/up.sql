@@ -0,0 +1,2 @@
+ALTER TABLE submissions
+    ADD COLUMN unranked TINYINT(1) NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    ADD COLUMN race_role_id BIGINT(20) UNSIGNED;
@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    DROP COLUMN stop_pending;
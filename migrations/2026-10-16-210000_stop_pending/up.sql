@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    ADD COLUMN stop_pending TINYTEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE submission_cursors(
+    channel_group_id BINARY(16) PRIMARY KEY,
+    last_message_id BIGINT(20) UNSIGNED NOT NULL,
+    FOREIGN KEY (channel_group_id)
+        REFERENCES channels(channel_group_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+DROP INDEX async_races_active ON async_races;
+ALTER TABLE async_races
+    DROP COLUMN race_deadline;
@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    ADD COLUMN race_deadline DATETIME;
+CREATE INDEX async_races_active ON async_races (channel_group_id, race_active);
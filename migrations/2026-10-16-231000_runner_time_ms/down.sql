@@ -0,0 +1,2 @@
+ALTER TABLE submissions
+    DROP COLUMN runner_time_ms;
@@ -0,0 +1,5 @@
+ALTER TABLE submissions
+    ADD COLUMN runner_time_ms BIGINT UNSIGNED;
+UPDATE submissions
+    SET runner_time_ms = TIME_TO_SEC(runner_time) * 1000
+    WHERE runner_time IS NOT NULL;
@@ -0,0 +1,13 @@
+ALTER TABLE submissions
+    ADD COLUMN runner_time TIME AFTER runner_name;
+UPDATE submissions
+    SET runner_time = SEC_TO_TIME(runner_time_ms DIV 1000)
+    WHERE runner_time_ms < 86400000;
+
+ALTER TABLE channels
+    ADD COLUMN verify_under TIME AFTER verify_under_ms;
+UPDATE channels
+    SET verify_under = SEC_TO_TIME(verify_under_ms DIV 1000)
+    WHERE verify_under_ms < 86400000;
+ALTER TABLE channels
+    DROP COLUMN verify_under_ms;
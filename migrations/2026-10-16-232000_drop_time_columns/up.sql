@@ -0,0 +1,13 @@
+UPDATE submissions
+    SET runner_time_ms = TIME_TO_SEC(runner_time) * 1000
+    WHERE runner_time_ms IS NULL AND runner_time IS NOT NULL;
+ALTER TABLE submissions
+    DROP COLUMN runner_time;
+
+ALTER TABLE channels
+    ADD COLUMN verify_under_ms BIGINT UNSIGNED AFTER verify_under;
+UPDATE channels
+    SET verify_under_ms = TIME_TO_SEC(verify_under) * 1000
+    WHERE verify_under IS NOT NULL;
+ALTER TABLE channels
+    DROP COLUMN verify_under;
@@ -0,0 +1,3 @@
+ALTER TABLE channels
+    DROP COLUMN show_flair;
+DROP TABLE users;
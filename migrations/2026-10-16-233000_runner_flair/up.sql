@@ -0,0 +1,6 @@
+CREATE TABLE users(
+    user_id BIGINT(20) UNSIGNED PRIMARY KEY,
+    flair VARCHAR(32) NOT NULL
+);
+ALTER TABLE channels
+    ADD COLUMN show_flair TINYINT(1) NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    ADD COLUMN race_sort TINYTEXT NOT NULL DEFAULT 'time';
@@ -0,0 +1,11 @@
+CREATE TABLE submission_notes(
+    note_id INT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    submission_id INT UNSIGNED NOT NULL,
+    author_id BIGINT(20) UNSIGNED NOT NULL,
+    author_name VARCHAR(32) NOT NULL,
+    note_datetime DATETIME NOT NULL,
+    note_text VARCHAR(500) NOT NULL,
+    FOREIGN KEY (submission_id)
+        REFERENCES submissions(submission_id)
+        ON DELETE CASCADE
+);
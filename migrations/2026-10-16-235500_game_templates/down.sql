@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    DROP COLUMN race_fields;
+DROP TABLE game_templates;
@@ -0,0 +1,12 @@
+CREATE TABLE game_templates(
+    server_id BIGINT(20) UNSIGNED NOT NULL,
+    template_name VARCHAR(32) NOT NULL,
+    template_fields TINYTEXT NOT NULL,
+    template_format TEXT NOT NULL,
+    PRIMARY KEY (server_id, template_name),
+    FOREIGN KEY (server_id)
+        REFERENCES servers(server_id)
+        ON DELETE CASCADE
+);
+ALTER TABLE async_races
+    ADD COLUMN race_fields TINYTEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE channels
+    ADD COLUMN forfeit_words TEXT;
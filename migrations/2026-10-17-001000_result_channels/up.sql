@@ -0,0 +1,9 @@
+CREATE TABLE result_channels(
+    channel_group_id BINARY(16) NOT NULL,
+    channel_id BIGINT(20) UNSIGNED NOT NULL,
+    top_places SMALLINT(5) UNSIGNED,
+    PRIMARY KEY (channel_group_id, channel_id),
+    FOREIGN KEY (channel_group_id)
+        REFERENCES channels(channel_group_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+ALTER TABLE channels
+    ADD COLUMN archive_channel BIGINT(20) UNSIGNED;
@@ -0,0 +1,9 @@
+CREATE TABLE command_aliases(
+    server_id BIGINT(20) UNSIGNED NOT NULL,
+    alias VARCHAR(16) NOT NULL,
+    command_name VARCHAR(32) NOT NULL,
+    PRIMARY KEY (server_id, alias),
+    FOREIGN KEY (server_id)
+        REFERENCES servers(server_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    ADD COLUMN race_blind BOOL NOT NULL DEFAULT FALSE;
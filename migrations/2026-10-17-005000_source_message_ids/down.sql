@@ -0,0 +1,6 @@
+ALTER TABLE async_races
+    DROP INDEX async_races_source_message,
+    DROP COLUMN source_message_id;
+ALTER TABLE submissions
+    DROP INDEX submissions_source_message,
+    DROP COLUMN source_message_id;
@@ -0,0 +1,6 @@
+ALTER TABLE submissions
+    ADD COLUMN source_message_id BIGINT UNSIGNED NULL,
+    ADD UNIQUE INDEX submissions_source_message (source_message_id);
+ALTER TABLE async_races
+    ADD COLUMN source_message_id BIGINT UNSIGNED NULL,
+    ADD UNIQUE INDEX async_races_source_message (source_message_id);
@@ -0,0 +1,5 @@
+ALTER TABLE channels
+    DROP COLUMN embed_color,
+    DROP COLUMN embed_thumbnail,
+    DROP COLUMN embed_banner,
+    DROP COLUMN embed_footer;
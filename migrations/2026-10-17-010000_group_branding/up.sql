@@ -0,0 +1,5 @@
+ALTER TABLE channels
+    ADD COLUMN embed_color INT UNSIGNED,
+    ADD COLUMN embed_thumbnail TEXT,
+    ADD COLUMN embed_banner TEXT,
+    ADD COLUMN embed_footer TEXT;
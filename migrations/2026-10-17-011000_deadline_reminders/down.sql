@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    DROP COLUMN race_reminders,
+    DROP COLUMN last_reminder_at;
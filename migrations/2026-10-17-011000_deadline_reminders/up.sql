@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    ADD COLUMN race_reminders BOOL NOT NULL DEFAULT TRUE,
+    ADD COLUMN last_reminder_at DATETIME;
@@ -0,0 +1,3 @@
+DROP TABLE seed_claims;
+ALTER TABLE async_races
+    DROP COLUMN race_claim;
@@ -0,0 +1,11 @@
+ALTER TABLE async_races
+    ADD COLUMN race_claim BOOL NOT NULL DEFAULT FALSE;
+CREATE TABLE seed_claims(
+    race_id INT UNSIGNED NOT NULL,
+    runner_id BIGINT(20) UNSIGNED NOT NULL,
+    claimed_at DATETIME NOT NULL,
+    PRIMARY KEY (race_id, runner_id),
+    FOREIGN KEY (race_id)
+        REFERENCES async_races(race_id)
+        ON DELETE CASCADE
+);
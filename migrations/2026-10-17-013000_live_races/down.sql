@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    DROP COLUMN race_live_at,
+    DROP COLUMN live_countdown_sent;
@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    ADD COLUMN race_live_at DATETIME,
+    ADD COLUMN live_countdown_sent BOOL NOT NULL DEFAULT FALSE;
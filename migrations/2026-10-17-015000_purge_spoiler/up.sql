@@ -0,0 +1,2 @@
+ALTER TABLE channels
+    ADD COLUMN purge_spoiler TINYINT(1) NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+CREATE TABLE custom_games(
+    server_id BIGINT(20) UNSIGNED NOT NULL,
+    game_name VARCHAR(32) NOT NULL,
+    game_definition TEXT NOT NULL,
+    PRIMARY KEY (server_id, game_name),
+    FOREIGN KEY (server_id)
+        REFERENCES servers(server_id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+ALTER TABLE channels
+    DROP COLUMN max_settings_length;
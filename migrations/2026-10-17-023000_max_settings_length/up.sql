@@ -0,0 +1,2 @@
+ALTER TABLE channels
+    ADD COLUMN max_settings_length SMALLINT UNSIGNED;
@@ -0,0 +1,3 @@
+ALTER TABLE async_races
+    DROP COLUMN race_preset;
+DROP TABLE presets;
@@ -0,0 +1,12 @@
+CREATE TABLE presets(
+    channel_group_id BINARY(16) NOT NULL,
+    preset_name VARCHAR(32) NOT NULL,
+    preset_game TINYTEXT NOT NULL,
+    preset_settings TEXT NOT NULL,
+    PRIMARY KEY (channel_group_id, preset_name),
+    FOREIGN KEY (channel_group_id)
+        REFERENCES channels(channel_group_id)
+        ON DELETE CASCADE
+);
+ALTER TABLE async_races
+    ADD COLUMN race_preset TINYTEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    DROP COLUMN race_shuffle;
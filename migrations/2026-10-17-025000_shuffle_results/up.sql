@@ -0,0 +1,2 @@
+ALTER TABLE async_races
+    ADD COLUMN race_shuffle BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use anyhow::Result;
+use diesel::prelude::*;
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup,
+        submissions::{build_submission, NewSubmission, RunTime, Submission},
+    },
+    games::AsyncRaceData,
+    helpers::*,
+};
+
+// the parts of a submission that come from parsing its message. pending isn't here
+// since that's decided after parsing.
+#[derive(Debug, PartialEq)]
+struct ParsedFields {
+    time: Option<RunTime>,
+    collection: Option<u16>,
+    number: Option<u32>,
+    text: Option<String>,
+    forfeit: bool,
+    unranked: bool,
+}
+
+impl From<&Submission> for ParsedFields {
+    fn from(s: &Submission) -> Self {
+        ParsedFields {
+            time: s.runner_time,
+            collection: s.runner_collection,
+            number: s.option_number,
+            text: s.option_text.clone(),
+            forfeit: s.runner_forfeit,
+            unranked: s.unranked,
+        }
+    }
+}
+
+impl From<&NewSubmission> for ParsedFields {
+    fn from(s: &NewSubmission) -> Self {
+        ParsedFields {
+            time: s.runner_time,
+            collection: s.runner_collection,
+            number: s.option_number,
+            text: s.option_text.clone(),
+            forfeit: s.runner_forfeit,
+            unranked: s.unranked,
+        }
+    }
+}
+
+impl fmt::Display for ParsedFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.forfeit, self.time) {
+            (true, _) => write!(f, "forfeit")?,
+            (false, Some(t)) => write!(f, "{}", t)?,
+            (false, None) => write!(f, "no time")?,
+        }
+        if let Some(c) = self.collection {
+            write!(f, ", collection {}", c)?;
+        }
+        if let Some(n) = self.number {
+            write!(f, ", score {}", n)?;
+        }
+        if let Some(t) = &self.text {
+            write!(f, ", fields \"{}\"", t)?;
+        }
+        if self.unranked {
+            write!(f, ", unranked")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ReparseChange {
+    pub submission_id: u32,
+    pub race_id: u32,
+    pub runner_name: String,
+    pub submission_text: String,
+    pub stored: String,
+    // what the parser makes of the message now, or why it rejects it
+    pub reparsed: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ReparseReport {
+    pub checked: usize,
+    // submissions from before message text was kept, which can't be checked
+    pub skipped: usize,
+    pub changes: Vec<ReparseChange>,
+}
+
+impl fmt::Display for ReparseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Checked {} submissions, skipped {} without their message text. {} would parse differently now.",
+            self.checked,
+            self.skipped,
+            self.changes.len()
+        )?;
+        for c in self.changes.iter() {
+            writeln!(
+                f,
+                "\nSubmission {} in race {} from \"{}\": \"{}\"",
+                c.submission_id, c.race_id, c.runner_name, c.submission_text
+            )?;
+            writeln!(f, "    stored:   {}", c.stored)?;
+            writeln!(f, "    reparsed: {}", c.reparsed)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn reparse_audit(conn: &PooledConn, this_server_id: u64) -> Result<ReparseReport> {
+    // runs every submission on a server that still has its message text back through
+    // the submission parser and lists the ones that come out differently, so mods can
+    // review them after a parser fix. nothing is changed. submissions a mod has edited
+    // since they were sent show up here too.
+    use crate::schema::async_races::columns::race_id;
+    use crate::schema::channels::columns::server_id;
+    use crate::schema::channels::dsl::channels;
+
+    let groups: Vec<ChannelGroup> = channels.filter(server_id.eq(this_server_id)).load(conn)?;
+    let mut report = ReparseReport::default();
+    for group in &groups {
+        let races: Vec<AsyncRaceData> = AsyncRaceData::belonging_to(group)
+            .order(race_id.asc())
+            .load(conn)?;
+        let submissions: Vec<Vec<Submission>> = Submission::belonging_to(&races)
+            .load::<Submission>(conn)?
+            .grouped_by(&races);
+        for (race, subs) in races.iter().zip(submissions) {
+            for s in subs.iter() {
+                match s.submission_text {
+                    Some(_) => report.checked += 1,
+                    None => {
+                        report.skipped += 1;
+                        continue;
+                    }
+                }
+                if let Some(c) = reparse(s, race, &group.forfeit_words()) {
+                    report.changes.push(c);
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn reparse(s: &Submission, race: &AsyncRaceData, forfeit_words: &[&str]) -> Option<ReparseChange> {
+    let text = s.submission_text.as_deref()?;
+    let stored = ParsedFields::from(s);
+    // the stored time stands in for a claim or !startrun timer. it's only used when the
+    // message didn't have a time in it, which is when it was used the first time too.
+    let reparsed = build_submission(
+        s.runner_id,
+        &s.runner_name,
+        text,
+        race,
+        forfeit_words,
+        s.runner_time,
+    );
+    let reparsed = match reparsed {
+        Ok(n) if ParsedFields::from(&n) == stored => return None,
+        Ok(n) => ParsedFields::from(&n).to_string(),
+        Err(e) => format!("rejected ({})", e),
+    };
+
+    Some(ReparseChange {
+        submission_id: s.submission_id,
+        race_id: s.race_id,
+        runner_name: s.runner_name.clone(),
+        submission_text: text.to_owned(),
+        stored: stored.to_string(),
+        reparsed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{test_race, test_submission};
+
+    fn submission(time_ms: u64, collection: u16, text: &str) -> Submission {
+        Submission {
+            submission_id: 7,
+            runner_id: 1,
+            runner_collection: Some(collection),
+            runner_time: Some(RunTime::from_millis(time_ms)),
+            source_message_id: Some(1),
+            submission_text: Some(text.to_owned()),
+            ..test_submission()
+        }
+    }
+
+    #[test]
+    fn only_changed_submissions_are_reported() {
+        let race = test_race();
+        assert!(reparse(&submission(5_025_000, 216, "1:23:45 216"), &race, &[]).is_none());
+
+        // stored as 1:23:45 by a parser that dropped the fraction
+        let change = reparse(&submission(5_025_000, 216, "1:23:45.5 216"), &race, &[]).unwrap();
+        assert_eq!(change.stored, "01:23:45, collection 216");
+        assert_eq!(change.reparsed, "01:23:45.500, collection 216");
+
+        let change = reparse(&submission(5_025_000, 216, "1:23:45 217"), &race, &[]).unwrap();
+        assert!(change.reparsed.starts_with("rejected"));
+    }
+}
@@ -0,0 +1,320 @@
+use std::{collections::HashMap, env, fs, io::ErrorKind, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::Duration;
+use serde::Deserialize;
+use serde_json::Value;
+use serenity::{client::Context, prelude::TypeMapKey};
+
+use crate::games::{parse_deadline, registry::provider, GameName};
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+// any of the environment variables below can instead name a file to read it from, e.g.
+// DATABASE_URL_FILE=/run/secrets/database_url for docker and kubernetes secrets
+const FILE_SUFFIX: &str = "_FILE";
+const TOKEN_VAR: &str = "MURAHDAHLA_DISCORD_TOKEN";
+const DATABASE_URL_VAR: &str = "DATABASE_URL";
+const MAINTENANCE_USER_VAR: &str = "MAINTENANCE_USER";
+const REDIS_URL_VAR: &str = "REDIS_URL";
+const ERROR_WEBHOOK_VAR: &str = "ERROR_WEBHOOK_URL";
+
+pub struct ConfigContainer;
+
+impl TypeMapKey for ConfigContainer {
+    type Value = BotConfig;
+}
+
+// what happens to messages in a submission channel after the bot has looked at them
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessagePolicy {
+    // delete everything including commands and messages that aren't submissions
+    DeleteAll,
+    // delete commands and submissions but leave messages that don't parse as submissions
+    KeepInvalid,
+}
+
+impl FromStr for MessagePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "delete_all" => Ok(MessagePolicy::DeleteAll),
+            "keep_invalid" => Ok(MessagePolicy::KeepInvalid),
+            x => Err(anyhow!("Unrecognized message policy: {}", x)),
+        }
+    }
+}
+
+// settings sent to a seed site's generator by !roll
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RollPreset {
+    pub game: String,
+    pub settings: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    // submissions newer than this are italicized in the leaderboard channel
+    pub leaderboard_highlight_secs: i64,
+    // how often to rebuild the leaderboards of active races. 0 turns this off.
+    pub leaderboard_refresh_secs: u64,
+    // how often a running instance checks it still holds the leader lock, and how often
+    // a standby tries to take it
+    pub leader_check_secs: u64,
+    // how many leaderboards can be rebuilt at once. only read at startup.
+    pub max_concurrent_leaderboard_builds: usize,
+    pub max_groups_per_server: usize,
+    // overrides the group limit for specific servers, keyed by server id
+    pub server_group_limits: HashMap<String, usize>,
+    pub submission_message_policy: MessagePolicy,
+    // how often one user can start races: a delay between starts and a limit on how
+    // many over a longer span
+    pub start_race_delay_secs: u64,
+    pub start_race_limit: u32,
+    pub start_race_limit_secs: u64,
+    // how many tasks process submissions. a channel's messages are always handled by the
+    // same one. only read at startup.
+    pub submission_workers: usize,
+    // each runner can send this many messages to submission channels in the window
+    // before the rest are ignored. 0 turns this off.
+    pub submission_attempt_limit: u32,
+    pub submission_attempt_window_secs: u64,
+    // how long before a race's deadline to remind runners, written like a deadline
+    // (e.g. "24h"). empty turns reminders off.
+    pub deadline_reminders: Vec<String>,
+    // a seed site's api is skipped for api_breaker_cooldown_secs after failing this many
+    // times in a row. 0 turns this off.
+    pub api_breaker_failures: u32,
+    pub api_breaker_cooldown_secs: u64,
+    // how often to log each seed site's latency and errors. 0 turns this off.
+    pub api_stats_log_secs: u64,
+    // the presets !roll can generate seeds with, keyed by the name used in the command
+    pub roll_presets: HashMap<String, RollPreset>,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            leaderboard_highlight_secs: 21600,
+            leaderboard_refresh_secs: 0,
+            leader_check_secs: 5,
+            max_concurrent_leaderboard_builds: 4,
+            max_groups_per_server: 10,
+            server_group_limits: HashMap::new(),
+            submission_message_policy: MessagePolicy::DeleteAll,
+            start_race_delay_secs: 5,
+            start_race_limit: 5,
+            start_race_limit_secs: 60,
+            submission_workers: 4,
+            submission_attempt_limit: 3,
+            submission_attempt_window_secs: 60,
+            deadline_reminders: vec!["24h".to_owned(), "1h".to_owned()],
+            api_breaker_failures: 3,
+            api_breaker_cooldown_secs: 300,
+            api_stats_log_secs: 3600,
+            roll_presets: HashMap::new(),
+        }
+    }
+}
+
+impl BotConfig {
+    pub fn load() -> Result<Self> {
+        // the config file is optional. if there isn't one we just use the defaults
+        let path = env::var("MURAHDAHLA_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_owned());
+        let config_str = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("No config file found at \"{}\", using defaults", &path);
+                return Ok(BotConfig::default());
+            }
+            Err(e) => return Err(anyhow!("Error reading config file \"{}\": {}", &path, e)),
+        };
+        let config: BotConfig = toml::from_str(&config_str)
+            .map_err(|e| anyhow!("Error parsing config file \"{}\": {}", &path, e))?;
+        let problems = config.problems();
+        if !problems.is_empty() {
+            return Err(anyhow!(
+                "Invalid config file \"{}\": {}",
+                &path,
+                problems.join("; ")
+            ));
+        }
+
+        Ok(config)
+    }
+
+    fn problems(&self) -> Vec<String> {
+        // values that parse fine but would leave the bot unable to do something
+        let mut problems: Vec<String> = Vec::new();
+        if self.max_concurrent_leaderboard_builds == 0 {
+            problems.push("max_concurrent_leaderboard_builds must be at least 1".to_owned());
+        }
+        if self.leader_check_secs == 0 {
+            problems.push("leader_check_secs must be at least 1".to_owned());
+        }
+        if self.submission_workers == 0 {
+            problems.push("submission_workers must be at least 1".to_owned());
+        }
+        if self.submission_attempt_limit > 0 && self.submission_attempt_window_secs == 0 {
+            problems.push("submission_attempt_window_secs must be at least 1".to_owned());
+        }
+        if self.start_race_limit == 0 {
+            problems.push("start_race_limit must be at least 1".to_owned());
+        }
+        for r in self.deadline_reminders.iter() {
+            if let Err(e) = parse_deadline(r) {
+                problems.push(format!("deadline_reminders: {}", e));
+            }
+        }
+        self.server_group_limits
+            .keys()
+            .filter(|k| k.parse::<u64>().is_err())
+            .for_each(|k| {
+                problems.push(format!(
+                    "server_group_limits key \"{}\" isn't a server id",
+                    k
+                ))
+            });
+        for (name, preset) in self.roll_presets.iter() {
+            match preset.game.parse::<GameName>() {
+                Ok(g) if provider(g).rolls_seeds() => (),
+                Ok(_) => problems.push(format!(
+                    "roll_presets.{}: the bot can't roll {} seeds",
+                    name, preset.game
+                )),
+                Err(e) => problems.push(format!("roll_presets.{}: {}", name, e)),
+            }
+            if !preset.settings.is_object() {
+                problems.push(format!("roll_presets.{}: settings must be a table", name));
+            }
+        }
+
+        problems
+    }
+
+    pub fn deadline_reminders(&self) -> Vec<Duration> {
+        // already checked when the config was loaded
+        self.deadline_reminders
+            .iter()
+            .filter_map(|r| parse_deadline(r).ok())
+            .collect()
+    }
+
+    pub fn roll_preset(&self, name: &str) -> Option<&RollPreset> {
+        self.roll_presets
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn max_groups_for(&self, server_id: u64) -> usize {
+        self.server_group_limits
+            .get(&server_id.to_string())
+            .copied()
+            .unwrap_or(self.max_groups_per_server)
+    }
+}
+
+// the settings that come from the environment rather than config.toml
+#[derive(Debug, Clone)]
+pub struct Environment {
+    pub discord_token: String,
+    pub database_url: String,
+    pub maintenance_user: u64,
+    // share groups, servers and submission channels between instances through redis.
+    // each instance only keeps them in memory when this isn't set.
+    pub redis_url: Option<String>,
+    // command failures are posted here with their context when this is set
+    pub error_webhook_url: Option<String>,
+}
+
+impl Environment {
+    pub fn load() -> Result<Self> {
+        let mut problems: Vec<String> = Vec::new();
+        let mut required = |name: &str| match read_env(name) {
+            Ok(Some(v)) if !v.is_empty() => Some(v),
+            Ok(_) => {
+                problems.push(format!("Expected {} in the environment", name));
+                None
+            }
+            Err(e) => {
+                problems.push(e.to_string());
+                None
+            }
+        };
+        let discord_token = required(TOKEN_VAR);
+        let database_url = required(DATABASE_URL_VAR);
+        let maintenance_user = required(MAINTENANCE_USER_VAR);
+        let maintenance_user = match maintenance_user.map(|u| u.parse::<u64>()) {
+            Some(Ok(u)) => Some(u),
+            Some(Err(_)) => {
+                problems.push(format!(
+                    "{} must be a Discord user id",
+                    MAINTENANCE_USER_VAR
+                ));
+                None
+            }
+            None => None,
+        };
+        let mut optional = |name: &str| {
+            read_env(name)
+                .map_err(|e| problems.push(e.to_string()))
+                .ok()
+                .flatten()
+                .filter(|v| !v.is_empty())
+        };
+        let redis_url = optional(REDIS_URL_VAR);
+        let error_webhook_url = optional(ERROR_WEBHOOK_VAR);
+
+        match (discord_token, database_url, maintenance_user) {
+            (Some(discord_token), Some(database_url), Some(maintenance_user)) => Ok(Environment {
+                discord_token,
+                database_url,
+                maintenance_user,
+                redis_url,
+                error_webhook_url,
+            }),
+            _ => Err(anyhow!(problems.join("\n"))),
+        }
+    }
+}
+
+pub fn read_env(name: &str) -> Result<Option<String>> {
+    // NAME_FILE takes priority over NAME when both are set
+    let file_var = format!("{}{}", name, FILE_SUFFIX);
+    match env::var(&file_var) {
+        Ok(path) => fs::read_to_string(&path)
+            .map(|s| Some(s.trim().to_owned()))
+            .map_err(|e| anyhow!("Error reading {} \"{}\": {}", &file_var, &path, e)),
+        Err(_) => Ok(env::var(name).ok()),
+    }
+}
+
+pub fn load_startup_config() -> Result<(Environment, BotConfig)> {
+    // everything wrong with the environment and config file is reported together so a new
+    // deployment doesn't have to be fixed one restart at a time
+    match (Environment::load(), BotConfig::load()) {
+        (Ok(environment), Ok(config)) => Ok((environment, config)),
+        (environment, config) => {
+            let problems: Vec<String> = [environment.err(), config.err()]
+                .into_iter()
+                .flatten()
+                .map(|e| e.to_string())
+                .collect();
+            Err(anyhow!(
+                "The bot isn't configured correctly:\n{}",
+                problems.join("\n")
+            ))
+        }
+    }
+}
+
+pub async fn get_config(ctx: &Context) -> BotConfig {
+    let data = ctx.data.read().await;
+    data.get::<ConfigContainer>()
+        .expect("No config in share map")
+        .clone()
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, replace_into};
+use serenity::{
+    async_trait,
+    client::{Client, FullEvent},
+    framework::Framework,
+    model::{channel::Message, id::GuildId},
+    prelude::*,
+};
+
+use crate::{
+    discord::{
+        leader::is_leader,
+        server_settings::{get_setting, ServerSetting},
+    },
+    error::MurahdahlaError,
+    helpers::*,
+    schema::command_aliases,
+};
+
+pub const MAX_ALIAS_CHARS: usize = 16;
+const DEFAULT_PREFIX: &str = "!";
+
+#[derive(Debug, Clone, Insertable, Queryable)]
+#[table_name = "command_aliases"]
+pub struct CommandAlias {
+    pub server_id: u64,
+    pub alias: String,
+    pub command_name: String,
+}
+
+// alias -> the command it runs
+pub type ServerAliases = HashMap<String, String>;
+
+pub struct AliasContainer;
+
+impl TypeMapKey for AliasContainer {
+    type Value = HashMap<GuildId, ServerAliases>;
+}
+
+// the framework only knows the names commands are declared with, so we swap a server's
+// aliases for the real command before handing the message over. everything else
+// (checks, permissions, error handling) then works the same as if the user had typed the
+// full command.
+pub struct AliasFramework(pub poise::Framework<(), MurahdahlaError>);
+
+#[async_trait]
+impl Framework for AliasFramework {
+    async fn init(&mut self, client: &Client) {
+        self.0.init(client).await;
+    }
+
+    async fn dispatch(&self, ctx: Context, mut event: FullEvent) {
+        match &mut event {
+            // a standby instance ignores every command until it holds the leader lock. the
+            // other events still go through since the framework sets itself up on ready.
+            FullEvent::Message { .. } | FullEvent::InteractionCreate { .. } if !is_leader() => {
+                return;
+            }
+            FullEvent::Message { new_message } => {
+                if let Some(content) = expand_alias(&ctx, new_message).await {
+                    new_message.content = content;
+                }
+            }
+            _ => (),
+        };
+        self.0.dispatch(ctx, event).await;
+    }
+}
+
+pub fn clean_alias(alias: &str) -> Result<String> {
+    let alias = alias.trim().to_lowercase();
+    if alias.is_empty()
+        || alias.chars().count() > MAX_ALIAS_CHARS
+        || alias.contains(char::is_whitespace)
+    {
+        return Err(anyhow!(
+            "Aliases must be 1-{} characters with no whitespace",
+            MAX_ALIAS_CHARS
+        ));
+    }
+
+    Ok(alias)
+}
+
+pub fn expand_content(
+    content: &str,
+    prefix: Option<&str>,
+    aliases: &ServerAliases,
+) -> Option<String> {
+    let content = content.trim_start();
+    let used_prefix = match content.starts_with(DEFAULT_PREFIX) {
+        true => DEFAULT_PREFIX,
+        false => prefix.filter(|p| content.starts_with(p))?,
+    };
+    let invocation = &content[used_prefix.len()..];
+    let (name, rest) = match invocation.split_once(char::is_whitespace) {
+        Some((n, r)) => (n, Some(r)),
+        None => (invocation, None),
+    };
+    let command = aliases.get(&name.to_lowercase())?;
+
+    Some(match rest {
+        Some(r) => format!("{}{} {}", used_prefix, command, r),
+        None => format!("{}{}", used_prefix, command),
+    })
+}
+
+async fn expand_alias(ctx: &Context, msg: &Message) -> Option<String> {
+    let guild_id = msg.guild_id?;
+    let aliases = {
+        let data = ctx.data.read().await;
+        data.get::<AliasContainer>()
+            .expect("No alias container in share map")
+            .get(&guild_id)
+            .filter(|a| !a.is_empty())
+            .cloned()?
+    };
+    let prefix = get_setting(ctx, guild_id, ServerSetting::Prefix).await;
+
+    expand_content(&msg.content, prefix.as_deref(), &aliases)
+}
+
+pub fn get_aliases(conn: &PooledConn) -> Result<HashMap<GuildId, ServerAliases>> {
+    use crate::schema::command_aliases::dsl::*;
+
+    let rows: Vec<CommandAlias> = command_aliases.load(conn)?;
+    let mut alias_map: HashMap<GuildId, ServerAliases> = HashMap::new();
+    rows.into_iter().for_each(|a| {
+        alias_map
+            .entry(GuildId::from(a.server_id))
+            .or_default()
+            .insert(a.alias, a.command_name);
+    });
+
+    Ok(alias_map)
+}
+
+pub async fn set_alias(
+    ctx: &Context,
+    guild_id: GuildId,
+    alias_name: &str,
+    cmd_name: Option<&str>,
+) -> Result<()> {
+    use crate::schema::command_aliases::columns::*;
+
+    let conn = get_connection(ctx).await;
+    match cmd_name {
+        Some(c) => {
+            let new_alias = CommandAlias {
+                server_id: guild_id.get(),
+                alias: alias_name.to_owned(),
+                command_name: c.to_owned(),
+            };
+            replace_into(command_aliases::table)
+                .values(&new_alias)
+                .execute(&conn)?;
+        }
+        None => {
+            let removed = diesel::delete(
+                command_aliases::table
+                    .filter(server_id.eq(guild_id.get()))
+                    .filter(alias.eq(alias_name)),
+            )
+            .execute(&conn)?;
+            if removed == 0 {
+                return Err(anyhow!("No alias named \"{}\"", alias_name));
+            }
+        }
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let aliases = data
+            .get_mut::<AliasContainer>()
+            .expect("No alias container in share map")
+            .entry(guild_id)
+            .or_default();
+        match cmd_name {
+            Some(c) => aliases.insert(alias_name.to_owned(), c.to_owned()),
+            None => aliases.remove(alias_name),
+        };
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_are_expanded() {
+        let aliases: ServerAliases = [
+            ("s".to_owned(), "startigt".to_owned()),
+            ("lb".to_owned(), "refresh".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            expand_content("!s https://alttpr.com/h/abc", None, &aliases).as_deref(),
+            Some("!startigt https://alttpr.com/h/abc")
+        );
+        assert_eq!(
+            expand_content("?LB", Some("?"), &aliases).as_deref(),
+            Some("?refresh")
+        );
+        assert!(expand_content("!stop", None, &aliases).is_none());
+        assert!(expand_content("s 1:23:45", None, &aliases).is_none());
+        assert!(expand_content("?lb", None, &aliases).is_none());
+        assert!(clean_alias("two words").is_err());
+        assert_eq!(clean_alias(" LB ").unwrap(), "lb");
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use diesel::prelude::*;
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup,
+        submissions::{RunTime, Submission},
+    },
+    games::{AsyncRaceData, GameName},
+    helpers::*,
+    schema::{async_races, channels, submissions},
+};
+
+// how runners did on every race a server has run with the same settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsStats {
+    pub game: GameName,
+    pub settings: String,
+    pub races: usize,
+    pub finishes: usize,
+    pub forfeits: usize,
+    pub average: Option<RunTime>,
+}
+
+impl SettingsStats {
+    pub fn forfeit_rate(&self) -> f64 {
+        match self.finishes + self.forfeits {
+            0 => 0.0,
+            n => self.forfeits as f64 / n as f64,
+        }
+    }
+}
+
+pub fn load_server_history(
+    conn: &PooledConn,
+    this_server_id: u64,
+) -> Result<(Vec<AsyncRaceData>, Vec<Submission>)> {
+    // stopped races only, a running race's forfeit rate isn't settled yet
+    let races: Vec<AsyncRaceData> = async_races::table
+        .inner_join(channels::table)
+        .filter(channels::server_id.eq(this_server_id))
+        .filter(async_races::race_active.eq(false))
+        .select(async_races::all_columns)
+        .load(conn)?;
+    let race_ids: Vec<u32> = races.iter().map(|r| r.race_id).collect();
+    let submissions: Vec<Submission> = submissions::table
+        .filter(submissions::race_id.eq_any(race_ids))
+        .load(conn)?;
+
+    Ok((races, submissions))
+}
+
+pub fn settings_stats(races: &[AsyncRaceData], submissions: &[Submission]) -> Vec<SettingsStats> {
+    // races are grouped by game and settings string. unranked and unverified submissions
+    // don't count, same as on the leaderboard.
+    let mut race_settings: HashMap<u32, (GameName, &str)> = HashMap::new();
+    let mut stats: HashMap<(GameName, &str), (SettingsStats, u64)> = HashMap::new();
+    for race in races {
+        let key = (race.race_game, race.race_info.as_str());
+        race_settings.insert(race.race_id, key);
+        let entry = stats.entry(key).or_insert_with(|| {
+            let empty = SettingsStats {
+                game: race.race_game,
+                settings: race.race_info.clone(),
+                races: 0,
+                finishes: 0,
+                forfeits: 0,
+                average: None,
+            };
+            (empty, 0)
+        });
+        entry.0.races += 1;
+    }
+    submissions
+        .iter()
+        .filter(|s| !s.unranked && !s.pending)
+        .for_each(|s| {
+            let (entry, total_ms) = match race_settings.get(&s.race_id) {
+                Some(key) => stats.get_mut(key).unwrap(),
+                None => return,
+            };
+            match (s.runner_forfeit, s.runner_time) {
+                (true, _) => entry.forfeits += 1,
+                (false, Some(t)) => {
+                    entry.finishes += 1;
+                    *total_ms += t.millis();
+                }
+                // a finish without a time, like a collection-only race, can't be averaged
+                (false, None) => (),
+            }
+        });
+
+    let mut stats: Vec<SettingsStats> = stats
+        .into_values()
+        .map(|(mut s, total_ms)| {
+            if s.finishes > 0 {
+                s.average = Some(RunTime::from_millis(total_ms / s.finishes as u64));
+            }
+            s
+        })
+        .collect();
+    // the settings run most often first
+    stats.sort_by(|a, b| {
+        b.races
+            .cmp(&a.races)
+            .then((b.finishes + b.forfeits).cmp(&(a.finishes + a.forfeits)))
+            .then(a.settings.cmp(&b.settings))
+    });
+
+    stats
+}
+
+// submission counts by weekday (monday first) and hour, in UTC
+pub type Heatmap = [[u32; 24]; 7];
+
+// from no submissions to the busiest hour
+const HEAT_SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+pub fn load_recent_submission_times(
+    conn: &PooledConn,
+    group: &ChannelGroup,
+    races: i64,
+) -> Result<Vec<NaiveDateTime>> {
+    let race_ids: Vec<u32> = AsyncRaceData::belonging_to(group)
+        .select(async_races::race_id)
+        .order(async_races::race_id.desc())
+        .limit(races)
+        .load(conn)?;
+    let times: Vec<NaiveDateTime> = submissions::table
+        .filter(submissions::race_id.eq_any(race_ids))
+        .select(submissions::submission_datetime)
+        .load(conn)?;
+
+    Ok(times)
+}
+
+pub fn activity_heatmap(times: &[NaiveDateTime]) -> Heatmap {
+    let mut heatmap: Heatmap = [[0; 24]; 7];
+    times.iter().for_each(|t| {
+        heatmap[t.weekday().num_days_from_monday() as usize][t.hour() as usize] += 1;
+    });
+
+    heatmap
+}
+
+pub fn heatmap_text(heatmap: &Heatmap) -> String {
+    // one row per weekday and one column per hour, shaded relative to the busiest hour
+    let busiest = heatmap.iter().flatten().copied().max().unwrap_or(0);
+    let mut text = String::from("     0     6     12    18    (UTC)\n");
+    for (day, hours) in WEEKDAYS.iter().zip(heatmap.iter()) {
+        text.push_str(day);
+        text.push_str("  ");
+        hours.iter().for_each(|&n| {
+            let shade = match (n, busiest) {
+                (0, _) => 0,
+                // anything above zero gets at least the lightest shade
+                (n, b) => ((n * (HEAT_SHADES.len() as u32 - 1) + b - 1) / b) as usize,
+            };
+            text.push(HEAT_SHADES[shade]);
+        });
+        let total: u32 = hours.iter().sum();
+        text.push_str(format!("  {}\n", total).as_str());
+    }
+
+    text
+}
+
+// how a runner did in one race compared to everyone else in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunnerResult {
+    pub race_id: u32,
+    pub race_date: NaiveDate,
+    pub game: GameName,
+    pub time: RunTime,
+    pub place: usize,
+    pub field: usize,
+    // the share of the rest of the field they beat, forfeits included
+    pub percentile: f64,
+}
+
+pub fn load_runner_history(
+    conn: &PooledConn,
+    this_server_id: u64,
+    this_runner_id: u64,
+    game: Option<GameName>,
+) -> Result<(Vec<AsyncRaceData>, Vec<Submission>)> {
+    // the stopped races on this server the runner finished, and every submission in them
+    let mut query = async_races::table
+        .inner_join(channels::table)
+        .inner_join(submissions::table)
+        .filter(channels::server_id.eq(this_server_id))
+        .filter(async_races::race_active.eq(false))
+        .filter(submissions::runner_id.eq(this_runner_id))
+        .filter(submissions::runner_forfeit.eq(false))
+        .select(async_races::all_columns)
+        .into_boxed();
+    if let Some(g) = game {
+        query = query.filter(async_races::race_game.eq(g));
+    }
+    let races: Vec<AsyncRaceData> = query.load(conn)?;
+    let race_ids: Vec<u32> = races.iter().map(|r| r.race_id).collect();
+    let submissions: Vec<Submission> = submissions::table
+        .filter(submissions::race_id.eq_any(race_ids))
+        .load(conn)?;
+
+    Ok((races, submissions))
+}
+
+pub fn runner_results(
+    this_runner_id: u64,
+    races: &[AsyncRaceData],
+    submissions: &[Submission],
+) -> Vec<RunnerResult> {
+    // oldest first. only ranked, verified, timed submissions count on either side.
+    let counted = |s: &&Submission| !s.unranked && !s.pending;
+    let mut results: Vec<RunnerResult> = races
+        .iter()
+        .filter_map(|race| {
+            let field: Vec<&Submission> = submissions
+                .iter()
+                .filter(|s| s.race_id == race.race_id)
+                .filter(counted)
+                .collect();
+            let time = field
+                .iter()
+                .find(|s| s.runner_id == this_runner_id && !s.runner_forfeit)?
+                .runner_time?;
+            let faster = field
+                .iter()
+                .filter(|s| !s.runner_forfeit && s.runner_time.map_or(false, |t| t < time))
+                .count();
+            let slower = field
+                .iter()
+                .filter(|s| s.runner_forfeit || s.runner_time.map_or(false, |t| t > time))
+                .count();
+            let percentile = match field.len() {
+                1 => 100.0,
+                n => slower as f64 / (n - 1) as f64 * 100.0,
+            };
+
+            Some(RunnerResult {
+                race_id: race.race_id,
+                race_date: race.race_date,
+                game: race.race_game,
+                time,
+                place: faster + 1,
+                field: field.len(),
+                percentile,
+            })
+        })
+        .collect();
+    results.sort_by_key(|r| (r.race_date, r.race_id));
+
+    results
+}
+
+pub fn percentile_trend(results: &[RunnerResult]) -> Option<f64> {
+    // the least squares slope of percentile over races, in points per race. a couple of
+    // races isn't enough to call it a trend.
+    if results.len() < 3 {
+        return None;
+    }
+    let n = results.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = results.iter().map(|r| r.percentile).sum::<f64>() / n;
+    let (num, den) = results
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(num, den), (i, r)| {
+            let dx = i as f64 - mean_x;
+            (num + dx * (r.percentile - mean_y), den + dx * dx)
+        });
+
+    Some(num / den)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::testing::{test_race, test_submission};
+
+    fn race(race_id: u32, race_info: &str) -> AsyncRaceData {
+        AsyncRaceData {
+            race_id,
+            race_info: race_info.to_owned(),
+            ..test_race()
+        }
+    }
+
+    fn submission(race_id: u32, time_ms: Option<u64>, unranked: bool) -> Submission {
+        runner_submission(0, race_id, time_ms, unranked)
+    }
+
+    fn runner_submission(
+        runner_id: u64,
+        race_id: u32,
+        time_ms: Option<u64>,
+        unranked: bool,
+    ) -> Submission {
+        Submission {
+            runner_id,
+            race_id,
+            runner_forfeit: time_ms.is_none(),
+            unranked,
+            runner_time: time_ms.map(RunTime::from_millis),
+            ..test_submission()
+        }
+    }
+
+    #[test]
+    fn stats_are_grouped_by_settings() {
+        let races = vec![race(1, "Open"), race(2, "Open"), race(3, "Keysanity")];
+        let subs = vec![
+            submission(1, Some(1_000), false),
+            submission(2, Some(3_000), false),
+            submission(2, None, false),
+            submission(2, Some(1), true),
+            submission(3, None, false),
+            // a race from another server
+            submission(4, Some(5), false),
+        ];
+        let stats = settings_stats(&races, &subs);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].settings, "Open");
+        assert_eq!(stats[0].races, 2);
+        assert_eq!(stats[0].finishes, 2);
+        assert_eq!(stats[0].forfeits, 1);
+        assert_eq!(stats[0].average, Some(RunTime::from_millis(2_000)));
+        assert!((stats[0].forfeit_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats[1].average, None);
+        assert_eq!(stats[1].forfeit_rate(), 1.0);
+    }
+
+    #[test]
+    fn heatmap_is_shaded_by_busiest_hour() {
+        // 2026-01-05 is a monday
+        let at = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2026, 1, day)
+                .unwrap()
+                .and_hms_opt(hour, 30, 0)
+                .unwrap()
+        };
+        let times = vec![at(5, 20), at(5, 20), at(5, 20), at(5, 20), at(11, 3)];
+        let heatmap = activity_heatmap(&times);
+        assert_eq!(heatmap[0][20], 4);
+        assert_eq!(heatmap[6][3], 1);
+
+        let text = heatmap_text(&heatmap);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[1].starts_with("Mon  ····················█···  4"));
+        assert!(lines[7].starts_with("Sun  ···░····················  1"));
+    }
+
+    #[test]
+    fn runner_results_rank_against_the_field() {
+        let races = vec![race(2, "Open"), race(1, "Open"), race(3, "Open")];
+        let subs = vec![
+            runner_submission(7, 1, Some(2_000), false),
+            runner_submission(8, 1, Some(1_000), false),
+            runner_submission(9, 1, None, false),
+            runner_submission(10, 1, Some(500), true),
+            runner_submission(7, 2, Some(1_000), false),
+            runner_submission(8, 2, Some(3_000), false),
+            runner_submission(7, 3, Some(1_000), false),
+        ];
+        let results = runner_results(7, &races, &subs);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].race_id, 1);
+        assert_eq!((results[0].place, results[0].field), (2, 3));
+        assert_eq!(results[0].percentile, 50.0);
+        assert_eq!((results[1].place, results[1].percentile), (1, 100.0));
+        // alone in the race
+        assert_eq!(results[2].percentile, 100.0);
+        assert!((percentile_trend(&results).unwrap() - 25.0).abs() < 1e-9);
+        assert_eq!(percentile_trend(&results[..2]), None);
+    }
+}
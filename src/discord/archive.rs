@@ -0,0 +1,214 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use chrono::Utc;
+use diesel::prelude::*;
+use serenity::model::id::ChannelId;
+
+use crate::{
+    discord::{
+        branding::post_branded,
+        channel_groups::ChannelGroup,
+        messages::{get_race_post, BotMessage},
+        ops::DiscordOps,
+        submissions::{
+            leaderboard_text, load_leaderboard, render_runner_name, shuffled_results_text,
+            split_leaderboard, RunTime, Submission,
+        },
+    },
+    error::MurahdahlaError,
+    games::{AsyncRaceData, DataDisplay},
+    helpers::*,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RaceStats {
+    pub best: RunTime,
+    pub median: RunTime,
+    pub average: RunTime,
+}
+
+pub fn race_stats(leaderboard: &[Submission]) -> Option<RaceStats> {
+    // only ranked finishers count towards the times
+    let mut times: Vec<u64> = leaderboard
+        .iter()
+        .filter(|s| !s.unranked)
+        .filter_map(|s| s.runner_time.map(RunTime::millis))
+        .collect();
+    if times.is_empty() {
+        return None;
+    }
+    times.sort_unstable();
+    let mid = times.len() / 2;
+    let median = match times.len() % 2 {
+        0 => (times[mid - 1] + times[mid]) / 2,
+        _ => times[mid],
+    };
+
+    Some(RaceStats {
+        best: RunTime::from_millis(times[0]),
+        median: RunTime::from_millis(median),
+        average: RunTime::from_millis(times.iter().sum::<u64>() / times.len() as u64),
+    })
+}
+
+pub fn submitted_links(submissions: &[Submission]) -> Vec<(&str, &str)> {
+    // runners put vods in a game template's fields, which end up in option_text
+    submissions
+        .iter()
+        .filter_map(|s| {
+            s.option_text
+                .as_deref()
+                .map(|t| (s.runner_name.as_str(), t))
+        })
+        .flat_map(|(name, text)| {
+            text.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|w| w.starts_with("https://") || w.starts_with("http://"))
+                .map(move |w| (name, w))
+        })
+        .collect()
+}
+
+pub fn archive_text(
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    leaderboard: &[Submission],
+    forfeits: &[Submission],
+    results_post: Option<&BotMessage>,
+) -> String {
+    let mut text = format!(
+        "**Race archive for {}**\n{}\n",
+        &group.group_name,
+        race.base_string(group.max_settings_chars())
+    );
+    // writing to a string can't fail
+    let _ = writeln!(
+        text,
+        "Started {}, stopped {}",
+        race.race_date,
+        Utc::now().date_naive()
+    );
+    if let Some(p) = results_post {
+        let _ = writeln!(text, "Results post: {}", p.jump_link());
+    }
+    let ranked = leaderboard.iter().filter(|s| !s.unranked).count();
+    let _ = write!(
+        text,
+        "\n{} finished, {} forfeited, {} unranked",
+        ranked,
+        forfeits.len(),
+        leaderboard.len() - ranked
+    );
+    // the standings of a shuffled race are only sent to the mod who stopped it
+    match race.race_shuffle {
+        true => {
+            text.push_str("\n\n");
+            text.push_str(&shuffled_results_text("Finishers:", leaderboard));
+        }
+        false => {
+            if let Some(stats) = race_stats(leaderboard) {
+                let _ = write!(
+                    text,
+                    "\nBest {}, median {}, average {}",
+                    stats.best, stats.median, stats.average
+                );
+            }
+            text.push_str("\n\n");
+            text.push_str(&leaderboard_text("Final standings:", leaderboard, None));
+        }
+    }
+    if !forfeits.is_empty() {
+        let names = forfeits
+            .iter()
+            .map(|s| s.runner_name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let _ = write!(text, "\n\nForfeits: {}", names);
+    }
+    let links = submitted_links(leaderboard);
+    if !links.is_empty() {
+        text.push_str("\n\nVODs:");
+        for (name, link) in links {
+            // angle brackets stop discord from embedding every link
+            let _ = write!(text, "\n{} - <{}>", name, link);
+        }
+    }
+
+    text
+}
+
+pub async fn post_archive(
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+) -> Result<(), MurahdahlaError> {
+    use crate::schema::submissions::columns::runner_forfeit;
+
+    let channel_id = match group.archive_channel {
+        Some(c) => ChannelId::from(c),
+        None => return Ok(()),
+    };
+    let (leaderboard, forfeits, results_post) = {
+        let conn = pool.get()?;
+        let leaderboard = load_leaderboard(&conn, group, race)?;
+        let mut forfeits: Vec<Submission> = Submission::belonging_to(race)
+            .filter(runner_forfeit.eq(true))
+            .load(&conn)?;
+        forfeits
+            .iter_mut()
+            .for_each(|s| s.runner_name = render_runner_name(&s.runner_name, group));
+        (leaderboard, forfeits, get_race_post(&conn, race)?)
+    };
+    let text = archive_text(group, race, &leaderboard, &forfeits, results_post.as_ref());
+    for post in split_leaderboard(&text) {
+        post_branded(ops, group, channel_id, None, &post).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_submission;
+
+    fn finisher(name: &str, ms: u64, unranked: bool, text: Option<&str>) -> Submission {
+        Submission {
+            runner_name: name.to_owned(),
+            option_text: text.map(str::to_owned),
+            unranked,
+            runner_time: Some(RunTime::from_millis(ms)),
+            ..test_submission()
+        }
+    }
+
+    #[test]
+    fn stats_skip_unranked() {
+        let lb = vec![
+            finisher("a", 1_000, false, None),
+            finisher("b", 2_000, false, None),
+            finisher("c", 6_000, false, None),
+            finisher("d", 500, true, None),
+        ];
+        let stats = race_stats(&lb).unwrap();
+        assert_eq!(stats.best.millis(), 1_000);
+        assert_eq!(stats.median.millis(), 2_000);
+        assert_eq!(stats.average.millis(), 3_000);
+        assert!(race_stats(&[]).is_none());
+    }
+
+    #[test]
+    fn links_are_found_in_fields() {
+        let lb = vec![
+            finisher(
+                "a",
+                1_000,
+                false,
+                Some("vod: https://twitch.tv/v/1, rank: 3"),
+            ),
+            finisher("b", 2_000, false, Some("no link here")),
+        ];
+        assert_eq!(submitted_links(&lb), vec![("a", "https://twitch.tv/v/1")]);
+    }
+}
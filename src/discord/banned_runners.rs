@@ -0,0 +1,69 @@
+use anyhow::Result;
+use diesel::{insert_or_ignore_into, prelude::*};
+
+use crate::{
+    discord::channel_groups::ChannelGroup,
+    games::AsyncRaceData,
+    helpers::*,
+    schema::{banned_runners, submissions},
+};
+
+// runners who can't submit to a group. their messages in the submission channel are
+// deleted without creating a submission.
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "ChannelGroup", foreign_key = "channel_group_id")]
+#[table_name = "banned_runners"]
+#[primary_key(channel_group_id, runner_id)]
+pub struct BannedRunner {
+    pub channel_group_id: Vec<u8>,
+    pub runner_id: u64,
+}
+
+pub fn is_banned(conn: &PooledConn, group: &ChannelGroup, this_runner_id: u64) -> Result<bool> {
+    use crate::schema::banned_runners::columns::runner_id;
+
+    let banned = BannedRunner::belonging_to(group)
+        .filter(runner_id.eq(this_runner_id))
+        .first::<BannedRunner>(conn)
+        .optional()?;
+
+    Ok(banned.is_some())
+}
+
+pub fn ban_runner(conn: &PooledConn, group: &ChannelGroup, this_runner_id: u64) -> Result<()> {
+    let banned = BannedRunner {
+        channel_group_id: group.channel_group_id.clone(),
+        runner_id: this_runner_id,
+    };
+    insert_or_ignore_into(banned_runners::table)
+        .values(&banned)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn unban_runner(conn: &PooledConn, group: &ChannelGroup, this_runner_id: u64) -> Result<()> {
+    use crate::schema::banned_runners::columns::runner_id;
+
+    diesel::delete(BannedRunner::belonging_to(group).filter(runner_id.eq(this_runner_id)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn void_submissions(
+    conn: &PooledConn,
+    group: &ChannelGroup,
+    this_runner_id: u64,
+) -> Result<usize> {
+    // removes the runner's submissions from every race this group has had
+    let race_ids = AsyncRaceData::belonging_to(group).select(crate::schema::async_races::race_id);
+    let voided = diesel::delete(
+        submissions::table
+            .filter(submissions::runner_id.eq(this_runner_id))
+            .filter(submissions::race_id.eq_any(race_ids)),
+    )
+    .execute(conn)?;
+
+    Ok(voided)
+}
@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::{CreateEmbed, CreateEmbedFooter},
+    model::id::{ChannelId, MessageId},
+};
+use url::Url;
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup,
+        ops::{DiscordOps, PostedMessage},
+    },
+    error::MurahdahlaError,
+};
+
+// discord's limit on the length of an embed footer in characters
+const MAX_FOOTER_CHARS: usize = 2048;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BrandingYaml {
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub banner: Option<String>,
+    #[serde(default)]
+    pub footer: Option<String>,
+}
+
+// how a group's race posts, leaderboards and results look. a group with any of these
+// set has those posts sent as embeds instead of plain messages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Branding {
+    pub color: Option<u32>,
+    pub thumbnail: Option<String>,
+    pub banner: Option<String>,
+    pub footer: Option<String>,
+}
+
+impl Branding {
+    pub fn from_yaml(yaml: &BrandingYaml) -> Result<Self> {
+        let footer = match yaml.footer.as_deref().map(str::trim) {
+            Some(f) if f.chars().count() > MAX_FOOTER_CHARS => {
+                return Err(anyhow!(
+                    "Branding footer can't be longer than {} characters",
+                    MAX_FOOTER_CHARS
+                ))
+            }
+            Some(f) if !f.is_empty() => Some(f.to_owned()),
+            _ => None,
+        };
+
+        Ok(Branding {
+            color: yaml.color.as_deref().map(parse_color).transpose()?,
+            thumbnail: yaml.thumbnail.as_deref().map(image_url).transpose()?,
+            banner: yaml.banner.as_deref().map(image_url).transpose()?,
+            footer,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Branding::default()
+    }
+
+    pub fn style(&self, mut embed: CreateEmbed) -> CreateEmbed {
+        if let Some(c) = self.color {
+            embed = embed.colour(c);
+        }
+        if let Some(t) = &self.thumbnail {
+            embed = embed.thumbnail(t);
+        }
+        if let Some(b) = &self.banner {
+            embed = embed.image(b);
+        }
+        if let Some(f) = &self.footer {
+            embed = embed.footer(CreateEmbedFooter::new(f));
+        }
+
+        embed
+    }
+}
+
+fn parse_color(color: &str) -> Result<u32> {
+    // "#1abc9c" or "1abc9c", the way colors are usually written
+    let hex = color.trim().trim_start_matches('#');
+    match hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(u32::from_str_radix(hex, 16)?),
+        false => Err(anyhow!(
+            "\"{}\" isn't a color. Use a hex color like \"#1abc9c\"",
+            color
+        )),
+    }
+}
+
+fn image_url(url: &str) -> Result<String> {
+    // discord only loads embed images over http(s)
+    let parsed = Url::parse(url.trim()).map_err(|e| anyhow!("\"{}\" isn't a URL: {}", url, e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed.to_string()),
+        _ => Err(anyhow!("Branding image \"{}\" must be an http(s) URL", url)),
+    }
+}
+
+pub async fn post_branded(
+    ops: &dyn DiscordOps,
+    group: &ChannelGroup,
+    channel_id: ChannelId,
+    mention: Option<&str>,
+    text: &str,
+) -> Result<PostedMessage, MurahdahlaError> {
+    match (group.branding(), mention) {
+        (Some(b), _) => ops.say_branded(channel_id, mention, text, &b).await,
+        (None, Some(m)) => ops.say(channel_id, &format!("{} {}", m, text)).await,
+        (None, None) => ops.say(channel_id, text).await,
+    }
+}
+
+pub async fn edit_branded(
+    ops: &dyn DiscordOps,
+    group: &ChannelGroup,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    text: &str,
+) -> Result<(), MurahdahlaError> {
+    match group.branding() {
+        Some(b) => ops.edit_branded(channel_id, message_id, text, &b).await,
+        None => ops.edit(channel_id, message_id, text).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branding_from_yaml() {
+        let yaml = BrandingYaml {
+            color: Some("#1ABC9C".to_owned()),
+            thumbnail: Some("https://example.com/logo.png".to_owned()),
+            banner: None,
+            footer: Some("  Weekly async  ".to_owned()),
+        };
+        let branding = Branding::from_yaml(&yaml).unwrap();
+        assert_eq!(branding.color, Some(0x1abc9c));
+        assert_eq!(
+            branding.thumbnail.as_deref(),
+            Some("https://example.com/logo.png")
+        );
+        assert_eq!(branding.footer.as_deref(), Some("Weekly async"));
+        assert!(Branding::from_yaml(&BrandingYaml::default())
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(parse_color("ff0000").unwrap(), 0xff0000);
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("red").is_err());
+        assert!(image_url("ftp://example.com/a.png").is_err());
+        assert!(image_url("logo.png").is_err());
+        let long_footer = BrandingYaml {
+            footer: Some("a".repeat(MAX_FOOTER_CHARS + 1)),
+            ..Default::default()
+        };
+        assert!(Branding::from_yaml(&long_footer).is_err());
+    }
+}
@@ -9,12 +9,36 @@ use diesel::{
     backend::Backend, deserialize, deserialize::FromSql, expression::AsExpression,
     helper_types::AsExprOf, prelude::*, sql_types::Text,
 };
-use serde::Deserialize;
-use serenity::{model::channel::Message, prelude::*};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::{
+        guild::Guild,
+        id::{ChannelId, GuildId},
+    },
+    prelude::*,
+};
+
+use crate::{
+    discord::{
+        branding::{Branding, BrandingYaml},
+        result_channels::{result_channels_from_yaml, ResultChannel, ResultChannelYaml},
+        servers::{check_bot_can_assign, check_bot_can_overwrite, resolve_role, DiscordServer},
+        spoiler_roles::{spoiler_roles_from_yaml, SpoilerAccess, SpoilerRole, SpoilerRoleYaml},
+        submission_parser::parse_variable_time,
+        submissions::RunTime,
+    },
+    error::MurahdahlaError,
+    games::DEFAULT_MAX_SETTINGS_CHARS,
+    helpers::*,
+    schema::channels,
+};
 
-use crate::{discord::servers::DiscordServer, helpers::*, schema::channels};
+// anything shorter would leave little besides the "..."
+const MIN_SETTINGS_LENGTH: u16 = 20;
 
-#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[derive(
+    Debug, Clone, Insertable, Queryable, Identifiable, Associations, Serialize, Deserialize,
+)]
 #[belongs_to(parent = "DiscordServer", foreign_key = "server_id")]
 #[table_name = "channels"]
 #[primary_key(channel_group_id)]
@@ -25,10 +49,39 @@ pub struct ChannelGroup {
     pub submission: u64,
     pub leaderboard: u64,
     pub spoiler: u64,
-    pub spoiler_role_id: u64,
+    // only set when spoiler access is given out with a role
+    pub spoiler_role_id: Option<u64>,
+    // strip non-ascii characters from runner names on the leaderboard
+    pub ascii_names: bool,
+    // cut runner names on the leaderboard down to this many characters
+    pub max_name_length: Option<u16>,
+    pub spoiler_access: SpoilerAccess,
+    // submissions faster than this wait for a mod in the verification channel before
+    // they show up on the leaderboard
+    #[column_name = "verify_under_ms"]
+    pub verify_under: Option<RunTime>,
+    pub verification_channel: Option<u64>,
+    // show runners' flair after their names on the leaderboard
+    pub show_flair: bool,
+    // comma separated words runners can forfeit with on top of "ff" and "forfeit"
+    pub forfeit_words: Option<String>,
+    // gets a summary of each race when it's stopped
+    pub archive_channel: Option<u64>,
+    // branding for the group's posts, see branding.rs
+    pub embed_color: Option<u32>,
+    pub embed_thumbnail: Option<String>,
+    pub embed_banner: Option<String>,
+    pub embed_footer: Option<String>,
+    // delete messages in the spoiler channel from anyone who hasn't submitted to the
+    // active race, see spoiler_guard.rs
+    pub guard_spoiler: bool,
+    // clear the spoiler channel when a race starts, see spoiler_purge.rs
+    pub purge_spoiler: bool,
+    // cut race settings in the group's posts down to this many characters
+    pub max_settings_length: Option<u16>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ChannelGroupYaml {
     #[serde(skip)]
     #[serde(default = "new_uuid")]
@@ -37,22 +90,58 @@ pub struct ChannelGroupYaml {
     pub submission: String,
     pub leaderboard: String,
     pub spoiler: String,
-    pub spoiler_role: String,
+    #[serde(default)]
+    pub spoiler_role: Option<String>,
+    #[serde(default)]
+    pub spoiler_access: SpoilerAccess,
+    #[serde(default)]
+    pub ascii_names: bool,
+    #[serde(default)]
+    pub max_name_length: Option<u16>,
+    #[serde(default)]
+    pub spoiler_roles: Vec<SpoilerRoleYaml>,
+    #[serde(default)]
+    pub verify_under: Option<String>,
+    #[serde(default)]
+    pub verification_channel: Option<String>,
+    #[serde(default)]
+    pub show_flair: bool,
+    #[serde(default)]
+    pub forfeit_words: Vec<String>,
+    #[serde(default)]
+    pub result_channels: Vec<ResultChannelYaml>,
+    #[serde(default)]
+    pub archive_channel: Option<String>,
+    #[serde(default)]
+    pub branding: BrandingYaml,
+    #[serde(default)]
+    pub guard_spoiler: bool,
+    #[serde(default)]
+    pub purge_spoiler: bool,
+    #[serde(default)]
+    pub max_settings_length: Option<u16>,
 }
 
 impl ChannelGroup {
     pub async fn new_from_yaml(
-        msg: &Message,
         ctx: &Context,
+        guild_id: GuildId,
         yaml_bytes: &[u8],
-    ) -> Result<Self, BoxedError> {
-        let yaml: ChannelGroupYaml = match serde_yaml::from_slice(yaml_bytes) {
-            Ok(g) => g,
-            Err(e) => return Err(Box::new(e) as BoxedError),
-        };
+    ) -> Result<(Self, Vec<SpoilerRole>, Vec<ResultChannel>), MurahdahlaError> {
+        let yaml: ChannelGroupYaml =
+            serde_yaml::from_slice(yaml_bytes).map_err(MurahdahlaError::user)?;
 
-        let server = msg.guild(&ctx).unwrap();
-        let submission_channel_id = match server.channel_id_from_name(&ctx, &yaml.submission) {
+        ChannelGroup::from_yaml(ctx, guild_id, yaml).await
+    }
+
+    pub async fn from_yaml(
+        ctx: &Context,
+        guild_id: GuildId,
+        yaml: ChannelGroupYaml,
+    ) -> Result<(Self, Vec<SpoilerRole>, Vec<ResultChannel>), MurahdahlaError> {
+        let server = cached_guild(ctx, guild_id)
+            .ok_or_else(|| anyhow!("Server {} isn't in the cache", guild_id))?;
+        let submission_channel_id = match channel_id_by_name(&server, &yaml.submission) {
             Some(i) => i,
             None => {
                 return Err(anyhow!(
@@ -61,7 +150,7 @@ impl ChannelGroup {
                 .into())
             }
         };
-        let leaderboard_channel_id = match server.channel_id_from_name(&ctx, &yaml.leaderboard) {
+        let leaderboard_channel_id = match channel_id_by_name(&server, &yaml.leaderboard) {
             Some(i) => i,
             None => {
                 return Err(anyhow!(
@@ -70,7 +159,7 @@ impl ChannelGroup {
                 .into())
             }
         };
-        let spoiler_channel_id = match server.channel_id_from_name(&ctx, &yaml.spoiler) {
+        let spoiler_channel_id = match channel_id_by_name(&server, &yaml.spoiler) {
             Some(i) => i,
             None => {
                 return Err(
@@ -78,28 +167,149 @@ impl ChannelGroup {
                 )
             }
         };
-        let spoiler_role_id = match server.role_by_name(&yaml.spoiler_role) {
-            Some(r) => r.id,
-            None => {
+        let spoiler_role_id = match (yaml.spoiler_access, &yaml.spoiler_role) {
+            (SpoilerAccess::Role, Some(r)) => {
+                let role_id = resolve_role(&server, r)
+                    .map_err(|e| anyhow!("Could not get spoiler role from yaml: {}", e))?;
+                check_bot_can_assign(ctx, &server, role_id).await?;
+                Some(role_id)
+            }
+            (SpoilerAccess::Role, None) => {
+                return Err(anyhow!("Group yaml must have a spoiler_role").into())
+            }
+            (SpoilerAccess::Overwrite | SpoilerAccess::RaceRole, None)
+                if yaml.spoiler_roles.is_empty() =>
+            {
+                for c in [spoiler_channel_id, leaderboard_channel_id] {
+                    check_bot_can_overwrite(ctx, &server, c).await?;
+                }
+                None
+            }
+            (SpoilerAccess::Overwrite | SpoilerAccess::RaceRole, _) => {
                 return Err(anyhow!(
-                    "Could not get spoiler channel role id from role name provided in yaml"
+                    "Groups with {} spoiler access can't have spoiler roles",
+                    yaml.spoiler_access
                 )
                 .into())
             }
         };
+        let (verify_under, verification_channel_id) =
+            match (&yaml.verify_under, &yaml.verification_channel) {
+                (Some(t), Some(c)) => {
+                    let time = parse_variable_time(t)
+                        .map_err(|e| anyhow!("Could not parse verify_under from yaml: {}", e))?;
+                    let channel_id = channel_id_by_name(&server, c).ok_or_else(|| {
+                        anyhow!("Could not get verification channel id from name provided in yaml")
+                    })?;
+                    (Some(time), Some(channel_id.get()))
+                }
+                (None, None) => (None, None),
+                _ => {
+                    return Err(anyhow!(
+                        "Group yaml must have both verify_under and verification_channel or neither"
+                    )
+                    .into())
+                }
+            };
+        let extra_roles = spoiler_roles_from_yaml(
+            ctx,
+            &server,
+            &yaml.channel_group_id,
+            spoiler_role_id,
+            &yaml.spoiler_roles,
+        )
+        .await?;
+        let archive_channel = match &yaml.archive_channel {
+            Some(c) => Some(
+                channel_id_by_name(&server, c)
+                    .ok_or_else(|| {
+                        anyhow!("Could not get archive channel id from name provided in yaml")
+                    })?
+                    .get(),
+            ),
+            None => None,
+        };
+        let result_channels =
+            result_channels_from_yaml(&server, &yaml.channel_group_id, &yaml.result_channels)?;
+        let branding = Branding::from_yaml(&yaml.branding)?;
 
         let new_group = ChannelGroup {
             channel_group_id: yaml.channel_group_id,
-            server_id: *server.id.as_u64(),
+            server_id: server.id.get(),
             group_name: yaml.group_name.clone(),
-            submission: *submission_channel_id.as_u64(),
-            leaderboard: *leaderboard_channel_id.as_u64(),
-            spoiler: *spoiler_channel_id.as_u64(),
-            spoiler_role_id: *spoiler_role_id.as_u64(),
+            submission: submission_channel_id.get(),
+            leaderboard: leaderboard_channel_id.get(),
+            spoiler: spoiler_channel_id.get(),
+            spoiler_role_id: spoiler_role_id.map(|r| r.get()),
+            ascii_names: yaml.ascii_names,
+            max_name_length: yaml.max_name_length,
+            spoiler_access: yaml.spoiler_access,
+            verify_under,
+            verification_channel: verification_channel_id,
+            show_flair: yaml.show_flair,
+            forfeit_words: clean_forfeit_words(&yaml.forfeit_words)?,
+            archive_channel,
+            embed_color: branding.color,
+            embed_thumbnail: branding.thumbnail,
+            embed_banner: branding.banner,
+            embed_footer: branding.footer,
+            guard_spoiler: yaml.guard_spoiler,
+            purge_spoiler: yaml.purge_spoiler,
+            max_settings_length: yaml.max_settings_length,
         };
-        validate_new_group(ctx, msg, &new_group, &yaml.spoiler_role).await?;
+        let spoiler_role_name = yaml.spoiler_role.as_deref().unwrap_or_default();
+        validate_new_group(ctx, &server, &new_group, spoiler_role_name).await?;
 
-        Ok(new_group)
+        Ok((new_group, extra_roles, result_channels))
+    }
+}
+
+impl ChannelGroup {
+    pub fn forfeit_words(&self) -> Vec<&str> {
+        self.forfeit_words
+            .as_deref()
+            .map_or_else(Vec::new, |w| w.split(',').collect())
+    }
+
+    pub fn branding(&self) -> Option<Branding> {
+        let branding = Branding {
+            color: self.embed_color,
+            thumbnail: self.embed_thumbnail.clone(),
+            banner: self.embed_banner.clone(),
+            footer: self.embed_footer.clone(),
+        };
+
+        Some(branding).filter(|b| !b.is_empty())
+    }
+
+    pub fn max_settings_chars(&self) -> usize {
+        self.max_settings_length
+            .map_or(DEFAULT_MAX_SETTINGS_CHARS, usize::from)
+    }
+}
+
+fn clean_forfeit_words(words: &[String]) -> Result<Option<String>> {
+    // forfeits are matched against the first word of a submission so these can't have
+    // spaces, and they can't look like a time or a comment
+    let words = words
+        .iter()
+        .map(|w| w.trim().to_lowercase())
+        .collect::<Vec<String>>();
+    if let Some(w) = words.iter().find(|w| {
+        w.is_empty()
+            || w.contains(|c: char| c.is_whitespace() || c == ',')
+            || w.starts_with('#')
+            || parse_variable_time(w).is_ok()
+    }) {
+        return Err(anyhow!(
+            "\"{}\" can't be used as a forfeit word. They must be single words that aren't times",
+            w
+        ));
+    }
+
+    match words.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(words.join(","))),
     }
 }
 
@@ -153,10 +363,10 @@ impl fmt::Display for ChannelType {
 
 async fn validate_new_group(
     ctx: &Context,
-    msg: &Message,
+    server: &Guild,
     new_group: &ChannelGroup,
     spoiler_role_name: &str,
-) -> Result<(), BoxedError> {
+) -> Result<(), MurahdahlaError> {
     // check to make sure the group & role names are < 255 characters
     if [&new_group.group_name, spoiler_role_name]
         .iter()
@@ -164,6 +374,19 @@ async fn validate_new_group(
     {
         return Err(anyhow!("Group name or spoiler role exceeds 255 characters").into());
     }
+    if new_group.max_name_length == Some(0) {
+        return Err(anyhow!("Group max_name_length must be at least 1").into());
+    }
+    if new_group
+        .max_settings_length
+        .map_or(false, |l| l < MIN_SETTINGS_LENGTH)
+    {
+        return Err(anyhow!(
+            "Group max_settings_length must be at least {}",
+            MIN_SETTINGS_LENGTH
+        )
+        .into());
+    }
 
     // check to make sure the channels provided in the yaml are actually in this server
     let bot_channels = [
@@ -171,17 +394,11 @@ async fn validate_new_group(
         &new_group.leaderboard,
         &new_group.spoiler,
     ];
-    let all_channels: HashSet<u64> = msg
-        .guild(&ctx)
-        .unwrap()
-        .channels
-        .keys()
-        .map(|k| *k.as_u64())
-        .collect();
+    let all_channels: HashSet<u64> = server.channels.keys().map(|k| k.get()).collect();
     match bot_channels.iter().all(|c| all_channels.contains(c)) {
         true => (),
         false => {
-            let err: BoxedError =
+            let err: MurahdahlaError =
                 anyhow!("Channels provided in group yaml not found in server").into();
             return Err(err);
         }
@@ -198,7 +415,7 @@ async fn validate_new_group(
         match sub_channels.contains(&new_group.submission) {
             false => (),
             true => {
-                let err: BoxedError = anyhow!(
+                let err: MurahdahlaError = anyhow!(
                     "Provided yaml contains submission channel which has already been assigned"
                 )
                 .into();
@@ -216,7 +433,7 @@ async fn validate_new_group(
         {
             false => (),
             true => {
-                let err: BoxedError =
+                let err: MurahdahlaError =
                     anyhow!("Provided yaml contains duplicate group name for this server").into();
                 return Err(err);
             }
@@ -239,19 +456,29 @@ pub fn get_groups(conn: &PooledConn) -> Result<HashMap<u64, ChannelGroup>> {
     Ok(group_map)
 }
 
-pub async fn get_group(ctx: &Context, msg: &Message) -> ChannelGroup {
+pub async fn get_group(ctx: &Context, channel_id: ChannelId) -> ChannelGroup {
     // this should only be called when we've checked that the message is in
     // a submission channel so we know there is a group in the map
     let data = ctx.data.read().await;
     let group = data
         .get::<GroupContainer>()
         .expect("No group container in share map")
-        .get(msg.channel_id.as_u64())
+        .get(&channel_id.get())
         .unwrap();
 
     group.clone()
 }
 
+pub async fn get_spoiler_group(ctx: &Context, channel_id: ChannelId) -> Option<ChannelGroup> {
+    // groups are keyed by their submission channel so this one has to look through them
+    let data = ctx.data.read().await;
+    data.get::<GroupContainer>()
+        .expect("No group container in share map")
+        .values()
+        .find(|g| g.spoiler == channel_id.get())
+        .cloned()
+}
+
 #[inline]
 pub fn get_submission_channels(conn: &PooledConn) -> Result<HashSet<u64>> {
     use crate::schema::channels::columns::*;
@@ -262,10 +489,10 @@ pub fn get_submission_channels(conn: &PooledConn) -> Result<HashSet<u64>> {
     Ok(submission_channels)
 }
 
-pub async fn in_submission_channel(ctx: &Context, msg: &Message) -> bool {
+pub async fn in_submission_channel(ctx: &Context, channel_id: ChannelId) -> bool {
     let data = ctx.data.read().await;
     let channels = data
         .get::<SubmissionSet>()
         .expect("Error getting submission channels");
-    channels.contains(msg.channel_id.as_u64())
+    channels.contains(&channel_id.get())
 }
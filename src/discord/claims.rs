@@ -0,0 +1,180 @@
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+use diesel::{dsl::exists, insert_or_ignore_into, prelude::*, select};
+use serenity::{
+    client::Context,
+    model::id::{GuildId, UserId},
+    utils::MessageBuilder,
+};
+
+use crate::{
+    discord::{channel_groups::ChannelGroup, submissions::RunTime},
+    error::MurahdahlaError,
+    games::{AsyncRaceData, DataDisplay, RaceType},
+    helpers::*,
+    schema::{async_races, seed_claims, submissions},
+};
+
+// when a runner's timer started, either when they were sent the seed for a claim race
+// or when they ran !startrun in an RTA race. their time is how long it's been since then
+// when they submit.
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "AsyncRaceData", foreign_key = "race_id")]
+#[table_name = "seed_claims"]
+#[primary_key(race_id, runner_id)]
+pub struct SeedClaim {
+    pub race_id: u32,
+    pub runner_id: u64,
+    pub claimed_at: NaiveDateTime,
+}
+
+impl SeedClaim {
+    pub fn new(race: &AsyncRaceData, runner_id: u64) -> Self {
+        SeedClaim {
+            race_id: race.race_id,
+            runner_id,
+            claimed_at: Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn elapsed(&self, submitted_at: NaiveDateTime) -> Option<RunTime> {
+        elapsed_since(self.claimed_at, submitted_at)
+    }
+}
+
+fn elapsed_since(start: NaiveDateTime, end: NaiveDateTime) -> Option<RunTime> {
+    // a clock that's off by a little could put the submission before the start
+    let elapsed = (end - start).num_milliseconds();
+    u64::try_from(elapsed).ok().map(RunTime::from_millis)
+}
+
+pub fn timed_run(
+    conn: &PooledConn,
+    race: &AsyncRaceData,
+    this_runner_id: u64,
+    submitted_at: NaiveDateTime,
+) -> Result<Option<RunTime>> {
+    // the time for a runner who sent "done", if the bot has been timing them. live races
+    // time everyone from the end of the countdown.
+    if let Some(l) = race.race_live_at {
+        return Ok(elapsed_since(l, submitted_at));
+    }
+    let claim = get_claim(conn, race, this_runner_id)?;
+
+    Ok(claim.and_then(|c| c.elapsed(submitted_at)))
+}
+
+pub fn get_claim(
+    conn: &PooledConn,
+    race: &AsyncRaceData,
+    this_runner_id: u64,
+) -> Result<Option<SeedClaim>> {
+    use crate::schema::seed_claims::columns::runner_id;
+
+    let claim = SeedClaim::belonging_to(race)
+        .filter(runner_id.eq(this_runner_id))
+        .first::<SeedClaim>(conn)
+        .optional()?;
+
+    Ok(claim)
+}
+
+pub fn add_claim(conn: &PooledConn, claim: &SeedClaim) -> Result<()> {
+    // a runner only ever has one claim per race so their timer can't be restarted
+    insert_or_ignore_into(seed_claims::table)
+        .values(claim)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn build_claim_message(
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    claim: &SeedClaim,
+) -> String {
+    MessageBuilder::new()
+        .push_bold_line("Your seed:")
+        .push_line(race.base_string(group.max_settings_chars()))
+        .push_line(format!(
+            "Your time started at {} UTC. Send \"done\" in the submission channel when you \
+            finish, or \"ff\" to forfeit.",
+            claim.claimed_at.format("%Y-%m-%d %H:%M:%S")
+        ))
+        .build()
+}
+
+pub fn running_timers(conn: &PooledConn, this_runner_id: u64) -> Result<Vec<AsyncRaceData>> {
+    // active races the runner started a timer in and hasn't submitted to yet
+    let races: Vec<AsyncRaceData> = async_races::table
+        .inner_join(seed_claims::table)
+        .filter(seed_claims::runner_id.eq(this_runner_id))
+        .filter(async_races::race_active.eq(true))
+        .select(async_races::all_columns)
+        .load(conn)?;
+    let mut running = Vec::with_capacity(races.len());
+    for race in races {
+        let submitted: bool = select(exists(
+            submissions::table
+                .filter(submissions::race_id.eq(race.race_id))
+                .filter(submissions::runner_id.eq(this_runner_id)),
+        ))
+        .get_result(conn)?;
+        if !submitted {
+            running.push(race);
+        }
+    }
+
+    Ok(running)
+}
+
+pub async fn timer_races(
+    ctx: &Context,
+    runner: UserId,
+    group_name: Option<&str>,
+) -> Result<Vec<(ChannelGroup, AsyncRaceData)>, MurahdahlaError> {
+    // the active RTA races a runner could start a timer for from their DMs. only groups
+    // in servers they're in count, and a group name narrows it down when there's more
+    // than one.
+    let groups: Vec<ChannelGroup> = {
+        let data = ctx.data.read().await;
+        data.get::<GroupContainer>()
+            .expect("No group container in share map")
+            .values()
+            .filter(|g| group_name.map_or(true, |n| g.group_name.eq_ignore_ascii_case(n)))
+            .cloned()
+            .collect()
+    };
+    let mut races = Vec::new();
+    for group in groups {
+        let race = {
+            let conn = get_connection(ctx).await;
+            crate::games::get_maybe_active_race(&conn, &group)
+        };
+        let race = match race {
+            Some(r)
+                if r.race_type == RaceType::RTA && !r.race_claim && r.race_live_at.is_none() =>
+            {
+                r
+            }
+            _ => continue,
+        };
+        if GuildId::from(group.server_id)
+            .member(ctx, runner)
+            .await
+            .is_ok()
+        {
+            races.push((group, race));
+        }
+    }
+
+    Ok(races)
+}
+
+pub fn build_timer_message(claim: &SeedClaim) -> String {
+    format!(
+        "Your timer started at {} UTC. Use !done when you finish, followed by anything else \
+        your submission needs like your collection rate.",
+        claim.claimed_at.format("%Y-%m-%d %H:%M:%S")
+    )
+}
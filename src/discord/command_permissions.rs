@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use diesel::{insert_or_ignore_into, prelude::*};
+use serenity::{
+    model::{
+        id::{GuildId, RoleId},
+        user::User,
+    },
+    prelude::*,
+};
+
+use crate::{
+    discord::servers::{check_permissions, Permission},
+    helpers::*,
+    schema::command_permissions,
+};
+
+// commands that can't be granted to other roles since they'd let a role give itself
+// more permissions
+const UNGRANTABLE_COMMANDS: [&str; 7] = [
+    "allowcommand",
+    "denycommand",
+    "setadminrole",
+    "removeadminrole",
+    "setmodrole",
+    "removemodrole",
+    "importconfig",
+];
+
+#[derive(Debug, Clone, Insertable, Queryable)]
+#[table_name = "command_permissions"]
+pub struct CommandGrant {
+    pub server_id: u64,
+    pub command_name: String,
+    pub role_id: u64,
+}
+
+// command name -> role ids that can run it regardless of mod/admin status
+pub type ServerGrants = HashMap<String, HashSet<u64>>;
+
+pub struct GrantContainer;
+
+impl TypeMapKey for GrantContainer {
+    type Value = HashMap<GuildId, ServerGrants>;
+}
+
+pub fn required_permission(cmd_name: &str) -> Permission {
+    // the permission level needed to run a command when a server hasn't granted it to
+    // any other roles. anything not listed here needs admin.
+    match cmd_name {
+        "start" | "live" | "roll" | "igtstart" | "startigt" | "rtastart" | "startrta"
+        | "newrace" | "stop" | "preview" | "settime" | "setcollection" | "refresh"
+        | "removetime" | "banrunner" | "unbanrunner" | "grantaccess" | "clearspoilers"
+        | "backfill" | "roles" | "note" | "notes" | "settingsstats" | "activity"
+        | "listpresets" => Permission::Mod,
+        "flair" | "raceinfo" | "claim" | "startrun" | "done" | "progress" | "crboard" | "help" => {
+            Permission::None
+        }
+        _ => Permission::Admin,
+    }
+}
+
+pub fn is_grantable(cmd_name: &str) -> bool {
+    !UNGRANTABLE_COMMANDS.contains(&cmd_name)
+}
+
+pub fn get_grants(conn: &PooledConn) -> Result<HashMap<GuildId, ServerGrants>> {
+    use crate::schema::command_permissions::dsl::*;
+
+    let rows: Vec<CommandGrant> = command_permissions.load(conn)?;
+    let mut grant_map: HashMap<GuildId, ServerGrants> = HashMap::new();
+    rows.into_iter().for_each(|g| {
+        grant_map
+            .entry(GuildId::from(g.server_id))
+            .or_default()
+            .entry(g.command_name)
+            .or_default()
+            .insert(g.role_id);
+    });
+
+    Ok(grant_map)
+}
+
+pub async fn check_command_permissions(
+    ctx: &Context,
+    guild_id: GuildId,
+    author: &User,
+    user_roles: &[RoleId],
+    cmd_name: &str,
+) -> Result<()> {
+    // this is the one place we decide whether a user can run a command. the owner,
+    // maintenance user, and anyone at or above the required level always can. otherwise
+    // we look for a role the server has granted this command to.
+    let required = required_permission(cmd_name);
+    let level_err = match check_permissions(ctx, guild_id, author, user_roles, required).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    let granted = {
+        let data = ctx.data.read().await;
+        data.get::<GrantContainer>()
+            .expect("No grant container in share map")
+            .get(&guild_id)
+            .and_then(|g| g.get(cmd_name))
+            .map_or(false, |roles| {
+                user_roles.iter().any(|r| roles.contains(&r.get()))
+            })
+    };
+    match granted {
+        true => Ok(()),
+        false => Err(level_err),
+    }
+}
+
+pub async fn denied_message(ctx: &Context, guild_id: GuildId, cmd_name: &str) -> String {
+    // names the role the invoker is missing so they know who to ask
+    let required = required_permission(cmd_name);
+    let role_id = {
+        let data = ctx.data.read().await;
+        data.get::<ServerContainer>()
+            .expect("No server hashmap in share map")
+            .get(&guild_id)
+            .and_then(|s| match required {
+                Permission::Admin => s.admin_role_id,
+                Permission::Mod => s.mod_role_id,
+                Permission::None => None,
+            })
+    };
+    let role_name = role_id.and_then(|id| {
+        guild_id
+            .to_guild_cached(&ctx.cache)
+            .and_then(|g| g.roles.get(&RoleId::from(id)).map(|r| r.name.clone()))
+    });
+    let role = match (role_name, required) {
+        (Some(r), _) => format!("the \"{}\" role", r),
+        (None, Permission::Mod) => "the mod role".to_owned(),
+        (None, _) => "the admin role".to_owned(),
+    };
+
+    format!(
+        "You need {} or a role allowed to use \"{}\" to run it.",
+        role, cmd_name
+    )
+}
+
+pub async fn set_command_grant(
+    ctx: &Context,
+    guild_id: GuildId,
+    cmd_name: &str,
+    this_role_id: u64,
+    allow: bool,
+) -> Result<()> {
+    use crate::schema::command_permissions::columns::*;
+    use crate::schema::command_permissions::dsl::command_permissions;
+
+    if !is_grantable(cmd_name) {
+        return Err(anyhow!(
+            "The \"{}\" command can't be granted to other roles",
+            cmd_name
+        ));
+    }
+    let conn = get_connection(ctx).await;
+    match allow {
+        true => {
+            let grant = CommandGrant {
+                server_id: guild_id.get(),
+                command_name: cmd_name.to_owned(),
+                role_id: this_role_id,
+            };
+            insert_or_ignore_into(command_permissions)
+                .values(&grant)
+                .execute(&conn)?;
+        }
+        false => {
+            diesel::delete(
+                command_permissions
+                    .filter(server_id.eq(guild_id.get()))
+                    .filter(command_name.eq(cmd_name))
+                    .filter(role_id.eq(this_role_id)),
+            )
+            .execute(&conn)?;
+        }
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let grants = data
+            .get_mut::<GrantContainer>()
+            .expect("No grant container in share map")
+            .entry(guild_id)
+            .or_default()
+            .entry(cmd_name.to_owned())
+            .or_default();
+        match allow {
+            true => grants.insert(this_role_id),
+            false => grants.remove(&this_role_id),
+        };
+    }
+
+    Ok(())
+}
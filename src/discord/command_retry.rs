@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::{
+    discord::commands::CommandContext,
+    error::{ErrorKind, MurahdahlaError},
+    helpers::jitter_ms,
+};
+
+// commands that only read state and answer the invoker. when one fails because discord
+// had a problem it's run again, since running it twice can't change anything twice.
+// the rest tell the invoker to try again once they've checked what already happened.
+const RETRY_COMMANDS: [&str; 18] = [
+    "listgroups",
+    "roles",
+    "refresh",
+    "notes",
+    "cursors",
+    "settings",
+    "permissions",
+    "listraces",
+    "settingsstats",
+    "activity",
+    "raceinfo",
+    "progress",
+    "crboard",
+    "templates",
+    "games",
+    "listpresets",
+    "aliases",
+    "exportconfig",
+];
+const COMMAND_ATTEMPTS: u32 = 3;
+const COMMAND_BACKOFF_MS: u64 = 2000;
+const COMMAND_JITTER_MS: u64 = 500;
+
+pub fn is_retryable_command(cmd_name: &str) -> bool {
+    RETRY_COMMANDS.contains(&cmd_name)
+}
+
+pub async fn rerun_command(
+    cmd: CommandContext<'_>,
+    error: MurahdahlaError,
+) -> Result<(), MurahdahlaError> {
+    // called from the error handler for a command that failed because of discord. the
+    // command is run again with the same arguments until it works, fails some other way,
+    // or runs out of attempts. whatever the last attempt returned is handed back.
+    let mut result = Err(error);
+    let mut attempt: u32 = 1;
+    while let Err(e) = &result {
+        if attempt >= COMMAND_ATTEMPTS
+            || !is_retryable_command(&cmd.command().name)
+            || e.kind() != ErrorKind::Discord
+        {
+            break;
+        }
+        attempt += 1;
+        let delay = COMMAND_BACKOFF_MS * 2u64.pow(attempt - 2) + jitter_ms(COMMAND_JITTER_MS);
+        warn!(
+            "Running command {} again in {}ms (attempt {}/{})",
+            cmd.id(),
+            delay,
+            attempt,
+            COMMAND_ATTEMPTS
+        );
+        sleep(Duration::from_millis(delay)).await;
+        result = cmd.rerun().await;
+    }
+
+    result
+}
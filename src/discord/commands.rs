@@ -1,221 +1,980 @@
-use std::{convert::TryFrom, str::FromStr};
+use std::{fmt, str::FromStr, time::Duration};
 
 use anyhow::{anyhow, Result};
+use chrono::Utc;
 use diesel::{insert_into, prelude::*};
-use futures::{join, try_join};
+use futures::join;
+use once_cell::sync::Lazy;
+use poise::{BoxFuture, CreateReply, FrameworkError};
 use serenity::{
-    framework::standard::{
-        macros::{command, group, hook},
-        Args, CommandError, CommandResult,
+    builder::{
+        CreateActionRow, CreateAttachment, CreateButton, CreateInputText,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, CreateModal,
+        CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditMessage, GetMessages,
+    },
+    model::{
+        application::{
+            ActionRowComponent, ButtonStyle, ComponentInteractionDataKind, InputTextStyle,
+        },
+        channel::Attachment,
+        guild::Role,
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        user::User,
     },
-    model::channel::{Message, ReactionType},
     prelude::*,
+    utils::MessageBuilder,
 };
 
 use crate::{
+    config::{get_config, BotConfig, ConfigContainer},
     discord::{
-        channel_groups::{get_group, in_submission_channel, ChannelGroup, ChannelType},
+        aliases::{clean_alias, set_alias, AliasContainer},
+        analytics::{
+            activity_heatmap, heatmap_text, load_recent_submission_times, load_runner_history,
+            load_server_history, runner_results, settings_stats,
+        },
+        archive::post_archive,
+        banned_runners::{ban_runner, is_banned, unban_runner, void_submissions},
+        branding::post_branded,
+        channel_groups::{
+            get_group, get_spoiler_group, in_submission_channel, ChannelGroup, ChannelType,
+        },
+        claims::{
+            add_claim, build_claim_message, build_timer_message, get_claim, running_timers,
+            timed_run, timer_races, SeedClaim,
+        },
+        command_permissions::{
+            check_command_permissions, denied_message, is_grantable, required_permission,
+            set_command_grant, GrantContainer,
+        },
+        command_retry::{is_retryable_command, rerun_command},
+        custom_games::{find_custom_game, get_custom_games, remove_custom_game, set_custom_game},
+        error_report::report_command_error,
+        events::{emit, RaceEvent},
+        flair::{clean_flair, clear_flair, set_flair},
+        ingest::enqueue_history,
         messages::{
-            build_listgroups_message, get_lb_msgs_data, handle_new_race_messages,
-            message_maintenance_user, BotMessage,
+            accept_submission, build_aliases_message, build_cursors_message,
+            build_custom_games_message, build_listgroups_message, build_listraces_message,
+            build_notes_messages, build_permissions_message, build_presets_message,
+            build_preview_message, build_progress_message, build_raceinfo_message,
+            build_removegroup_prompt, build_roles_message, build_settings_message,
+            build_settingsstats_messages, build_templates_message, delete_leaderboard_posts,
+            get_message_policy, get_race_post, get_race_post_id, handle_new_race_messages,
+            is_command_message, message_maintenance_user, notify_invoker, notify_seed_error,
+            warn_seed_reuse, BotMessage,
+        },
+        notes::{add_note, get_race_notes, NewSubmissionNote},
+        presets::{
+            find_preset, get_presets, remove_preset, set_preset, GroupPreset,
+            MAX_PRESET_SETTINGS_BYTES,
+        },
+        reactions::{react_outcome, CommandOutcome},
+        replay::get_channel_lags,
+        result_channels::{post_result_copies, ResultChannel},
+        secrets::{restart_client, rotate_secrets},
+        server_config::{export_server_config, ServerConfigYaml},
+        server_settings::{
+            check_seed_host, get_locale, parse_setting_value, set_setting, ServerSetting,
+            SettingsContainer,
         },
-        servers::{add_server, check_permissions, parse_role, Permission, ServerRoleAction},
-        submissions::{build_leaderboard, parse_variable_time, Submission},
+        servers::{
+            add_server, check_maintenance_user, resolve_role, write_server_role, Permission,
+            RoleCommand, ServerRoleAction,
+        },
+        shared_cache::{publish_shared_cache, reload_caches},
+        spoiler_cleanup::{clear_spoiler_roles, clearable_roles},
+        spoiler_roles::{
+            create_race_role, delete_race_role, get_spoiler_roles, grant_spoiler_access,
+            revoke_runners_access, revoke_spoiler_access, SpoilerAccess, SpoilerRole,
+        },
+        submission_parser::{parse_variable_time, DONE_WORD},
+        submissions::{
+            access_grant, build_leaderboard, build_submission, leaderboard_text, load_leaderboard,
+            load_leaderboard_sorted, split_leaderboard, write_submission_add_role, Submission,
+        },
+        templates::{get_template, get_templates, remove_template, set_template, GameTemplate},
+        throttle::check_start_limit,
     },
+    error::{ErrorKind, MurahdahlaError},
     games::{
-        get_game_boxed, get_maybe_active_race, AsyncRaceData, BoxedGame, NewAsyncRaceData, RaceType,
+        api_health::api_available,
+        custom::{CustomGame, CustomGameDefinition},
+        determine_game, find_seed_reuse, get_game_boxed, get_maybe_active_race, insert_race,
+        mark_stopped, next_word,
+        other::OtherGame,
+        race_started_by,
+        registry::{provider, registered_games},
+        scoring::CollectionScoring,
+        split_announcement, AsyncRaceData, BoxedGame, DataDisplay, GameName, LeaderboardSort,
+        NewAsyncRaceData, RaceType, StartFlags, DEADLINE_FLAG, DEFAULT_MAX_SETTINGS_CHARS,
+        LIVE_FLAG, MAX_SEED_JSON_BYTES, OTHER_FLAG, PRESET_FLAG, TEMPLATE_FLAG,
     },
     helpers::*,
+    user_error,
 };
 
-const REACT_COMMANDS: [&str; 6] = [
+pub type CommandContext<'a> = poise::Context<'a, (), MurahdahlaError>;
+pub type Command = poise::Command<(), MurahdahlaError>;
+type CommandResult = Result<(), MurahdahlaError>;
+
+const LIST_RACES_LIMIT: i64 = 10;
+const ACTIVITY_RACES: i64 = 10;
+const MAX_ACTIVITY_RACES: i64 = 100;
+const FORCE_FLAG: &str = "--force";
+const MAX_CONFIG_BYTES: u32 = 1024 * 1024;
+const CONFIG_FILE_NAME: &str = "server_config.yaml";
+const MAX_GAME_DEFINITION_BYTES: u32 = 16 * 1024;
+// problems past this many are counted instead of listed so the DM fits in one message
+const MAX_IMPORT_PROBLEMS: usize = 15;
+// the most messages discord will give us in one request
+const MAX_BACKFILL: u8 = 100;
+const CONFIRM_TIMEOUT_SECS: u64 = 60;
+const CONFIRM_BUTTON_ID: &str = "confirm";
+const CANCEL_BUTTON_ID: &str = "cancel";
+const NEWRACE_TIMEOUT_SECS: u64 = 300;
+const RACE_TYPE_MENU_ID: &str = "race_type";
+const GAME_MENU_ID: &str = "game";
+const DEADLINE_MENU_ID: &str = "deadline";
+const SEED_BUTTON_ID: &str = "seed";
+const SEED_MODAL_ID: &str = "seed_modal";
+const SEED_INPUT_ID: &str = "seed_input";
+// values for the game menu. known games and templates get a prefix before their name.
+const DETECT_CHOICE: &str = "detect";
+const OTHER_CHOICE: &str = "other";
+const GAME_CHOICE: &str = "game:";
+const TEMPLATE_CHOICE: &str = "template:";
+// discord won't show more than this many options in a select menu
+const MAX_MENU_OPTIONS: usize = 25;
+const DEADLINE_CHOICES: [(&str, i64); 5] = [
+    ("No deadline", 0),
+    ("1 day", 24),
+    ("2 days", 48),
+    ("3 days", 72),
+    ("1 week", 168),
+];
+
+const REACT_COMMANDS: [&str; 17] = [
+    "preview",
+    "flair",
+    "deleterace",
+    "allowcommand",
+    "denycommand",
+    "reloadcache",
+    "reloadconfig",
+    "setsetting",
+    "unsetsetting",
     "addgroup",
     "removegroup",
     "setmodrole",
     "setadminrole",
     "removemodrole",
     "removeadminrole",
+    "addalias",
+    "removealias",
 ];
 
-#[hook]
-pub async fn before_hook(ctx: &Context, msg: &Message, _cmd_name: &str) -> bool {
-    // before any command is run we check to see if we have the server in the share map
-    // if not, we add it to the map and the database
-    let server_check = {
-        let data = ctx.data.read().await;
-        let check = data
-            .get::<ServerContainer>()
-            .expect("No server hashmap in share map")
-            .contains_key(&msg.guild_id.unwrap());
+// every name a command can be run with, for checking the ones admins type in
+static COMMAND_NAMES: Lazy<Vec<String>> =
+    Lazy::new(|| commands().into_iter().map(|c| c.name).collect());
+
+pub fn commands() -> Vec<Command> {
+    vec![
+        start(),
+        live(),
+        roll(),
+        igtstart(),
+        startigt(),
+        rtastart(),
+        startrta(),
+        newrace(),
+        stop(),
+        preview(),
+        addgroup(),
+        exportconfig(),
+        importconfig(),
+        removegroup(),
+        listgroups(),
+        roles(),
+        setmodrole(),
+        setadminrole(),
+        removemodrole(),
+        removeadminrole(),
+        settime(),
+        setcollection(),
+        refresh(),
+        backfill(),
+        removetime(),
+        banrunner(),
+        unbanrunner(),
+        grantaccess(),
+        clearspoilers(),
+        note(),
+        notes(),
+        reloadcache(),
+        reloadconfig(),
+        cursors(),
+        rotatesecrets(),
+        setsetting(),
+        unsetsetting(),
+        settings(),
+        allowcommand(),
+        denycommand(),
+        permissions(),
+        listraces(),
+        settingsstats(),
+        activity(),
+        deleterace(),
+        flair(),
+        raceinfo(),
+        claim(),
+        startrun(),
+        done(),
+        progress(),
+        crboard(),
+        addtemplate(),
+        removetemplate(),
+        templates(),
+        addgame(),
+        removegame(),
+        games(),
+        addpreset(),
+        removepreset(),
+        listpresets(),
+        addalias(),
+        removealias(),
+        aliases(),
+        help(),
+    ]
+}
 
-        check
-    };
-    if !server_check {
-        match add_server(ctx, msg).await {
-            Ok(_) => (),
-            Err(e) => {
-                error!("Error adding new server: {}", e);
-                return false;
+pub fn command_check(cmd: CommandContext<'_>) -> BoxFuture<'_, Result<bool, MurahdahlaError>> {
+    Box::pin(async move {
+        let ctx = cmd.serenity_context();
+        // the commands that aren't guild_only don't need a server to run in
+        let guild_id = match cmd.guild_id() {
+            Some(g) => g,
+            None => return Ok(true),
+        };
+        // before any command is run we check to see if we have the server in the share map
+        // if not, we add it to the map and the database
+        let server_check = {
+            let data = ctx.data.read().await;
+            let check = data
+                .get::<ServerContainer>()
+                .expect("No server hashmap in share map")
+                .contains_key(&guild_id);
+
+            check
+        };
+        if !server_check {
+            match add_server(ctx, guild_id).await {
+                Ok(_) => (),
+                Err(e) => {
+                    error!("Error adding new server: {}", e);
+                    return Ok(false);
+                }
             }
         }
-    }
 
-    true
+        // every command's permissions are checked here so they can be configured per server
+        let cmd_name = &cmd.command().name;
+        let user_roles: Vec<RoleId> = match cmd.author_member().await {
+            Some(m) => m.roles.clone(),
+            None => Vec::new(),
+        };
+        if let Err(e) =
+            check_command_permissions(ctx, guild_id, cmd.author(), &user_roles, cmd_name).await
+        {
+            warn!("Refusing to run \"{}\" command: {}", cmd_name, e);
+            return Err(MurahdahlaError::User(
+                denied_message(ctx, guild_id, cmd_name).await,
+            ));
+        }
+
+        Ok(true)
+    })
 }
 
-#[hook]
-pub async fn after_hook(
-    ctx: &Context,
-    msg: &Message,
-    cmd_name: &str,
-    error: Result<(), CommandError>,
-) {
-    let mut successful: bool = true;
-    if let Err(e) = error {
-        successful = false;
-        let error_msg: String = format!(
-            "Error running \"{}\" command from user \"{}\": {:?}",
-            cmd_name, &msg.author.name, e
-        );
-        warn!("{}", &error_msg);
-        message_maintenance_user(ctx, error_msg).await;
+async fn start_limit(cmd: CommandContext<'_>) -> Result<bool, MurahdahlaError> {
+    // the start commands share one limit per user since any of them fetches a seed and
+    // posts in every channel of the group
+    match check_start_limit(cmd.serenity_context(), cmd.author()).await {
+        None => Ok(true),
+        Some(wait) => Err(user_error!(
+            "You're starting races too quickly. Try again in {} seconds",
+            wait.as_secs() + 1
+        )),
     }
-    if REACT_COMMANDS.iter().any(|&c| c == cmd_name) {
-        let reaction = match successful {
-            true => ReactionType::try_from("👍").unwrap(), // should never fail i think
-            false => ReactionType::try_from("👎").unwrap(),
-        };
-        match msg.react(&ctx, reaction).await {
-            Ok(_) => (),
-            Err(e) => {
-                warn!(
-                    "Error reacting to command \"{}\" from user \"{}\": {}",
-                    cmd_name, &msg.author.name, e
-                );
+}
+
+pub fn pre_command(cmd: CommandContext<'_>) -> BoxFuture<'_, ()> {
+    Box::pin(async move {
+        // slash commands have to be answered within a few seconds, which starting a race
+        // often isn't. the answer is filled in once the command is done.
+        if let poise::Context::Application(_) = cmd {
+            if let Err(e) = cmd.defer_ephemeral().await {
+                warn!("Error deferring \"{}\" command: {}", cmd.command().name, e);
             }
-        };
+        }
+    })
+}
+
+pub fn post_command(cmd: CommandContext<'_>) -> BoxFuture<'_, ()> {
+    Box::pin(finish_command(cmd, true))
+}
+
+pub fn on_error(error: FrameworkError<'_, (), MurahdahlaError>) -> BoxFuture<'_, ()> {
+    Box::pin(async move {
+        match error {
+            FrameworkError::Command { error, ctx, .. } => {
+                // a command that only reads state is run again if discord had a problem.
+                // only whatever the last attempt ended with is handled here.
+                match rerun_command(ctx, error).await {
+                    Ok(()) => finish_command(ctx, true).await,
+                    Err(e) => handle_command_error(ctx, e).await,
+                }
+            }
+            // denied permissions and the start limit
+            FrameworkError::CommandCheckFailed {
+                error: Some(e),
+                ctx,
+                ..
+            } => handle_command_error(ctx, e).await,
+            FrameworkError::ArgumentParse {
+                error, input, ctx, ..
+            } => {
+                // the arguments were typed wrong so point at how the command is used
+                let name = &ctx.command().name;
+                let e = match input {
+                    Some(i) => user_error!(
+                        "Couldn't read \"{}\": {}. Use \"!help {}\" to see how it's used.",
+                        i,
+                        error,
+                        name
+                    ),
+                    None => user_error!("{}. Use \"!help {}\" to see how it's used.", error, name),
+                };
+                handle_command_error(ctx, e).await
+            }
+            // commands from DMs that need a server, unknown commands and the context menus
+            // (which are handled with the other interactions) are ignored
+            FrameworkError::CommandCheckFailed { error: None, .. }
+            | FrameworkError::GuildOnly { .. }
+            | FrameworkError::UnknownCommand { .. }
+            | FrameworkError::UnknownInteraction { .. } => (),
+            e => {
+                if let Err(e) = poise::builtins::on_error(e).await {
+                    warn!("Error handling framework error: {}", e);
+                }
+            }
+        }
+    })
+}
+
+async fn handle_command_error(cmd: CommandContext<'_>, error: MurahdahlaError) {
+    let ctx = cmd.serenity_context();
+    let cmd_name = &cmd.command().name;
+    let error_msg: String = format!(
+        "Error running \"{}\" command from user \"{}\": {:?}",
+        cmd_name,
+        &cmd.author().name,
+        error
+    );
+    // only problems the invoker can't fix themselves go to the maintenance user
+    match error.kind() {
+        ErrorKind::User => {
+            info!("{}", &error_msg);
+            notify_invoker(ctx, cmd.author(), &cmd.invocation_string(), &error).await;
+        }
+        ErrorKind::Discord => {
+            warn!("{}", &error_msg);
+            let retry_msg = match is_retryable_command(cmd_name) {
+                true => "Discord didn't respond properly after a few tries. Please try again later.",
+                false => "Discord didn't respond properly. Check whether the command went through before trying again.",
+            };
+            notify_invoker(ctx, cmd.author(), &cmd.invocation_string(), retry_msg).await;
+            if !report_command_error(cmd, &error).await {
+                message_maintenance_user(ctx, error_msg).await;
+            }
+        }
+        ErrorKind::Internal => {
+            warn!("{}", &error_msg);
+            message_maintenance_user(ctx, error_msg).await;
+            report_command_error(cmd, &error).await;
+        }
+    };
+    finish_command(cmd, false).await;
+}
+
+async fn finish_command(cmd: CommandContext<'_>, successful: bool) {
+    let ctx = cmd.serenity_context();
+    let cmd_name = &cmd.command().name;
+    match cmd {
+        poise::Context::Prefix(p) => {
+            if REACT_COMMANDS.iter().any(|&c| c == cmd_name) {
+                let outcome = match successful {
+                    true => CommandOutcome::Success,
+                    false => CommandOutcome::Failure,
+                };
+                react_outcome(ctx, p.msg, outcome).await;
+            }
+            // always delete commands in the submission channel to keep it clean. the
+            // message policy only applies to messages that aren't commands or submissions.
+            if in_submission_channel(ctx, p.msg.channel_id).await {
+                p.msg
+                    .delete(ctx)
+                    .await
+                    .unwrap_or_else(|e| warn!("Error deleting message: {}", e));
+            }
+        }
+        // slash commands don't leave a message to react to, so the deferred answer says
+        // how it went. anything longer was sent as a DM.
+        poise::Context::Application(_) => {
+            let content = match successful {
+                true => "Done.",
+                false => "The command couldn't be run. I've sent you a DM with why.",
+            };
+            let reply = CreateReply::default().content(content).ephemeral(true);
+            if let Err(e) = cmd.send(reply).await {
+                warn!("Error answering \"{}\" command: {}", cmd_name, e);
+            }
+        }
+    };
+    if successful {
+        info!("Successfully executed command: {}", cmd_name);
+    }
+}
+
+#[derive(Debug, Copy, Clone, poise::ChoiceParameter)]
+pub enum RaceTypeArg {
+    #[name = "igt"]
+    Igt,
+    #[name = "rta"]
+    Rta,
+}
+
+impl From<RaceTypeArg> for RaceType {
+    fn from(arg: RaceTypeArg) -> Self {
+        match arg {
+            RaceTypeArg::Igt => RaceType::IGT,
+            RaceTypeArg::Rta => RaceType::RTA,
+        }
     }
+}
 
-    // always delete messages in the submission channel to keep it clean
-    if in_submission_channel(ctx, msg).await {
-        msg.delete(&ctx)
-            .await
-            .unwrap_or_else(|e| warn!("Error deleting message: {}", e));
-    }
-    info!("Successfully executed command: {}", cmd_name);
-
-    ()
-}
-
-#[group]
-#[commands(
-    igtstart,
-    startigt,
-    rtastart,
-    startrta,
-    stop,
-    addgroup,
-    removegroup,
-    listgroups,
-    setmodrole,
-    setadminrole,
-    removemodrole,
-    removeadminrole,
-    settime,
-    setcollection,
-    refresh,
-    removetime
+/// Start a race in this submission channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    category = "Races",
+    check = "start_limit"
 )]
-struct General;
+pub async fn start(
+    cmd: CommandContext<'_>,
+    #[description = "igt or rta"] race_type: RaceTypeArg,
+    #[description = "Seed data to use instead of fetching it from the seed site"] seed_json: Option<
+        Attachment,
+    >,
+    #[description = "Flags, then the seed url or game info, then \"| announcement\""]
+    #[rest]
+    game_info: Option<String>,
+) -> CommandResult {
+    let game_info = game_info.unwrap_or_default();
+    start_race(cmd, &game_info, seed_json.as_ref(), race_type.into()).await
+}
 
-// it's basically free to have two commands for starting each kind of race so why
-// not for the sake of ease-of-use
-#[command]
-#[bucket = "startrace"]
-pub async fn igtstart(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    start_race(ctx, msg, args, RaceType::IGT).await?;
+/// Start an RTA race that begins for everyone after a countdown
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    category = "Races",
+    check = "start_limit"
+)]
+pub async fn live(
+    cmd: CommandContext<'_>,
+    #[description = "Seed data to use instead of fetching it from the seed site"] seed_json: Option<
+        Attachment,
+    >,
+    #[description = "Time until the start, then the same as /start"]
+    #[rest]
+    game_info: Option<String>,
+) -> CommandResult {
+    // optional only because slash commands can't have a required option after the
+    // attachment, and the rest of the message has to be the last argument
+    let game_info = match game_info {
+        Some(g) => g,
+        None => return Err(user_error!("How long until the race starts?")),
+    };
+    // the same as "!start rta --live [time until start]"
+    let game_info = format!("{} {}", LIVE_FLAG, game_info);
+    start_race(cmd, &game_info, seed_json.as_ref(), RaceType::RTA).await
+}
 
-    Ok(())
+/// Roll a seed from a preset and start a race with it
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    category = "Races",
+    check = "start_limit"
+)]
+pub async fn roll(
+    cmd: CommandContext<'_>,
+    #[description = "igt or rta"] race_type: RaceTypeArg,
+    #[description = "Flags, then the preset, then \"| announcement\""]
+    #[rest]
+    preset_info: String,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    // checked here too so a preset isn't rolled for nothing
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    // the preset name comes after the flags. it's swapped for the new seed's url and
+    // everything else is left for start_race.
+    let after_flags = match StartFlags::take(&preset_info) {
+        Ok((_, rest)) => rest,
+        Err(e) => return Err(MurahdahlaError::user(e)),
+    };
+    let flags_str = &preset_info[..preset_info.len() - after_flags.len()];
+    let (preset_name, rest) = match next_word(after_flags) {
+        Some(p) => p,
+        None => return Err(user_error!("Which preset? Use `!roll [igt/rta] [preset]`")),
+    };
+    // the group's own presets come before the bot's
+    let group_preset = {
+        let group_fut = get_group(ctx, cmd.channel_id());
+        let conn_fut = get_connection(ctx);
+        let (group, conn) = join!(group_fut, conn_fut);
+        find_preset(&conn, &group, preset_name)?
+    };
+    let preset = match group_preset {
+        Some(p) => p.roll_preset()?,
+        None => match get_config(ctx).await.roll_preset(preset_name) {
+            Some(p) => p.clone(),
+            None => return Err(user_error!("There's no preset called \"{}\"", preset_name)),
+        },
+    };
+    // already checked when the preset was added
+    let game = GameName::from_str(&preset.game)?;
+    let game_provider = provider(game);
+    if !game_provider.rolls_seeds() {
+        return Err(user_error!("The bot can't roll {} seeds", preset.game));
+    }
+    if !api_available(ctx, game).await {
+        return Err(user_error!(
+            "{} isn't answering right now, try again later",
+            game
+        ));
+    }
+    let seed_url = match game_provider.roll(preset.settings).await {
+        Ok(u) => u,
+        Err(e) => {
+            warn!("Error rolling a \"{}\" seed: {}", preset_name, e);
+            return Err(user_error!(
+                "Couldn't generate a seed from {}, try again later",
+                game
+            ));
+        }
+    };
+    let game_info = format!(
+        "{} {} {}{} {}",
+        PRESET_FLAG,
+        preset_name.to_lowercase(),
+        flags_str,
+        seed_url,
+        rest
+    );
+    start_race(cmd, game_info.trim_end(), None, race_type.into()).await
 }
 
-#[command]
-#[bucket = "startrace"]
-pub async fn startigt(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    start_race(ctx, msg, args, RaceType::IGT).await?;
+// the old start commands from before "!start" existed. they still work so nobody's
+// habits or aliases break, but they're left out of the docs and help.
+#[poise::command(prefix_command, guild_only, hide_in_help, check = "start_limit")]
+pub async fn igtstart(
+    cmd: CommandContext<'_>,
+    seed_json: Option<Attachment>,
+    #[rest] game_info: Option<String>,
+) -> CommandResult {
+    let game_info = game_info.unwrap_or_default();
+    start_race(cmd, &game_info, seed_json.as_ref(), RaceType::IGT).await
+}
 
-    Ok(())
+#[poise::command(prefix_command, guild_only, hide_in_help, check = "start_limit")]
+pub async fn startigt(
+    cmd: CommandContext<'_>,
+    seed_json: Option<Attachment>,
+    #[rest] game_info: Option<String>,
+) -> CommandResult {
+    let game_info = game_info.unwrap_or_default();
+    start_race(cmd, &game_info, seed_json.as_ref(), RaceType::IGT).await
 }
 
-#[command]
-#[bucket = "startrace"]
-pub async fn rtastart(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    start_race(ctx, msg, args, RaceType::RTA).await?;
+#[poise::command(prefix_command, guild_only, hide_in_help, check = "start_limit")]
+pub async fn rtastart(
+    cmd: CommandContext<'_>,
+    seed_json: Option<Attachment>,
+    #[rest] game_info: Option<String>,
+) -> CommandResult {
+    let game_info = game_info.unwrap_or_default();
+    start_race(cmd, &game_info, seed_json.as_ref(), RaceType::RTA).await
+}
 
-    Ok(())
+#[poise::command(prefix_command, guild_only, hide_in_help, check = "start_limit")]
+pub async fn startrta(
+    cmd: CommandContext<'_>,
+    seed_json: Option<Attachment>,
+    #[rest] game_info: Option<String>,
+) -> CommandResult {
+    let game_info = game_info.unwrap_or_default();
+    start_race(cmd, &game_info, seed_json.as_ref(), RaceType::RTA).await
 }
 
-#[command]
-#[bucket = "startrace"]
-pub async fn startrta(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    start_race(ctx, msg, args, RaceType::RTA).await?;
+/// Start a race by picking the game and deadline from menus
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    category = "Races",
+    check = "start_limit"
+)]
+pub async fn newrace(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    // like the start commands this has to run in a submission channel
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let game_choices = {
+        let conn = get_connection(ctx).await;
+        newrace_game_choices(&get_templates(&conn, cmd.guild_id().unwrap().get())?)
+    };
+    let race_type_options = vec![
+        CreateSelectMenuOption::new("IGT", "igt").default_selection(true),
+        CreateSelectMenuOption::new("RTA", "rta"),
+    ];
+    let game_options = game_choices
+        .iter()
+        .enumerate()
+        .map(|(i, (label, value))| {
+            CreateSelectMenuOption::new(label, value).default_selection(i == 0)
+        })
+        .collect();
+    let deadline_options = DEADLINE_CHOICES
+        .iter()
+        .map(|(label, hours)| {
+            CreateSelectMenuOption::new(*label, hours.to_string()).default_selection(*hours == 0)
+        })
+        .collect();
+    let prompt = cmd
+        .channel_id()
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content("Choose the race type, game and deadline, then press \"Enter seed\".")
+                .components(vec![
+                    CreateActionRow::SelectMenu(CreateSelectMenu::new(
+                        RACE_TYPE_MENU_ID,
+                        CreateSelectMenuKind::String {
+                            options: race_type_options,
+                        },
+                    )),
+                    CreateActionRow::SelectMenu(CreateSelectMenu::new(
+                        GAME_MENU_ID,
+                        CreateSelectMenuKind::String {
+                            options: game_options,
+                        },
+                    )),
+                    CreateActionRow::SelectMenu(CreateSelectMenu::new(
+                        DEADLINE_MENU_ID,
+                        CreateSelectMenuKind::String {
+                            options: deadline_options,
+                        },
+                    )),
+                    CreateActionRow::Buttons(vec![
+                        CreateButton::new(SEED_BUTTON_ID)
+                            .label("Enter seed")
+                            .style(ButtonStyle::Primary),
+                        CreateButton::new(CANCEL_BUTTON_ID)
+                            .label("Cancel")
+                            .style(ButtonStyle::Secondary),
+                    ]),
+                ]),
+        )
+        .await?;
 
-    Ok(())
+    // the menus start on their first option so the mod only has to change what differs
+    let mut this_race_type = RaceType::IGT;
+    let mut game_choice = DETECT_CHOICE.to_owned();
+    let mut deadline_hours: i64 = 0;
+    let seed_interaction = loop {
+        let interaction = match prompt
+            .await_component_interaction(ctx)
+            .author_id(cmd.author().id)
+            .timeout(Duration::from_secs(NEWRACE_TIMEOUT_SECS))
+            .await
+        {
+            Some(i) => i,
+            None => {
+                prompt.delete(ctx).await?;
+                return Ok(());
+            }
+        };
+        let value = match &interaction.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => {
+                values.first().cloned().unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        match interaction.data.custom_id.as_str() {
+            SEED_BUTTON_ID => break interaction,
+            CANCEL_BUTTON_ID => {
+                prompt.delete(ctx).await?;
+                return Ok(());
+            }
+            RACE_TYPE_MENU_ID => {
+                this_race_type = match value.as_str() {
+                    "rta" => RaceType::RTA,
+                    _ => RaceType::IGT,
+                }
+            }
+            GAME_MENU_ID => game_choice = value,
+            DEADLINE_MENU_ID => deadline_hours = value.parse().unwrap_or(0),
+            _ => (),
+        };
+        interaction
+            .create_response(ctx, CreateInteractionResponse::Acknowledge)
+            .await?;
+    };
+
+    let seed_input = CreateInputText::new(
+        InputTextStyle::Short,
+        "Seed URL or game info",
+        SEED_INPUT_ID,
+    )
+    .required(true);
+    seed_interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Modal(
+                CreateModal::new(SEED_MODAL_ID, "New race")
+                    .components(vec![CreateActionRow::InputText(seed_input)]),
+            ),
+        )
+        .await?;
+    let modal = match prompt
+        .await_modal_interaction(ctx)
+        .author_id(cmd.author().id)
+        .timeout(Duration::from_secs(NEWRACE_TIMEOUT_SECS))
+        .await
+    {
+        Some(m) => m,
+        None => {
+            prompt.delete(ctx).await?;
+            return Ok(());
+        }
+    };
+    let seed_text = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|r| r.components.iter())
+        .find_map(|c| match c {
+            ActionRowComponent::InputText(t) if t.custom_id == SEED_INPUT_ID => t.value.clone(),
+            _ => None,
+        })
+        .unwrap_or_default();
+    modal
+        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+        .await?;
+    prompt.delete(ctx).await?;
+
+    let mut game_info = match newrace_args(&game_choice, &seed_text) {
+        Ok(a) => a,
+        Err(e) => {
+            return Err(MurahdahlaError::user(e));
+        }
+    };
+    if deadline_hours > 0 {
+        game_info = format!("{} {}h {}", DEADLINE_FLAG, deadline_hours, game_info);
+    }
+    start_race(cmd, &game_info, None, this_race_type).await
 }
 
-#[command]
-pub async fn stop(ctx: &Context, msg: &Message) -> CommandResult {
+/// Stop the race running in this submission channel
+#[poise::command(prefix_command, slash_command, guild_only, category = "Races")]
+pub async fn stop(
+    cmd: CommandContext<'_>,
+    #[description = "--force to retry the steps that failed when the last race was stopped"]
+    force: Option<String>,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
     // this must run in a submission channel because we need a group and a maybe-race
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    if !in_submission_channel(ctx, msg).await {
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
         return Ok(());
     }
-    let group_fut = get_group(ctx, msg);
+    let force = match force.as_deref() {
+        None => false,
+        Some(FORCE_FLAG) => true,
+        Some(f) => return Err(user_error!("Unrecognized stop argument: {}", f)),
+    };
+    let group_fut = get_group(ctx, cmd.channel_id());
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
 
     let maybe_active_race = get_maybe_active_race(&conn, &group);
-    match maybe_active_race {
-        Some(r) => stop_race(ctx, &r, &group).await?,
-        None => return Ok(()),
+    match (maybe_active_race, force) {
+        (Some(r), _) => stop_race(ctx, &r, &group, cmd.author().id).await?,
+        (None, false) => (),
+        (None, true) => retry_stop(ctx, &group, cmd.author().id).await?,
     };
 
     Ok(())
 }
 
-#[command]
-pub async fn addgroup(ctx: &Context, msg: &Message) -> CommandResult {
-    use crate::schema::channels::dsl::*;
-
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    match msg.attachments.len() {
-        1 => (),
-        _ => {
-            let err: BoxedError = anyhow!("!addgroup requires one attachment").into();
-            return Err(err);
+/// DM yourself the race post a start command would make, without starting it
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    category = "Races",
+    check = "start_limit"
+)]
+pub async fn preview(
+    cmd: CommandContext<'_>,
+    #[description = "igt or rta, igt if left out"] race_type: Option<RaceTypeArg>,
+    #[description = "Seed data to use instead of fetching it from the seed site"] seed_json: Option<
+        Attachment,
+    >,
+    #[description = "The same as /start"]
+    #[rest]
+    game_info: Option<String>,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    // builds the race data exactly as a start command would but only sends the result
+    // to the user who asked for it. nothing here touches the database.
+    let this_race_type: RaceType = race_type.unwrap_or(RaceTypeArg::Igt).into();
+    let game_info = game_info.unwrap_or_default();
+    let (flags, rest) = StartFlags::take(&game_info).map_err(MurahdahlaError::user)?;
+    let (game_args, announcement) = split_announcement(rest)?;
+    let server = cmd.guild_id().unwrap().get();
+    let (game, fields): (BoxedGame, Option<String>) = match flags.template.as_deref() {
+        Some(name) => {
+            let conn = get_connection(ctx).await;
+            match template_game(&conn, server, name, &game_args) {
+                Ok(g) => g,
+                Err(e) => {
+                    return Err(MurahdahlaError::user(e));
+                }
+            }
         }
-    }
+        None => {
+            let seed_json = download_seed_json(seed_json.as_ref()).await?;
+            match detect_game(ctx, server, &game_args, seed_json.as_deref()).await {
+                Ok(g) => g,
+                Err(e) => {
+                    if let MurahdahlaError::Seed(seed_err) = &e {
+                        notify_seed_error(ctx, cmd.author(), seed_err, "preview").await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    };
+    let mut preview_data = NewAsyncRaceData::new_from_game(&game, &[], this_race_type)?;
+    preview_data.race_sort = flags.sort;
+    preview_data.race_fields = fields;
+    preview_data.race_deadline = flags.deadline.map(|d| Utc::now().naive_utc() + d);
+    preview_data.race_blind = flags.blind || flags.shuffle;
+    preview_data.race_shuffle = flags.shuffle;
+    preview_data.race_claim = flags.claim;
+    preview_data.race_live_at = flags.live.map(|l| Utc::now().naive_utc() + l);
+    preview_data.race_announcement = announcement;
+    // previews outside a submission channel can't know which group the race is for
+    let max_settings = match in_submission_channel(ctx, cmd.channel_id()).await {
+        true => get_group(ctx, cmd.channel_id()).await.max_settings_chars(),
+        false => DEFAULT_MAX_SETTINGS_CHARS,
+    };
+    let preview_string = build_preview_message(&preview_data, max_settings);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(preview_string))
+        .await?;
 
-    // let's check and make sure that no server has more than ten groups
-    // for the sake of performance and not crashing the bot
-    let conn = get_connection(ctx).await;
+    Ok(())
+}
+
+/// Add a group of channels from a yaml file
+#[poise::command(prefix_command, slash_command, guild_only, category = "Groups")]
+pub async fn addgroup(
+    cmd: CommandContext<'_>,
+    #[description = "The group's yaml file"] group_yaml: Attachment,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let guild_id = cmd.guild_id().unwrap();
+    check_group_limit(ctx, guild_id.get()).await?;
+
+    let attachment = group_yaml.download().await?;
+    // most problems here are mistakes in the yaml so let the person who sent it know
+    let (new_group, extra_roles, result_channels) =
+        match ChannelGroup::new_from_yaml(ctx, guild_id, &attachment).await {
+            Ok(g) => g,
+            Err(e) => {
+                return Err(MurahdahlaError::user(e));
+            }
+        };
+    insert_group(ctx, new_group, &extra_roles, &result_channels).await?;
+    publish_shared_cache(ctx).await;
+
+    Ok(())
+}
+
+async fn check_group_limit(ctx: &Context, this_server_id: u64) -> Result<(), MurahdahlaError> {
+    // let's check and make sure that no server has more than the configured number of
+    // groups for the sake of performance and not crashing the bot
     let num_groups: usize = {
         let data = ctx.data.read().await;
         let group_map = data
             .get::<GroupContainer>()
             .expect("No group container in share map");
-        group_map.len()
+        group_map
+            .values()
+            .filter(|g| g.server_id == this_server_id)
+            .count()
     };
-    if num_groups >= 10 {
-        return Err(anyhow!("Cannot add more than 10 groups per server").into());
+    let max_groups = get_config(ctx).await.max_groups_for(this_server_id);
+    if num_groups >= max_groups {
+        return Err(user_error!(
+            "Cannot add more than {} groups per server",
+            max_groups
+        ));
     }
 
-    let attachment = msg.attachments[0].download().await?;
-    let new_group = ChannelGroup::new_from_yaml(msg, ctx, &attachment).await?;
-    insert_into(channels).values(&new_group).execute(&conn)?;
+    Ok(())
+}
+
+async fn insert_group(
+    ctx: &Context,
+    new_group: ChannelGroup,
+    extra_roles: &[SpoilerRole],
+    result_channels: &[ResultChannel],
+) -> Result<(), MurahdahlaError> {
+    use crate::schema::channels::dsl::*;
+    use crate::schema::{result_channels as result_channels_table, spoiler_roles};
+
+    let conn = get_connection(ctx).await;
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        insert_into(channels).values(&new_group).execute(&conn)?;
+        insert_into(spoiler_roles::table)
+            .values(extra_roles)
+            .execute(&conn)?;
+        insert_into(result_channels_table::table)
+            .values(result_channels)
+            .execute(&conn)?;
+
+        Ok(())
+    })?;
     {
         let mut data = ctx.data.write().await;
         let submission_set = data
@@ -228,50 +987,279 @@ pub async fn addgroup(ctx: &Context, msg: &Message) -> CommandResult {
         group_map.insert(new_group.submission, new_group);
     }
 
-    msg.react(&ctx, ReactionType::try_from("👍")?).await?;
     Ok(())
 }
 
-#[command]
-pub async fn removegroup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+/// Remove a group along with all of its races
+#[poise::command(prefix_command, slash_command, guild_only, category = "Groups")]
+pub async fn removegroup(
+    cmd: CommandContext<'_>,
+    #[description = "The group's name"] group: String,
+) -> CommandResult {
+    use crate::schema::async_races::columns::race_active;
     use crate::schema::channels::columns::*;
     use crate::schema::channels::dsl::*;
 
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    let this_group_name = args.single_quoted::<String>()?;
-    let this_server_id = *msg.guild_id.unwrap().as_u64();
+    let ctx = cmd.serenity_context();
+    let this_group_name = group;
+    let this_server_id = cmd.guild_id().unwrap().get();
     let conn = get_connection(ctx).await;
     let this_group: ChannelGroup = channels
         .filter(server_id.eq(this_server_id))
         .filter(group_name.eq(&this_group_name))
         .get_result(&conn)?;
+    let active_races: Vec<AsyncRaceData> = AsyncRaceData::belonging_to(&this_group)
+        .filter(race_active.eq(true))
+        .load(&conn)?;
+
+    // removing a group takes all of its races and submissions with it so make the user
+    // confirm first
+    let mut prompt = cmd
+        .channel_id()
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(build_removegroup_prompt(&this_group_name, &active_races))
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new(CONFIRM_BUTTON_ID)
+                        .label("Remove")
+                        .style(ButtonStyle::Danger),
+                    CreateButton::new(CANCEL_BUTTON_ID)
+                        .label("Cancel")
+                        .style(ButtonStyle::Secondary),
+                ])]),
+        )
+        .await?;
+    let interaction = match prompt
+        .await_component_interaction(ctx)
+        .author_id(cmd.author().id)
+        .timeout(Duration::from_secs(CONFIRM_TIMEOUT_SECS))
+        .await
+    {
+        Some(i) => i,
+        None => {
+            prompt
+                .edit(
+                    ctx,
+                    EditMessage::new()
+                        .content(format!(
+                            "Timed out waiting for confirmation. Group \"{}\" was not removed.",
+                            &this_group_name
+                        ))
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    if interaction.data.custom_id != CONFIRM_BUTTON_ID {
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("Group \"{}\" was not removed.", &this_group_name))
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    // update the database first so a failure there leaves the group fully in place
+    diesel::delete(channels.filter(channel_group_id.eq(&this_group.channel_group_id)))
+        .execute(&conn)?;
     {
         let mut data = ctx.data.write().await;
-        let group_map = data
-            .get_mut::<GroupContainer>()
-            .expect("No group container in share map");
-        group_map
-            .remove(&this_group.submission)
-            .ok_or_else(|| anyhow!("Error removing group from share map"))?;
-        let submission_set = data
-            .get_mut::<SubmissionSet>()
-            .expect("No submission set in share map");
-        submission_set.remove(&this_group.submission);
+        data.get_mut::<GroupContainer>()
+            .expect("No group container in share map")
+            .remove(&this_group.submission);
+        data.get_mut::<SubmissionSet>()
+            .expect("No submission set in share map")
+            .remove(&this_group.submission);
     };
-    diesel::delete(
-        channels
-            .filter(group_name.eq(this_group.group_name))
-            .filter(server_id.eq(this_group.server_id)),
-    )
-    .execute(&conn)?;
+    publish_shared_cache(ctx).await;
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("Removed group \"{}\".", &this_group_name))
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// DM yourself this server's setup as a file for /importconfig
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn exportconfig(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let guild = cached_guild(ctx, cmd.guild_id().unwrap())
+        .ok_or_else(|| anyhow!("Server {} isn't in the cache", cmd.guild_id().unwrap()))?;
+    let config = export_server_config(ctx, &guild).await?;
+    let yaml = serde_yaml::to_string(&config)?;
+    cmd.author()
+        .direct_message(
+            ctx,
+            CreateMessage::new()
+                .content(
+                    "Attach this file to !importconfig on another server to set it up the same way.",
+                )
+                .add_file(CreateAttachment::bytes(yaml.into_bytes(), CONFIG_FILE_NAME)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Set this server up from a file made by /exportconfig
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn importconfig(
+    cmd: CommandContext<'_>,
+    #[description = "The file from /exportconfig"] config_file: Attachment,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if config_file.size > MAX_CONFIG_BYTES {
+        return Err(user_error!("Attached server config is too large"));
+    }
+    let bytes = config_file.download().await?;
+    let config: ServerConfigYaml = match serde_yaml::from_slice(&bytes) {
+        Ok(c) => c,
+        Err(e) => return Err(user_error!("Error reading server config: {}", e)),
+    };
+    let problems = import_server_config(ctx, cmd.guild_id().unwrap(), config).await?;
+    publish_shared_cache(ctx).await;
+    let mut report = match problems.is_empty() {
+        true => "Imported the server config.".to_owned(),
+        false => "Imported the server config except for:\n".to_owned(),
+    };
+    problems
+        .iter()
+        .take(MAX_IMPORT_PROBLEMS)
+        .for_each(|p| report.push_str(&format!("- {}\n", p)));
+    if problems.len() > MAX_IMPORT_PROBLEMS {
+        report.push_str(&format!(
+            "and {} more",
+            problems.len() - MAX_IMPORT_PROBLEMS
+        ));
+    }
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(report))
+        .await?;
 
     Ok(())
 }
 
-#[command]
-pub async fn listgroups(ctx: &Context, msg: &Message) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    let this_server_id = *msg.guild_id.unwrap().as_u64();
+async fn import_server_config(
+    ctx: &Context,
+    guild_id: GuildId,
+    config: ServerConfigYaml,
+) -> Result<Vec<String>, MurahdahlaError> {
+    // everything is applied the same way its own command would, and one part failing
+    // doesn't stop the rest. whatever went wrong is returned to tell the admin.
+    let guild = cached_guild(ctx, guild_id)
+        .ok_or_else(|| anyhow!("Server {} isn't in the cache", guild_id))?;
+    let mut problems: Vec<String> = Vec::new();
+    let roles = [
+        (RoleCommand::SetAdmin, "admin", &config.admin_role),
+        (RoleCommand::SetMod, "mod", &config.mod_role),
+    ];
+    for (command, label, role) in roles {
+        let role = match role {
+            Some(r) => r,
+            None => continue,
+        };
+        let result: Result<(), MurahdahlaError> = async {
+            let role_id = resolve_role(&guild, role)?;
+            set_server_role(ctx, guild_id, command, Some(role_id.get())).await
+        }
+        .await;
+        if let Err(e) = result {
+            problems.push(format!("{} role \"{}\": {}", label, role, e));
+        }
+    }
+    for (key, value) in config.settings.iter() {
+        let result: Result<()> = async {
+            let setting = ServerSetting::from_str(key)?;
+            let value = parse_setting_value(&guild, setting, value)?;
+            set_setting(ctx, guild_id, setting, Some(value)).await
+        }
+        .await;
+        if let Err(e) = result {
+            problems.push(format!("setting {}: {}", key, e));
+        }
+    }
+    for grant in config.command_grants.iter() {
+        let result: Result<()> = async {
+            validate_command_name(&grant.command)?;
+            let role_id = resolve_role(&guild, &grant.role)?;
+            set_command_grant(ctx, guild_id, &grant.command, role_id.get(), true).await
+        }
+        .await;
+        if let Err(e) = result {
+            problems.push(format!("{} for \"{}\": {}", grant.command, grant.role, e));
+        }
+    }
+    for (alias, cmd_name) in config.aliases.iter() {
+        let result: Result<()> = async {
+            let alias = clean_alias(alias)?;
+            if command_names().any(|c| c == alias) {
+                return Err(anyhow!("\"{}\" is already a command", alias));
+            }
+            if !command_names().any(|c| c == cmd_name) {
+                return Err(anyhow!("Unrecognized command \"{}\"", cmd_name));
+            }
+            set_alias(ctx, guild_id, &alias, Some(cmd_name)).await
+        }
+        .await;
+        if let Err(e) = result {
+            problems.push(format!("alias {}: {}", alias, e));
+        }
+    }
+    {
+        let conn = get_connection(ctx).await;
+        for t in config.templates.iter() {
+            let result = GameTemplate::new(guild_id.get(), &t.name, &t.fields, &t.format)
+                .and_then(|template| set_template(&conn, &template));
+            if let Err(e) = result {
+                problems.push(format!("template {}: {}", t.name, e));
+            }
+        }
+        for g in config.games.iter() {
+            let mut definition = g.clone();
+            let result = definition
+                .validate()
+                .and_then(|_| set_custom_game(&conn, guild_id.get(), &definition));
+            if let Err(e) = result {
+                problems.push(format!("game {}: {}", g.name, e));
+            }
+        }
+    }
+    for group_yaml in config.groups {
+        let name = group_yaml.group_name.clone();
+        let result: Result<(), MurahdahlaError> = async {
+            check_group_limit(ctx, guild_id.get()).await?;
+            let (new_group, extra_roles, result_channels) =
+                ChannelGroup::from_yaml(ctx, guild_id, group_yaml).await?;
+            insert_group(ctx, new_group, &extra_roles, &result_channels).await
+        }
+        .await;
+        if let Err(e) = result {
+            problems.push(format!("group \"{}\": {}", name, e));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// DM yourself the names of this server's groups
+#[poise::command(prefix_command, slash_command, guild_only, category = "Groups")]
+pub async fn listgroups(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let this_server_id = cmd.guild_id().unwrap().get();
     let group_names = {
         let data = ctx.data.read().await;
         let group_map = data
@@ -286,66 +1274,106 @@ pub async fn listgroups(ctx: &Context, msg: &Message) -> CommandResult {
         group_names
     };
     let group_string = build_listgroups_message(group_names);
-    msg.author
-        .direct_message(&ctx, |m| m.content(group_string))
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(group_string))
         .await?;
 
     Ok(())
 }
 
-#[command]
-pub async fn setadminrole(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    set_role_from_command(ctx, msg, args, Permission::Admin, ServerRoleAction::Add).await?;
+/// DM yourself which roles are this server's admin and mod roles
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn roles(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let this_server_id = cmd.guild_id().unwrap();
+    let server = {
+        let data = ctx.data.read().await;
+        *data
+            .get::<ServerContainer>()
+            .expect("No server container in share map")
+            .get(&this_server_id)
+            .unwrap() // the server will be here on account of the command check
+    };
+    let roles_string = {
+        let guild = cached_guild(ctx, this_server_id)
+            .ok_or_else(|| anyhow!("Server {} isn't in the cache", this_server_id))?;
+        let role_name = |r: Option<u64>| {
+            r.map(|id| match guild.roles.get(&RoleId::from(id)) {
+                Some(role) => role.name.clone(),
+                None => format!("(deleted role {})", id),
+            })
+        };
+        build_roles_message(
+            role_name(server.admin_role_id),
+            role_name(server.mod_role_id),
+        )
+    };
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(roles_string))
+        .await?;
 
     Ok(())
 }
 
-#[command]
-pub async fn setmodrole(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    set_role_from_command(ctx, msg, args, Permission::Admin, ServerRoleAction::Add).await?;
-
-    Ok(())
+/// Make a role this server's admin role
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn setadminrole(
+    cmd: CommandContext<'_>,
+    #[description = "The new admin role"] role: Role,
+) -> CommandResult {
+    set_role_from_command(cmd, Some(role), RoleCommand::SetAdmin).await
 }
 
-#[command]
-pub async fn removeadminrole(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    set_role_from_command(ctx, msg, args, Permission::Admin, ServerRoleAction::Remove).await?;
-
-    Ok(())
+/// Make a role this server's mod role
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn setmodrole(
+    cmd: CommandContext<'_>,
+    #[description = "The new mod role"] role: Role,
+) -> CommandResult {
+    set_role_from_command(cmd, Some(role), RoleCommand::SetMod).await
 }
 
-#[command]
-pub async fn removemodrole(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Admin).await?;
-    set_role_from_command(ctx, msg, args, Permission::Admin, ServerRoleAction::Remove).await?;
+/// Stop giving a role admin permissions
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn removeadminrole(cmd: CommandContext<'_>) -> CommandResult {
+    set_role_from_command(cmd, None, RoleCommand::RemoveAdmin).await
+}
 
-    Ok(())
+/// Stop giving a role mod permissions
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn removemodrole(cmd: CommandContext<'_>) -> CommandResult {
+    set_role_from_command(cmd, None, RoleCommand::RemoveMod).await
 }
 
-#[command]
-pub async fn removetime(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+/// Remove a runner's submission from the current race
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn removetime(
+    cmd: CommandContext<'_>,
+    #[description = "The name the runner submitted under"] runner: String,
+) -> CommandResult {
     use crate::schema::submissions::columns::*;
     use crate::schema::submissions::dsl::*;
 
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    if !in_submission_channel(ctx, msg).await {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
         return Ok(());
     }
-    if args.len() != 1 {
-        return Err(anyhow!("removetime command must have a single argument (runner name)").into());
-    }
-    let maybe_runner: &str = args.rest().trim_end();
+    let maybe_runner: &str = runner.trim_end();
 
-    let group_fut = get_group(ctx, msg);
+    let group_fut = get_group(ctx, cmd.channel_id());
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
     let race = match get_maybe_active_race(&conn, &group) {
         Some(r) => r,
         None => return Ok(()),
     };
+    // this is the runner we take spoiler access away from, not whoever ran the command
+    let this_runner_id: Option<u64> = submissions
+        .filter(race_id.eq(race.race_id))
+        .filter(runner_name.eq(maybe_runner))
+        .select(runner_id)
+        .first(&conn)
+        .optional()?;
     match diesel::delete(submissions)
         .filter(race_id.eq(race.race_id))
         .filter(runner_name.eq(maybe_runner))
@@ -360,277 +1388,2003 @@ pub async fn removetime(ctx: &Context, msg: &Message, args: Args) -> CommandResu
             .into())
         }
     };
-    let mut member = msg.member(&ctx).await?;
-    match &member.remove_role(&ctx, group.spoiler_role_id).await {
-        Ok(()) => (),
-        Err(e) => warn!(
-            "Error removing role for user \"{}\": {}",
-            &msg.author.name, e
-        ),
-    };
-    build_leaderboard(ctx, &group, &race, ChannelType::Leaderboard).await?;
+    if let Some(id) = this_runner_id {
+        let rules = get_spoiler_roles(&conn, &group)?;
+        if let Err(e) =
+            revoke_spoiler_access(&*ctx.http, &group, &rules, &race, UserId::from(id)).await
+        {
+            warn!(
+                "Error removing spoiler access for \"{}\": {}",
+                maybe_runner, e
+            );
+        }
+    }
+    emit(
+        ctx,
+        RaceEvent::TimeEdited {
+            group,
+            race,
+            runner_name: maybe_runner.to_owned(),
+        },
+    )
+    .await;
 
     Ok(())
 }
 
-#[command]
-pub async fn refresh(ctx: &Context, msg: &Message) -> CommandResult {
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    if !in_submission_channel(ctx, msg).await {
+/// Stop someone from submitting to this group
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn banrunner(
+    cmd: CommandContext<'_>,
+    #[description = "The runner to ban"] runner: User,
+    #[description = "Also remove everything they've submitted to this group"]
+    #[flag]
+    void: bool,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
         return Ok(());
     }
-    let group_fut = get_group(ctx, msg);
+    let runner = runner.id;
+
+    let group_fut = get_group(ctx, cmd.channel_id());
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
-
-    let maybe_active_race = get_maybe_active_race(&conn, &group);
-    match maybe_active_race {
-        Some(r) => build_leaderboard(ctx, &group, &r, ChannelType::Leaderboard).await?,
-        None => return Ok(()),
-    };
+    ban_runner(&conn, &group, runner.get())?;
+    info!(
+        "Banned user {} from group \"{}\"",
+        runner, &group.group_name
+    );
+    if !void {
+        return Ok(());
+    }
+    let voided = void_submissions(&conn, &group, runner.get())?;
+    info!("Voided {} submissions from user {}", voided, runner);
+    if let Some(race) = get_maybe_active_race(&conn, &group) {
+        let rules = get_spoiler_roles(&conn, &group)?;
+        if let Err(e) = revoke_spoiler_access(&*ctx.http, &group, &rules, &race, runner).await {
+            warn!("Error removing spoiler access for user {}: {}", runner, e);
+        }
+        build_leaderboard(ctx, &group, &race, ChannelType::Leaderboard).await?;
+    }
 
     Ok(())
 }
 
-#[command]
-pub async fn settime(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    use crate::schema::submissions::columns::*;
-    // we could and should write a command that will change an entire submission based on
-    // game, especially if we get games were people will be using any optional, non
-    // collection rate fields etc. but for now a command that simply changes the time
-    // is sufficient.
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    if !in_submission_channel(ctx, msg).await {
+/// Give someone spoiler access to the current race without a submission
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn grantaccess(
+    cmd: CommandContext<'_>,
+    #[description = "Who to give access to"] user: User,
+) -> CommandResult {
+    use crate::schema::submissions::columns::runner_id;
+
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
         return Ok(());
     }
+    let grantee = user.id;
 
-    let group_fut = get_group(ctx, msg);
+    let group_fut = get_group(ctx, cmd.channel_id());
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
     let race = match get_maybe_active_race(&conn, &group) {
         Some(r) => r,
-        None => return Ok(()),
+        None => return Err(user_error!("There is no active race to grant access to")),
     };
-    if args.len() != 2 {
-        return Err(
-            anyhow!("settime command requires two arguments (runner name and new time)").into(),
-        );
-    }
-    //
-    let maybe_runner = args.single::<String>()?;
-    let maybe_time = args.single::<String>()?;
-    let new_time = parse_variable_time(&maybe_time)?;
-    let submission: Submission = match Submission::belonging_to(&race)
-        .filter(runner_name.eq(&maybe_runner))
+    let existing: Option<Submission> = Submission::belonging_to(&race)
+        .filter(runner_id.eq(grantee.get()))
         .first(&conn)
-    {
+        .optional()?;
+    if existing.is_some() {
+        return Err(user_error!(
+            "User {} already has a submission in this race",
+            grantee
+        ));
+    }
+    let grant = access_grant(grantee.get(), &user.name, &race);
+    let rules = get_spoiler_roles(&conn, &group)?;
+    let role_fut = grant_spoiler_access(&*ctx.http, &group, &rules, &race, grantee);
+    let pool = get_db_pool(ctx).await;
+    write_submission_add_role(&pool, &grant, role_fut).await?;
+    info!("Granted spoiler access to \"{}\"", &user.name);
+
+    Ok(())
+}
+
+/// Take this group's spoiler roles away from everyone
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn clearspoilers(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let roles = clearable_roles(group.spoiler_role_id, &get_spoiler_roles(&conn, &group)?);
+    drop(conn);
+    if roles.is_empty() {
+        return Err(user_error!("This group doesn't give out spoiler roles"));
+    }
+    clear_spoiler_roles(ctx, &group, &roles, cmd.channel_id()).await?;
+
+    Ok(())
+}
+
+/// Add a note to a runner's submission in the current race
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn note(
+    cmd: CommandContext<'_>,
+    #[description = "The runner"] runner: User,
+    #[description = "The note"]
+    #[rest]
+    text: String,
+) -> CommandResult {
+    use crate::schema::submissions::columns::runner_id;
+
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let runner = runner.id;
+
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => return Err(user_error!("There is no active race to add a note to")),
+    };
+    let submission: Submission = match Submission::belonging_to(&race)
+        .filter(runner_id.eq(runner.get()))
+        .first(&conn)
+        .optional()?
+    {
+        Some(s) => s,
+        None => {
+            return Err(user_error!(
+                "User {} has no submission in this race",
+                runner
+            ))
+        }
+    };
+    let new_note = NewSubmissionNote::new(
+        &submission,
+        cmd.author().id.get(),
+        &cmd.author().name,
+        &text,
+    )?;
+    add_note(&conn, &new_note)?;
+    info!(
+        "Added note to \"{}\"'s submission in race {}",
+        &submission.runner_name, race.race_id
+    );
+
+    Ok(())
+}
+
+/// DM yourself the notes on the current race's submissions
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn notes(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => return Err(user_error!("There is no active race to get notes for")),
+    };
+    let noted = get_race_notes(&conn, &race)?;
+    for content in build_notes_messages(&noted) {
+        cmd.author()
+            .direct_message(ctx, CreateMessage::new().content(content))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Let a banned runner submit to this group again
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn unbanrunner(
+    cmd: CommandContext<'_>,
+    #[description = "The runner to unban"] runner: User,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let runner = runner.id;
+
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    unban_runner(&conn, &group, runner.get())?;
+    info!(
+        "Unbanned user {} from group \"{}\"",
+        runner, &group.group_name
+    );
+
+    Ok(())
+}
+
+/// Rebuild the current race's leaderboard
+#[poise::command(prefix_command, slash_command, guild_only, category = "Races")]
+pub async fn refresh(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+
+    let maybe_active_race = get_maybe_active_race(&conn, &group);
+    match maybe_active_race {
+        Some(r) => build_leaderboard(ctx, &group, &r, ChannelType::Leaderboard).await?,
+        None => return Ok(()),
+    };
+
+    Ok(())
+}
+
+/// Read submissions from this channel's recent messages that the bot missed
+#[poise::command(prefix_command, slash_command, guild_only, category = "Races")]
+pub async fn backfill(
+    cmd: CommandContext<'_>,
+    #[description = "How many messages to look back through"] count: u8,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    // anything still in the channel wasn't handled when it was posted since the message
+    // handler deletes submissions once they're in. we run these through the handler's
+    // logic oldest first so duplicates are caught the same way.
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    if count == 0 || count > MAX_BACKFILL {
+        return Err(user_error!(
+            "Message count must be between 1 and {}",
+            MAX_BACKFILL
+        ));
+    }
+
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => return Err(user_error!("There is no active race to backfill")),
+    };
+    let race_post_id = get_race_post_id(&conn, race.race_id)?.unwrap_or(0);
+    drop(conn);
+    let policy = get_message_policy(ctx, &group).await;
+
+    let mut history = cmd
+        .channel_id()
+        .messages(
+            ctx,
+            GetMessages::new()
+                .before(MessageId::from(cmd.id()))
+                .limit(count),
+        )
+        .await?;
+    // discord gives us the newest messages first
+    history.retain(|m| m.id.get() > race_post_id && !m.author.bot);
+    history.reverse();
+    let mut processed: usize = 0;
+    for m in history.iter() {
+        if is_command_message(ctx, &group, m).await {
+            continue;
+        }
+        enqueue_history(ctx, m, &group, &race, policy).await;
+        processed += 1;
+    }
+    info!(
+        "Queued {} messages for backfill in group \"{}\"",
+        processed, &group.group_name
+    );
+
+    Ok(())
+}
+
+/// Change a runner's time in the current race
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn settime(
+    cmd: CommandContext<'_>,
+    #[description = "The name the runner submitted under"] runner: String,
+    #[description = "The new time"] time: String,
+) -> CommandResult {
+    use crate::schema::submissions::columns::*;
+    // we could and should write a command that will change an entire submission based on
+    // game, especially if we get games were people will be using any optional, non
+    // collection rate fields etc. but for now a command that simply changes the time
+    // is sufficient.
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    if race.race_sort == LeaderboardSort::Completion {
+        return Err(user_error!("This race is untimed"));
+    }
+    let new_time = parse_variable_time(&time).map_err(MurahdahlaError::user)?;
+    let submission: Submission = match Submission::belonging_to(&race)
+        .filter(runner_name.eq(&runner))
+        .first(&conn)
+    {
         Ok(s) => s,
         Err(_) => {
-            return Err(anyhow!(
+            return Err(user_error!(
                 "Could not find submission for runner \"{}\" in this race",
-                &maybe_runner
+                &runner
+            ))
+        }
+    };
+    diesel::update(&submission)
+        .set(runner_time_ms.eq(new_time))
+        .execute(&conn)?;
+    emit(
+        ctx,
+        RaceEvent::TimeEdited {
+            group,
+            race,
+            runner_name: runner,
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Change a runner's collection rate in the current race
+#[poise::command(prefix_command, slash_command, guild_only, category = "Runners")]
+pub async fn setcollection(
+    cmd: CommandContext<'_>,
+    #[description = "The name the runner submitted under"] runner: String,
+    #[description = "The new collection rate"] collection: u16,
+) -> CommandResult {
+    use crate::schema::submissions::columns::*;
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    let submission: Submission = match Submission::belonging_to(&race)
+        .filter(runner_name.eq(&runner))
+        .first(&conn)
+    {
+        Ok(s) => s,
+        Err(_) => {
+            return Err(user_error!(
+                "Could not find submission for runner \"{}\" in this race",
+                &runner
+            ))
+        }
+    };
+    diesel::update(&submission)
+        .set(runner_collection.eq(collection))
+        .execute(&conn)?;
+    emit(
+        ctx,
+        RaceEvent::TimeEdited {
+            group,
+            race,
+            runner_name: runner,
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Reload everything the bot keeps in memory from the database
+#[poise::command(prefix_command, slash_command, category = "Maintenance")]
+pub async fn reloadcache(cmd: CommandContext<'_>) -> CommandResult {
+    // re-reads everything we keep in the share map from the database. this is for
+    // recovering after the database has been edited by hand without restarting the bot.
+    check_maintenance_user(cmd.author()).map_err(MurahdahlaError::user)?;
+    reload_caches(cmd.serenity_context()).await?;
+    info!("Reloaded share map caches from database");
+
+    Ok(())
+}
+
+/// Read the secrets again and restart if the token changed
+#[poise::command(prefix_command, slash_command, category = "Maintenance")]
+pub async fn rotatesecrets(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    check_maintenance_user(cmd.author()).map_err(MurahdahlaError::user)?;
+    let rotation = rotate_secrets(&ctx.data).await?;
+    info!("{}", rotation);
+    // reply before a token change takes this client down with it
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(rotation.to_string()))
+        .await?;
+    if rotation.token {
+        restart_client(&ctx.data).await;
+    }
+
+    Ok(())
+}
+
+/// DM yourself how far behind each submission channel is
+#[poise::command(prefix_command, slash_command, category = "Maintenance")]
+pub async fn cursors(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    check_maintenance_user(cmd.author()).map_err(MurahdahlaError::user)?;
+    let lags = get_channel_lags(ctx).await?;
+    let cursors_string = build_cursors_message(&lags);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(cursors_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Read the config file again
+#[poise::command(prefix_command, slash_command, category = "Maintenance")]
+pub async fn reloadconfig(cmd: CommandContext<'_>) -> CommandResult {
+    check_maintenance_user(cmd.author()).map_err(MurahdahlaError::user)?;
+    let config = BotConfig::load()?;
+    {
+        let mut data = cmd.serenity_context().data.write().await;
+        data.insert::<ConfigContainer>(config);
+    }
+    info!("Reloaded config file");
+
+    Ok(())
+}
+
+/// Change one of this server's settings
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn setsetting(
+    cmd: CommandContext<'_>,
+    #[description = "The setting to change"] setting: String,
+    #[description = "Its new value"]
+    #[rest]
+    value: String,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let guild_id = cmd.guild_id().unwrap();
+    let key = ServerSetting::from_str(&setting).map_err(MurahdahlaError::user)?;
+    let value = {
+        let guild = cached_guild(ctx, guild_id)
+            .ok_or_else(|| anyhow!("Server {} isn't in the cache", guild_id))?;
+        parse_setting_value(&guild, key, &value).map_err(MurahdahlaError::user)?
+    };
+    set_setting(ctx, guild_id, key, Some(value)).await?;
+
+    Ok(())
+}
+
+/// Put one of this server's settings back to the default
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn unsetsetting(
+    cmd: CommandContext<'_>,
+    #[description = "The setting to reset"] setting: String,
+) -> CommandResult {
+    let key = ServerSetting::from_str(&setting).map_err(MurahdahlaError::user)?;
+    set_setting(cmd.serenity_context(), cmd.guild_id().unwrap(), key, None).await?;
+
+    Ok(())
+}
+
+/// DM yourself this server's settings
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn settings(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let guild_id = cmd.guild_id().unwrap();
+    let server_settings = {
+        let data = ctx.data.read().await;
+        data.get::<SettingsContainer>()
+            .expect("No settings container in share map")
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let settings_string = build_settings_message(&server_settings);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(settings_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Let a role run a command it otherwise couldn't
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn allowcommand(
+    cmd: CommandContext<'_>,
+    #[description = "The command"] command: String,
+    #[description = "The role to allow it for"] role: Role,
+) -> CommandResult {
+    // lets members with a role run a command they otherwise wouldn't have permission for
+    let cmd_name = command.to_lowercase();
+    validate_command_name(&cmd_name).map_err(MurahdahlaError::user)?;
+    let guild_id = cmd.guild_id().unwrap();
+    set_command_grant(
+        cmd.serenity_context(),
+        guild_id,
+        &cmd_name,
+        role.id.get(),
+        true,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Take back a command given to a role with /allowcommand
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn denycommand(
+    cmd: CommandContext<'_>,
+    #[description = "The command"] command: String,
+    #[description = "The role to take it back from"] role: Role,
+) -> CommandResult {
+    let cmd_name = command.to_lowercase();
+    validate_command_name(&cmd_name).map_err(MurahdahlaError::user)?;
+    let guild_id = cmd.guild_id().unwrap();
+    set_command_grant(
+        cmd.serenity_context(),
+        guild_id,
+        &cmd_name,
+        role.id.get(),
+        false,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// DM yourself who can run each command on this server
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn permissions(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let guild_id = cmd.guild_id().unwrap();
+    let server_grants = {
+        let data = ctx.data.read().await;
+        data.get::<GrantContainer>()
+            .expect("No grant container in share map")
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let permission_lines: Vec<(String, Permission, Vec<String>)> = {
+        let guild = cached_guild(ctx, guild_id)
+            .ok_or_else(|| anyhow!("Server {} isn't in the cache", guild_id))?;
+        command_names()
+            .map(|c| {
+                let role_names: Vec<String> = server_grants
+                    .get(c)
+                    .map(|roles| {
+                        roles
+                            .iter()
+                            .filter_map(|r| guild.roles.get(&RoleId::from(*r)))
+                            .map(|r| r.name.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (c.to_owned(), required_permission(c), role_names)
+            })
+            .collect()
+    };
+    let permissions_string = build_permissions_message(&permission_lines);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(permissions_string))
+        .await?;
+
+    Ok(())
+}
+
+/// DM yourself a group's most recent races
+#[poise::command(prefix_command, slash_command, guild_only, category = "Groups")]
+pub async fn listraces(
+    cmd: CommandContext<'_>,
+    #[description = "The group's name"] group: String,
+) -> CommandResult {
+    use crate::schema::async_races::columns::race_id;
+    use crate::schema::channels::columns::*;
+    use crate::schema::channels::dsl::channels;
+
+    let ctx = cmd.serenity_context();
+    let this_server_id = cmd.guild_id().unwrap().get();
+    let conn = get_connection(ctx).await;
+    let this_group: ChannelGroup = channels
+        .filter(server_id.eq(this_server_id))
+        .filter(group_name.eq(&group))
+        .get_result(&conn)?;
+    let races: Vec<AsyncRaceData> = AsyncRaceData::belonging_to(&this_group)
+        .order(race_id.desc())
+        .limit(LIST_RACES_LIMIT)
+        .load(&conn)?;
+    let races_string = build_listraces_message(&races);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(races_string))
+        .await?;
+
+    Ok(())
+}
+
+/// DM yourself which settings this server's races have used
+#[poise::command(prefix_command, slash_command, guild_only, category = "Groups")]
+pub async fn settingsstats(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let this_server_id = cmd.guild_id().unwrap().get();
+    let stats = {
+        let conn = get_connection(ctx).await;
+        let (races, submissions) = load_server_history(&conn, this_server_id)?;
+        settings_stats(&races, &submissions)
+    };
+    for content in build_settingsstats_messages(&stats) {
+        cmd.author()
+            .direct_message(ctx, CreateMessage::new().content(content))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// DM yourself when a group's runners submit during the week
+#[poise::command(prefix_command, slash_command, guild_only, category = "Groups")]
+pub async fn activity(
+    cmd: CommandContext<'_>,
+    #[description = "The group's name"] group: String,
+    #[description = "How many of the latest races to look at"] races: Option<i64>,
+) -> CommandResult {
+    use crate::schema::channels::columns::*;
+    use crate::schema::channels::dsl::channels;
+
+    let ctx = cmd.serenity_context();
+    let races: i64 = match races {
+        Some(n) if (1..=MAX_ACTIVITY_RACES).contains(&n) => n,
+        Some(_) => {
+            return Err(user_error!(
+                "The number of races has to be between 1 and {}",
+                MAX_ACTIVITY_RACES
+            ));
+        }
+        None => ACTIVITY_RACES,
+    };
+    let this_server_id = cmd.guild_id().unwrap().get();
+    let conn = get_connection(ctx).await;
+    let this_group: ChannelGroup = match channels
+        .filter(server_id.eq(this_server_id))
+        .filter(group_name.eq(&group))
+        .get_result(&conn)
+        .optional()?
+    {
+        Some(g) => g,
+        None => {
+            return Err(user_error!("There's no group named \"{}\"", group));
+        }
+    };
+    let times = load_recent_submission_times(&conn, &this_group, races)?;
+    let activity_string = MessageBuilder::new()
+        .push_bold_line(format!(
+            "Submissions for {} over the last {} races",
+            this_group.group_name, races
+        ))
+        .push_codeblock_safe(heatmap_text(&activity_heatmap(&times)), None)
+        .build();
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(activity_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Add a template for races of games the bot doesn't know
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn addtemplate(
+    cmd: CommandContext<'_>,
+    #[description = "The template's name"] name: String,
+    #[description = "The fields runners submit"] fields: String,
+    #[description = "How the race post is written"]
+    #[rest]
+    format: String,
+) -> CommandResult {
+    let format = format.trim_matches('"');
+    let template = GameTemplate::new(cmd.guild_id().unwrap().get(), &name, &fields, format)
+        .map_err(MurahdahlaError::user)?;
+    let conn = get_connection(cmd.serenity_context()).await;
+    set_template(&conn, &template)?;
+
+    Ok(())
+}
+
+/// Remove a race template
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn removetemplate(
+    cmd: CommandContext<'_>,
+    #[description = "The template's name"] name: String,
+) -> CommandResult {
+    let conn = get_connection(cmd.serenity_context()).await;
+    remove_template(&conn, cmd.guild_id().unwrap().get(), &name).map_err(MurahdahlaError::user)?;
+
+    Ok(())
+}
+
+/// DM yourself this server's race templates
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn templates(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let conn = get_connection(ctx).await;
+    let server_templates = get_templates(&conn, cmd.guild_id().unwrap().get())?;
+    let templates_string = build_templates_message(&server_templates);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(templates_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Add a game from a yaml definition
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn addgame(
+    cmd: CommandContext<'_>,
+    #[description = "The game's yaml definition"] definition: Attachment,
+) -> CommandResult {
+    if definition.size > MAX_GAME_DEFINITION_BYTES {
+        return Err(user_error!("Game definition is too large"));
+    }
+    let yaml = definition.download().await?;
+    let definition = match CustomGameDefinition::from_yaml(&yaml) {
+        Ok(d) => d,
+        Err(e) => {
+            return Err(MurahdahlaError::user(e));
+        }
+    };
+    let conn = get_connection(cmd.serenity_context()).await;
+    if let Err(e) = set_custom_game(&conn, cmd.guild_id().unwrap().get(), &definition) {
+        return Err(MurahdahlaError::user(e));
+    }
+
+    Ok(())
+}
+
+/// Remove a game added with /addgame
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn removegame(
+    cmd: CommandContext<'_>,
+    #[description = "The game's name"] name: String,
+) -> CommandResult {
+    let conn = get_connection(cmd.serenity_context()).await;
+    remove_custom_game(&conn, cmd.guild_id().unwrap().get(), &name)
+        .map_err(MurahdahlaError::user)?;
+
+    Ok(())
+}
+
+/// DM yourself the games added to this server
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn games(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let conn = get_connection(ctx).await;
+    let server_games = get_custom_games(&conn, cmd.guild_id().unwrap().get())?;
+    let games_string = build_custom_games_message(&server_games);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(games_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Add a preset to roll seeds from in this group
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn addpreset(
+    cmd: CommandContext<'_>,
+    #[description = "The preset's name"] name: String,
+    #[description = "The game it rolls seeds for"] game: String,
+    #[description = "The settings as a file"] settings_file: Option<Attachment>,
+    #[description = "The settings"]
+    #[rest]
+    settings: Option<String>,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let settings = match (settings.as_deref().map(str::trim), settings_file) {
+        (Some(s), _) if !s.is_empty() => s.to_owned(),
+        (_, Some(a)) if a.size as usize > MAX_PRESET_SETTINGS_BYTES => {
+            return Err(user_error!("Preset settings are too large"));
+        }
+        (_, Some(a)) => String::from_utf8(a.download().await?).map_err(MurahdahlaError::user)?,
+        (_, None) => {
+            return Err(user_error!("!addpreset needs settings or one attachment"));
+        }
+    };
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let preset = GroupPreset::new(&group.channel_group_id, &name, &game, &settings)
+        .map_err(MurahdahlaError::user)?;
+    set_preset(&conn, &preset).map_err(MurahdahlaError::user)?;
+
+    Ok(())
+}
+
+/// Remove one of this group's presets
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn removepreset(
+    cmd: CommandContext<'_>,
+    #[description = "The preset's name"] name: String,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    remove_preset(&conn, &group, &name).map_err(MurahdahlaError::user)?;
+
+    Ok(())
+}
+
+/// DM yourself the presets this group can roll
+#[poise::command(prefix_command, slash_command, guild_only, category = "Games")]
+pub async fn listpresets(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let group_presets = get_presets(&conn, &group)?;
+    drop(conn);
+    let config = get_config(ctx).await;
+    let presets_string = build_presets_message(&group_presets, &config.roll_presets);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(presets_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Add another name for a command on this server
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn addalias(
+    cmd: CommandContext<'_>,
+    #[description = "The new name"] alias: String,
+    #[description = "The command it runs"] command: String,
+) -> CommandResult {
+    // aliases run the command they point to with its usual permissions, so there's
+    // nothing extra to check here besides not shadowing a real command
+    let alias = clean_alias(&alias).map_err(MurahdahlaError::user)?;
+    let cmd_name = command.to_lowercase();
+    if command_names().any(|c| c == alias) {
+        return Err(user_error!("\"{}\" is already a command", alias));
+    }
+    if !command_names().any(|c| c == cmd_name) {
+        return Err(user_error!("Unrecognized command \"{}\"", cmd_name));
+    }
+    set_alias(
+        cmd.serenity_context(),
+        cmd.guild_id().unwrap(),
+        &alias,
+        Some(&cmd_name),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Remove a command alias
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn removealias(
+    cmd: CommandContext<'_>,
+    #[description = "The alias"] alias: String,
+) -> CommandResult {
+    let alias = alias.to_lowercase();
+    set_alias(
+        cmd.serenity_context(),
+        cmd.guild_id().unwrap(),
+        &alias,
+        None,
+    )
+    .await
+    .map_err(MurahdahlaError::user)?;
+
+    Ok(())
+}
+
+/// DM yourself this server's command aliases
+#[poise::command(prefix_command, slash_command, guild_only, category = "Server")]
+pub async fn aliases(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let server_aliases = {
+        let data = ctx.data.read().await;
+        data.get::<AliasContainer>()
+            .expect("No alias container in share map")
+            .get(&cmd.guild_id().unwrap())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let aliases_string = build_aliases_message(&server_aliases);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(aliases_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Delete a stopped race and everything that belongs to it
+#[poise::command(prefix_command, slash_command, guild_only, category = "Races")]
+pub async fn deleterace(
+    cmd: CommandContext<'_>,
+    #[description = "The race's id"] race_id: u32,
+) -> CommandResult {
+    // removes a race created by mistake along with everything that belongs to it
+    use crate::schema::async_races;
+    use crate::schema::channels;
+    use crate::schema::messages;
+    use crate::schema::submissions;
+
+    let ctx = cmd.serenity_context();
+    let this_race_id = race_id;
+    let this_server_id = cmd.guild_id().unwrap().get();
+    let conn = get_connection(ctx).await;
+    // make sure the race belongs to this server before we touch it
+    let race: AsyncRaceData = match async_races::table
+        .inner_join(channels::table)
+        .filter(async_races::race_id.eq(this_race_id))
+        .filter(channels::server_id.eq(this_server_id))
+        .select(async_races::all_columns)
+        .first(&conn)
+    {
+        Ok(r) => r,
+        Err(_) => {
+            return Err(user_error!(
+                "No race with id {} in this server",
+                this_race_id
+            ))
+        }
+    };
+    if race.race_active {
+        return Err(user_error!(
+            "Race {} is still active and must be stopped first",
+            race.race_id
+        ));
+    }
+
+    let bot_messages: Vec<BotMessage> = BotMessage::belonging_to(&race).load(&conn)?;
+    // delete children explicitly instead of relying on the foreign keys cascading since
+    // older databases may not have cascading deletes on every table
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::delete(submissions::table.filter(submissions::race_id.eq(race.race_id)))
+            .execute(&conn)?;
+        diesel::delete(messages::table.filter(messages::race_id.eq(race.race_id)))
+            .execute(&conn)?;
+        diesel::delete(&race).execute(&conn)?;
+
+        Ok(())
+    })?;
+    for m in bot_messages.iter() {
+        ctx.http
+            .delete_message(
+                ChannelId::from(m.channel_id),
+                MessageId::from(m.message_id),
+                None,
             )
-            .into())
+            .await
+            .unwrap_or_else(|e| warn!("Error deleting message for race {}: {}", race.race_id, e));
+    }
+    info!(
+        "Deleted race {} from server {}",
+        race.race_id, this_server_id
+    );
+
+    Ok(())
+}
+
+/// DM yourself the current race's details
+#[poise::command(prefix_command, slash_command, guild_only, category = "Racing")]
+pub async fn raceinfo(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    // runs in the submission channel so runners don't have to scroll back through a
+    // week of chat for the race post
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
+        return Ok(());
+    }
+    let group_fut = get_group(ctx, cmd.channel_id());
+    let conn_fut = get_connection(ctx);
+    let (group, conn) = join!(group_fut, conn_fut);
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => {
+            return Err(user_error!("There is no race running in this channel"));
         }
     };
-    diesel::update(&submission)
-        .set(runner_time.eq(new_time))
-        .execute(&conn)?;
-    build_leaderboard(ctx, &group, &race, ChannelType::Leaderboard).await?;
+    let participants: i64 = Submission::belonging_to(&race).count().get_result(&conn)?;
+    let race_post = get_race_post(&conn, &race)?;
+    let locale = get_locale(ctx, GuildId::from(group.server_id)).await;
+    let info_string =
+        build_raceinfo_message(&group, &race, participants, race_post.as_ref(), locale);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(info_string))
+        .await?;
 
     Ok(())
 }
 
-#[command]
-pub async fn setcollection(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    use crate::schema::submissions::columns::*;
-    check_permissions(ctx, msg, Permission::Mod).await?;
-    if !in_submission_channel(ctx, msg).await {
+/// Get the seed by DM and start your time for this race
+#[poise::command(prefix_command, slash_command, guild_only, category = "Racing")]
+pub async fn claim(cmd: CommandContext<'_>) -> CommandResult {
+    use crate::schema::submissions::columns::runner_id;
+
+    let ctx = cmd.serenity_context();
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
         return Ok(());
     }
-
-    let group_fut = get_group(ctx, msg);
+    let group_fut = get_group(ctx, cmd.channel_id());
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
     let race = match get_maybe_active_race(&conn, &group) {
-        Some(r) => r,
-        None => return Ok(()),
+        Some(r) if r.race_claim => r,
+        Some(_) => {
+            return Err(user_error!("This race doesn't need the seed to be claimed"));
+        }
+        None => {
+            return Err(user_error!("There is no race running in this channel"));
+        }
     };
-    if args.len() != 2 {
-        return Err(anyhow!(
-            "setcollection command requires two arguments (runner name and new collection rate)"
-        )
-        .into());
+    let this_runner_id = cmd.author().id.get();
+    let submitted: i64 = Submission::belonging_to(&race)
+        .filter(runner_id.eq(this_runner_id))
+        .count()
+        .get_result(&conn)?;
+    if submitted > 0 {
+        return Err(user_error!("You've already submitted to this race"));
     }
-    //
-    let maybe_runner = args.single::<String>()?;
-    let maybe_collection = args.single::<String>()?;
-    let new_collection = u16::from_str(&maybe_collection)?;
-    let submission: Submission = match Submission::belonging_to(&race)
-        .filter(runner_name.eq(&maybe_runner))
-        .first(&conn)
+
+    // claiming again just sends the seed again. the time still starts from the first claim.
+    let (claim, is_new) = match get_claim(&conn, &race, this_runner_id)? {
+        Some(c) => (c, false),
+        None => (SeedClaim::new(&race, this_runner_id), true),
+    };
+    let claim_string = build_claim_message(&group, &race, &claim);
+    // the claim is only saved once the seed was sent so a runner with DMs closed isn't
+    // timed for a seed they never got
+    if let Err(e) = cmd
+        .author()
+        .direct_message(ctx, CreateMessage::new().content(claim_string))
+        .await
     {
-        Ok(s) => s,
-        Err(_) => {
-            return Err(anyhow!(
-                "Could not find submission for runner \"{}\" in this race",
-                &maybe_runner
-            )
-            .into())
+        return Err(user_error!(
+            "I couldn't DM you the seed ({}). Check that you allow DMs from this server",
+            e
+        ));
+    }
+    if is_new {
+        add_claim(&conn, &claim)?;
+        info!(
+            "User \"{}\" claimed the seed for race {}",
+            cmd.author().name,
+            race.race_id
+        );
+    }
+
+    Ok(())
+}
+
+/// Start your timer for an RTA race
+#[poise::command(prefix_command, slash_command, category = "Racing")]
+pub async fn startrun(
+    cmd: CommandContext<'_>,
+    #[description = "The group, if you're in more than one with an RTA race running"]
+    #[rest]
+    group: Option<String>,
+) -> CommandResult {
+    use crate::schema::submissions::columns::runner_id;
+
+    let ctx = cmd.serenity_context();
+    let (group, race) = match cmd.guild_id() {
+        Some(_) => {
+            if !in_submission_channel(ctx, cmd.channel_id()).await {
+                return Ok(());
+            }
+            let group = get_group(ctx, cmd.channel_id()).await;
+            let conn = get_connection(ctx).await;
+            match get_maybe_active_race(&conn, &group) {
+                Some(r) => (group, r),
+                None => {
+                    return Err(user_error!("There is no race running in this channel"));
+                }
+            }
+        }
+        None => {
+            let group_name = group.as_deref().map(str::trim).filter(|n| !n.is_empty());
+            let mut races = timer_races(ctx, cmd.author().id, group_name).await?;
+            match races.len() {
+                0 => {
+                    return Err(user_error!(
+                        "There's no RTA race running that you can start a timer for"
+                    ));
+                }
+                1 => races.remove(0),
+                _ => {
+                    let names: Vec<&str> =
+                        races.iter().map(|(g, _)| g.group_name.as_str()).collect();
+                    return Err(user_error!(
+                        "You're in more than one group with an RTA race running. Use \
+                        \"!startrun [group name]\" with one of: {}",
+                        names.join(", ")
+                    ));
+                }
+            }
         }
     };
-    diesel::update(&submission)
-        .set(runner_collection.eq(new_collection))
-        .execute(&conn)?;
-    build_leaderboard(ctx, &group, &race, ChannelType::Leaderboard).await?;
+    if race.race_claim {
+        return Err(user_error!(
+            "This race is timed from !claim. Use that to get the seed"
+        ));
+    }
+    if race.race_live_at.is_some() {
+        return Err(user_error!(
+            "Live races are timed from the countdown, no timer needed"
+        ));
+    }
+    if race.race_type != RaceType::RTA {
+        return Err(user_error!(
+            "Timers are only for RTA races. Submit your in-game time instead"
+        ));
+    }
+    let this_runner_id = cmd.author().id.get();
+    let conn = get_connection(ctx).await;
+    let submitted: i64 = Submission::belonging_to(&race)
+        .filter(runner_id.eq(this_runner_id))
+        .count()
+        .get_result(&conn)?;
+    if submitted > 0 {
+        return Err(user_error!("You've already submitted to this race"));
+    }
+    // a timer can't be restarted, same as a claimed seed
+    if let Some(c) = get_claim(&conn, &race, this_runner_id)? {
+        return Err(user_error!(
+            "Your timer for this race already started at {} UTC",
+            c.claimed_at.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+    let claim = SeedClaim::new(&race, this_runner_id);
+    add_claim(&conn, &claim)?;
+    info!(
+        "User \"{}\" started a timer in group \"{}\"",
+        cmd.author().name,
+        group.group_name
+    );
+    let timer_string = build_timer_message(&claim);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(timer_string))
+        .await?;
 
     Ok(())
 }
 
-async fn set_role_from_command(
-    ctx: &Context,
-    msg: &Message,
-    args: Args,
-    role_type: Permission,
-    role_action: ServerRoleAction,
-) -> Result<(), BoxedError> {
-    use crate::schema::servers::columns::*;
-    use crate::schema::servers::dsl::*;
-
-    let role_id: Option<u64> = match role_action {
-        ServerRoleAction::Add => Some(parse_role(ctx, msg, args).await?),
-        ServerRoleAction::Remove => None,
+/// Stop your timer and submit
+#[poise::command(prefix_command, slash_command, category = "Racing")]
+pub async fn done(
+    cmd: CommandContext<'_>,
+    #[description = "Anything else your submission needs, like a collection rate"]
+    #[rest]
+    extra: Option<String>,
+) -> CommandResult {
+    use crate::schema::submissions::columns::runner_id;
+
+    let ctx = cmd.serenity_context();
+    let this_runner_id = cmd.author().id.get();
+    let (group, race) = match cmd.guild_id() {
+        Some(_) => {
+            if !in_submission_channel(ctx, cmd.channel_id()).await {
+                return Ok(());
+            }
+            let group = get_group(ctx, cmd.channel_id()).await;
+            let conn = get_connection(ctx).await;
+            match get_maybe_active_race(&conn, &group) {
+                Some(r) => (group, r),
+                None => {
+                    return Err(user_error!("There is no race running in this channel"));
+                }
+            }
+        }
+        None => {
+            let mut races = {
+                let conn = get_connection(ctx).await;
+                running_timers(&conn, this_runner_id)?
+            };
+            let race = match races.len() {
+                0 => {
+                    return Err(user_error!(
+                        "You don't have a timer running. Start one with !startrun"
+                    ));
+                }
+                1 => races.remove(0),
+                _ => {
+                    return Err(user_error!(
+                        "You have timers running in more than one race. Run !done in the race's \
+                        submission channel instead"
+                    ));
+                }
+            };
+            let group = {
+                let data = ctx.data.read().await;
+                data.get::<GroupContainer>()
+                    .expect("No group container in share map")
+                    .values()
+                    .find(|g| g.channel_group_id == race.channel_group_id)
+                    .cloned()
+            };
+            match group {
+                Some(g) => (g, race),
+                None => {
+                    return Err(anyhow!("No group found for race {}", race.race_id).into());
+                }
+            }
+        }
+    };
+    let conn = get_connection(ctx).await;
+    if is_banned(&conn, &group, this_runner_id)? {
+        return Err(user_error!("You can't submit to this group"));
+    }
+    let submitted: i64 = Submission::belonging_to(&race)
+        .filter(runner_id.eq(this_runner_id))
+        .count()
+        .get_result(&conn)?;
+    if submitted > 0 {
+        return Err(user_error!("You've already submitted to this race"));
+    }
+    // timed to when the command was sent, not when we got around to running it
+    let claim_time = timed_run(&conn, &race, this_runner_id, cmd.created_at().naive_utc())?;
+    drop(conn);
+    let content = format!("{} {}", DONE_WORD, extra.unwrap_or_default());
+    let submission = build_submission(
+        this_runner_id,
+        &cmd.author().name,
+        &content,
+        &race,
+        &group.forfeit_words(),
+        claim_time,
+    )
+    .map_err(MurahdahlaError::user)?;
+    let submitted_time = submission.runner_time;
+    accept_submission(ctx, &group, &race, submission, cmd.author().id).await;
+    if let Some(t) = submitted_time {
+        cmd.author()
+            .direct_message(
+                ctx,
+                CreateMessage::new()
+                    .content(format!("Submitted {} in \"{}\"", t, group.group_name)),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// DM yourself a runner's results on this server
+#[poise::command(prefix_command, slash_command, guild_only, category = "Racing")]
+pub async fn progress(
+    cmd: CommandContext<'_>,
+    #[description = "The runner, you if left out"] runner: Option<User>,
+    #[description = "Only this game's races"]
+    #[rest]
+    game: Option<String>,
+) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    let runner = runner.unwrap_or_else(|| cmd.author().clone());
+    let game = match game.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(g) => Some(GameName::from_str(g).map_err(MurahdahlaError::user)?),
+    };
+    let this_server_id = cmd.guild_id().unwrap().get();
+    let results = {
+        let conn = get_connection(ctx).await;
+        let (races, submissions) =
+            load_runner_history(&conn, this_server_id, runner.id.get(), game)?;
+        runner_results(runner.id.get(), &races, &submissions)
+    };
+    let progress_string = build_progress_message(&runner.name, &results);
+    cmd.author()
+        .direct_message(ctx, CreateMessage::new().content(progress_string))
+        .await?;
+
+    Ok(())
+}
+
+/// Post the current race's collection rate leaderboard in the spoiler channel
+#[poise::command(prefix_command, slash_command, guild_only, category = "Racing")]
+pub async fn crboard(cmd: CommandContext<'_>) -> CommandResult {
+    let ctx = cmd.serenity_context();
+    // the spoiler channel, since everyone who can see it has already seen the leaderboard
+    let group = match get_spoiler_group(ctx, cmd.channel_id()).await {
+        Some(g) => g,
+        None => return Ok(()),
     };
-    let this_server_id = msg.guild_id.unwrap();
     let conn = get_connection(ctx).await;
+    let race = match get_maybe_active_race(&conn, &group) {
+        Some(r) => r,
+        None => {
+            return Err(user_error!("There is no race running in this group"));
+        }
+    };
+    if race.race_blind {
+        return Err(user_error!(
+            "Times in this race are hidden until it's stopped"
+        ));
+    }
+    let leaderboard = load_leaderboard_sorted(&conn, &group, &race, &CollectionScoring)?;
+    drop(conn);
+    if leaderboard.iter().all(|s| s.runner_collection.is_none()) {
+        return Err(user_error!(
+            "Nobody in this race has submitted a collection rate"
+        ));
+    }
+    let header = format!(
+        "Collection rate leaderboard for {}",
+        race.base_string(group.max_settings_chars())
+    );
+    for post in split_leaderboard(&leaderboard_text(&header, &leaderboard, None)) {
+        post_branded(&*ctx.http, &group, cmd.channel_id(), None, &post).await?;
+    }
+
+    Ok(())
+}
 
-    match role_type {
-        Permission::Admin => {
-            diesel::update(servers.find(*this_server_id.as_u64()))
-                .set(admin_role_id.eq(role_id))
-                .execute(&conn)?;
+/// Set the flair shown next to your name on leaderboards
+#[poise::command(prefix_command, slash_command, guild_only, category = "Racing")]
+pub async fn flair(
+    cmd: CommandContext<'_>,
+    #[description = "Your new flair, or nothing to clear it"]
+    #[rest]
+    flair: Option<String>,
+) -> CommandResult {
+    let this_user_id = cmd.author().id.get();
+    let flair = match clean_flair(flair.as_deref().unwrap_or_default()) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(MurahdahlaError::user(e));
         }
-        Permission::Mod => {
-            diesel::update(servers.find(*this_server_id.as_u64()))
-                .set(mod_role_id.eq(role_id))
-                .execute(&conn)?;
+    };
+    let conn = get_connection(cmd.serenity_context()).await;
+    match flair.is_empty() {
+        true => clear_flair(&conn, this_user_id)?,
+        false => set_flair(&conn, this_user_id, &flair)?,
+    };
+
+    Ok(())
+}
+
+/// DM yourself what a command does, or the list of commands
+#[poise::command(prefix_command, slash_command, category = "Help")]
+pub async fn help(
+    cmd: CommandContext<'_>,
+    #[description = "The command to explain"] command: Option<String>,
+) -> CommandResult {
+    let commands = &cmd.framework().options().commands;
+    let help_posts = match command {
+        Some(name) => {
+            let name = name
+                .trim_start_matches('!')
+                .trim_start_matches('/')
+                .to_lowercase();
+            match commands.iter().find(|c| c.name == name && !c.hide_in_help) {
+                Some(c) => vec![command_help(c)],
+                None => return Err(user_error!("Unrecognized command \"{}\"", name)),
+            }
         }
-        _ => (),
+        None => split_leaderboard(&commands_help(commands)),
+    };
+    for content in help_posts {
+        cmd.author()
+            .direct_message(
+                cmd.serenity_context(),
+                CreateMessage::new().content(content),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn command_help(command: &Command) -> String {
+    // "!name <required> [optional]", what it does and what each argument is
+    let usage = command
+        .parameters
+        .iter()
+        .fold(format!("!{}", command.name), |u, p| match p.required {
+            true => format!("{} <{}>", u, p.name),
+            false => format!("{} [{}]", u, p.name),
+        });
+    let mut help_string = MessageBuilder::new();
+    help_string
+        .push_line(format!("`{}`", usage))
+        .push_line(command.description.as_deref().unwrap_or_default());
+    command.parameters.iter().for_each(|p| {
+        help_string.push_line(format!(
+            "- `{}`: {}",
+            p.name,
+            p.description.as_deref().unwrap_or_default()
+        ));
+    });
+
+    help_string.build()
+}
+
+fn commands_help(commands: &[Command]) -> String {
+    // grouped by category in the order the categories first show up in the command list
+    let mut categories: Vec<&str> = Vec::new();
+    commands
+        .iter()
+        .filter_map(|c| c.category.as_deref())
+        .for_each(|c| {
+            if !categories.contains(&c) {
+                categories.push(c);
+            }
+        });
+    let mut help_string = MessageBuilder::new();
+    for category in categories {
+        help_string.push_bold_line(category);
+        commands
+            .iter()
+            .filter(|c| !c.hide_in_help && c.category.as_deref() == Some(category))
+            .for_each(|c| {
+                help_string.push_line(format!(
+                    "`!{}` {}",
+                    c.name,
+                    c.description.as_deref().unwrap_or_default()
+                ));
+            });
+    }
+    help_string.push_line(
+        "Use `!help [command]` for more about one. Every command also works as a slash command.",
+    );
+
+    help_string.build()
+}
+
+fn command_names() -> impl Iterator<Item = &'static str> {
+    COMMAND_NAMES.iter().map(|c| c.as_str())
+}
+
+fn validate_command_name(cmd_name: &str) -> Result<()> {
+    if !command_names().any(|c| c == cmd_name) {
+        return Err(anyhow!("Unrecognized command \"{}\"", cmd_name));
+    }
+    if !is_grantable(cmd_name) {
+        return Err(anyhow!(
+            "The \"{}\" command can't be granted to other roles",
+            cmd_name
+        ));
+    }
+
+    Ok(())
+}
+
+async fn set_role_from_command(
+    cmd: CommandContext<'_>,
+    role: Option<Role>,
+    command: RoleCommand,
+) -> Result<(), MurahdahlaError> {
+    let role_id: Option<u64> = match command.action() {
+        ServerRoleAction::Add => role.map(|r| r.id.get()),
+        ServerRoleAction::Remove => None,
     };
+    set_server_role(
+        cmd.serenity_context(),
+        cmd.guild_id().unwrap(),
+        command,
+        role_id,
+    )
+    .await
+}
+
+async fn set_server_role(
+    ctx: &Context,
+    this_server_id: GuildId,
+    command: RoleCommand,
+    role_id: Option<u64>,
+) -> Result<(), MurahdahlaError> {
+    let conn = get_connection(ctx).await;
+    write_server_role(&conn, this_server_id.get(), command, role_id)?;
     {
         let mut data = ctx.data.write().await;
         let server = data
             .get_mut::<ServerContainer>()
             .expect("No server container in share map")
             .get_mut(&this_server_id)
-            .unwrap(); // the server will be here on account of the before hook
-        server.set_role(role_id, role_type);
+            .unwrap(); // the server will be here on account of the command check
+        server.apply_role_command(command, role_id);
     }
-
-    msg.react(&ctx, ReactionType::try_from("👍")?).await?;
+    publish_shared_cache(ctx).await;
 
     Ok(())
 }
 
 async fn start_race(
-    ctx: &Context,
-    msg: &Message,
-    args: Args,
+    cmd: CommandContext<'_>,
+    game_info: &str,
+    seed_json: Option<&Attachment>,
     this_race_type: RaceType,
-) -> Result<(), BoxedError> {
+) -> Result<(), MurahdahlaError> {
     use crate::schema::async_races::columns::*;
-    use crate::schema::async_races::dsl::*;
 
+    let ctx = cmd.serenity_context();
     // this command must be run in a submission channel
-    if !in_submission_channel(ctx, msg).await {
+    if !in_submission_channel(ctx, cmd.channel_id()).await {
         return Ok(());
     }
-    let group_fut = get_group(ctx, msg);
+    let group_fut = get_group(ctx, cmd.channel_id());
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
+    // a message we've seen before mustn't stop the race it started
+    if race_started_by(&conn, cmd.id())? {
+        info!("Message {} already started a race", cmd.id());
+        return Ok(());
+    }
+
+    let (flags, rest) = match StartFlags::take(game_info) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(MurahdahlaError::user(e));
+        }
+    };
+    let (game_args, announcement) = match split_announcement(rest) {
+        Ok(a) => a,
+        Err(e) => {
+            return Err(MurahdahlaError::user(e));
+        }
+    };
+    if flags.live.is_some() {
+        if this_race_type != RaceType::RTA {
+            return Err(user_error!(
+                "Live races are timed by the bot so they have to be RTA"
+            ));
+        }
+        if flags.claim {
+            return Err(user_error!("A race can't be both live and claimed"));
+        }
+    }
+    if flags.sort == LeaderboardSort::Completion && (flags.claim || flags.live.is_some()) {
+        return Err(user_error!(
+            "Claimed and live races are timed so they can't be untimed"
+        ));
+    }
+    // get the game first so a seed site being down doesn't end the current race
+    let (game, fields): (BoxedGame, Option<String>) = match flags.template.as_deref() {
+        Some(name) => match template_game(&conn, group.server_id, name, &game_args) {
+            Ok(g) => g,
+            Err(e) => {
+                return Err(MurahdahlaError::user(e));
+            }
+        },
+        None => {
+            let seed_json = download_seed_json(seed_json).await?;
+            if game_args.trim().is_empty() && seed_json.is_none() {
+                return Err(user_error!(
+                    "Which seed? Give a seed url or game info, or attach the seed's JSON"
+                ));
+            }
+            match detect_game(ctx, group.server_id, &game_args, seed_json.as_deref()).await {
+                Ok(g) => g,
+                Err(e) => {
+                    if let MurahdahlaError::Seed(seed_err) = &e {
+                        let cmd_name = match this_race_type {
+                            RaceType::IGT => "start igt",
+                            RaceType::RTA => "start rta",
+                        };
+                        notify_seed_error(ctx, cmd.author(), seed_err, cmd_name).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    };
+    if let (Some(u), Some(guild_id)) = (game.game_url(), cmd.guild_id()) {
+        if let Err(e) = check_seed_host(ctx, guild_id, u).await {
+            return Err(MurahdahlaError::user(e));
+        }
+    }
 
     // determine if a game is already running in this group. if yes, stop the game
     // before starting a new one.
-    let maybe_active_race = get_maybe_active_race(&conn, &group);
-    match maybe_active_race {
-        Some(r) => stop_race(ctx, &r, &group).await?,
-        None => (),
-    };
-    let game: BoxedGame = get_game_boxed(&args).await?;
-    let new_race_data =
+    // once the old race is marked stopped a problem cleaning it up shouldn't keep the new
+    // one from starting. whatever failed can be retried with "!stop --force".
+    if let Some(r) = get_maybe_active_race(&conn, &group) {
+        mark_stopped(&conn, &r)?;
+        if let Err(e) = finish_stop(ctx, &r, &group, &StopStep::ALL, cmd.author().id).await {
+            warn!("{}", e);
+            message_maintenance_user(ctx, e).await;
+        }
+    }
+    let mut new_race_data =
         NewAsyncRaceData::new_from_game(&game, &group.channel_group_id, this_race_type)?;
-    insert_into(async_races)
-        .values(&new_race_data)
-        .execute(&conn)?;
-
-    // we need to pull this back out for the race id
-    let race_data: AsyncRaceData = async_races
-        .filter(channel_group_id.eq(&group.channel_group_id))
-        .filter(race_active.eq(true))
-        .get_result(&conn)?;
+    new_race_data.race_sort = flags.sort;
+    new_race_data.race_fields = fields;
+    new_race_data.race_deadline = flags.deadline.map(|d| Utc::now().naive_utc() + d);
+    // nobody should see times before a shuffled race's standings are sent
+    new_race_data.race_blind = flags.blind || flags.shuffle;
+    new_race_data.race_shuffle = flags.shuffle;
+    new_race_data.race_reminders = !flags.no_reminders;
+    new_race_data.race_claim = flags.claim;
+    new_race_data.race_live_at = flags.live.map(|l| Utc::now().naive_utc() + l);
+    new_race_data.race_announcement = announcement;
+    new_race_data.race_preset = flags.preset;
+    new_race_data.source_message_id = Some(cmd.id());
+    let reused_in = match &new_race_data.race_url {
+        Some(u) => find_seed_reuse(&conn, group.server_id, u)?,
+        None => Vec::new(),
+    };
+    let mut race_data = match insert_race(&conn, &new_race_data)? {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    if group.spoiler_access == SpoilerAccess::RaceRole {
+        // the role is named after the race so it can only be made once the race exists.
+        // if we can't make it, take the race back out rather than leave one nobody can
+        // get access to.
+        let role_id = match create_race_role(ctx, &group, &race_data).await {
+            Ok(r) => r.get(),
+            Err(e) => {
+                diesel::delete(&race_data).execute(&conn)?;
+                return Err(e);
+            }
+        };
+        diesel::update(&race_data)
+            .set(race_role_id.eq(role_id))
+            .execute(&conn)?;
+        race_data.race_role_id = Some(role_id);
+    }
 
     // use boxed game to build and post messages in submission and leaderboard channels
     // add both messages to messages table. rows in this table belong to async races.
     handle_new_race_messages(ctx, &group, &race_data).await?;
+    if !reused_in.is_empty() {
+        warn_seed_reuse(ctx, cmd.author(), &reused_in).await;
+    }
+    emit(
+        ctx,
+        RaceEvent::RaceStarted {
+            group,
+            race: race_data,
+        },
+    )
+    .await;
 
     Ok(())
 }
 
+fn newrace_game_choices(templates: &[GameTemplate]) -> Vec<(String, String)> {
+    // (label, value) pairs for the game menu in "!newrace"
+    let mut choices: Vec<(String, String)> = vec![(
+        "Detect from the seed link".to_owned(),
+        DETECT_CHOICE.to_owned(),
+    )];
+    registered_games()
+        .iter()
+        .filter(|p| p.fetches_seeds())
+        .for_each(|p| choices.push((p.name().to_string(), format!("{}{}", GAME_CHOICE, p.name()))));
+    choices.push((
+        "Other (don't look up the seed)".to_owned(),
+        OTHER_CHOICE.to_owned(),
+    ));
+    let room = MAX_MENU_OPTIONS.saturating_sub(choices.len());
+    templates.iter().take(room).for_each(|t| {
+        choices.push((
+            format!("Template: {}", t.template_name),
+            format!("{}{}", TEMPLATE_CHOICE, t.template_name),
+        ))
+    });
+
+    choices
+}
+
+fn newrace_args(game_choice: &str, seed_text: &str) -> Result<String> {
+    // turns what was picked in "!newrace" into the arguments a start command would take
+    let seed_text = seed_text.trim();
+    if let Some(name) = game_choice.strip_prefix(TEMPLATE_CHOICE) {
+        return Ok(format!("{} {} {}", TEMPLATE_FLAG, name, seed_text));
+    }
+    if let Some(name) = game_choice.strip_prefix(GAME_CHOICE) {
+        let game = GameName::from_str(name)?;
+        if determine_game(seed_text) != game {
+            return Err(anyhow!("That doesn't look like a {} seed link", game));
+        }
+    }
+
+    Ok(match game_choice {
+        OTHER_CHOICE => format!("{} {}", OTHER_FLAG, seed_text),
+        _ => seed_text.to_owned(),
+    })
+}
+
+fn template_game(
+    conn: &PooledConn,
+    server_id: u64,
+    name: &str,
+    text: &str,
+) -> Result<(BoxedGame, Option<String>), MurahdahlaError> {
+    // template races are always "other" games with the template's header
+    let template = get_template(conn, server_id, name)?;
+    let game: BoxedGame = Box::new(OtherGame::new_from_str(&template.render(text))?);
+
+    Ok((game, template.fields()))
+}
+
+async fn detect_game(
+    ctx: &Context,
+    server_id: u64,
+    game_info: &str,
+    seed_json: Option<&[u8]>,
+) -> Result<(BoxedGame, Option<String>), MurahdahlaError> {
+    // games added on a server only get urls none of the built in games recognize
+    if seed_json.is_none() && determine_game(game_info) == GameName::Other {
+        let definition = {
+            let conn = get_connection(ctx).await;
+            find_custom_game(&conn, server_id, game_info)?
+        };
+        if let Some(definition) = definition {
+            let game = CustomGame::new_from_url(&definition, game_info)
+                .await
+                .map_err(MurahdahlaError::user)?;
+            return Ok((Box::new(game), definition.race_fields()));
+        }
+    }
+
+    Ok((get_game_boxed(ctx, game_info, seed_json).await?, None))
+}
+
+async fn download_seed_json(
+    attachment: Option<&Attachment>,
+) -> Result<Option<Vec<u8>>, MurahdahlaError> {
+    // start commands can have seed data attached in place of fetching it from the seed site.
+    // anything else attached, like a screenshot, is left alone so the url is used.
+    let attachment = match attachment.filter(|a| is_json_attachment(a)) {
+        Some(a) => a,
+        None => return Ok(None),
+    };
+    if attachment.size > MAX_SEED_JSON_BYTES {
+        return Err(user_error!("Attached seed JSON is too large"));
+    }
+
+    Ok(Some(attachment.download().await?))
+}
+
+fn is_json_attachment(attachment: &Attachment) -> bool {
+    // discord guesses the content type from the file so we check the name too
+    let is_json_type = attachment
+        .content_type
+        .as_deref()
+        .map_or(false, |t| t.starts_with("application/json"));
+
+    is_json_type || attachment.filename.to_lowercase().ends_with(".json")
+}
+
+// the parts of stopping a race after it has been marked inactive. each one runs even if
+// another fails and the ones that failed are saved so "!stop --force" can retry them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum StopStep {
+    DeleteLeaderboard,
+    PostResults,
+    CopyResults,
+    Archive,
+    RevokeAccess,
+    SendStandings,
+}
+
+impl StopStep {
+    const ALL: [StopStep; 6] = [
+        StopStep::DeleteLeaderboard,
+        StopStep::PostResults,
+        StopStep::CopyResults,
+        StopStep::Archive,
+        StopStep::RevokeAccess,
+        StopStep::SendStandings,
+    ];
+}
+
+impl FromStr for StopStep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "delete_leaderboard" => Ok(StopStep::DeleteLeaderboard),
+            "post_results" => Ok(StopStep::PostResults),
+            "copy_results" => Ok(StopStep::CopyResults),
+            "archive" => Ok(StopStep::Archive),
+            "revoke_access" => Ok(StopStep::RevokeAccess),
+            "send_standings" => Ok(StopStep::SendStandings),
+            x => Err(anyhow!("Unrecognized stop step: {}", x)),
+        }
+    }
+}
+
+impl fmt::Display for StopStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            StopStep::DeleteLeaderboard => write!(f, "delete_leaderboard"),
+            StopStep::PostResults => write!(f, "post_results"),
+            StopStep::CopyResults => write!(f, "copy_results"),
+            StopStep::Archive => write!(f, "archive"),
+            StopStep::RevokeAccess => write!(f, "revoke_access"),
+            StopStep::SendStandings => write!(f, "send_standings"),
+        }
+    }
+}
+
+async fn retry_stop(
+    ctx: &Context,
+    group: &ChannelGroup,
+    stopped_by: UserId,
+) -> Result<(), MurahdahlaError> {
+    use crate::schema::async_races::columns::*;
+
+    let conn = get_connection(ctx).await;
+    let race: Option<AsyncRaceData> = AsyncRaceData::belonging_to(group)
+        .filter(stop_pending.is_not_null())
+        .order(race_id.desc())
+        .first(&conn)
+        .optional()?;
+    let race = match race {
+        Some(r) => r,
+        None => {
+            return Err(user_error!(
+                "There is no stopped race with steps left to retry"
+            ))
+        }
+    };
+    let steps = race
+        .stop_pending
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(StopStep::from_str)
+        .collect::<Result<Vec<StopStep>>>()?;
+
+    finish_stop(ctx, &race, group, &steps, stopped_by).await
+}
+
 async fn stop_race(
     ctx: &Context,
     race: &AsyncRaceData,
     group: &ChannelGroup,
-) -> Result<(), BoxedError> {
+    stopped_by: UserId,
+) -> Result<(), MurahdahlaError> {
+    let conn = get_connection(ctx).await;
+    mark_stopped(&conn, race)?;
+    let result = finish_stop(ctx, race, group, &StopStep::ALL, stopped_by).await;
+    emit(
+        ctx,
+        RaceEvent::RaceStopped {
+            group: group.clone(),
+            race: race.clone(),
+        },
+    )
+    .await;
+
+    result
+}
+
+async fn finish_stop(
+    ctx: &Context,
+    race: &AsyncRaceData,
+    group: &ChannelGroup,
+    steps: &[StopStep],
+    // the mod who stopped the race, or retried stopping it
+    stopped_by: UserId,
+) -> Result<(), MurahdahlaError> {
     use crate::schema::async_races;
+
+    let pool = get_db_pool(ctx).await;
+    let mut failed: Vec<StopStep> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    for step in steps {
+        let result = match step {
+            StopStep::DeleteLeaderboard => delete_leaderboard_posts(&*ctx.http, &pool, race).await,
+            StopStep::PostResults => {
+                build_leaderboard(ctx, group, race, ChannelType::Submission).await
+            }
+            StopStep::CopyResults => post_result_copies(&*ctx.http, &pool, group, race).await,
+            StopStep::Archive => post_archive(&*ctx.http, &pool, group, race).await,
+            StopStep::RevokeAccess => revoke_race_spoiler_access(ctx, group, race).await,
+            StopStep::SendStandings => send_standings(ctx, group, race, stopped_by).await,
+        };
+        if let Err(e) = result {
+            failed.push(*step);
+            errors.push(format!("{}: {}", step, e));
+        }
+    }
+
     let conn = get_connection(ctx).await;
+    let pending: Option<String> = match failed.is_empty() {
+        true => None,
+        false => Some(
+            failed
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+        ),
+    };
     diesel::update(race)
-        .set(async_races::race_active.eq(false))
+        .set(async_races::stop_pending.eq(&pending))
         .execute(&conn)?;
-    let leaderboard_msgs_data: Vec<BotMessage> = get_lb_msgs_data(&conn, race.race_id)?;
-    if leaderboard_msgs_data.is_empty() {
-        // this should never happen
-        return Err(
-            anyhow!("Tried to stop active game with no leaderboard messages in database").into(),
-        );
-    }
-    for d in leaderboard_msgs_data.iter() {
-        ctx.http.delete_message(d.channel_id, d.message_id).await?;
+    // the mod who stopped the race is the one who can retry, so this goes to them
+    match errors.is_empty() {
+        true => Ok(()),
+        false => {
+            warn!(
+                "Race {} stop steps failed: {}",
+                race.race_id,
+                errors.join("; ")
+            );
+            Err(user_error!(
+                "Race {} was stopped but some steps failed. Run \"!stop --force\" to retry them. {}",
+                race.race_id,
+                errors.join("; ")
+            ))
+        }
     }
+}
 
-    let lb_fut = build_leaderboard(ctx, group, race, ChannelType::Submission);
-    let role_del_fut = remove_spoiler_roles(ctx, group, race);
-
-    try_join!(lb_fut, role_del_fut)?;
+async fn send_standings(
+    ctx: &Context,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    stopped_by: UserId,
+) -> Result<(), MurahdahlaError> {
+    // everyone else only sees a shuffled list of who finished
+    if !race.race_shuffle {
+        return Ok(());
+    }
+    let leaderboard = {
+        let conn = get_connection(ctx).await;
+        load_leaderboard(&conn, group, race)?
+    };
+    let header = format!(
+        "Standings for {} - {}",
+        &group.group_name,
+        race.base_string(group.max_settings_chars())
+    );
+    let text = leaderboard_text(&header, &leaderboard, None);
+    let dm = stopped_by.create_dm_channel(&ctx).await?;
+    for post in split_leaderboard(&text) {
+        dm.say(&ctx, post).await?;
+    }
 
     Ok(())
 }
 
-async fn remove_spoiler_roles(
+async fn revoke_race_spoiler_access(
     ctx: &Context,
     group: &ChannelGroup,
     race: &AsyncRaceData,
-) -> Result<(), BoxedError> {
-    // collect the user ids of everyone with a submission in this race
-    // so we can use them to remove the spoiler role when the race has stopped
-    use crate::schema::submissions::columns::*;
-
-    let conn = get_connection(ctx).await;
-    let user_ids = Submission::belonging_to(race)
-        .select(runner_id)
-        .load::<u64>(&conn)?;
-    for id in user_ids {
-        let mut member = match ctx.http.get_member(group.server_id, id).await {
-            Ok(m) => m,
-            Err(e) => {
-                warn!("Error getting member from id: {}", e);
-                continue;
-            }
-        };
-        match &member.remove_role(&ctx, group.spoiler_role_id).await {
-            Ok(()) => (),
-            Err(e) => warn!("Error removing role for user id \"{}\": {}", id, e),
+) -> Result<(), MurahdahlaError> {
+    // deleting a race's own role takes it away from everyone at once
+    if group.spoiler_access == SpoilerAccess::RaceRole {
+        return match delete_race_role(ctx, group, race).await {
+            Err(e) if !is_not_found(&e) => Err(e),
+            _ => Ok(()),
         };
     }
+    let pool = get_db_pool(ctx).await;
 
-    Ok(())
+    revoke_runners_access(&*ctx.http, &pool, group, race).await
 }
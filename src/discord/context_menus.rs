@@ -0,0 +1,366 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use serenity::{
+    builder::{
+        CreateActionRow, CreateCommand, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption, EditInteractionResponse,
+    },
+    model::{
+        application::{CommandInteraction, CommandType, ComponentInteractionDataKind},
+        channel::Message,
+        id::UserId,
+    },
+    prelude::*,
+    utils::MessageBuilder,
+};
+
+use crate::{
+    discord::{
+        banned_runners::is_banned,
+        channel_groups::ChannelGroup,
+        claims::timed_run,
+        messages::accept_submission,
+        servers::{has_permission, Permission},
+        submissions::{load_leaderboard, process_submission, Submission},
+    },
+    error::MurahdahlaError,
+    games::{get_maybe_active_race, AsyncRaceData},
+    helpers::*,
+};
+
+// the names shown when right clicking a member or message. discord matches them exactly
+// when the command is used.
+pub const SHOW_SUBMISSION_COMMAND: &str = "Show submission";
+pub const CONVERT_SUBMISSION_COMMAND: &str = "Convert to submission";
+
+const NO_RACES: &str = "There are no races running on this server.";
+const GROUP_MENU_ID: &str = "convert_group";
+const CONVERT_TIMEOUT_SECS: u64 = 120;
+// the most options discord allows in a select menu
+const MAX_MENU_OPTIONS: usize = 25;
+
+pub fn context_menu_commands() -> Vec<CreateCommand> {
+    // registered alongside the slash commands since setting the global commands replaces
+    // whatever was registered before
+    vec![
+        CreateCommand::new(SHOW_SUBMISSION_COMMAND)
+            .kind(CommandType::User)
+            .dm_permission(false),
+        CreateCommand::new(CONVERT_SUBMISSION_COMMAND)
+            .kind(CommandType::Message)
+            .dm_permission(false),
+    ]
+}
+
+pub async fn handle_context_menu(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+) -> Result<(), MurahdahlaError> {
+    // the framework only knows about slash and prefix commands so the permission check
+    // happens here
+    let guild_id = interaction
+        .guild_id
+        .ok_or_else(|| anyhow!("Context menu command used outside of a server"))?;
+    let user_roles = interaction
+        .member
+        .as_ref()
+        .map_or(&[][..], |m| &m.roles[..]);
+    if !has_permission(
+        ctx,
+        guild_id,
+        interaction.user.id,
+        user_roles,
+        Permission::Mod,
+    )
+    .await
+    {
+        return respond(ctx, interaction, "Only mods can use this.").await;
+    }
+    let target = interaction
+        .data
+        .target_id
+        .ok_or_else(|| anyhow!("\"{}\" has no target", interaction.data.name))?;
+    match interaction.data.name.as_str() {
+        SHOW_SUBMISSION_COMMAND => {
+            let content = show_submission(ctx, guild_id.get(), target.to_user_id()).await?;
+            respond(ctx, interaction, &content).await
+        }
+        CONVERT_SUBMISSION_COMMAND => {
+            let msg = interaction
+                .data
+                .resolved
+                .messages
+                .get(&target.to_message_id())
+                .ok_or_else(|| anyhow!("Message to convert wasn't sent with the command"))?;
+            convert_to_submission(ctx, interaction, guild_id.get(), msg).await
+        }
+        x => Err(anyhow!("Unrecognized context menu command: {}", x).into()),
+    }
+}
+
+async fn respond(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    content: &str,
+) -> Result<(), MurahdahlaError> {
+    let response = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await?;
+
+    Ok(())
+}
+
+async fn racing_groups(ctx: &Context, server_id: u64) -> Vec<(ChannelGroup, AsyncRaceData)> {
+    let mut groups: Vec<ChannelGroup> = {
+        let data = ctx.data.read().await;
+        data.get::<GroupContainer>()
+            .expect("No group container in share map")
+            .values()
+            .filter(|g| g.server_id == server_id)
+            .cloned()
+            .collect()
+    };
+    groups.sort_by(|a, b| a.group_name.cmp(&b.group_name));
+    let conn = get_connection(ctx).await;
+
+    groups
+        .into_iter()
+        .filter_map(|g| get_maybe_active_race(&conn, &g).map(|r| (g, r)))
+        .collect()
+}
+
+async fn show_submission(ctx: &Context, server_id: u64, runner: UserId) -> Result<String> {
+    // a server can have a race going in more than one group so we show all of them
+    let groups = racing_groups(ctx, server_id).await;
+    if groups.is_empty() {
+        return Ok(NO_RACES.to_owned());
+    }
+    let conn = get_connection(ctx).await;
+    let mut content = MessageBuilder::new();
+    for (group, race) in groups.iter() {
+        let submission = runner_submission(&conn, race, runner)?;
+        let place = match &submission {
+            Some(s) if !s.pending && !s.unranked && !s.runner_forfeit => {
+                let leaderboard = load_leaderboard(&conn, group, race)?;
+                ranked_place(&leaderboard, s.submission_id)
+            }
+            _ => None,
+        };
+        content.push_bold_safe(&group.group_name).push_line(format!(
+            ": {}",
+            submission_status(submission.as_ref(), place)
+        ));
+    }
+
+    Ok(content.build())
+}
+
+async fn convert_to_submission(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    server_id: u64,
+    msg: &Message,
+) -> Result<(), MurahdahlaError> {
+    if msg.author.bot {
+        return respond(ctx, interaction, "Messages from bots can't be submissions.").await;
+    }
+    let groups = racing_groups(ctx, server_id).await;
+    let (group, choice) = match groups.len() {
+        0 => return respond(ctx, interaction, NO_RACES).await,
+        // nothing to choose from
+        1 => {
+            let content = convert_message(ctx, msg, &groups[0].0).await?;
+            return respond(ctx, interaction, &content).await;
+        }
+        _ => {
+            let options = groups
+                .iter()
+                .take(MAX_MENU_OPTIONS)
+                .map(|(g, _)| CreateSelectMenuOption::new(&g.group_name, g.submission.to_string()))
+                .collect();
+            let menu =
+                CreateSelectMenu::new(GROUP_MENU_ID, CreateSelectMenuKind::String { options });
+            let response = CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(format!(
+                    "Which group's race should {}'s message be submitted to?",
+                    msg.author.name
+                ))
+                .components(vec![CreateActionRow::SelectMenu(menu)]);
+            interaction
+                .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                .await?;
+            let prompt = interaction.get_response(&ctx.http).await?;
+            match prompt
+                .await_component_interaction(ctx)
+                .author_id(interaction.user.id)
+                .timeout(Duration::from_secs(CONVERT_TIMEOUT_SECS))
+                .await
+            {
+                Some(choice) => {
+                    let channel = match &choice.data.kind {
+                        ComponentInteractionDataKind::StringSelect { values } => {
+                            values.first().cloned().unwrap_or_default()
+                        }
+                        _ => String::new(),
+                    };
+                    let group = groups
+                        .into_iter()
+                        .map(|(g, _)| g)
+                        .find(|g| g.submission.to_string() == channel)
+                        .ok_or_else(|| anyhow!("Chose a group that wasn't offered"))?;
+                    (group, choice)
+                }
+                None => {
+                    let edit = EditInteractionResponse::new()
+                        .content("No group was chosen so nothing was submitted.")
+                        .components(vec![]);
+                    interaction.edit_response(&ctx.http, edit).await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+    let content = convert_message(ctx, msg, &group).await?;
+    let response = CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(vec![]);
+    choice
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(response),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn convert_message(ctx: &Context, msg: &Message, group: &ChannelGroup) -> Result<String> {
+    // the same checks a message in the submission channel goes through, except that the
+    // message is left where it is. the race is looked up again since it could have been
+    // stopped while the mod was choosing a group.
+    let conn = get_connection(ctx).await;
+    let race = match get_maybe_active_race(&conn, group) {
+        Some(r) => r,
+        None => return Ok(format!("{}'s race has been stopped.", group.group_name)),
+    };
+    let runner = msg.author.id.get();
+    if is_banned(&conn, group, runner)? {
+        return Ok(format!(
+            "{} is banned from submitting to {}.",
+            msg.author.name, group.group_name
+        ));
+    }
+    if runner_submission(&conn, &race, msg.author.id)?.is_some() {
+        return Ok(format!(
+            "{} already has a submission in {}'s race.",
+            msg.author.name, group.group_name
+        ));
+    }
+    let claim_time = timed_run(&conn, &race, runner, msg.timestamp.naive_utc())?;
+    let submission = match process_submission(msg, &race, group, claim_time) {
+        Ok(s) => s,
+        Err(e) => return Ok(format!("That message isn't a submission: {}", e)),
+    };
+    drop(conn);
+    accept_submission(ctx, group, &race, submission, msg.author.id).await;
+    info!(
+        "Converted a message from \"{}\" into a submission to {}",
+        &msg.author.name, &group.group_name
+    );
+
+    Ok(format!(
+        "Submitted {}'s message to {}'s race.",
+        msg.author.name, group.group_name
+    ))
+}
+
+fn runner_submission(
+    conn: &PooledConn,
+    race: &AsyncRaceData,
+    this_runner_id: UserId,
+) -> Result<Option<Submission>> {
+    use crate::schema::submissions::columns::runner_id;
+
+    let submission = Submission::belonging_to(race)
+        .filter(runner_id.eq(this_runner_id.get()))
+        .first(conn)
+        .optional()?;
+
+    Ok(submission)
+}
+
+fn ranked_place(leaderboard: &[Submission], this_submission_id: u32) -> Option<usize> {
+    leaderboard
+        .iter()
+        .filter(|s| !s.unranked)
+        .position(|s| s.submission_id == this_submission_id)
+        .map(|p| p + 1)
+}
+
+pub fn submission_status(submission: Option<&Submission>, place: Option<usize>) -> String {
+    let s = match submission {
+        Some(s) => s,
+        None => return "not submitted yet".to_owned(),
+    };
+    if s.runner_forfeit {
+        return "forfeited".to_owned();
+    }
+    let finish = match s.runner_time {
+        Some(t) => format!("submitted {}", t),
+        None if s.unranked => return "given spoiler access without a run".to_owned(),
+        // completion races don't have times
+        None => "done".to_owned(),
+    };
+    let mut status = match (s.pending, s.unranked, place) {
+        (true, _, _) => format!("{}, waiting for a mod to verify it", finish),
+        (false, true, _) => format!("{}, unranked", finish),
+        (false, false, Some(p)) => format!("{}, place {}", finish, p),
+        (false, false, None) => finish,
+    };
+    if let Some(c) = s.runner_collection {
+        status.push_str(&format!(" ({} collected)", c));
+    }
+
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_submission;
+
+    #[test]
+    fn status_covers_every_kind_of_submission() {
+        let mut s = Submission {
+            runner_collection: Some(216),
+            ..test_submission()
+        };
+        assert_eq!(submission_status(None, None), "not submitted yet");
+        assert_eq!(
+            submission_status(Some(&s), Some(2)),
+            "submitted 01:23:45, place 2 (216 collected)"
+        );
+        s.pending = true;
+        assert_eq!(
+            submission_status(Some(&s), None),
+            "submitted 01:23:45, waiting for a mod to verify it (216 collected)"
+        );
+        s.pending = false;
+        s.runner_forfeit = true;
+        assert_eq!(submission_status(Some(&s), None), "forfeited");
+        s.runner_forfeit = false;
+        s.unranked = true;
+        s.runner_time = None;
+        assert_eq!(
+            submission_status(Some(&s), None),
+            "given spoiler access without a run"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, replace_into};
+use url::Url;
+
+use crate::{games::custom::CustomGameDefinition, helpers::*, schema::custom_games};
+
+pub const MAX_CUSTOM_GAMES: i64 = 25;
+
+// definitions are kept as json so adding something to them doesn't need a migration
+#[derive(Debug, Clone, Insertable, Queryable)]
+#[table_name = "custom_games"]
+pub struct CustomGameRow {
+    pub server_id: u64,
+    pub game_name: String,
+    pub game_definition: String,
+}
+
+impl CustomGameRow {
+    fn definition(&self) -> Result<CustomGameDefinition> {
+        serde_json::from_str(&self.game_definition)
+            .map_err(|e| anyhow!("Bad definition for game \"{}\": {}", self.game_name, e))
+    }
+}
+
+pub fn set_custom_game(
+    conn: &PooledConn,
+    this_server_id: u64,
+    definition: &CustomGameDefinition,
+) -> Result<()> {
+    use crate::schema::custom_games::columns::*;
+
+    // replacing a game doesn't count against the limit
+    let others: i64 = custom_games::table
+        .filter(server_id.eq(this_server_id))
+        .filter(game_name.ne(&definition.name))
+        .count()
+        .get_result(conn)?;
+    if others >= MAX_CUSTOM_GAMES {
+        return Err(anyhow!(
+            "Cannot add more than {} games per server",
+            MAX_CUSTOM_GAMES
+        ));
+    }
+    let row = CustomGameRow {
+        server_id: this_server_id,
+        game_name: definition.name.clone(),
+        game_definition: serde_json::to_string(definition)?,
+    };
+    replace_into(custom_games::table)
+        .values(&row)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn remove_custom_game(conn: &PooledConn, this_server_id: u64, name: &str) -> Result<()> {
+    use crate::schema::custom_games::columns::*;
+
+    let removed = diesel::delete(
+        custom_games::table
+            .filter(server_id.eq(this_server_id))
+            .filter(game_name.eq(name.to_lowercase())),
+    )
+    .execute(conn)?;
+    match removed {
+        0 => Err(anyhow!("No game named \"{}\"", name)),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_custom_games(
+    conn: &PooledConn,
+    this_server_id: u64,
+) -> Result<Vec<CustomGameDefinition>> {
+    use crate::schema::custom_games::columns::*;
+
+    let rows: Vec<CustomGameRow> = custom_games::table
+        .filter(server_id.eq(this_server_id))
+        .order(game_name.asc())
+        .load(conn)?;
+
+    rows.iter().map(CustomGameRow::definition).collect()
+}
+
+pub fn find_custom_game(
+    conn: &PooledConn,
+    this_server_id: u64,
+    args_str: &str,
+) -> Result<Option<CustomGameDefinition>> {
+    let game_url = match Url::parse(args_str.trim()) {
+        Ok(u) => u,
+        Err(_) => return Ok(None),
+    };
+    let game = get_custom_games(conn, this_server_id)?
+        .into_iter()
+        .find(|g| g.matches_url(&game_url));
+
+    Ok(game)
+}
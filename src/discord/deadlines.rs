@@ -0,0 +1,206 @@
+use std::sync::atomic::Ordering;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId},
+};
+
+use crate::{
+    config::get_config,
+    discord::{
+        branding::post_branded,
+        channel_groups::ChannelGroup,
+        leader::is_leader,
+        secrets::CLIENT_GENERATION,
+        server_settings::{get_locale, get_setting_parsed, ServerSetting},
+    },
+    error::MurahdahlaError,
+    games::AsyncRaceData,
+    helpers::*,
+};
+
+// how often races with deadlines are checked. reminders can be up to this late.
+const DEADLINE_CHECK_SECS: u64 = 60;
+
+// the one place that acts on race deadlines as they pass. for now that's reminding
+// runners before a race ends.
+pub async fn deadline_task(ctx: Context) {
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(DEADLINE_CHECK_SECS)).await;
+        if CLIENT_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if !is_leader() {
+            continue;
+        }
+        if let Err(e) = check_deadlines(&ctx).await {
+            warn!("Error checking race deadlines: {}", e);
+        }
+    }
+}
+
+async fn check_deadlines(ctx: &Context) -> Result<(), MurahdahlaError> {
+    use crate::schema::async_races::columns::*;
+
+    let reminders = get_config(ctx).await.deadline_reminders();
+    if reminders.is_empty() {
+        return Ok(());
+    }
+    let races: Vec<AsyncRaceData> = {
+        let conn = get_connection(ctx).await;
+        crate::schema::async_races::table
+            .filter(race_active.eq(true))
+            .filter(race_reminders.eq(true))
+            .filter(race_deadline.is_not_null())
+            .load(&conn)?
+    };
+    let now = Utc::now().naive_utc();
+    for race in races {
+        let deadline = race.race_deadline.unwrap();
+        if due_reminder(deadline, race.last_reminder_at, now, &reminders).is_none() {
+            continue;
+        }
+        let group = {
+            let data = ctx.data.read().await;
+            data.get::<GroupContainer>()
+                .expect("No group container in share map")
+                .values()
+                .find(|g| g.channel_group_id == race.channel_group_id)
+                .cloned()
+        };
+        let group = match group {
+            Some(g) => g,
+            None => continue,
+        };
+        // a reminder that fails to send isn't tried again so a channel the bot can't
+        // post in doesn't get retried every minute until the deadline
+        {
+            let conn = get_connection(ctx).await;
+            diesel::update(&race)
+                .set(last_reminder_at.eq(Some(now)))
+                .execute(&conn)?;
+        }
+        if let Err(e) = send_reminder(ctx, &group, &race, deadline - now).await {
+            warn!(
+                "Error sending deadline reminder for race {}: {}",
+                race.race_id, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_reminder(
+    ctx: &Context,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    remaining: Duration,
+) -> Result<(), MurahdahlaError> {
+    let guild_id = GuildId::from(group.server_id);
+    let ping_role: Option<u64> = get_setting_parsed(ctx, guild_id, ServerSetting::PingRole).await;
+    let locale = get_locale(ctx, guild_id).await;
+    let mention = ping_role.map(|r| format!("<@&{}>", r));
+    let text = format!(
+        "This race ends in {} ({} UTC). Get your submission in before then!",
+        remaining_text(remaining),
+        locale.format_date(&race.race_deadline.unwrap_or_default())
+    );
+    post_branded(
+        &*ctx.http,
+        group,
+        ChannelId::from(group.submission),
+        mention.as_deref(),
+        &text,
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub fn due_reminder(
+    deadline: NaiveDateTime,
+    last_reminder_at: Option<NaiveDateTime>,
+    now: NaiveDateTime,
+    reminders: &[Duration],
+) -> Option<Duration> {
+    // when several reminders have come due since the last one, like after the bot was
+    // down for a while, only the one closest to the deadline is sent
+    if now >= deadline {
+        return None;
+    }
+    reminders
+        .iter()
+        .filter(|r| {
+            let at = deadline - **r;
+            at <= now && last_reminder_at.map_or(true, |l| at > l)
+        })
+        .min()
+        .copied()
+}
+
+fn remaining_text(remaining: Duration) -> String {
+    let plural = |n: i64, unit: &str| match n {
+        1 => format!("1 {}", unit),
+        _ => format!("{} {}s", n, unit),
+    };
+    // rounded so a reminder that runs a few seconds late still says "1 hour"
+    let minutes = (remaining.num_seconds() + 30) / 60;
+    match minutes {
+        m if m >= 48 * 60 => plural((m + 12 * 60) / (24 * 60), "day"),
+        m if m >= 2 * 60 => plural((m + 30) / 60, "hour"),
+        m if m >= 60 => plural(1, "hour"),
+        m => plural(m.max(1), "minute"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn reminders_come_due_once() {
+        let deadline = NaiveDate::from_ymd_opt(2026, 1, 10)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let reminders = [Duration::hours(24), Duration::hours(1)];
+        let started = deadline - Duration::days(3);
+        let due = |last, now| due_reminder(deadline, last, now, &reminders);
+
+        assert_eq!(due(Some(started), started + Duration::hours(1)), None);
+        let day_before = deadline - Duration::hours(23);
+        assert_eq!(due(Some(started), day_before), Some(Duration::hours(24)));
+        // already sent
+        assert_eq!(due(Some(day_before), day_before + Duration::hours(1)), None);
+        let hour_before = deadline - Duration::minutes(59);
+        assert_eq!(due(Some(day_before), hour_before), Some(Duration::hours(1)));
+        assert_eq!(due(Some(hour_before), hour_before), None);
+        // both came due while the bot was down
+        assert_eq!(due(Some(started), hour_before), Some(Duration::hours(1)));
+        assert_eq!(due(None, deadline), None);
+
+        // a race started less than a day out only gets the reminders after it started
+        let late_start = deadline - Duration::hours(12);
+        assert_eq!(due(Some(late_start), late_start), None);
+        assert_eq!(due(Some(late_start), hour_before), Some(Duration::hours(1)));
+    }
+
+    #[test]
+    fn remaining_time_is_rounded() {
+        assert_eq!(
+            remaining_text(Duration::seconds(23 * 3600 + 3540)),
+            "24 hours"
+        );
+        assert_eq!(remaining_text(Duration::seconds(3590)), "1 hour");
+        assert_eq!(remaining_text(Duration::minutes(90)), "1 hour");
+        assert_eq!(remaining_text(Duration::minutes(10)), "10 minutes");
+        assert_eq!(remaining_text(Duration::seconds(5)), "1 minute");
+        assert_eq!(remaining_text(Duration::days(3)), "3 days");
+    }
+}
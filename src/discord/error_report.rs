@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Serialize;
+use serenity::prelude::*;
+
+use crate::{
+    discord::commands::CommandContext, error::MurahdahlaError, games::get_maybe_active_race,
+    helpers::*,
+};
+
+const REPORT_TIMEOUT_SECS: u64 = 10;
+
+// where command failures are posted as json, on top of the maintenance user's DM. the DM
+// only has room for the error itself so this is where the rest of the context goes.
+pub struct ErrorReporterContainer;
+
+impl TypeMapKey for ErrorReporterContainer {
+    type Value = ErrorReporter;
+}
+
+#[derive(Clone)]
+pub struct ErrorReporter {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub command: String,
+    pub error: String,
+    pub server_id: Option<u64>,
+    pub channel_id: u64,
+    pub group_name: Option<String>,
+    pub race_id: Option<u32>,
+    pub user_id: u64,
+    pub user_name: String,
+    pub message: String,
+    pub message_link: String,
+    pub time: String,
+}
+
+impl ErrorReporter {
+    pub fn new(url: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(REPORT_TIMEOUT_SECS))
+            .build()?;
+
+        Ok(ErrorReporter {
+            client,
+            url: url.to_owned(),
+        })
+    }
+
+    async fn send(&self, report: &ErrorReport) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(report)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+// returns whether the report was posted, so callers can fall back to the maintenance DM
+pub async fn report_command_error(cmd: CommandContext<'_>, error: &MurahdahlaError) -> bool {
+    let ctx = cmd.serenity_context();
+    let reporter = match ctx.data.read().await.get::<ErrorReporterContainer>() {
+        Some(r) => r.clone(),
+        None => return false,
+    };
+    let group = {
+        let data = ctx.data.read().await;
+        data.get::<GroupContainer>()
+            .expect("No group container in share map")
+            .get(&cmd.channel_id().get())
+            .cloned()
+    };
+    let race_id = match &group {
+        Some(g) => get_db_pool(ctx)
+            .await
+            .get()
+            .ok()
+            .and_then(|conn| get_maybe_active_race(&conn, g))
+            .map(|r| r.race_id),
+        None => None,
+    };
+    let message_link = match cmd {
+        poise::Context::Prefix(p) => p.msg.link(),
+        // slash commands don't leave a message behind so this goes to the channel
+        poise::Context::Application(_) => format!(
+            "https://discord.com/channels/{}/{}",
+            cmd.guild_id()
+                .map_or_else(|| "@me".to_owned(), |g| g.to_string()),
+            cmd.channel_id()
+        ),
+    };
+    let report = ErrorReport {
+        command: cmd.command().name.clone(),
+        // the debug output has the whole chain of causes
+        error: format!("{:?}", error),
+        server_id: cmd.guild_id().map(|g| g.get()),
+        channel_id: cmd.channel_id().get(),
+        group_name: group.map(|g| g.group_name),
+        race_id,
+        user_id: cmd.author().id.get(),
+        user_name: cmd.author().name.clone(),
+        message: cmd.invocation_string(),
+        message_link,
+        time: Utc::now().to_rfc3339(),
+    };
+    match reporter.send(&report).await {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Error sending error report: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::atomic::Ordering;
+
+use serenity::{client::Context, prelude::*};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup, secrets::CLIENT_GENERATION,
+        spoiler_purge::purge_spoiler_channel, submissions::queue_leaderboard,
+    },
+    games::AsyncRaceData,
+};
+
+// how many events a slow subscriber can fall behind by before it starts missing them
+const EVENT_CAPACITY: usize = 256;
+
+// things that happen to a race that other parts of the bot may want to act on. commands
+// emit these instead of calling every side effect themselves. anything that has to
+// finish before a command replies (like the stop steps, which can be retried) is still
+// done inline.
+#[derive(Debug, Clone)]
+pub enum RaceEvent {
+    RaceStarted {
+        group: ChannelGroup,
+        race: AsyncRaceData,
+    },
+    SubmissionAccepted {
+        group: ChannelGroup,
+        race: AsyncRaceData,
+        runner_id: u64,
+    },
+    TimeEdited {
+        group: ChannelGroup,
+        race: AsyncRaceData,
+        runner_name: String,
+    },
+    RaceStopped {
+        group: ChannelGroup,
+        race: AsyncRaceData,
+    },
+}
+
+impl RaceEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RaceEvent::RaceStarted { .. } => "race_started",
+            RaceEvent::SubmissionAccepted { .. } => "submission_accepted",
+            RaceEvent::TimeEdited { .. } => "time_edited",
+            RaceEvent::RaceStopped { .. } => "race_stopped",
+        }
+    }
+
+    pub fn race(&self) -> (&ChannelGroup, &AsyncRaceData) {
+        match self {
+            RaceEvent::RaceStarted { group, race }
+            | RaceEvent::SubmissionAccepted { group, race, .. }
+            | RaceEvent::TimeEdited { group, race, .. }
+            | RaceEvent::RaceStopped { group, race } => (group, race),
+        }
+    }
+}
+
+pub struct EventBusContainer;
+
+impl TypeMapKey for EventBusContainer {
+    type Value = broadcast::Sender<RaceEvent>;
+}
+
+pub fn new_event_bus() -> broadcast::Sender<RaceEvent> {
+    broadcast::channel(EVENT_CAPACITY).0
+}
+
+pub async fn emit(ctx: &Context, event: RaceEvent) {
+    let bus = {
+        let data = ctx.data.read().await;
+        data.get::<EventBusContainer>()
+            .expect("No event bus in share map")
+            .clone()
+    };
+    // sending only fails when nothing is subscribed yet, which is fine to ignore
+    let _ = bus.send(event);
+}
+
+pub async fn start_subscribers(ctx: Context) {
+    // started on ready, once for each client. each subscriber gets every event.
+    let bus = {
+        let data = ctx.data.read().await;
+        data.get::<EventBusContainer>()
+            .expect("No event bus in share map")
+            .clone()
+    };
+    tokio::spawn(subscribe(
+        ctx.clone(),
+        bus.subscribe(),
+        rebuild_leaderboards,
+    ));
+    tokio::spawn(subscribe(ctx.clone(), bus.subscribe(), purge_spoiler));
+    tokio::spawn(subscribe(ctx, bus.subscribe(), log_event));
+}
+
+async fn subscribe<F, Fut>(ctx: Context, mut events: broadcast::Receiver<RaceEvent>, handler: F)
+where
+    F: Fn(Context, RaceEvent) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    loop {
+        let event = match events.recv().await {
+            Ok(e) => e,
+            Err(RecvError::Lagged(n)) => {
+                warn!("Event subscriber fell behind and missed {} events", n);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if CLIENT_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        handler(ctx.clone(), event).await;
+    }
+}
+
+async fn rebuild_leaderboards(ctx: Context, event: RaceEvent) {
+    match &event {
+        RaceEvent::SubmissionAccepted { group, race, .. }
+        | RaceEvent::TimeEdited { group, race, .. } => queue_leaderboard(&ctx, group, race).await,
+        RaceEvent::RaceStarted { .. } | RaceEvent::RaceStopped { .. } => (),
+    };
+}
+
+async fn purge_spoiler(ctx: Context, event: RaceEvent) {
+    // a long purge only holds up this subscriber, the race is already posted
+    if let RaceEvent::RaceStarted { group, .. } = &event {
+        if !group.purge_spoiler {
+            return;
+        }
+        if let Err(e) = purge_spoiler_channel(&ctx, group).await {
+            warn!(
+                "Error clearing the spoiler channel for group \"{}\": {}",
+                group.group_name, e
+            );
+        }
+    }
+}
+
+async fn log_event(_ctx: Context, event: RaceEvent) {
+    let (group, race) = event.race();
+    let detail = match &event {
+        RaceEvent::SubmissionAccepted { runner_id, .. } => format!(" runner {}", runner_id),
+        RaceEvent::TimeEdited { runner_name, .. } => format!(" runner \"{}\"", runner_name),
+        RaceEvent::RaceStarted { .. } | RaceEvent::RaceStopped { .. } => String::new(),
+    };
+    info!(
+        "{}: race {} in \"{}\" on server {}{}",
+        event.name(),
+        race.race_id,
+        group.group_name,
+        group.server_id,
+        detail
+    );
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, replace_into};
+
+use crate::{helpers::*, schema::users};
+
+pub const MAX_FLAIR_CHARS: usize = 32;
+
+// a short bit of text runners can have shown after their name on leaderboards, like
+// pronouns or a flag. it follows the runner to every server the bot is in but groups
+// only show it if they turn on show_flair.
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable)]
+#[table_name = "users"]
+#[primary_key(user_id)]
+pub struct RunnerFlair {
+    pub user_id: u64,
+    pub flair: String,
+}
+
+pub fn clean_flair(text: &str) -> Result<String> {
+    let flair = text.trim();
+    if flair.chars().any(char::is_control) {
+        return Err(anyhow!(
+            "Flair can't have line breaks or other control characters"
+        ));
+    }
+    if flair.chars().count() > MAX_FLAIR_CHARS {
+        return Err(anyhow!(
+            "Flair can be at most {} characters long",
+            MAX_FLAIR_CHARS
+        ));
+    }
+
+    Ok(flair.to_owned())
+}
+
+pub fn set_flair(conn: &PooledConn, this_user_id: u64, text: &str) -> Result<()> {
+    let runner_flair = RunnerFlair {
+        user_id: this_user_id,
+        flair: text.to_owned(),
+    };
+    replace_into(users::table)
+        .values(&runner_flair)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn clear_flair(conn: &PooledConn, this_user_id: u64) -> Result<()> {
+    diesel::delete(users::table.find(this_user_id)).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_flairs(conn: &PooledConn, user_ids: &[u64]) -> Result<HashMap<u64, String>> {
+    let flairs = users::table
+        .filter(users::user_id.eq_any(user_ids))
+        .load::<RunnerFlair>(conn)?
+        .into_iter()
+        .map(|f| (f.user_id, f.flair))
+        .collect();
+
+    Ok(flairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flair_is_trimmed() {
+        assert_eq!(clean_flair("  they/them 🏳️‍🌈 ").unwrap(), "they/them 🏳️‍🌈");
+    }
+
+    #[test]
+    fn long_or_multiline_flair_is_rejected() {
+        assert!(clean_flair(&"a".repeat(MAX_FLAIR_CHARS)).is_ok());
+        assert!(clean_flair(&"a".repeat(MAX_FLAIR_CHARS + 1)).is_err());
+        assert!(clean_flair("she/her\n@everyone").is_err());
+    }
+}
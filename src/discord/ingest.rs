@@ -0,0 +1,130 @@
+use serenity::{client::Context, model::channel::Message, prelude::*};
+use tokio::{runtime::Handle, sync::mpsc, task::spawn_blocking};
+
+use crate::{
+    config::MessagePolicy,
+    discord::{
+        channel_groups::ChannelGroup,
+        messages::{handle_submission, process_candidate},
+    },
+    games::AsyncRaceData,
+};
+
+// how many messages each worker can have waiting before the handler has to wait
+const WORKER_QUEUE_CAPACITY: usize = 1000;
+
+// messages from submission channels are handed off to a few workers so a slow database
+// or discord call doesn't hold up the framework. a channel always goes to the same
+// worker so its messages are still handled in the order they were sent, which the
+// duplicate check and the replay cursor rely on.
+pub struct SubmissionQueue {
+    workers: Vec<mpsc::Sender<SubmissionJob>>,
+}
+
+enum SubmissionJob {
+    // a message the handler just picked up
+    Live(Context, Message),
+    // a message from the channel's history, found by replay or !backfill for a race
+    // they've already looked up
+    History(Context, Message, Box<RaceLookup>),
+}
+
+struct RaceLookup {
+    group: ChannelGroup,
+    race: AsyncRaceData,
+    policy: MessagePolicy,
+}
+
+impl SubmissionJob {
+    fn message(&self) -> &Message {
+        match self {
+            SubmissionJob::Live(_, msg) => msg,
+            SubmissionJob::History(_, msg, _) => msg,
+        }
+    }
+
+    async fn process(self) {
+        match self {
+            SubmissionJob::Live(ctx, msg) => process_candidate(&ctx, &msg).await,
+            SubmissionJob::History(ctx, msg, found) => {
+                handle_submission(&ctx, &msg, &found.group, &found.race, found.policy).await
+            }
+        }
+    }
+}
+
+impl SubmissionQueue {
+    pub fn new(workers: usize) -> Self {
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let (tx, rx) = mpsc::channel(WORKER_QUEUE_CAPACITY);
+                tokio::spawn(submission_worker(rx));
+                tx
+            })
+            .collect();
+
+        SubmissionQueue { workers }
+    }
+
+    fn worker_for(&self, msg: &Message) -> &mpsc::Sender<SubmissionJob> {
+        &self.workers[(msg.channel_id.get() % self.workers.len() as u64) as usize]
+    }
+}
+
+pub struct SubmissionQueueContainer;
+
+impl TypeMapKey for SubmissionQueueContainer {
+    type Value = SubmissionQueue;
+}
+
+pub async fn enqueue_submission(ctx: &Context, msg: &Message) {
+    enqueue(ctx, SubmissionJob::Live(ctx.clone(), msg.clone())).await;
+}
+
+// messages from the channel's history go through the same worker as live ones so a
+// runner's old and new submissions can't both get past the duplicate check
+pub async fn enqueue_history(
+    ctx: &Context,
+    msg: &Message,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    policy: MessagePolicy,
+) {
+    let found = RaceLookup {
+        group: group.clone(),
+        race: race.clone(),
+        policy,
+    };
+    let job = SubmissionJob::History(ctx.clone(), msg.clone(), Box::new(found));
+    enqueue(ctx, job).await;
+}
+
+async fn enqueue(ctx: &Context, job: SubmissionJob) {
+    let worker = {
+        let data = ctx.data.read().await;
+        data.get::<SubmissionQueueContainer>()
+            .expect("No submission queue in share map")
+            .worker_for(job.message())
+            .clone()
+    };
+    let message_id = job.message().id;
+    // a full queue makes us wait here rather than drop a submission
+    if worker.send(job).await.is_err() {
+        warn!("Submission worker stopped, dropping message {}", message_id);
+    }
+}
+
+async fn submission_worker(mut rx: mpsc::Receiver<SubmissionJob>) {
+    // runs until the queue is dropped along with the client that owns it. processing a
+    // message makes synchronous database calls in between its discord calls, so it's
+    // driven from the blocking pool where waiting on the database can't tie up the
+    // runtime threads the gateway uses.
+    while let Some(job) = rx.recv().await {
+        let handle = Handle::current();
+        let message_id = job.message().id;
+        let processed = spawn_blocking(move || handle.block_on(job.process())).await;
+        if let Err(e) = processed {
+            error!("Error processing message {}: {}", message_id, e);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    sql_types::{Integer, Nullable},
+};
+use serenity::client::Context;
+
+use crate::{
+    config::get_config,
+    discord::{
+        replay::replay_missed_messages,
+        secrets::{EnvironmentContainer, CLIENT_GENERATION},
+        shared_cache::reload_caches,
+    },
+};
+
+// more than one instance can be connected to discord at once (e.g. during a deploy) but
+// only the one holding this mysql lock acts on anything. the lock belongs to a
+// connection of its own, so it's let go as soon as that instance goes away.
+const LEADER_LOCK: &str = "murahdahla_leader";
+
+static IS_LEADER: AtomicBool = AtomicBool::new(false);
+
+pub fn is_leader() -> bool {
+    IS_LEADER.load(Ordering::SeqCst)
+}
+
+fn try_lock(conn: &MysqlConnection) -> Result<bool> {
+    // GET_LOCK returns 1 if we got it, 0 if someone else has it and NULL on error
+    let got: Option<i32> = diesel::select(sql::<Nullable<Integer>>(&format!(
+        "GET_LOCK('{}', 0)",
+        LEADER_LOCK
+    )))
+    .get_result(conn)?;
+
+    Ok(got == Some(1))
+}
+
+fn still_locked(conn: &MysqlConnection) -> Result<bool> {
+    let ours: Option<i32> = diesel::select(sql::<Nullable<Integer>>(&format!(
+        "IS_USED_LOCK('{}') = CONNECTION_ID()",
+        LEADER_LOCK
+    )))
+    .get_result(conn)?;
+
+    Ok(ours == Some(1))
+}
+
+pub async fn leader_task(ctx: Context) {
+    // started on ready, once for each client. a standby keeps trying for the lock and
+    // catches up on missed submissions when it gets it.
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    let mut lock_conn: Option<MysqlConnection> = None;
+    while CLIENT_GENERATION.load(Ordering::SeqCst) == generation {
+        if lock_conn.is_none() {
+            lock_conn = lock_connection(&ctx)
+                .await
+                .map_err(|e| warn!("Error connecting for leader lock: {}", e))
+                .ok();
+        }
+        let held = match lock_conn.as_ref() {
+            Some(c) if is_leader() => still_locked(c),
+            Some(c) => try_lock(c),
+            None => Ok(false),
+        };
+        match held {
+            Ok(true) => become_leader(&ctx).await,
+            Ok(false) => stand_by(),
+            Err(e) => {
+                // the lock went with the connection if it's broken
+                warn!("Error checking leader lock: {}", e);
+                lock_conn = None;
+                stand_by();
+            }
+        }
+        let check_secs = get_config(&ctx).await.leader_check_secs;
+        tokio::time::sleep(std::time::Duration::from_secs(check_secs)).await;
+    }
+    // the connection is dropped here, which releases the lock for the next client
+    IS_LEADER.store(false, Ordering::SeqCst);
+}
+
+async fn lock_connection(ctx: &Context) -> Result<MysqlConnection> {
+    let database_url = {
+        let data = ctx.data.read().await;
+        data.get::<EnvironmentContainer>()
+            .expect("No environment in share map")
+            .database_url
+            .clone()
+    };
+
+    Ok(MysqlConnection::establish(&database_url)?)
+}
+
+fn stand_by() {
+    if IS_LEADER.swap(false, Ordering::SeqCst) {
+        warn!("Lost leader lock, standing by");
+    }
+}
+
+async fn become_leader(ctx: &Context) {
+    if is_leader() {
+        return;
+    }
+    // a standby's caches are whatever was in the database when it started, and the
+    // old leader may have added groups or changed settings since then. if they can't be
+    // reloaded we hold on to the lock and try again next time around.
+    if let Err(e) = reload_caches(ctx).await {
+        warn!("Error reloading caches after acquiring leader lock: {}", e);
+        return;
+    }
+    IS_LEADER.store(true, Ordering::SeqCst);
+    info!("Acquired leader lock, processing submissions");
+    tokio::spawn(replay_missed_messages(ctx.clone()));
+}
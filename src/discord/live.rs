@@ -0,0 +1,117 @@
+use std::sync::atomic::Ordering;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serenity::{client::Context, model::id::ChannelId};
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup, leader::is_leader, ops::DiscordOps,
+        secrets::CLIENT_GENERATION,
+    },
+    error::MurahdahlaError,
+    games::AsyncRaceData,
+    helpers::*,
+};
+
+// how often we look for live races about to start
+const LIVE_CHECK_SECS: u64 = 5;
+// the countdown is posted at these many seconds before the start. discord lets a
+// channel have 5 messages every 5 seconds so this never has to wait on a rate limit.
+const COUNTDOWN: [i64; 6] = [10, 5, 4, 3, 2, 1];
+// a countdown that's this late, like after the bot was down, isn't posted at all
+const MAX_COUNTDOWN_LATENESS_SECS: i64 = 30;
+
+pub async fn live_task(ctx: Context) {
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(LIVE_CHECK_SECS)).await;
+        if CLIENT_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if !is_leader() {
+            continue;
+        }
+        if let Err(e) = check_live_races(&ctx).await {
+            warn!("Error checking live races: {}", e);
+        }
+    }
+}
+
+async fn check_live_races(ctx: &Context) -> Result<(), MurahdahlaError> {
+    use crate::schema::async_races::columns::*;
+
+    let now = Utc::now().naive_utc();
+    // picked up a little before the first number so there's time to sleep until it
+    let soon = now + Duration::seconds(COUNTDOWN[0] + LIVE_CHECK_SECS as i64 + 1);
+    let races: Vec<AsyncRaceData> = {
+        let conn = get_connection(ctx).await;
+        crate::schema::async_races::table
+            .filter(race_active.eq(true))
+            .filter(live_countdown_sent.eq(false))
+            .filter(race_live_at.le(soon))
+            .load(&conn)?
+    };
+    for race in races {
+        // marked first so a second leader or the next check can't count down twice
+        {
+            let conn = get_connection(ctx).await;
+            diesel::update(&race)
+                .set(live_countdown_sent.eq(true))
+                .execute(&conn)?;
+        }
+        let start = race.race_live_at.unwrap();
+        if now - start > Duration::seconds(MAX_COUNTDOWN_LATENESS_SECS) {
+            warn!("Skipped the countdown for live race {}", race.race_id);
+            continue;
+        }
+        let group = {
+            let data = ctx.data.read().await;
+            data.get::<GroupContainer>()
+                .expect("No group container in share map")
+                .values()
+                .find(|g| g.channel_group_id == race.channel_group_id)
+                .cloned()
+        };
+        if let Some(g) = group {
+            let http = ctx.http.clone();
+            tokio::spawn(async move {
+                if let Err(e) = count_down(&*http, &g, start).await {
+                    warn!("Error counting down live race {}: {}", race.race_id, e);
+                }
+            });
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn count_down(
+    ops: &dyn DiscordOps,
+    group: &ChannelGroup,
+    start: NaiveDateTime,
+) -> Result<(), MurahdahlaError> {
+    // every number is slept for from the same start time so a slow send doesn't push the
+    // rest of the countdown back
+    let channel = ChannelId::from(group.submission);
+    for secs in COUNTDOWN {
+        sleep_until(start - Duration::seconds(secs)).await;
+        let text = match secs == COUNTDOWN[0] {
+            true => format!("The race starts in {} seconds!", secs),
+            false => format!("{}...", secs),
+        };
+        ops.say(channel, &text).await?;
+    }
+    sleep_until(start).await;
+    ops.say(channel, "Go! Send \".done\" here when you finish.")
+        .await?;
+
+    Ok(())
+}
+
+async fn sleep_until(at: NaiveDateTime) {
+    // a time that's already passed doesn't wait at all
+    if let Ok(wait) = (at - Utc::now().naive_utc()).to_std() {
+        tokio::time::sleep(wait).await;
+    }
+}
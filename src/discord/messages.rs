@@ -1,27 +1,66 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
 use anyhow::{anyhow, Result};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use futures::{join, try_join};
 use serenity::{
-    framework::standard::macros::hook,
+    builder::CreateMessage,
     model::{
+        application::{CommandType, Interaction},
         channel::Message,
-        id::{ChannelId, UserId},
+        event::ResumedEvent,
+        gateway::Ready,
+        id::{ChannelId, GuildId, MessageId, UserId},
+        user::User,
     },
     prelude::*,
     utils::MessageBuilder,
 };
 
 use crate::{
+    config::{get_config, MessagePolicy, RollPreset},
     discord::{
+        aliases::ServerAliases,
+        analytics::{percentile_trend, RunnerResult, SettingsStats},
+        banned_runners::is_banned,
+        branding::post_branded,
         channel_groups::{get_group, in_submission_channel, ChannelGroup, ChannelType},
-        servers::add_spoiler_role,
+        claims::timed_run,
+        context_menus::handle_context_menu,
+        deadlines::deadline_task,
+        events::{emit, start_subscribers, RaceEvent},
+        ingest::enqueue_submission,
+        leader::{is_leader, leader_task},
+        live::live_task,
+        notes::SubmissionNote,
+        ops::{DiscordOps, PostedMessage},
+        presets::GroupPreset,
+        replay::{advance_cursor, replay_missed_messages, ChannelLag},
+        secrets::CLIENT_GENERATION,
+        server_settings::{get_setting, get_setting_parsed, Locale, ServerSetting, ServerSettings},
+        servers::Permission,
+        shared_cache::follow_shared_cache,
+        spoiler_guard::guard_spoiler_channel,
+        spoiler_roles::{get_spoiler_roles, grant_spoiler_access},
         submissions::{
-            build_leaderboard, process_submission, write_submission_add_role, NewSubmission,
-            Submission,
+            process_submission, refresh_leaderboards_task, write_submission_add_role,
+            NewSubmission, Submission, BLIND_NOTE,
         },
+        templates::GameTemplate,
+        throttle::{check_throttle, Attempt},
+        verification::{
+            handle_verification, needs_verification, post_verification_request, VERIFY_PREFIX,
+        },
+    },
+    error::MurahdahlaError,
+    games::{
+        api_health::api_stats_task, custom::CustomGameDefinition, get_maybe_active_race,
+        AsyncRaceData, DataDisplay, NewAsyncRaceData, SeedError, OTHER_FLAG,
     },
-    games::{get_maybe_active_race, AsyncRaceData, DataDisplay},
     helpers::*,
     schema::*,
     MAINTENANCE_USER,
@@ -41,44 +80,135 @@ pub struct BotMessage {
 }
 
 impl BotMessage {
-    pub fn from_serenity_msg(
-        msg: &Message,
+    pub fn from_posted(
+        msg: &PostedMessage,
         server_id: u64,
         race_id: u32,
         channel_type: ChannelType,
     ) -> Self {
         BotMessage {
-            message_id: *msg.id.as_u64(),
-            message_datetime: msg.timestamp.naive_utc(),
+            message_id: msg.message_id.get(),
+            message_datetime: msg.timestamp,
             race_id,
             server_id,
-            channel_id: *msg.channel_id.as_u64(),
+            channel_id: msg.channel_id.get(),
             channel_type,
         }
     }
+
+    // a link that jumps straight to the post in discord
+    pub fn jump_link(&self) -> String {
+        format!(
+            "https://discord.com/channels/{}/{}/{}",
+            self.server_id, self.channel_id, self.message_id
+        )
+    }
 }
 
+pub fn get_race_post(conn: &PooledConn, race: &AsyncRaceData) -> Result<Option<BotMessage>> {
+    // the first post in the submission channel is the race announcement. it's edited into
+    // the first post of the final results when the race is stopped.
+    use crate::schema::messages::columns::*;
+
+    let post = BotMessage::belonging_to(race)
+        .filter(channel_type.eq(ChannelType::Submission))
+        .order(message_datetime.asc())
+        .first(conn)
+        .optional()?;
+
+    Ok(post)
+}
+
+// how much of each preset's settings !listpresets shows
+const MAX_PRESET_LIST_CHARS: usize = 80;
+
+// the client generation the running background tasks belong to
+static BACKGROUND_TASK_GENERATION: AtomicU64 = AtomicU64::new(u64::MAX);
+
 pub struct Handler;
 
 #[serenity::async_trait]
 impl EventHandler for Handler {
-    // we may not need an event handler since our hooks grab everything we need
-    // but let's keep this around for now
-    async fn message(&self, _ctx: Context, _msg: Message) {}
+    async fn message(&self, ctx: Context, msg: Message) {
+        // the framework sees every message too and takes the ones that are commands
+        if !is_leader() || is_command(&ctx, msg.guild_id, &msg.content).await {
+            return;
+        }
+        handle_normal_message(&ctx, &msg).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        // a standby instance leaves it to the leader
+        if !is_leader() {
+            return;
+        }
+        // buttons from commands that wait for a reply are handled by collectors. the
+        // only ones we handle here are on the verification queue. slash commands go
+        // through the framework, which leaves our context menu commands.
+        match interaction {
+            Interaction::Component(c) if c.data.custom_id.starts_with(VERIFY_PREFIX) => {
+                if let Err(e) = handle_verification(&ctx, &c).await {
+                    warn!("Error handling verification: {}", e);
+                    message_maintenance_user(&ctx, e).await;
+                }
+            }
+            Interaction::Command(c) if c.data.kind != CommandType::ChatInput => {
+                if let Err(e) = handle_context_menu(&ctx, &c).await {
+                    warn!("Error handling context menu command: {}", e);
+                    message_maintenance_user(&ctx, e).await;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    async fn ready(&self, ctx: Context, _ready: Ready) {
+        // the first replay for a client happens when it gets the leader lock
+        if is_leader() {
+            tokio::spawn(replay_missed_messages(ctx.clone()));
+        }
+        // ready fires again on reconnects so make sure we only start one set of
+        // background tasks for each client. a client rebuilt after a token rotation gets
+        // its own.
+        let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+        if BACKGROUND_TASK_GENERATION.swap(generation, Ordering::SeqCst) == generation {
+            return;
+        }
+        // subscribe before anything that could emit an event starts
+        start_subscribers(ctx.clone()).await;
+        tokio::spawn(leader_task(ctx.clone()));
+        tokio::spawn(follow_shared_cache(ctx.clone()));
+        tokio::spawn(refresh_leaderboards_task(ctx.clone()));
+        tokio::spawn(deadline_task(ctx.clone()));
+        tokio::spawn(api_stats_task(ctx.clone()));
+        tokio::spawn(live_task(ctx));
+    }
+
+    async fn resume(&self, ctx: Context, _resumed: ResumedEvent) {
+        if is_leader() {
+            tokio::spawn(replay_missed_messages(ctx));
+        }
+    }
 }
 
-#[hook]
-pub async fn normal_message_hook(ctx: &Context, msg: &Message) {
-    use crate::schema::submissions::columns::runner_name;
+pub async fn handle_normal_message(ctx: &Context, msg: &Message) {
     // the only non-command messages we're interested in are time submissions from
-    // non bot users
-    if !in_submission_channel(ctx, msg).await || (msg.author.id == { ctx.cache.current_user_id() })
-    {
+    // non bot users and posts in guarded spoiler channels
+    if msg.author.id == ctx.cache.current_user().id {
         return;
     }
-    let group_fut = get_group(ctx, msg);
+    match in_submission_channel(ctx, msg.channel_id).await {
+        true => enqueue_submission(ctx, msg).await,
+        false => guard_spoiler_channel(ctx, msg).await,
+    }
+}
+
+pub async fn process_candidate(ctx: &Context, msg: &Message) {
+    // runs on a submission worker for every message the message handler picked up
+    let group_fut = get_group(ctx, msg.channel_id);
     let conn_fut = get_connection(ctx);
     let (group, conn) = join!(group_fut, conn_fut);
+    let policy = get_message_policy(ctx, &group).await;
 
     let maybe_active_race: Option<AsyncRaceData> = get_maybe_active_race(&conn, &group);
     let race = match maybe_active_race {
@@ -86,13 +216,87 @@ pub async fn normal_message_hook(ctx: &Context, msg: &Message) {
         None => {
             // if there's no active race we still want to delete messages and keep this
             // channel tidy before returning
+            if policy == MessagePolicy::DeleteAll {
+                let _ = delete_sub_msg(ctx, msg).await.map_err(|e| warn!("{}", e));
+            }
+            if let Err(e) = advance_cursor(&conn, &group, msg.id) {
+                warn!("Error saving last processed message: {}", e);
+            }
+            return;
+        }
+    };
+    if let Attempt::Throttled { .. } = check_throttle(ctx, msg).await {
+        info!("Throttled submission from \"{}\"", &msg.author.name);
+        if policy == MessagePolicy::DeleteAll {
             let _ = delete_sub_msg(ctx, msg).await.map_err(|e| warn!("{}", e));
+        }
+        if let Err(e) = advance_cursor(&conn, &group, msg.id) {
+            warn!("Error saving last processed message: {}", e);
+        }
+        return;
+    }
+    // handle_submission takes its own connection
+    drop(conn);
+    handle_submission(ctx, msg, &group, &race, policy).await;
+}
+
+pub async fn get_message_policy(ctx: &Context, group: &ChannelGroup) -> MessagePolicy {
+    get_setting_parsed(
+        ctx,
+        GuildId::from(group.server_id),
+        ServerSetting::MessagePolicy,
+    )
+    .await
+    .unwrap_or(get_config(ctx).await.submission_message_policy)
+}
+
+pub async fn handle_submission(
+    ctx: &Context,
+    msg: &Message,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    policy: MessagePolicy,
+) {
+    // everything past finding the race is shared with anything that processes messages
+    // after the fact, like backfilling the channel's history or replaying what we missed
+    // while disconnected
+    submission_pipeline(ctx, msg, group, race, policy).await;
+    let conn = get_connection(ctx).await;
+    if let Err(e) = advance_cursor(&conn, group, msg.id) {
+        warn!("Error saving last processed message: {}", e);
+    }
+}
+
+async fn submission_pipeline(
+    ctx: &Context,
+    msg: &Message,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    policy: MessagePolicy,
+) {
+    use crate::schema::submissions::columns::runner_name;
+
+    let conn = get_connection(ctx).await;
+    // banned runners' messages are deleted without looking at them any further
+    match is_banned(&conn, group, msg.author.id.get()) {
+        Ok(false) => (),
+        Ok(true) => {
+            info!(
+                "Ignoring message from banned runner \"{}\"",
+                &msg.author.name
+            );
+            let _ = delete_sub_msg(ctx, msg).await.map_err(|e| info!("{}", e));
+            return;
+        }
+        Err(e) => {
+            warn!("Error checking for banned runner: {}", e);
+            message_maintenance_user(ctx, e).await;
             return;
         }
     };
 
     // check for duplicates
-    if Submission::belonging_to(&race)
+    if Submission::belonging_to(race)
         .filter(runner_name.eq(&msg.author.name))
         .first::<Submission>(&conn)
         .ok()
@@ -106,8 +310,22 @@ pub async fn normal_message_hook(ctx: &Context, msg: &Message) {
     // here we parse a possible time submission. If we get a good submission, insert
     // it into the database and we'll call a function to refresh the leaderboard from the
     // db below
-    let submission: NewSubmission = match process_submission(msg, &race) {
+    // runners the bot is timing are timed to when they sent this
+    let claim_time = match timed_run(&conn, race, msg.author.id.get(), msg.timestamp.naive_utc()) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Error getting seed claim: {}", e);
+            message_maintenance_user(ctx, e).await;
+            return;
+        }
+    };
+    let submission: NewSubmission = match process_submission(msg, race, group, claim_time) {
         Ok(s) => s,
+        Err(e) if policy == MessagePolicy::KeepInvalid => {
+            // when we're keeping non-submissions around most of these will just be chat
+            info!("Message was not a submission: {}", e);
+            return;
+        }
         Err(e) => {
             let _ = delete_sub_msg(ctx, msg).await.map_err(|e| warn!("{}", e));
             warn!("Error processing submission: {}", e);
@@ -115,30 +333,72 @@ pub async fn normal_message_hook(ctx: &Context, msg: &Message) {
             return;
         }
     };
+    drop(conn);
+    accept_submission(ctx, group, race, submission, msg.author.id).await;
+    if let Err(e) = delete_sub_msg(ctx, msg).await {
+        warn!("Error during post-submission: {}", e);
+        message_maintenance_user(ctx, e).await;
+    }
+}
 
-    let role_fut = add_spoiler_role(ctx, msg, group.spoiler_role_id);
-    match write_submission_add_role(ctx, &submission, role_fut).await {
-        Ok(_) => (),
-        Err(e) => {
-            warn!("Error finalizing submission: {}", e);
-            message_maintenance_user(ctx, e).await
+pub async fn accept_submission(
+    ctx: &Context,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    mut submission: NewSubmission,
+    runner: UserId,
+) {
+    // everything after a submission was parsed, whether it came from a message in the
+    // submission channel or from !done
+    let conn = get_connection(ctx).await;
+    // if we can't read the group's extra roles just fall back to its spoiler role
+    let rules = get_spoiler_roles(&conn, group).unwrap_or_else(|e| {
+        warn!("Error getting spoiler roles: {}", e);
+        Vec::new()
+    });
+    submission.pending = needs_verification(group, &submission);
+    let role_fut = grant_spoiler_access(&*ctx.http, group, &rules, race, runner);
+    let pool = get_db_pool(ctx).await;
+    match write_submission_add_role(&pool, &submission, role_fut).await {
+        Ok(true) => (),
+        Ok(false) => {
+            info!(
+                "Submission from \"{}\" was already stored",
+                &submission.runner_name
+            );
+            return;
         }
-    };
-
-    // refresh leaderboard from db
-    let lb_fut = build_leaderboard(ctx, &group, &race, ChannelType::Leaderboard);
-    let delete_fut = delete_sub_msg(ctx, msg);
-
-    match try_join!(lb_fut, delete_fut) {
-        Ok(_) => (),
         Err(e) => {
-            warn!("Error during post-submission: {}", e);
+            warn!("Error finalizing submission: {}", e);
             message_maintenance_user(ctx, e).await;
             return;
         }
     };
+    if submission.pending {
+        // we need the id the database gave the submission for the queue's buttons
+        let inserted: Result<Submission, _> = Submission::belonging_to(race)
+            .filter(submissions::runner_id.eq(submission.runner_id))
+            .first(&conn);
+        let posted = match inserted {
+            Ok(s) => post_verification_request(ctx, group, &s).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = posted {
+            warn!("Error posting submission for verification: {}", e);
+            message_maintenance_user(ctx, e).await;
+        }
+    }
 
-    ()
+    // the leaderboard is rebuilt in the background by the event's subscriber
+    emit(
+        ctx,
+        RaceEvent::SubmissionAccepted {
+            group: group.clone(),
+            race: race.clone(),
+            runner_id: submission.runner_id,
+        },
+    )
+    .await;
 }
 
 pub fn build_listgroups_message(mut groups: Vec<String>) -> String {
@@ -169,31 +429,549 @@ pub fn build_listgroups_message(mut groups: Vec<String>) -> String {
     }
 }
 
+pub fn build_roles_message(admin_role: Option<String>, mod_role: Option<String>) -> String {
+    let roles_list = format!(
+        "admin: {}\nmod: {}\n",
+        admin_role.as_deref().unwrap_or("(none)"),
+        mod_role.as_deref().unwrap_or("(none)")
+    );
+
+    MessageBuilder::new()
+        .push_codeblock_safe(roles_list, None)
+        .build()
+}
+
+pub fn build_settings_message(settings: &ServerSettings) -> String {
+    let mut settings_list = String::with_capacity(100);
+    ServerSetting::ALL.iter().for_each(|k| {
+        let value = settings.get(k).map(|v| v.as_str()).unwrap_or("(default)");
+        settings_list.push_str(format!("{}: {}\n", k, value).as_str());
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(settings_list, None)
+        .build()
+}
+
+pub fn build_removegroup_prompt(group_name: &str, active_races: &[AsyncRaceData]) -> String {
+    let mut prompt = MessageBuilder::new();
+    prompt
+        .push("Remove group ")
+        .push_bold_safe(group_name)
+        .push("? This deletes all of its races and submissions.");
+    if !active_races.is_empty() {
+        prompt
+            .push_line("")
+            .push_line("These races are still active:");
+        active_races.iter().for_each(|r| {
+            prompt.push_line_safe(format!("{} - {} {}", r.race_id, r.race_date, r.race_info));
+        });
+    }
+
+    prompt.build()
+}
+
+pub fn build_cursors_message(lags: &[ChannelLag]) -> String {
+    if lags.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock("There are no groups.", None)
+            .build();
+    }
+    let mut lag_list = String::with_capacity(lags.len() * 60);
+    lags.iter().for_each(|l| {
+        let last = l
+            .last_processed
+            .map_or("(none)".to_owned(), |m| m.to_string());
+        let lag = l
+            .lag_secs()
+            .map_or("unknown".to_owned(), |s| format!("{}s", s));
+        lag_list.push_str(
+            format!(
+                "{} ({}) - last processed: {} - lag: {}\n",
+                l.group_name, l.channel_id, last, lag
+            )
+            .as_str(),
+        );
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(lag_list, None)
+        .build()
+}
+
+pub fn build_notes_messages(noted: &[(Submission, Vec<SubmissionNote>)]) -> Vec<String> {
+    // a race can have a lot of notes so they're split over as many dms as it takes,
+    // leaving room in each for the codeblock around it
+    const NOTES_POST_CHARS: usize = 1900;
+
+    if noted.is_empty() {
+        return vec![MessageBuilder::new()
+            .push_codeblock("There are no notes on this race's submissions.", None)
+            .build()];
+    }
+    let mut posts: Vec<String> = Vec::new();
+    let mut post = String::new();
+    for (s, notes) in noted {
+        let mut entry = format!("{}:\n", s.runner_name);
+        notes.iter().for_each(|n| {
+            entry.push_str(
+                format!(
+                    "  - {} ({}, {})\n",
+                    n.note_text, n.author_name, n.note_datetime
+                )
+                .as_str(),
+            )
+        });
+        if !post.is_empty() && post.chars().count() + entry.chars().count() > NOTES_POST_CHARS {
+            posts.push(std::mem::take(&mut post));
+        }
+        post.push_str(&entry);
+    }
+    posts.push(post);
+
+    posts
+        .into_iter()
+        .map(|p| MessageBuilder::new().push_codeblock_safe(p, None).build())
+        .collect()
+}
+
+pub fn build_settingsstats_messages(stats: &[SettingsStats]) -> Vec<String> {
+    // long settings strings are cut short so one line fits a handful of settings
+    const STATS_POST_CHARS: usize = 1900;
+    const SETTINGS_CHARS: usize = 80;
+
+    if stats.is_empty() {
+        return vec![MessageBuilder::new()
+            .push_codeblock("This server has no finished races yet.", None)
+            .build()];
+    }
+    let mut posts: Vec<String> = Vec::new();
+    let mut post = String::new();
+    for s in stats {
+        let mut settings: String = s.settings.chars().take(SETTINGS_CHARS).collect();
+        if settings.len() < s.settings.len() {
+            settings.push_str("...");
+        }
+        let average = match s.average {
+            Some(a) => format!("average {}", a),
+            None => "no timed finishes".to_owned(),
+        };
+        let entry = format!(
+            "{} - {}\n  {} races, {} finishes, {} forfeits ({:.0}%), {}\n",
+            s.game,
+            settings,
+            s.races,
+            s.finishes,
+            s.forfeits,
+            s.forfeit_rate() * 100.0,
+            average
+        );
+        if !post.is_empty() && post.chars().count() + entry.chars().count() > STATS_POST_CHARS {
+            posts.push(std::mem::take(&mut post));
+        }
+        post.push_str(&entry);
+    }
+    posts.push(post);
+
+    posts
+        .into_iter()
+        .map(|p| MessageBuilder::new().push_codeblock_safe(p, None).build())
+        .collect()
+}
+
+pub fn build_progress_message(runner_name: &str, results: &[RunnerResult]) -> String {
+    // only the most recent races fit in one message, the trend still uses all of them
+    const PROGRESS_RACES: usize = 25;
+    const BAR_WIDTH: f64 = 10.0;
+
+    if results.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock_safe(
+                format!(
+                    "{} hasn't finished any stopped races here yet.",
+                    runner_name
+                ),
+                None,
+            )
+            .build();
+    }
+    let mut table = String::from("Date        Game      Time      Place    Percentile\n");
+    results
+        .iter()
+        .skip(results.len().saturating_sub(PROGRESS_RACES))
+        .for_each(|r| {
+            let bar: String = "█".repeat((r.percentile / 100.0 * BAR_WIDTH).round() as usize);
+            table.push_str(
+                format!(
+                    "{}  {:<8}  {}  {:>3}/{:<3}  {:>3.0}% {}\n",
+                    r.race_date,
+                    r.game.to_string(),
+                    r.time,
+                    r.place,
+                    r.field,
+                    r.percentile,
+                    bar
+                )
+                .as_str(),
+            )
+        });
+    let trend = match percentile_trend(results) {
+        Some(t) if t.abs() < 0.5 => "Holding steady".to_owned(),
+        Some(t) => format!("{:+.1} percentile points per race", t),
+        None => "Not enough races for a trend yet".to_owned(),
+    };
+
+    MessageBuilder::new()
+        .push_bold_line(format!("{} over {} races", runner_name, results.len()))
+        .push_codeblock_safe(table, None)
+        .push_line(format!("Trend: {}", trend))
+        .build()
+}
+
+pub fn build_listraces_message(races: &[AsyncRaceData]) -> String {
+    if races.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock("There are no races in this group.", None)
+            .build();
+    }
+    let mut race_list = String::with_capacity(races.len() * 40);
+    races.iter().for_each(|r| {
+        let status = match r.race_active {
+            true => " (active)",
+            false => "",
+        };
+        race_list.push_str(
+            format!(
+                "{} - {} - {} ({}){}\n",
+                r.race_id, r.race_date, r.race_game, r.race_type, status
+            )
+            .as_str(),
+        );
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(race_list, None)
+        .build()
+}
+
+pub fn build_aliases_message(aliases: &ServerAliases) -> String {
+    if aliases.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock("There are no command aliases on this server.", None)
+            .build();
+    }
+    let mut alias_list: Vec<(&String, &String)> = aliases.iter().collect();
+    alias_list.sort_unstable();
+    let alias_string = alias_list
+        .iter()
+        .map(|(a, c)| format!("{} -> {}\n", a, c))
+        .collect::<String>();
+
+    MessageBuilder::new()
+        .push_codeblock_safe(alias_string, None)
+        .build()
+}
+
+pub fn build_presets_message(
+    group_presets: &[GroupPreset],
+    bot_presets: &HashMap<String, RollPreset>,
+) -> String {
+    // the group's presets shadow the bot's ones with the same name
+    let mut bot_presets = bot_presets
+        .iter()
+        .filter(|(name, _)| {
+            !group_presets
+                .iter()
+                .any(|p| p.preset_name.eq_ignore_ascii_case(name))
+        })
+        .collect::<Vec<(&String, &RollPreset)>>();
+    if group_presets.is_empty() && bot_presets.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock("There are no presets for this group.", None)
+            .build();
+    }
+    bot_presets.sort_by_key(|(name, _)| name.to_lowercase());
+    let mut preset_list = String::with_capacity((group_presets.len() + bot_presets.len()) * 80);
+    group_presets.iter().for_each(|p| {
+        preset_list.push_str(
+            format!(
+                "{} ({}) - {}\n",
+                p.preset_name,
+                p.preset_game,
+                shorten_settings(&p.preset_settings)
+            )
+            .as_str(),
+        );
+    });
+    bot_presets.iter().for_each(|(name, p)| {
+        preset_list.push_str(
+            format!(
+                "{} ({}, for every group) - {}\n",
+                name.to_lowercase(),
+                p.game,
+                shorten_settings(&p.settings.to_string())
+            )
+            .as_str(),
+        );
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(preset_list, None)
+        .build()
+}
+
+fn shorten_settings(settings: &str) -> String {
+    // presets can be long so the list only shows the start of each one
+    match settings.chars().count() > MAX_PRESET_LIST_CHARS {
+        true => format!(
+            "{}...",
+            settings
+                .chars()
+                .take(MAX_PRESET_LIST_CHARS)
+                .collect::<String>()
+        ),
+        false => settings.to_owned(),
+    }
+}
+
+pub fn build_custom_games_message(games: &[CustomGameDefinition]) -> String {
+    if games.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock("There are no added games on this server.", None)
+            .build();
+    }
+    let mut game_list = String::with_capacity(games.len() * 80);
+    games.iter().for_each(|g| {
+        let mut fields = g.fields.clone();
+        if g.collection {
+            fields.insert(0, "collection".to_owned());
+        }
+        let fields = match fields.is_empty() {
+            true => "time only".to_owned(),
+            false => fields.join(","),
+        };
+        game_list.push_str(
+            format!(
+                "{} - \"{}\" on {} ({})\n",
+                g.name,
+                g.display_name,
+                g.hosts.join(", "),
+                fields
+            )
+            .as_str(),
+        );
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(game_list, None)
+        .build()
+}
+
+pub fn build_templates_message(templates: &[GameTemplate]) -> String {
+    if templates.is_empty() {
+        return MessageBuilder::new()
+            .push_codeblock("There are no game templates on this server.", None)
+            .build();
+    }
+    let mut template_list = String::with_capacity(templates.len() * 60);
+    templates.iter().for_each(|t| {
+        let fields = match t.template_fields.is_empty() {
+            true => "time only",
+            false => t.template_fields.as_str(),
+        };
+        template_list.push_str(
+            format!(
+                "{} - \"{}\" ({})\n",
+                t.template_name, t.template_format, fields
+            )
+            .as_str(),
+        );
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(template_list, None)
+        .build()
+}
+
+pub fn build_permissions_message(commands: &[(String, Permission, Vec<String>)]) -> String {
+    let mut permissions_list = String::with_capacity(commands.len() * 30);
+    commands.iter().for_each(|(c, p, roles)| {
+        let level = match p {
+            Permission::Admin => "admin",
+            Permission::Mod => "mod",
+            Permission::None => "everyone",
+        };
+        permissions_list.push_str(format!("{}: {}", c, level).as_str());
+        if !roles.is_empty() {
+            permissions_list.push_str(format!(" + {}", roles.join(", ")).as_str());
+        }
+        permissions_list.push('\n');
+    });
+
+    MessageBuilder::new()
+        .push_codeblock_safe(permissions_list, None)
+        .build()
+}
+
+pub fn build_preview_message(race_data: &NewAsyncRaceData, max_settings: usize) -> String {
+    let mut content = MessageBuilder::new();
+    content
+        .push_bold_line("Submission channel:")
+        .push_line(race_data.announcement_string(max_settings))
+        .push("\n")
+        .push_bold_line("Leaderboard channel:")
+        .push_line(race_data.leaderboard_string(max_settings));
+    if race_data.race_blind {
+        content.push_line(BLIND_NOTE);
+    }
+
+    content.build()
+}
+
+pub fn build_raceinfo_message(
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    participants: i64,
+    race_post: Option<&BotMessage>,
+    locale: Locale,
+) -> String {
+    let mut content = MessageBuilder::new();
+    content
+        .push_bold_line("Current race:")
+        .push_line(race.announcement_string(group.max_settings_chars()));
+    if let Some(p) = race_post {
+        content.push_line(format!("Race post: {}", p.jump_link()));
+    }
+    if let Some(d) = race.race_deadline {
+        content.push_line(format!("Ends {} UTC", locale.format_date(&d)));
+    }
+    if race.race_blind {
+        content.push_line(BLIND_NOTE);
+    }
+    if let Some(p) = &race.race_preset {
+        content.push_line_safe(format!("Rolled with the \"{}\" preset", p));
+    }
+    let plural = match participants {
+        1 => "",
+        _ => "s",
+    };
+    content.push_line(format!("{} submission{} so far", participants, plural));
+
+    content.build()
+}
+
+pub async fn notify_seed_error(ctx: &Context, author: &User, e: &SeedError, cmd_name: &str) {
+    // the command message is usually deleted right after this so a DM is the only way
+    // to tell a mod why their race didn't start
+    let content = MessageBuilder::new()
+        .push_line(
+            "Could not start the race. The seed site may be down or the seed may be too old:",
+        )
+        .push_codeblock_safe(e.to_string(), None)
+        .push_line("To start the race anyway without seed information, run:")
+        .push_mono_safe(format!("!{} {} {}", cmd_name, OTHER_FLAG, e.url))
+        .build();
+    match author
+        .direct_message(&ctx, CreateMessage::new().content(content))
+        .await
+    {
+        Ok(_) => (),
+        Err(e) => warn!(
+            "Error sending seed error DM to user \"{}\": {}",
+            &author.name, e
+        ),
+    };
+}
+
+pub async fn notify_invoker<T: std::fmt::Display>(
+    ctx: &Context,
+    author: &User,
+    invocation: &str,
+    e: T,
+) {
+    // for problems the person running a command can fix themselves, like a role that
+    // doesn't exist, rather than ones for the maintenance user
+    let content = MessageBuilder::new()
+        .push_line(format!("Your command \"{}\" couldn't be run:", invocation))
+        .push_codeblock_safe(e.to_string(), None)
+        .build();
+    author
+        .direct_message(&ctx, CreateMessage::new().content(content))
+        .await
+        .map(|_| ())
+        .unwrap_or_else(|e| {
+            warn!(
+                "Error sending command error DM to user \"{}\": {}",
+                &author.name, e
+            )
+        });
+}
+
+pub async fn warn_seed_reuse(ctx: &Context, author: &User, races: &[AsyncRaceData]) {
+    // the race still starts, this is so a mod can pick a new seed if reusing it wasn't
+    // on purpose
+    let mut content = MessageBuilder::new();
+    content.push_line("This seed was already used in earlier races on this server:");
+    let race_list = races
+        .iter()
+        .map(|r| format!("{} - {} {}", r.race_id, r.race_date, r.race_info))
+        .collect::<Vec<String>>()
+        .join("\n");
+    content
+        .push_codeblock_safe(race_list, None)
+        .push("Runners from those races may have already played it.");
+    author
+        .direct_message(&ctx, CreateMessage::new().content(content.build()))
+        .await
+        .map(|_| ())
+        .unwrap_or_else(|e| {
+            warn!(
+                "Error sending seed reuse DM to user \"{}\": {}",
+                &author.name, e
+            )
+        });
+}
+
 pub async fn handle_new_race_messages(
     ctx: &Context,
     group: &ChannelGroup,
     race_data: &AsyncRaceData,
-) -> Result<(), BoxedError> {
+) -> Result<(), MurahdahlaError> {
+    let ping_role: Option<u64> =
+        get_setting_parsed(ctx, GuildId::from(group.server_id), ServerSetting::PingRole).await;
+    let pool = get_db_pool(ctx).await;
+
+    post_race_messages(&*ctx.http, &pool, group, race_data, ping_role).await
+}
+
+pub async fn post_race_messages(
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
+    group: &ChannelGroup,
+    race_data: &AsyncRaceData,
+    ping_role: Option<u64>,
+) -> Result<(), MurahdahlaError> {
     use crate::schema::messages::dsl::*;
 
-    let base_game_string = race_data.base_string();
-    let leaderboard_string = race_data.leaderboard_string();
+    let leaderboard_string = race_data.leaderboard_string(group.max_settings_chars());
+    let announcement = race_data.announcement_string(group.max_settings_chars());
+    let ping = ping_role.map(|r| format!("<@&{}>", r));
     let sub_channel = ChannelId::from(group.submission);
     let lb_channel = ChannelId::from(group.leaderboard);
     let (lb_message, sub_message) = try_join!(
-        lb_channel.say(&ctx, &leaderboard_string),
-        sub_channel.say(&ctx, &base_game_string)
+        post_branded(ops, group, lb_channel, None, &leaderboard_string),
+        post_branded(ops, group, sub_channel, ping.as_deref(), &announcement)
     )?;
 
-    let conn = get_connection(ctx).await;
+    let conn = pool.get()?;
     let new_messages = vec![
-        BotMessage::from_serenity_msg(
+        BotMessage::from_posted(
             &sub_message,
             group.server_id,
             race_data.race_id,
             ChannelType::Submission,
         ),
-        BotMessage::from_serenity_msg(
+        BotMessage::from_posted(
             &lb_message,
             group.server_id,
             race_data.race_id,
@@ -207,6 +985,39 @@ pub async fn handle_new_race_messages(
     Ok(())
 }
 
+pub async fn delete_leaderboard_posts(
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
+    race: &AsyncRaceData,
+) -> Result<(), MurahdahlaError> {
+    let leaderboard_msgs_data: Vec<BotMessage> = {
+        let conn = pool.get()?;
+        get_lb_msgs_data(&conn, race.race_id)?
+    };
+    let mut failures: Vec<String> = Vec::new();
+    for d in leaderboard_msgs_data.iter() {
+        let deleted = ops
+            .delete(ChannelId::from(d.channel_id), MessageId::from(d.message_id))
+            .await;
+        // a post that's already gone was probably deleted by an earlier try
+        match deleted {
+            Ok(()) => (),
+            Err(e) if is_not_found(&e) => (),
+            Err(e) => failures.push(format!("message {}: {}", d.message_id, e)),
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Failed to delete {} leaderboard posts: {}",
+            failures.len(),
+            failures.join("; ")
+        )
+        .into()),
+    }
+}
+
 #[inline]
 pub fn get_lb_msgs_data(conn: &PooledConn, this_race_id: u32) -> Result<Vec<BotMessage>> {
     // retrieves data about bot messages in a leaderboard channel for a given race id
@@ -220,8 +1031,36 @@ pub fn get_lb_msgs_data(conn: &PooledConn, this_race_id: u32) -> Result<Vec<BotM
     Ok(active_posts)
 }
 
+pub async fn is_command(ctx: &Context, guild_id: Option<GuildId>, content: &str) -> bool {
+    let prefix = match guild_id {
+        Some(g) => get_setting(ctx, g, ServerSetting::Prefix).await,
+        None => None,
+    };
+    content.starts_with('!') || prefix.map_or(false, |p| content.starts_with(&p))
+}
+
+pub async fn is_command_message(ctx: &Context, group: &ChannelGroup, msg: &Message) -> bool {
+    // commands are left to the framework but they do show up in a channel's history
+    is_command(ctx, Some(GuildId::from(group.server_id)), &msg.content).await
+}
+
+pub fn get_race_post_id(conn: &PooledConn, this_race_id: u32) -> Result<Option<u64>> {
+    // the race's post in the submission channel. anything older than this can't be a
+    // submission for the race.
+    use crate::schema::messages::columns::*;
+    use crate::schema::messages::dsl::messages;
+    let post_id = messages
+        .select(message_id)
+        .filter(race_id.eq(this_race_id))
+        .filter(channel_type.eq(ChannelType::Submission))
+        .first::<u64>(conn)
+        .optional()?;
+
+    Ok(post_id)
+}
+
 #[inline]
-async fn delete_sub_msg(ctx: &Context, msg: &Message) -> Result<(), BoxedError> {
+async fn delete_sub_msg(ctx: &Context, msg: &Message) -> Result<(), MurahdahlaError> {
     let del = msg.delete(ctx).await;
     match del {
         Ok(_) => Ok(()),
@@ -241,7 +1080,10 @@ pub async fn message_maintenance_user<T: std::fmt::Display>(ctx: &Context, msg:
             return;
         }
     };
-    match recipient.direct_message(&ctx, |m| m.content(&msg)).await {
+    match recipient
+        .direct_message(&ctx, CreateMessage::new().content(msg.to_string()))
+        .await
+    {
         Ok(_) => (),
         Err(e) => {
             error!("Error messaging maintenance user: {}", e);
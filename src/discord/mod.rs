@@ -1,16 +1,53 @@
 use serenity::model::gateway::GatewayIntents;
 
+pub mod aliases;
+pub mod analytics;
+pub mod archive;
+pub mod banned_runners;
+pub mod branding;
 pub mod channel_groups;
+pub mod claims;
+pub mod command_permissions;
+pub mod command_retry;
 pub mod commands;
+pub mod context_menus;
+pub mod custom_games;
+pub mod deadlines;
+pub mod error_report;
+pub mod events;
+pub mod flair;
+pub mod ingest;
+pub mod leader;
+pub mod live;
 pub mod messages;
+pub mod notes;
+pub mod ops;
+pub mod presets;
+pub mod reactions;
+pub mod replay;
+pub mod result_channels;
+pub mod secrets;
+pub mod server_config;
+pub mod server_settings;
 pub mod servers;
+pub mod shared_cache;
+pub mod spoiler_cleanup;
+pub mod spoiler_guard;
+pub mod spoiler_purge;
+pub mod spoiler_roles;
+pub mod submission_parser;
 pub mod submissions;
+pub mod templates;
+pub mod throttle;
+pub mod verification;
 
 pub fn intents() -> GatewayIntents {
     let mut intents: GatewayIntents = GatewayIntents::empty();
     intents.insert(GatewayIntents::MESSAGE_CONTENT);
     intents.insert(GatewayIntents::GUILD_MESSAGES);
     intents.insert(GatewayIntents::GUILDS);
+    // for the commands runners can DM to the bot
+    intents.insert(GatewayIntents::DIRECT_MESSAGES);
 
     intents
 }
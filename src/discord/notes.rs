@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::{
+    discord::submissions::Submission, games::AsyncRaceData, helpers::*, schema::submission_notes,
+};
+
+pub const MAX_NOTE_CHARS: usize = 500;
+
+// private notes mods leave on a submission, e.g. "verified vod". these are only ever
+// sent to mods and never show up on a leaderboard.
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "Submission", foreign_key = "submission_id")]
+#[table_name = "submission_notes"]
+#[primary_key(note_id)]
+pub struct SubmissionNote {
+    pub note_id: u32,
+    pub submission_id: u32,
+    pub author_id: u64,
+    pub author_name: String,
+    pub note_datetime: NaiveDateTime,
+    pub note_text: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "submission_notes"]
+pub struct NewSubmissionNote {
+    pub submission_id: u32,
+    pub author_id: u64,
+    pub author_name: String,
+    pub note_datetime: NaiveDateTime,
+    pub note_text: String,
+}
+
+impl NewSubmissionNote {
+    pub fn new(
+        submission: &Submission,
+        author_id: u64,
+        author_name: &str,
+        text: &str,
+    ) -> Result<Self> {
+        let note_text = text.trim();
+        if note_text.is_empty() {
+            return Err(anyhow!("Note is empty"));
+        }
+        if note_text.chars().count() > MAX_NOTE_CHARS {
+            return Err(anyhow!(
+                "Notes can be at most {} characters long",
+                MAX_NOTE_CHARS
+            ));
+        }
+
+        Ok(NewSubmissionNote {
+            submission_id: submission.submission_id,
+            author_id,
+            author_name: author_name.to_owned(),
+            note_datetime: Utc::now().naive_utc(),
+            note_text: note_text.to_owned(),
+        })
+    }
+}
+
+pub fn add_note(conn: &PooledConn, note: &NewSubmissionNote) -> Result<()> {
+    diesel::insert_into(submission_notes::table)
+        .values(note)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_race_notes(
+    conn: &PooledConn,
+    race: &AsyncRaceData,
+) -> Result<Vec<(Submission, Vec<SubmissionNote>)>> {
+    // every submission in the race that has at least one note, oldest notes first
+    let race_submissions: Vec<Submission> = Submission::belonging_to(race).load(conn)?;
+    let notes = SubmissionNote::belonging_to(&race_submissions)
+        .order(submission_notes::note_datetime.asc())
+        .load::<SubmissionNote>(conn)?
+        .grouped_by(&race_submissions);
+    let noted = race_submissions
+        .into_iter()
+        .zip(notes)
+        .filter(|(_, n)| !n.is_empty())
+        .collect();
+
+    Ok(noted)
+}
@@ -0,0 +1,252 @@
+use chrono::NaiveDateTime;
+use serenity::{
+    async_trait,
+    builder::{CreateEmbed, CreateMessage, EditMessage},
+    http::Http,
+    model::{
+        channel::{PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        permissions::Permissions,
+    },
+};
+
+use crate::{discord::branding::Branding, error::MurahdahlaError, helpers::*};
+
+// the parts of a message we keep after posting it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostedMessage {
+    pub message_id: MessageId,
+    pub channel_id: ChannelId,
+    pub timestamp: NaiveDateTime,
+}
+
+// every call the race logic makes to discord goes through here so it can run against
+// something other than discord, e.g. the mock in the integration tests. the serenity
+// http client is the real implementation and retries the same way the rest of the bot
+// does.
+#[async_trait]
+pub trait DiscordOps: Send + Sync {
+    async fn say(
+        &self,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<PostedMessage, MurahdahlaError>;
+
+    async fn edit(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: &str,
+    ) -> Result<(), MurahdahlaError>;
+
+    // posts text in an embed styled with a group's branding. mentions don't ping from
+    // inside an embed so they go in the message itself. anything that can't show
+    // embeds gets the text as a plain message.
+    async fn say_branded(
+        &self,
+        channel_id: ChannelId,
+        mention: Option<&str>,
+        text: &str,
+        _branding: &Branding,
+    ) -> Result<PostedMessage, MurahdahlaError> {
+        match mention {
+            Some(m) => self.say(channel_id, &format!("{} {}", m, text)).await,
+            None => self.say(channel_id, text).await,
+        }
+    }
+
+    async fn edit_branded(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        text: &str,
+        _branding: &Branding,
+    ) -> Result<(), MurahdahlaError> {
+        self.edit(channel_id, message_id, text).await
+    }
+
+    async fn delete(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), MurahdahlaError>;
+
+    async fn member_roles(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Vec<RoleId>, MurahdahlaError>;
+
+    async fn add_role(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<(), MurahdahlaError>;
+
+    async fn remove_role(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<(), MurahdahlaError>;
+
+    async fn allow_member(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+        allow: Permissions,
+    ) -> Result<(), MurahdahlaError>;
+
+    async fn remove_member_overwrite(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+    ) -> Result<(), MurahdahlaError>;
+}
+
+#[async_trait]
+impl DiscordOps for Http {
+    async fn say(
+        &self,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<PostedMessage, MurahdahlaError> {
+        let msg = discord_with_retry(|| channel_id.say(self, content)).await?;
+
+        Ok(PostedMessage {
+            message_id: msg.id,
+            channel_id: msg.channel_id,
+            timestamp: msg.timestamp.naive_utc(),
+        })
+    }
+
+    async fn edit(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: &str,
+    ) -> Result<(), MurahdahlaError> {
+        discord_with_retry(|| {
+            channel_id.edit_message(self, message_id, EditMessage::new().content(content))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn say_branded(
+        &self,
+        channel_id: ChannelId,
+        mention: Option<&str>,
+        text: &str,
+        branding: &Branding,
+    ) -> Result<PostedMessage, MurahdahlaError> {
+        let msg = discord_with_retry(|| {
+            let mut m =
+                CreateMessage::new().embed(branding.style(CreateEmbed::new()).description(text));
+            if let Some(mention) = mention {
+                m = m.content(mention);
+            }
+            channel_id.send_message(self, m)
+        })
+        .await?;
+
+        Ok(PostedMessage {
+            message_id: msg.id,
+            channel_id: msg.channel_id,
+            timestamp: msg.timestamp.naive_utc(),
+        })
+    }
+
+    async fn edit_branded(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        text: &str,
+        branding: &Branding,
+    ) -> Result<(), MurahdahlaError> {
+        // clearing the content turns a post that started out plain, like a new
+        // leaderboard post's placeholder, into just the embed
+        discord_with_retry(|| {
+            let m = EditMessage::new()
+                .content("")
+                .embed(branding.style(CreateEmbed::new()).description(text));
+            channel_id.edit_message(self, message_id, m)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), MurahdahlaError> {
+        discord_with_retry(|| self.delete_message(channel_id, message_id, None)).await?;
+
+        Ok(())
+    }
+
+    async fn member_roles(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Vec<RoleId>, MurahdahlaError> {
+        let member = discord_with_retry(|| self.get_member(guild_id, user_id)).await?;
+
+        Ok(member.roles)
+    }
+
+    async fn add_role(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<(), MurahdahlaError> {
+        discord_with_retry(|| self.add_member_role(guild_id, user_id, role_id, None)).await?;
+
+        Ok(())
+    }
+
+    async fn remove_role(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<(), MurahdahlaError> {
+        discord_with_retry(|| self.remove_member_role(guild_id, user_id, role_id, None)).await?;
+
+        Ok(())
+    }
+
+    async fn allow_member(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+        allow: Permissions,
+    ) -> Result<(), MurahdahlaError> {
+        let overwrite = PermissionOverwrite {
+            allow,
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(user_id),
+        };
+        discord_with_retry(|| channel_id.create_permission(self, overwrite.clone())).await?;
+
+        Ok(())
+    }
+
+    async fn remove_member_overwrite(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+    ) -> Result<(), MurahdahlaError> {
+        discord_with_retry(|| {
+            channel_id.delete_permission(self, PermissionOverwriteType::Member(user_id))
+        })
+        .await?;
+
+        Ok(())
+    }
+}
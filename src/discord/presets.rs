@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, replace_into};
+use serde_json::Value;
+
+use crate::{
+    config::RollPreset,
+    discord::channel_groups::ChannelGroup,
+    games::{registry::provider, GameName},
+    helpers::*,
+    schema::presets,
+};
+
+pub const MAX_PRESETS: i64 = 25;
+pub const MAX_PRESET_NAME_CHARS: usize = 32;
+pub const MAX_PRESET_SETTINGS_BYTES: usize = 16 * 1024;
+
+// named settings a group can roll seeds with. they're looked up before the presets in
+// the bot's config so a group can have its own version of one.
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "ChannelGroup", foreign_key = "channel_group_id")]
+#[table_name = "presets"]
+#[primary_key(channel_group_id, preset_name)]
+pub struct GroupPreset {
+    pub channel_group_id: Vec<u8>,
+    pub preset_name: String,
+    pub preset_game: GameName,
+    // sent to the seed site's generator as is
+    pub preset_settings: String,
+}
+
+impl GroupPreset {
+    pub fn new(group_id: &[u8], name: &str, game: &str, settings: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty()
+            || name.chars().count() > MAX_PRESET_NAME_CHARS
+            || name.contains(char::is_whitespace)
+        {
+            return Err(anyhow!(
+                "Preset names must be 1-{} characters with no whitespace",
+                MAX_PRESET_NAME_CHARS
+            ));
+        }
+        let game = game.parse::<GameName>()?;
+        if !provider(game).rolls_seeds() {
+            return Err(anyhow!("The bot can't roll {} seeds", game));
+        }
+        if settings.len() > MAX_PRESET_SETTINGS_BYTES {
+            return Err(anyhow!("Preset settings are too large"));
+        }
+        let settings: Value = serde_json::from_str(settings.trim())
+            .map_err(|e| anyhow!("Preset settings aren't valid JSON: {}", e))?;
+        if !settings.is_object() {
+            return Err(anyhow!("Preset settings must be a JSON object"));
+        }
+
+        Ok(GroupPreset {
+            channel_group_id: group_id.to_vec(),
+            preset_name: name,
+            preset_game: game,
+            preset_settings: settings.to_string(),
+        })
+    }
+
+    pub fn roll_preset(&self) -> Result<RollPreset> {
+        let settings = serde_json::from_str(&self.preset_settings)
+            .map_err(|e| anyhow!("Bad settings for preset \"{}\": {}", self.preset_name, e))?;
+
+        Ok(RollPreset {
+            game: self.preset_game.to_string(),
+            settings,
+        })
+    }
+}
+
+pub fn set_preset(conn: &PooledConn, preset: &GroupPreset) -> Result<()> {
+    use crate::schema::presets::columns::*;
+
+    // replacing a preset doesn't count against the limit
+    let others: i64 = presets::table
+        .filter(channel_group_id.eq(&preset.channel_group_id))
+        .filter(preset_name.ne(&preset.preset_name))
+        .count()
+        .get_result(conn)?;
+    if others >= MAX_PRESETS {
+        return Err(anyhow!(
+            "Cannot add more than {} presets per group",
+            MAX_PRESETS
+        ));
+    }
+    replace_into(presets::table).values(preset).execute(conn)?;
+
+    Ok(())
+}
+
+pub fn remove_preset(conn: &PooledConn, group: &ChannelGroup, name: &str) -> Result<()> {
+    use crate::schema::presets::columns::preset_name;
+
+    let removed = diesel::delete(
+        GroupPreset::belonging_to(group).filter(preset_name.eq(name.to_lowercase())),
+    )
+    .execute(conn)?;
+    match removed {
+        0 => Err(anyhow!("No preset named \"{}\"", name)),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_presets(conn: &PooledConn, group: &ChannelGroup) -> Result<Vec<GroupPreset>> {
+    use crate::schema::presets::columns::preset_name;
+
+    let group_presets = GroupPreset::belonging_to(group)
+        .order(preset_name.asc())
+        .load(conn)?;
+
+    Ok(group_presets)
+}
+
+pub fn find_preset(
+    conn: &PooledConn,
+    group: &ChannelGroup,
+    name: &str,
+) -> Result<Option<GroupPreset>> {
+    use crate::schema::presets::columns::preset_name;
+
+    let preset = GroupPreset::belonging_to(group)
+        .filter(preset_name.eq(name.to_lowercase()))
+        .first(conn)
+        .optional()?;
+
+    Ok(preset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_checked_when_added() {
+        let group_id = vec![0; 16];
+        let preset =
+            GroupPreset::new(&group_id, " Open ", "alttpr", r#"{"mode": "open"}"#).unwrap();
+        assert_eq!(preset.preset_name, "open");
+        assert_eq!(preset.preset_game, GameName::ALTTPR);
+        let rolled = preset.roll_preset().unwrap();
+        assert_eq!(rolled.game, "ALTTPR");
+        assert_eq!(rolled.settings["mode"], "open");
+
+        assert!(GroupPreset::new(&group_id, "two words", "alttpr", "{}").is_err());
+        assert!(GroupPreset::new(&group_id, "open", "ff4fe", "{}").is_err());
+        assert!(GroupPreset::new(&group_id, "open", "alttpr", "[1, 2]").is_err());
+        assert!(GroupPreset::new(&group_id, "open", "alttpr", "{mode: open}").is_err());
+    }
+}
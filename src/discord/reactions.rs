@@ -0,0 +1,85 @@
+use std::convert::TryFrom;
+
+use anyhow::{anyhow, Result};
+use serenity::{
+    model::channel::{Message, ReactionType},
+    prelude::*,
+};
+
+use crate::discord::server_settings::{get_setting, ServerSetting};
+
+// written as escapes so an editor or terminal can't mangle them
+pub const SUCCESS_EMOJI: &str = "\u{1F44D}"; // thumbs up
+pub const FAILURE_EMOJI: &str = "\u{1F44E}"; // thumbs down
+
+const SUCCESS_REPLY: &str = "Done.";
+const FAILURE_REPLY: &str = "Something went wrong running that command.";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CommandOutcome {
+    Success,
+    Failure,
+}
+
+impl CommandOutcome {
+    fn setting(self) -> ServerSetting {
+        match self {
+            CommandOutcome::Success => ServerSetting::SuccessEmoji,
+            CommandOutcome::Failure => ServerSetting::FailureEmoji,
+        }
+    }
+
+    fn default_emoji(self) -> &'static str {
+        match self {
+            CommandOutcome::Success => SUCCESS_EMOJI,
+            CommandOutcome::Failure => FAILURE_EMOJI,
+        }
+    }
+
+    fn reply(self) -> &'static str {
+        match self {
+            CommandOutcome::Success => SUCCESS_REPLY,
+            CommandOutcome::Failure => FAILURE_REPLY,
+        }
+    }
+}
+
+pub fn parse_emoji(raw: &str) -> Result<ReactionType> {
+    // accepts a unicode emoji or a custom server emoji like <:name:id>
+    let raw = raw.trim();
+    if raw.is_empty() || raw.contains(char::is_whitespace) {
+        return Err(anyhow!("Expected a single emoji"));
+    }
+    match ReactionType::try_from(raw) {
+        Ok(r @ ReactionType::Custom { .. }) => Ok(r),
+        // discord will reject anything that isn't really an emoji but we can at least
+        // catch someone passing a word
+        Ok(r @ ReactionType::Unicode(_)) if !raw.chars().any(|c| c.is_ascii_alphanumeric()) => {
+            Ok(r)
+        }
+        _ => Err(anyhow!("\"{}\" isn't an emoji", raw)),
+    }
+}
+
+pub async fn react_outcome(ctx: &Context, msg: &Message, outcome: CommandOutcome) {
+    // react with the server's emoji for this outcome. if the bot can't react, usually
+    // because the channel doesn't allow it, reply with plain text instead.
+    let emoji = match msg.guild_id {
+        Some(g) => get_setting(ctx, g, outcome.setting()).await,
+        None => None,
+    };
+    let reaction = emoji
+        .as_deref()
+        .and_then(|e| parse_emoji(e).ok())
+        .unwrap_or_else(|| ReactionType::Unicode(outcome.default_emoji().to_owned()));
+    if let Err(e) = msg.react(&ctx, reaction).await {
+        warn!(
+            "Error reacting to message from user \"{}\", replying instead: {}",
+            &msg.author.name, e
+        );
+        msg.reply(&ctx, outcome.reply())
+            .await
+            .map(|_| ())
+            .unwrap_or_else(|e| warn!("Error replying to message: {}", e));
+    }
+}
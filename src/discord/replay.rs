@@ -0,0 +1,204 @@
+use anyhow::Result;
+use diesel::{prelude::*, replace_into};
+use serenity::{
+    builder::GetMessages,
+    model::{
+        channel::{Channel, Message},
+        id::{ChannelId, MessageId},
+    },
+    prelude::*,
+};
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup,
+        ingest::enqueue_history,
+        messages::{
+            get_message_policy, get_race_post_id, is_command_message, message_maintenance_user,
+        },
+    },
+    error::MurahdahlaError,
+    games::get_maybe_active_race,
+    helpers::*,
+    schema::submission_cursors,
+};
+
+// the most messages discord will give us in one request
+const REPLAY_BATCH: u8 = 100;
+
+// the newest message we've handled in each group's submission channel. when we come back
+// from being disconnected we pick up from here.
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "ChannelGroup", foreign_key = "channel_group_id")]
+#[table_name = "submission_cursors"]
+#[primary_key(channel_group_id)]
+pub struct SubmissionCursor {
+    pub channel_group_id: Vec<u8>,
+    pub last_message_id: u64,
+}
+
+pub fn get_cursor(conn: &PooledConn, group: &ChannelGroup) -> Result<Option<u64>> {
+    let cursor = SubmissionCursor::belonging_to(group)
+        .first::<SubmissionCursor>(conn)
+        .optional()?;
+
+    Ok(cursor.map(|c| c.last_message_id))
+}
+
+pub fn advance_cursor(
+    conn: &PooledConn,
+    group: &ChannelGroup,
+    message_id: MessageId,
+) -> Result<()> {
+    // backfilling goes through older messages so only ever move the cursor forward
+    let message_id = message_id.get();
+    if get_cursor(conn, group)?.map_or(false, |c| c >= message_id) {
+        return Ok(());
+    }
+    let cursor = SubmissionCursor {
+        channel_group_id: group.channel_group_id.clone(),
+        last_message_id: message_id,
+    };
+    replace_into(submission_cursors::table)
+        .values(&cursor)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// how far behind we are in a group's submission channel
+#[derive(Debug, Clone)]
+pub struct ChannelLag {
+    pub group_name: String,
+    pub channel_id: u64,
+    pub last_processed: Option<MessageId>,
+    pub newest: Option<MessageId>,
+}
+
+impl ChannelLag {
+    pub fn lag_secs(&self) -> Option<i64> {
+        // message ids carry the time they were sent so we don't need to fetch anything
+        // else to see how far apart they are. the newest message may be from the bot or
+        // a command, neither of which the message handler takes, so this is only a rough guide.
+        match (self.last_processed, self.newest) {
+            (Some(p), Some(n)) => {
+                Some((n.created_at().unix_timestamp() - p.created_at().unix_timestamp()).max(0))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub async fn get_channel_lags(ctx: &Context) -> Result<Vec<ChannelLag>, MurahdahlaError> {
+    let groups: Vec<ChannelGroup> = {
+        let data = ctx.data.read().await;
+        data.get::<GroupContainer>()
+            .expect("No group container in share map")
+            .values()
+            .cloned()
+            .collect()
+    };
+    let cursors: Vec<SubmissionCursor> = {
+        let conn = get_connection(ctx).await;
+        submission_cursors::table.load(&conn)?
+    };
+    let mut lags: Vec<ChannelLag> = Vec::with_capacity(groups.len());
+    for group in groups {
+        let last_processed = cursors
+            .iter()
+            .find(|c| c.channel_group_id == group.channel_group_id)
+            .map(|c| MessageId::from(c.last_message_id));
+        // the cache doesn't reliably know a channel's newest message so ask discord
+        let newest = match ctx
+            .http
+            .get_channel(ChannelId::from(group.submission))
+            .await?
+        {
+            Channel::Guild(c) => c.last_message_id,
+            _ => None,
+        };
+        lags.push(ChannelLag {
+            group_name: group.group_name,
+            channel_id: group.submission,
+            last_processed,
+            newest,
+        });
+    }
+    lags.sort_by(|a, b| a.group_name.cmp(&b.group_name));
+
+    Ok(lags)
+}
+
+pub async fn replay_missed_messages(ctx: Context) {
+    // runs on ready and resume so submissions posted while we were gone aren't lost
+    let groups: Vec<ChannelGroup> = {
+        let data = ctx.data.read().await;
+        data.get::<GroupContainer>()
+            .expect("No group container in share map")
+            .values()
+            .cloned()
+            .collect()
+    };
+    for group in groups.iter() {
+        if let Err(e) = replay_group(&ctx, group).await {
+            warn!(
+                "Error replaying messages for group \"{}\": {}",
+                &group.group_name, e
+            );
+            message_maintenance_user(&ctx, e).await;
+        }
+    }
+}
+
+async fn replay_group(ctx: &Context, group: &ChannelGroup) -> Result<(), MurahdahlaError> {
+    let (race, mut after) = {
+        let conn = get_connection(ctx).await;
+        let race = match get_maybe_active_race(&conn, group) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        // anything from before the race was posted isn't a submission for it
+        let race_post_id = match get_race_post_id(&conn, race.race_id)? {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let cursor = get_cursor(&conn, group)?.unwrap_or(0);
+        (race, race_post_id.max(cursor))
+    };
+    let policy = get_message_policy(ctx, group).await;
+    let channel = ChannelId::from(group.submission);
+    let mut replayed: usize = 0;
+    loop {
+        let mut batch: Vec<Message> = channel
+            .messages(
+                &ctx,
+                GetMessages::new()
+                    .after(MessageId::from(after))
+                    .limit(REPLAY_BATCH),
+            )
+            .await?;
+        let batch_len = batch.len();
+        batch.sort_by_key(|m| m.id);
+        if let Some(m) = batch.last() {
+            after = m.id.get();
+        }
+        for m in batch.iter().filter(|m| !m.author.bot) {
+            if is_command_message(ctx, group, m).await {
+                continue;
+            }
+            enqueue_history(ctx, m, group, &race, policy).await;
+            replayed += 1;
+        }
+        if batch_len < REPLAY_BATCH as usize {
+            break;
+        }
+    }
+    if replayed > 0 {
+        info!(
+            "Queued {} missed messages for replay in group \"{}\"",
+            replayed, &group.group_name
+        );
+    }
+
+    Ok(())
+}
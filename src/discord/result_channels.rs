@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serenity::model::{guild::Guild, id::ChannelId};
+
+use crate::{
+    discord::{
+        branding::post_branded,
+        channel_groups::ChannelGroup,
+        ops::DiscordOps,
+        submissions::{
+            leaderboard_text, load_leaderboard, shuffled_results_text, split_leaderboard,
+            Submission,
+        },
+    },
+    error::MurahdahlaError,
+    games::{AsyncRaceData, DataDisplay},
+    helpers::*,
+    schema::result_channels,
+};
+
+// extra channels, like a server's announcements channel, that get a copy of a race's
+// final standings when it's stopped
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "ChannelGroup", foreign_key = "channel_group_id")]
+#[table_name = "result_channels"]
+#[primary_key(channel_group_id, channel_id)]
+pub struct ResultChannel {
+    pub channel_group_id: Vec<u8>,
+    pub channel_id: u64,
+    // only post this many places. unranked submissions are left out when this is set.
+    pub top_places: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResultChannelYaml {
+    pub channel: String,
+    #[serde(default)]
+    pub top: Option<u16>,
+}
+
+pub fn result_channels_from_yaml(
+    server: &Guild,
+    group_id: &[u8],
+    yaml: &[ResultChannelYaml],
+) -> Result<Vec<ResultChannel>> {
+    let mut seen: HashSet<ChannelId> = HashSet::new();
+    let mut channels: Vec<ResultChannel> = Vec::with_capacity(yaml.len());
+    for y in yaml {
+        let channel_id = channel_id_by_name(server, &y.channel)
+            .ok_or_else(|| anyhow!("Could not get result channel \"{}\" from yaml", &y.channel))?;
+        if !seen.insert(channel_id) {
+            return Err(anyhow!(
+                "Result channel \"{}\" is listed more than once in yaml",
+                &y.channel
+            ));
+        }
+        if y.top == Some(0) {
+            return Err(anyhow!(
+                "Result channel \"{}\" can't show 0 places",
+                &y.channel
+            ));
+        }
+        channels.push(ResultChannel {
+            channel_group_id: group_id.to_vec(),
+            channel_id: channel_id.get(),
+            top_places: y.top,
+        });
+    }
+
+    Ok(channels)
+}
+
+pub fn get_result_channels(conn: &PooledConn, group: &ChannelGroup) -> Result<Vec<ResultChannel>> {
+    Ok(ResultChannel::belonging_to(group).load::<ResultChannel>(conn)?)
+}
+
+pub fn results_text(
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    leaderboard: &[Submission],
+    top_places: Option<u16>,
+) -> String {
+    let header = format!(
+        "Final results for {} - {}",
+        &group.group_name,
+        race.base_string(group.max_settings_chars())
+    );
+    // a top few would give away who won
+    if race.race_shuffle {
+        return shuffled_results_text(&header, leaderboard);
+    }
+    match top_places {
+        Some(n) => {
+            let top: Vec<Submission> = leaderboard
+                .iter()
+                .filter(|s| !s.unranked)
+                .take(n as usize)
+                .cloned()
+                .collect();
+            leaderboard_text(&header, &top, None)
+        }
+        None => leaderboard_text(&header, leaderboard, None),
+    }
+}
+
+pub async fn post_result_copies(
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+) -> Result<(), MurahdahlaError> {
+    // these are copies so they aren't kept in the messages table. one channel failing
+    // doesn't stop the others from getting theirs.
+    let (channels, leaderboard) = {
+        let conn = pool.get()?;
+        let channels = get_result_channels(&conn, group)?;
+        if channels.is_empty() {
+            return Ok(());
+        }
+        (channels, load_leaderboard(&conn, group, race)?)
+    };
+    let mut errors: Vec<String> = Vec::new();
+    for c in channels {
+        let text = results_text(group, race, &leaderboard, c.top_places);
+        for post in split_leaderboard(&text) {
+            let posted = post_branded(ops, group, ChannelId::from(c.channel_id), None, &post).await;
+            if let Err(e) = posted {
+                errors.push(format!("{}: {}", c.channel_id, e));
+                break;
+            }
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Couldn't post results to some channels: {}",
+            errors.join("; ")
+        )
+        .into()),
+    }
+}
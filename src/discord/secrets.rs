@@ -0,0 +1,136 @@
+use std::{
+    env, fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Result;
+use serenity::{gateway::ShardManager, prelude::*};
+
+use crate::{config::Environment, helpers::*};
+
+// bumped every time the client is rebuilt with a new token. tasks that hold a context
+// from an older client stop when they see it change.
+pub static CLIENT_GENERATION: AtomicU64 = AtomicU64::new(0);
+static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub struct ShardManagerContainer;
+
+impl TypeMapKey for ShardManagerContainer {
+    type Value = Arc<ShardManager>;
+}
+
+// the environment the running client was built from, to compare against on a rotation
+pub struct EnvironmentContainer;
+
+impl TypeMapKey for EnvironmentContainer {
+    type Value = Environment;
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Rotation {
+    pub database: bool,
+    pub token: bool,
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.database, self.token) {
+            (false, false) => write!(f, "No secrets changed"),
+            (true, false) => write!(f, "Reconnected to the database with the new credentials"),
+            (false, true) => write!(f, "Restarting the Discord client with the new token"),
+            (true, true) => write!(
+                f,
+                "Reconnected to the database and restarting the Discord client with the new token"
+            ),
+        }
+    }
+}
+
+fn reload_dotenv() {
+    // dotenv won't replace variables that are already set, which is every one we care
+    // about after startup, so set them ourselves. secrets read from *_FILE paths are
+    // read fresh by Environment::load. dotenv has no other way to get a file's values
+    // back out, whatever the deprecation note says.
+    #[allow(deprecated)]
+    if let Ok(vars) = dotenv::dotenv_iter() {
+        vars.flatten().for_each(|(k, v)| env::set_var(k, v));
+    }
+}
+
+pub async fn rotate_secrets(data: &Arc<RwLock<TypeMap>>) -> Result<Rotation> {
+    // a new database pool is swapped in right away. a new token needs a new client, so
+    // that's left to restart_client once the caller is done with this one.
+    reload_dotenv();
+    let new_env = Environment::load()?;
+    let old_env = {
+        let data = data.read().await;
+        data.get::<EnvironmentContainer>()
+            .expect("No environment in share map")
+            .clone()
+    };
+    let rotation = Rotation {
+        database: new_env.database_url != old_env.database_url,
+        token: new_env.discord_token != old_env.discord_token,
+    };
+    if rotation.database {
+        let pool = get_pool(&new_env.database_url)?;
+        // make sure the new credentials work before the old pool goes away
+        pool.get()?;
+        data.write().await.insert::<DBPool>(pool);
+        info!("Replaced database pool with new credentials");
+    }
+    data.write().await.insert::<EnvironmentContainer>(new_env);
+
+    Ok(rotation)
+}
+
+pub async fn restart_client(data: &Arc<RwLock<TypeMap>>) {
+    // shutting down every shard makes client.start() return. serve sees the request and
+    // builds a new client, which reads the environment again.
+    let shard_manager = {
+        let data = data.read().await;
+        data.get::<ShardManagerContainer>()
+            .expect("No shard manager in share map")
+            .clone()
+    };
+    RESTART_REQUESTED.store(true, Ordering::SeqCst);
+    shard_manager.shutdown_all().await;
+}
+
+pub fn take_restart_request() -> bool {
+    RESTART_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+pub async fn rotate_on_sighup(current_data: Arc<Mutex<Option<Arc<RwLock<TypeMap>>>>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!(
+                "Couldn't listen for SIGHUP, secrets can only be rotated by command: {}",
+                e
+            );
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        let data = match current_data.lock().await.clone() {
+            Some(d) => d,
+            None => continue,
+        };
+        match rotate_secrets(&data).await {
+            Ok(rotation) => {
+                info!("SIGHUP: {}", rotation);
+                if rotation.token {
+                    restart_client(&data).await;
+                }
+            }
+            Err(e) => error!("SIGHUP: couldn't rotate secrets: {}", e),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    client::Context,
+    model::{
+        guild::Guild,
+        id::{ChannelId, RoleId},
+    },
+};
+
+use crate::{
+    discord::{
+        aliases::AliasContainer,
+        branding::BrandingYaml,
+        channel_groups::{ChannelGroup, ChannelGroupYaml},
+        command_permissions::GrantContainer,
+        custom_games::get_custom_games,
+        result_channels::{ResultChannel, ResultChannelYaml},
+        server_settings::{ServerSetting, SettingsContainer},
+        servers::DiscordServer,
+        spoiler_roles::{SpoilerRole, SpoilerRoleYaml},
+        templates::get_templates,
+    },
+    games::custom::CustomGameDefinition,
+    helpers::*,
+};
+
+// everything a server has set up with the bot, written with channel and role names
+// instead of ids so it can be imported on another server that has the same channels
+// and roles. races and submissions aren't included, see export.rs for those.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfigYaml {
+    #[serde(default)]
+    pub admin_role: Option<String>,
+    #[serde(default)]
+    pub mod_role: Option<String>,
+    // setting name -> value the way !setsetting takes it
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    #[serde(default)]
+    pub command_grants: Vec<CommandGrantYaml>,
+    // alias -> command
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    pub templates: Vec<TemplateYaml>,
+    // games added with !addgame, in the same form as the file it takes
+    #[serde(default)]
+    pub games: Vec<CustomGameDefinition>,
+    #[serde(default)]
+    pub groups: Vec<ChannelGroupYaml>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandGrantYaml {
+    pub command: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateYaml {
+    pub name: String,
+    #[serde(default)]
+    pub fields: String,
+    pub format: String,
+}
+
+fn role_name(guild: &Guild, role_id: u64) -> Result<String> {
+    guild
+        .roles
+        .get(&RoleId::from(role_id))
+        .map(|r| r.name.clone())
+        .ok_or_else(|| anyhow!("No role with id {} exists on this server", role_id))
+}
+
+fn channel_name(guild: &Guild, channel_id: u64) -> Result<String> {
+    guild
+        .channels
+        .get(&ChannelId::from(channel_id))
+        .map(|c| c.name.clone())
+        .ok_or_else(|| anyhow!("No channel with id {} exists on this server", channel_id))
+}
+
+pub async fn export_server_config(ctx: &Context, guild: &Guild) -> Result<ServerConfigYaml> {
+    let (server, settings, grants, aliases, groups) = {
+        let data = ctx.data.read().await;
+        let server: Option<DiscordServer> = data
+            .get::<ServerContainer>()
+            .expect("No server container in share map")
+            .get(&guild.id)
+            .copied();
+        let settings = data
+            .get::<SettingsContainer>()
+            .expect("No settings container in share map")
+            .get(&guild.id)
+            .cloned()
+            .unwrap_or_default();
+        let grants = data
+            .get::<GrantContainer>()
+            .expect("No grant container in share map")
+            .get(&guild.id)
+            .cloned()
+            .unwrap_or_default();
+        let aliases = data
+            .get::<AliasContainer>()
+            .expect("No alias container in share map")
+            .get(&guild.id)
+            .cloned()
+            .unwrap_or_default();
+        let mut groups: Vec<ChannelGroup> = data
+            .get::<GroupContainer>()
+            .expect("No group container in share map")
+            .values()
+            .filter(|g| g.server_id == guild.id.get())
+            .cloned()
+            .collect();
+        groups.sort_by(|a, b| a.group_name.cmp(&b.group_name));
+        (server, settings, grants, aliases, groups)
+    };
+    let conn = get_connection(ctx).await;
+
+    let mut config = ServerConfigYaml::default();
+    if let Some(s) = server {
+        config.admin_role = s.admin_role_id.map(|r| role_name(guild, r)).transpose()?;
+        config.mod_role = s.mod_role_id.map(|r| role_name(guild, r)).transpose()?;
+    }
+    for (key, value) in settings {
+        // the ping role is stored as an id but it's set by name
+        let value = match key {
+            ServerSetting::PingRole => role_name(guild, value.parse::<u64>()?)?,
+            _ => value,
+        };
+        config.settings.insert(key.to_string(), value);
+    }
+    for (command, roles) in grants {
+        for role in roles {
+            config.command_grants.push(CommandGrantYaml {
+                command: command.clone(),
+                role: role_name(guild, role)?,
+            });
+        }
+    }
+    config
+        .command_grants
+        .sort_by(|a, b| (&a.command, &a.role).cmp(&(&b.command, &b.role)));
+    config.aliases = aliases.into_iter().collect();
+    config.templates = get_templates(&conn, guild.id.get())?
+        .into_iter()
+        .map(|t| TemplateYaml {
+            name: t.template_name,
+            fields: t.template_fields,
+            format: t.template_format,
+        })
+        .collect();
+    config.games = get_custom_games(&conn, guild.id.get())?;
+    for group in groups.iter() {
+        let group_yaml = group_yaml(&conn, guild, group)
+            .map_err(|e| anyhow!("Couldn't export group \"{}\": {}", group.group_name, e))?;
+        config.groups.push(group_yaml);
+    }
+
+    Ok(config)
+}
+
+fn group_yaml(conn: &PooledConn, guild: &Guild, group: &ChannelGroup) -> Result<ChannelGroupYaml> {
+    // the inverse of ChannelGroup::new_from_yaml
+    let spoiler_roles: Vec<SpoilerRole> = SpoilerRole::belonging_to(group).load(conn)?;
+    let result_channels: Vec<ResultChannel> = ResultChannel::belonging_to(group).load(conn)?;
+    let spoiler_roles = spoiler_roles
+        .iter()
+        .map(|r| {
+            Ok(SpoilerRoleYaml {
+                role: role_name(guild, r.role_id)?,
+                game: r.race_game.clone(),
+                division: r
+                    .division_role_id
+                    .map(|d| role_name(guild, d))
+                    .transpose()?,
+            })
+        })
+        .collect::<Result<Vec<SpoilerRoleYaml>>>()?;
+    let result_channels = result_channels
+        .iter()
+        .map(|c| {
+            Ok(ResultChannelYaml {
+                channel: channel_name(guild, c.channel_id)?,
+                top: c.top_places,
+            })
+        })
+        .collect::<Result<Vec<ResultChannelYaml>>>()?;
+
+    Ok(ChannelGroupYaml {
+        channel_group_id: Vec::new(),
+        group_name: group.group_name.clone(),
+        submission: channel_name(guild, group.submission)?,
+        leaderboard: channel_name(guild, group.leaderboard)?,
+        spoiler: channel_name(guild, group.spoiler)?,
+        spoiler_role: group
+            .spoiler_role_id
+            .map(|r| role_name(guild, r))
+            .transpose()?,
+        spoiler_access: group.spoiler_access,
+        ascii_names: group.ascii_names,
+        max_name_length: group.max_name_length,
+        spoiler_roles,
+        verify_under: group.verify_under.map(|t| t.to_string()),
+        verification_channel: group
+            .verification_channel
+            .map(|c| channel_name(guild, c))
+            .transpose()?,
+        show_flair: group.show_flair,
+        forfeit_words: group
+            .forfeit_words()
+            .into_iter()
+            .map(str::to_owned)
+            .collect(),
+        result_channels,
+        archive_channel: group
+            .archive_channel
+            .map(|c| channel_name(guild, c))
+            .transpose()?,
+        branding: BrandingYaml {
+            color: group.embed_color.map(|c| format!("#{:06x}", c)),
+            thumbnail: group.embed_thumbnail.clone(),
+            banner: group.embed_banner.clone(),
+            footer: group.embed_footer.clone(),
+        },
+        guard_spoiler: group.guard_spoiler,
+        purge_spoiler: group.purge_spoiler,
+        max_settings_length: group.max_settings_length,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_reads_with_groups() {
+        let yaml = r#"
+admin_role: "Admins"
+settings:
+  ping_role: "Racers"
+  prefix: "?"
+aliases:
+  s: start
+templates:
+  - name: smw
+    format: "SMW Hack: {text}"
+games:
+  - name: smwr
+    display_name: "SMW Randomizer"
+    hosts: ["smwrando.example"]
+groups:
+  - group_name: "main"
+    submission: "submissions"
+    leaderboard: "leaderboard"
+    spoiler: "spoilers"
+    spoiler_role: "Finished"
+    forfeit_words: ["abandon"]
+"#;
+        let config: ServerConfigYaml = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.admin_role.as_deref(), Some("Admins"));
+        assert_eq!(config.mod_role, None);
+        assert_eq!(config.settings["prefix"], "?");
+        assert_eq!(config.templates[0].fields, "");
+        assert!(!config.games[0].collection);
+        assert_eq!(config.groups[0].spoiler_role.as_deref(), Some("Finished"));
+        // every imported group gets its own id
+        assert_eq!(config.groups[0].channel_group_id.len(), 16);
+
+        let written = serde_yaml::to_string(&config).unwrap();
+        let again: ServerConfigYaml = serde_yaml::from_str(&written).unwrap();
+        assert_eq!(again.aliases, config.aliases);
+        assert_eq!(again.groups[0].forfeit_words, vec!["abandon"]);
+    }
+}
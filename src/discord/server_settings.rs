@@ -0,0 +1,402 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, replace_into};
+use poise::{BoxFuture, PartialContext};
+use serenity::{
+    model::{guild::Guild, id::GuildId},
+    prelude::*,
+};
+use url::Url;
+
+use crate::{
+    config::MessagePolicy,
+    discord::{reactions::parse_emoji, servers::resolve_role},
+    error::MurahdahlaError,
+    helpers::*,
+    schema::server_settings,
+};
+
+// per-server toggles live in a key-value table so adding one doesn't mean adding a
+// column to the servers table
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ServerSetting {
+    Prefix,
+    MessagePolicy,
+    HighlightSecs,
+    PingRole,
+    SuccessEmoji,
+    FailureEmoji,
+    AllowedHosts,
+    BlockedHosts,
+    Locale,
+}
+
+impl ServerSetting {
+    pub const ALL: [ServerSetting; 9] = [
+        ServerSetting::Prefix,
+        ServerSetting::MessagePolicy,
+        ServerSetting::HighlightSecs,
+        ServerSetting::PingRole,
+        ServerSetting::SuccessEmoji,
+        ServerSetting::FailureEmoji,
+        ServerSetting::AllowedHosts,
+        ServerSetting::BlockedHosts,
+        ServerSetting::Locale,
+    ];
+}
+
+impl FromStr for ServerSetting {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "prefix" => Ok(ServerSetting::Prefix),
+            "message_policy" => Ok(ServerSetting::MessagePolicy),
+            "highlight_secs" => Ok(ServerSetting::HighlightSecs),
+            "ping_role" => Ok(ServerSetting::PingRole),
+            "success_emoji" => Ok(ServerSetting::SuccessEmoji),
+            "failure_emoji" => Ok(ServerSetting::FailureEmoji),
+            "allowed_hosts" => Ok(ServerSetting::AllowedHosts),
+            "blocked_hosts" => Ok(ServerSetting::BlockedHosts),
+            "locale" => Ok(ServerSetting::Locale),
+            x => Err(anyhow!("Unrecognized server setting: {}", x)),
+        }
+    }
+}
+
+impl fmt::Display for ServerSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ServerSetting::Prefix => write!(f, "prefix"),
+            ServerSetting::MessagePolicy => write!(f, "message_policy"),
+            ServerSetting::HighlightSecs => write!(f, "highlight_secs"),
+            ServerSetting::PingRole => write!(f, "ping_role"),
+            ServerSetting::SuccessEmoji => write!(f, "success_emoji"),
+            ServerSetting::FailureEmoji => write!(f, "failure_emoji"),
+            ServerSetting::AllowedHosts => write!(f, "allowed_hosts"),
+            ServerSetting::BlockedHosts => write!(f, "blocked_hosts"),
+            ServerSetting::Locale => write!(f, "locale"),
+        }
+    }
+}
+
+// decides how dates are written in messages to a server. times are always UTC.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Locale {
+    Iso,
+    EnUs,
+    EnGb,
+    De,
+    Fr,
+}
+
+impl Locale {
+    pub fn date_format(&self) -> &'static str {
+        match *self {
+            Locale::Iso => "%Y-%m-%d %H:%M",
+            Locale::EnUs => "%m/%d/%Y %H:%M",
+            Locale::EnGb | Locale::Fr => "%d/%m/%Y %H:%M",
+            Locale::De => "%d.%m.%Y %H:%M",
+        }
+    }
+
+    pub fn format_date(&self, date: &NaiveDateTime) -> String {
+        date.format(self.date_format()).to_string()
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::Iso
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // "en_US", "en-us" and "en-US" are all the same locale
+        match s.replace('_', "-").to_lowercase().as_str() {
+            "iso" => Ok(Locale::Iso),
+            "en-us" => Ok(Locale::EnUs),
+            "en-gb" => Ok(Locale::EnGb),
+            "de" | "de-de" => Ok(Locale::De),
+            "fr" | "fr-fr" => Ok(Locale::Fr),
+            x => Err(anyhow!(
+                "Unsupported locale: {}. Use one of iso, en-US, en-GB, de or fr",
+                x
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Locale::Iso => write!(f, "iso"),
+            Locale::EnUs => write!(f, "en-US"),
+            Locale::EnGb => write!(f, "en-GB"),
+            Locale::De => write!(f, "de"),
+            Locale::Fr => write!(f, "fr"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Queryable)]
+#[table_name = "server_settings"]
+pub struct ServerSettingRow {
+    pub server_id: u64,
+    pub setting_key: String,
+    pub setting_value: String,
+}
+
+pub type ServerSettings = HashMap<ServerSetting, String>;
+
+pub struct SettingsContainer;
+
+impl TypeMapKey for SettingsContainer {
+    type Value = HashMap<GuildId, ServerSettings>;
+}
+
+pub fn get_all_settings(conn: &PooledConn) -> Result<HashMap<GuildId, ServerSettings>> {
+    use crate::schema::server_settings::dsl::*;
+
+    let rows: Vec<ServerSettingRow> = server_settings.load(conn)?;
+    let mut settings_map: HashMap<GuildId, ServerSettings> = HashMap::new();
+    for row in rows {
+        // skip keys we don't know about rather than refusing to start
+        let key = match ServerSetting::from_str(&row.setting_key) {
+            Ok(k) => k,
+            Err(e) => {
+                warn!("Ignoring setting for server {}: {}", row.server_id, e);
+                continue;
+            }
+        };
+        settings_map
+            .entry(GuildId::from(row.server_id))
+            .or_default()
+            .insert(key, row.setting_value);
+    }
+
+    Ok(settings_map)
+}
+
+pub async fn get_setting(ctx: &Context, guild_id: GuildId, key: ServerSetting) -> Option<String> {
+    let data = ctx.data.read().await;
+    data.get::<SettingsContainer>()
+        .expect("No settings container in share map")
+        .get(&guild_id)
+        .and_then(|s| s.get(&key))
+        .cloned()
+}
+
+pub async fn get_setting_parsed<T: FromStr>(
+    ctx: &Context,
+    guild_id: GuildId,
+    key: ServerSetting,
+) -> Option<T> {
+    // values are validated when they're set so a parse failure here means someone
+    // edited the table by hand. treat it as unset.
+    get_setting(ctx, guild_id, key)
+        .await
+        .and_then(|v| T::from_str(&v).ok())
+}
+
+pub async fn set_setting(
+    ctx: &Context,
+    guild_id: GuildId,
+    key: ServerSetting,
+    value: Option<String>,
+) -> Result<()> {
+    // write to the database first and only update the cache if that succeeds
+    use crate::schema::server_settings::columns::*;
+    use crate::schema::server_settings::dsl::server_settings;
+
+    let conn = get_connection(ctx).await;
+    match &value {
+        Some(v) => {
+            let row = ServerSettingRow {
+                server_id: guild_id.get(),
+                setting_key: key.to_string(),
+                setting_value: v.clone(),
+            };
+            replace_into(server_settings).values(&row).execute(&conn)?;
+        }
+        None => {
+            diesel::delete(
+                server_settings
+                    .filter(server_id.eq(guild_id.get()))
+                    .filter(setting_key.eq(key.to_string())),
+            )
+            .execute(&conn)?;
+        }
+    };
+    {
+        let mut data = ctx.data.write().await;
+        let settings = data
+            .get_mut::<SettingsContainer>()
+            .expect("No settings container in share map")
+            .entry(guild_id)
+            .or_default();
+        match value {
+            Some(v) => settings.insert(key, v),
+            None => settings.remove(&key),
+        };
+    }
+
+    Ok(())
+}
+
+pub fn parse_setting_value(guild: &Guild, key: ServerSetting, raw: &str) -> Result<String> {
+    // validates a value from a command and returns what we should store for it
+    let raw = raw.trim();
+    match key {
+        ServerSetting::Prefix => {
+            if raw.is_empty() || raw.chars().count() > 5 || raw.contains(char::is_whitespace) {
+                return Err(anyhow!("Prefix must be 1-5 characters with no whitespace"));
+            }
+            Ok(raw.to_owned())
+        }
+        ServerSetting::MessagePolicy => {
+            MessagePolicy::from_str(raw)?;
+            Ok(raw.to_owned())
+        }
+        ServerSetting::HighlightSecs => match raw.parse::<i64>() {
+            Ok(n) if n >= 0 => Ok(n.to_string()),
+            _ => Err(anyhow!(
+                "Highlight window must be 0 or a positive number of seconds. 0 turns highlighting off"
+            )),
+        },
+        ServerSetting::PingRole => Ok(resolve_role(guild, raw)?.get().to_string()),
+        ServerSetting::SuccessEmoji | ServerSetting::FailureEmoji => {
+            parse_emoji(raw)?;
+            Ok(raw.to_owned())
+        }
+        ServerSetting::AllowedHosts | ServerSetting::BlockedHosts => parse_host_list(raw),
+        ServerSetting::Locale => Ok(Locale::from_str(raw)?.to_string()),
+    }
+}
+
+fn parse_host_list(raw: &str) -> Result<String> {
+    // stored as a comma separated list of lowercase hosts, e.g. "alttpr.com,samus.link"
+    let hosts = raw
+        .split(',')
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<String>>();
+    if let Some(h) = hosts
+        .iter()
+        .find(|h| h.is_empty() || h.contains(|c: char| c.is_whitespace() || c == '/'))
+    {
+        return Err(anyhow!(
+            "\"{}\" isn't a host. Hosts should look like \"alttpr.com\" and be separated by commas",
+            h
+        ));
+    }
+
+    Ok(hosts.join(","))
+}
+
+fn host_in_list(host: &str, list: &str) -> bool {
+    // subdomains count so "alttpr.com" also covers e.g. "beta.alttpr.com"
+    list.split(',')
+        .any(|h| host == h || host.ends_with(&format!(".{}", h)))
+}
+
+pub async fn check_seed_host(ctx: &Context, guild_id: GuildId, seed_url: &str) -> Result<()> {
+    let allowed = get_setting(ctx, guild_id, ServerSetting::AllowedHosts).await;
+    let blocked = get_setting(ctx, guild_id, ServerSetting::BlockedHosts).await;
+
+    seed_host_permitted(seed_url, allowed.as_deref(), blocked.as_deref())
+}
+
+fn seed_host_permitted(seed_url: &str, allowed: Option<&str>, blocked: Option<&str>) -> Result<()> {
+    if allowed.is_none() && blocked.is_none() {
+        return Ok(());
+    }
+    // anything we can't pull a host out of, e.g. game info passed with --other, isn't a
+    // seed site and isn't restricted
+    let host = match Url::parse(seed_url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+    {
+        Some(h) => h,
+        None => return Ok(()),
+    };
+    if let Some(list) = allowed {
+        if !host_in_list(&host, list) {
+            return Err(anyhow!(
+                "Races on this server can only be started from these sites: {}",
+                list.replace(',', ", ")
+            ));
+        }
+    }
+    if let Some(list) = blocked {
+        if host_in_list(&host, list) {
+            return Err(anyhow!(
+                "Races on this server can't be started from {}",
+                host
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_locale(ctx: &Context, guild_id: GuildId) -> Locale {
+    get_setting_parsed(ctx, guild_id, ServerSetting::Locale)
+        .await
+        .unwrap_or_default()
+}
+
+pub fn server_prefix(
+    ctx: PartialContext<'_, (), MurahdahlaError>,
+) -> BoxFuture<'_, Result<Option<String>, MurahdahlaError>> {
+    // servers can set a prefix in addition to the default "!"
+    Box::pin(async move {
+        let prefix = match ctx.guild_id {
+            Some(g) => get_setting(ctx.serenity_context, g, ServerSetting::Prefix).await,
+            None => None,
+        };
+
+        Ok(prefix)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_lists() {
+        assert_eq!(
+            parse_host_list(" Alttpr.com, samus.link ").unwrap(),
+            "alttpr.com,samus.link"
+        );
+        assert!(parse_host_list("alttpr.com,").is_err());
+        assert!(parse_host_list("https://alttpr.com/h/abc").is_err());
+    }
+
+    #[test]
+    fn seed_hosts() {
+        let seed = "https://beta.alttpr.com/en/h/abcdef";
+        assert!(seed_host_permitted(seed, None, None).is_ok());
+        assert!(seed_host_permitted(seed, Some("alttpr.com"), None).is_ok());
+        assert!(seed_host_permitted(seed, Some("samus.link"), None).is_err());
+        assert!(seed_host_permitted(seed, None, Some("alttpr.com")).is_err());
+        assert!(seed_host_permitted(seed, None, Some("ttpr.com")).is_ok());
+        assert!(seed_host_permitted("crossed keys", Some("alttpr.com"), None).is_ok());
+    }
+
+    #[test]
+    fn locales() {
+        assert_eq!(Locale::from_str("en_us").unwrap(), Locale::EnUs);
+        assert_eq!(Locale::from_str("de-DE").unwrap(), Locale::De);
+        assert!(Locale::from_str("xx").is_err());
+        let date = NaiveDateTime::parse_from_str("2026-03-04 05:06", "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(Locale::default().format_date(&date), "2026-03-04 05:06");
+        assert_eq!(Locale::EnUs.format_date(&date), "03/04/2026 05:06");
+        assert_eq!(Locale::De.format_date(&date), "04.03.2026 05:06");
+    }
+}
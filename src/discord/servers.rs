@@ -2,17 +2,19 @@ use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
 use serenity::{
-    framework::standard::Args,
     model::{
-        channel::Message,
         guild::Guild,
-        id::{GuildId, RoleId, UserId},
+        id::{ChannelId, GuildId, RoleId, UserId},
+        user::User,
     },
     prelude::*,
 };
 
-use crate::{helpers::*, schema::servers, MAINTENANCE_USER};
+use crate::{
+    discord::shared_cache::publish_shared_cache, helpers::*, schema::servers, MAINTENANCE_USER,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub enum Permission {
@@ -27,7 +29,32 @@ pub enum ServerRoleAction {
     Remove,
 }
 
-#[derive(Debug, Clone, Copy, Insertable, Queryable, Identifiable)]
+// the commands that change which roles map to mod and admin
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RoleCommand {
+    SetAdmin,
+    SetMod,
+    RemoveAdmin,
+    RemoveMod,
+}
+
+impl RoleCommand {
+    pub fn permission(self) -> Permission {
+        match self {
+            RoleCommand::SetAdmin | RoleCommand::RemoveAdmin => Permission::Admin,
+            RoleCommand::SetMod | RoleCommand::RemoveMod => Permission::Mod,
+        }
+    }
+
+    pub fn action(self) -> ServerRoleAction {
+        match self {
+            RoleCommand::SetAdmin | RoleCommand::SetMod => ServerRoleAction::Add,
+            RoleCommand::RemoveAdmin | RoleCommand::RemoveMod => ServerRoleAction::Remove,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Insertable, Queryable, Identifiable, Serialize, Deserialize)]
 #[table_name = "servers"]
 #[primary_key(server_id)]
 pub struct DiscordServer {
@@ -38,7 +65,7 @@ pub struct DiscordServer {
 }
 
 impl DiscordServer {
-    fn determine_user_permissions<T: Into<u64>>(self, id: T, roles: &[RoleId]) -> Permission {
+    pub fn determine_user_permissions<T: Into<u64>>(self, id: T, roles: &[RoleId]) -> Permission {
         if self.owner_id == id.into() {
             return Permission::Admin;
         };
@@ -49,9 +76,7 @@ impl DiscordServer {
         match self.admin_role_id.is_some() {
             false => (),
             true => {
-                let has_admin = roles
-                    .iter()
-                    .any(|r| r.as_u64() == &self.admin_role_id.unwrap());
+                let has_admin = roles.iter().any(|r| r.get() == self.admin_role_id.unwrap());
                 if has_admin {
                     return Permission::Admin;
                 };
@@ -60,9 +85,7 @@ impl DiscordServer {
         match self.mod_role_id.is_some() {
             false => (),
             true => {
-                let has_admin = roles
-                    .iter()
-                    .any(|r| r.as_u64() == &self.mod_role_id.unwrap());
+                let has_admin = roles.iter().any(|r| r.get() == self.mod_role_id.unwrap());
                 if has_admin {
                     return Permission::Mod;
                 };
@@ -72,6 +95,13 @@ impl DiscordServer {
         Permission::None
     }
 
+    pub fn apply_role_command(&mut self, command: RoleCommand, role_id: Option<u64>) {
+        match command.action() {
+            ServerRoleAction::Add => self.set_role(role_id, command.permission()),
+            ServerRoleAction::Remove => self.set_role(None, command.permission()),
+        };
+    }
+
     pub fn set_role(&mut self, role_id: Option<u64>, role_type: Permission) {
         match role_type {
             Permission::Mod => self.mod_role_id = role_id,
@@ -81,15 +111,124 @@ impl DiscordServer {
     }
 }
 
-pub async fn parse_role(ctx: &Context, msg: &Message, mut args: Args) -> Result<u64, BoxedError> {
-    let role_name = args.single_quoted::<String>()?;
-    let guild = msg.guild(&ctx).unwrap();
-    let role_id: u64 = match guild.role_by_name(&role_name) {
-        Some(r) => *r.id.as_u64(),
-        None => return Err(anyhow!("Tried to set role that doesn't exist on server").into()),
+pub fn write_server_role(
+    conn: &PooledConn,
+    this_server_id: u64,
+    command: RoleCommand,
+    role_id: Option<u64>,
+) -> Result<()> {
+    use crate::schema::servers::columns::*;
+    use crate::schema::servers::dsl::*;
+
+    match command.permission() {
+        Permission::Admin => {
+            diesel::update(servers.find(this_server_id))
+                .set(admin_role_id.eq(role_id))
+                .execute(conn)?;
+        }
+        Permission::Mod => {
+            diesel::update(servers.find(this_server_id))
+                .set(mod_role_id.eq(role_id))
+                .execute(conn)?;
+        }
+        Permission::None => (),
     };
 
-    Ok(role_id)
+    Ok(())
+}
+
+pub fn resolve_role(guild: &Guild, role_arg: &str) -> Result<RoleId> {
+    // roles can be given as a mention, a bare id, or a name
+    let role_arg = role_arg.trim();
+    let maybe_id = role_arg
+        .strip_prefix("<@&")
+        .and_then(|r| r.strip_suffix('>'))
+        .unwrap_or(role_arg);
+    if let Ok(id) = maybe_id.parse::<u64>() {
+        let role_id = RoleId::from(id);
+        return match guild.roles.contains_key(&role_id) {
+            true => Ok(role_id),
+            false => Err(anyhow!("No role with id {} exists on this server", id)),
+        };
+    }
+    match guild.role_by_name(role_arg) {
+        Some(r) => Ok(r.id),
+        None => Err(anyhow!(
+            "No role named \"{}\" exists on this server",
+            role_arg
+        )),
+    }
+}
+
+pub async fn check_bot_can_assign(ctx: &Context, guild: &Guild, role_id: RoleId) -> Result<()> {
+    // discord only lets the bot hand out roles that are below its own highest role
+    let bot_id = ctx.cache.current_user().id;
+    let bot_member = guild.member(ctx, bot_id).await?;
+    let bot_position = bot_member
+        .roles
+        .iter()
+        .filter_map(|r| guild.roles.get(r))
+        .map(|r| r.position)
+        .max()
+        .unwrap_or(0);
+    let role = guild
+        .roles
+        .get(&role_id)
+        .ok_or_else(|| anyhow!("No role with id {} exists on this server", role_id))?;
+    match bot_position > role.position {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "The bot can't assign the \"{}\" role. Move the bot's role above it in the server settings.",
+            role.name
+        )),
+    }
+}
+
+pub async fn check_bot_can_overwrite(
+    ctx: &Context,
+    guild: &Guild,
+    channel_id: ChannelId,
+) -> Result<()> {
+    // adding permission overwrites for runners needs manage permissions on the channel
+    let bot_id = ctx.cache.current_user().id;
+    let bot_member = guild.member(ctx, bot_id).await?;
+    let channel = guild
+        .channels
+        .get(&channel_id)
+        .ok_or_else(|| anyhow!("No channel with id {} exists on this server", channel_id))?;
+    match guild
+        .user_permissions_in(channel, &bot_member)
+        .manage_roles()
+    {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "The bot needs the Manage Permissions permission in #{}",
+            channel.name
+        )),
+    }
+}
+
+pub async fn has_permission(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    user_roles: &[RoleId],
+    req: Permission,
+) -> bool {
+    let owner_id = guild_id.to_guild_cached(&ctx.cache).map(|g| g.owner_id);
+    if owner_id == Some(user_id) || is_maintenance_user(user_id) {
+        return true;
+    }; // owner can do any command
+    let server_data: Option<DiscordServer> = {
+        let data = ctx.data.read().await;
+        data.get::<ServerContainer>()
+            .expect("No server hashmap in share map")
+            .get(&guild_id)
+            .copied()
+    };
+    server_data.map_or(false, |s| {
+        s.determine_user_permissions(user_id, user_roles) >= req
+    })
 }
 
 pub fn get_servers(conn: &PooledConn) -> Result<HashMap<GuildId, DiscordServer>> {
@@ -108,41 +247,51 @@ pub fn get_servers(conn: &PooledConn) -> Result<HashMap<GuildId, DiscordServer>>
     Ok(server_map)
 }
 
-pub async fn check_permissions(ctx: &Context, msg: &Message, req: Permission) -> Result<()> {
-    let server: Guild = msg.guild(&ctx).unwrap();
-    let maintenance_user_id = UserId::from(*MAINTENANCE_USER.get().unwrap());
-    if server.owner_id == msg.author.id
-        || (maintenance_user_id != 0u64 && maintenance_user_id == msg.author.id)
-    {
-        return Ok(());
-    }; // owner can do any command
-    let user_roles = &msg.member.as_ref().unwrap().roles;
-    let server_data: DiscordServer = {
-        let data = ctx.data.read().await;
-        *data
-            .get::<ServerContainer>()
-            .expect("No server hashmap in share map")
-            .get(&server.id)
-            .unwrap()
-    };
-    let user_permissions = server_data.determine_user_permissions(msg.author.id, user_roles);
-    match user_permissions >= req {
+pub async fn check_permissions(
+    ctx: &Context,
+    guild_id: GuildId,
+    author: &User,
+    user_roles: &[RoleId],
+    req: Permission,
+) -> Result<()> {
+    match has_permission(ctx, guild_id, author.id, user_roles, req).await {
         true => Ok(()),
         false => Err(anyhow!(
             "User \"{}\" does not have required permissions",
-            &msg.author.name
+            &author.name
         )),
     }
 }
 
-pub async fn add_server(ctx: &Context, msg: &Message) -> Result<()> {
+fn is_maintenance_user(user_id: UserId) -> bool {
+    // 0 means there isn't one, which discord ids can't hold
+    let maintenance_user_id = *MAINTENANCE_USER.get().unwrap();
+    maintenance_user_id != 0 && maintenance_user_id == user_id.get()
+}
+
+pub fn check_maintenance_user(author: &User) -> Result<()> {
+    // some commands affect every server the bot is in so only the maintenance user
+    // can run them
+    match is_maintenance_user(author.id) {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "User \"{}\" is not the maintenance user",
+            &author.name
+        )),
+    }
+}
+
+pub async fn add_server(ctx: &Context, guild_id: GuildId) -> Result<()> {
     use crate::schema::servers::dsl::*;
     use diesel::insert_or_ignore_into;
 
-    let guild_id = msg.guild_id.unwrap();
+    let guild_owner = guild_id
+        .to_guild_cached(&ctx.cache)
+        .map(|g| g.owner_id)
+        .ok_or_else(|| anyhow!("Server {} isn't in the cache", guild_id))?;
     let new_server = DiscordServer {
-        server_id: *guild_id.as_u64(),
-        owner_id: *msg.guild(&ctx).unwrap().owner_id.as_u64(),
+        server_id: guild_id.get(),
+        owner_id: guild_owner.get(),
         admin_role_id: None,
         mod_role_id: None,
     };
@@ -158,17 +307,7 @@ pub async fn add_server(ctx: &Context, msg: &Message) -> Result<()> {
             .expect("No server hashmap in share map.");
         server_map.insert(guild_id, new_server);
     }
-
-    Ok(())
-}
-
-pub async fn add_spoiler_role(
-    ctx: &Context,
-    msg: &Message,
-    role_id: u64,
-) -> Result<(), BoxedError> {
-    let mut member = msg.member(&ctx).await?;
-    member.add_role(&ctx, role_id).await?;
+    publish_shared_cache(ctx).await;
 
     Ok(())
 }
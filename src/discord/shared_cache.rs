@@ -0,0 +1,240 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serenity::{client::Context, model::id::GuildId, prelude::*};
+
+use crate::{
+    discord::{
+        aliases::{get_aliases, AliasContainer},
+        channel_groups::{get_groups, get_submission_channels, ChannelGroup},
+        command_permissions::{get_grants, GrantContainer},
+        secrets::CLIENT_GENERATION,
+        server_settings::{get_all_settings, SettingsContainer},
+        servers::{get_servers, DiscordServer},
+    },
+    helpers::*,
+};
+
+const SNAPSHOT_KEY: &str = "murahdahla:cache";
+const CHANGED_CHANNEL: &str = "murahdahla:cache_changed";
+// how long to wait for a change before checking whether this client has been replaced
+const FOLLOW_CHECK_SECS: u64 = 30;
+
+// the share map containers other instances need to agree on. the in-memory maps stay
+// the ones everything reads from. redis is only how they're kept the same everywhere
+// and how a restarted instance gets them back without going to the database.
+pub struct SharedCacheContainer;
+
+impl TypeMapKey for SharedCacheContainer {
+    type Value = SharedCache;
+}
+
+#[derive(Clone)]
+pub struct SharedCache {
+    client: redis::Client,
+    // so an instance can ignore its own change notices
+    instance_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheSnapshot {
+    pub groups: Vec<ChannelGroup>,
+    pub servers: Vec<DiscordServer>,
+}
+
+impl CacheSnapshot {
+    pub fn new(
+        groups: &HashMap<u64, ChannelGroup>,
+        servers: &HashMap<GuildId, DiscordServer>,
+    ) -> Self {
+        CacheSnapshot {
+            groups: groups.values().cloned().collect(),
+            servers: servers.values().copied().collect(),
+        }
+    }
+
+    // the submission set is every group's submission channel so it isn't stored
+    pub fn into_maps(
+        self,
+    ) -> (
+        HashMap<u64, ChannelGroup>,
+        HashMap<GuildId, DiscordServer>,
+        HashSet<u64>,
+    ) {
+        let submission_set: HashSet<u64> = self.groups.iter().map(|g| g.submission).collect();
+        let groups: HashMap<u64, ChannelGroup> =
+            self.groups.into_iter().map(|g| (g.submission, g)).collect();
+        let servers: HashMap<GuildId, DiscordServer> = self
+            .servers
+            .into_iter()
+            .map(|s| (GuildId::from(s.server_id), s))
+            .collect();
+
+        (groups, servers, submission_set)
+    }
+}
+
+impl SharedCache {
+    pub fn new(redis_url: &str) -> Result<Self> {
+        let instance_id = uuid::Uuid::new_v4().to_string();
+
+        Ok(SharedCache {
+            client: redis::Client::open(redis_url)?,
+            instance_id,
+        })
+    }
+
+    pub async fn load(&self) -> Result<Option<CacheSnapshot>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let json: Option<String> = conn.get(SNAPSHOT_KEY).await?;
+
+        Ok(match json {
+            Some(j) => Some(serde_json::from_str(&j)?),
+            None => None,
+        })
+    }
+
+    pub async fn store(&self, snapshot: &CacheSnapshot) -> Result<()> {
+        let json = serde_json::to_string(snapshot)?;
+        let mut conn = self.client.get_async_connection().await?;
+        conn.set::<_, _, ()>(SNAPSHOT_KEY, json).await?;
+        conn.publish::<_, _, ()>(CHANGED_CHANNEL, &self.instance_id)
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub async fn publish_shared_cache(ctx: &Context) {
+    // called after anything changes the groups or servers. a redis failure is logged
+    // rather than failing the command since this instance already has the change.
+    let (cache, snapshot) = {
+        let data = ctx.data.read().await;
+        let cache = match data.get::<SharedCacheContainer>() {
+            Some(c) => c.clone(),
+            None => return,
+        };
+        let snapshot = CacheSnapshot::new(
+            data.get::<GroupContainer>()
+                .expect("No group container in share map"),
+            data.get::<ServerContainer>()
+                .expect("No server container in share map"),
+        );
+        (cache, snapshot)
+    };
+    if let Err(e) = cache.store(&snapshot).await {
+        warn!("Error updating shared cache: {}", e);
+    }
+}
+
+pub async fn reload_caches(ctx: &Context) -> Result<()> {
+    // re-reads every cache we keep in the share map from the database. redis only
+    // shares groups and servers so this is also how an instance catches up on
+    // everything else another instance changed.
+    let conn = get_connection(ctx).await;
+    let submission_channel_set = get_submission_channels(&conn)?;
+    let servers = get_servers(&conn)?;
+    let groups = get_groups(&conn)?;
+    let settings = get_all_settings(&conn)?;
+    let grants = get_grants(&conn)?;
+    let aliases = get_aliases(&conn)?;
+    drop(conn);
+    {
+        let mut data = ctx.data.write().await;
+        data.insert::<SubmissionSet>(submission_channel_set);
+        data.insert::<ServerContainer>(servers);
+        data.insert::<GroupContainer>(groups);
+        data.insert::<SettingsContainer>(settings);
+        data.insert::<GrantContainer>(grants);
+        data.insert::<AliasContainer>(aliases);
+    }
+    publish_shared_cache(ctx).await;
+
+    Ok(())
+}
+
+pub async fn follow_shared_cache(ctx: Context) {
+    // started on ready. picks up changes other instances make until this client is
+    // replaced, reconnecting to redis if it goes away.
+    let cache = match ctx.data.read().await.get::<SharedCacheContainer>() {
+        Some(c) => c.clone(),
+        None => return,
+    };
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    while CLIENT_GENERATION.load(Ordering::SeqCst) == generation {
+        if let Err(e) = follow_changes(&ctx, &cache, generation).await {
+            warn!("Lost shared cache subscription: {}", e);
+            tokio::time::sleep(Duration::from_secs(FOLLOW_CHECK_SECS)).await;
+        }
+    }
+}
+
+async fn follow_changes(ctx: &Context, cache: &SharedCache, generation: u64) -> Result<()> {
+    let mut pubsub = cache.client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(CHANGED_CHANNEL).await?;
+    let mut changes = pubsub.on_message();
+    while CLIENT_GENERATION.load(Ordering::SeqCst) == generation {
+        let change = match tokio::time::timeout(
+            Duration::from_secs(FOLLOW_CHECK_SECS),
+            changes.next(),
+        )
+        .await
+        {
+            Ok(Some(c)) => c,
+            Ok(None) => return Err(anyhow::anyhow!("subscription closed")),
+            Err(_) => continue,
+        };
+        let from: String = change.get_payload()?;
+        if from == cache.instance_id {
+            continue;
+        }
+        if let Some(snapshot) = cache.load().await? {
+            let (groups, servers, submission_set) = snapshot.into_maps();
+            let mut data = ctx.data.write().await;
+            data.insert::<GroupContainer>(groups);
+            data.insert::<ServerContainer>(servers);
+            data.insert::<SubmissionSet>(submission_set);
+            info!("Updated groups and servers from shared cache");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{discord::spoiler_roles::SpoilerAccess, testing::test_group};
+
+    #[test]
+    fn snapshot_round_trips() {
+        let group = ChannelGroup {
+            group_name: "main".to_owned(),
+            submission: 10,
+            ..test_group()
+        };
+        let server = DiscordServer {
+            server_id: 1,
+            owner_id: 2,
+            admin_role_id: None,
+            mod_role_id: Some(3),
+        };
+        let groups: HashMap<u64, ChannelGroup> = [(10, group)].into_iter().collect();
+        let servers: HashMap<GuildId, DiscordServer> =
+            [(GuildId::from(1), server)].into_iter().collect();
+        let json = serde_json::to_string(&CacheSnapshot::new(&groups, &servers)).unwrap();
+        let snapshot: CacheSnapshot = serde_json::from_str(&json).unwrap();
+        let (groups, servers, submission_set) = snapshot.into_maps();
+        assert_eq!(groups[&10].group_name, "main");
+        assert_eq!(groups[&10].spoiler_access, SpoilerAccess::Role);
+        assert_eq!(servers[&GuildId::from(1)].mod_role_id, Some(3));
+        assert!(submission_set.contains(&10));
+    }
+}
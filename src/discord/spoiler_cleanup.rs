@@ -0,0 +1,153 @@
+use serenity::{
+    client::Context,
+    model::{
+        guild::Member,
+        id::{ChannelId, GuildId, RoleId, UserId},
+    },
+};
+
+use crate::{
+    discord::{channel_groups::ChannelGroup, ops::DiscordOps, spoiler_roles::SpoilerRole},
+    error::MurahdahlaError,
+    helpers::*,
+};
+
+// how many members we look at for each request, the most discord gives at once
+const MEMBER_BATCH: u64 = 1000;
+// how many roles we take away between edits of the progress post
+const PROGRESS_EVERY: usize = 25;
+
+// every role a group could have given out for any race, from its spoiler role and its
+// extra spoiler roles
+pub fn clearable_roles(spoiler_role_id: Option<u64>, rules: &[SpoilerRole]) -> Vec<RoleId> {
+    let mut roles: Vec<RoleId> = spoiler_role_id
+        .into_iter()
+        .chain(rules.iter().map(|r| r.role_id))
+        .map(RoleId::from)
+        .collect();
+    roles.sort();
+    roles.dedup();
+
+    roles
+}
+
+// takes the group's spoiler roles away from everyone who has one, whether or not
+// there's a race going. this is for cleaning up after a stop that couldn't remove them
+// all. discord only lists a server's members to bots with the server members intent
+// turned on in the developer portal.
+pub async fn clear_spoiler_roles(
+    ctx: &Context,
+    group: &ChannelGroup,
+    roles: &[RoleId],
+    progress_channel: ChannelId,
+) -> Result<(), MurahdahlaError> {
+    let ops: &dyn DiscordOps = &*ctx.http;
+    let guild_id = GuildId::from(group.server_id);
+    let progress = ops
+        .say(progress_channel, "Clearing spoiler roles...")
+        .await?;
+    let mut after: Option<UserId> = None;
+    let mut checked: usize = 0;
+    let mut cleared: usize = 0;
+    let mut failed: usize = 0;
+    loop {
+        let batch: Vec<Member> =
+            discord_with_retry(|| guild_id.members(&ctx, Some(MEMBER_BATCH), after)).await?;
+        match batch.last() {
+            Some(m) => after = Some(m.user.id),
+            None => break,
+        }
+        checked += batch.len();
+        for member in batch.iter() {
+            let held: Vec<RoleId> = roles
+                .iter()
+                .filter(|r| member.roles.contains(r))
+                .copied()
+                .collect();
+            if held.is_empty() {
+                continue;
+            }
+            // one member we can't change shouldn't stop the rest from being cleaned up
+            let mut removed = true;
+            for r in held {
+                if let Err(e) = ops.remove_role(guild_id, member.user.id, r).await {
+                    warn!(
+                        "Error removing spoiler role from \"{}\": {}",
+                        &member.user.name, e
+                    );
+                    removed = false;
+                }
+            }
+            match removed {
+                true => cleared += 1,
+                false => failed += 1,
+            }
+            if (cleared + failed) % PROGRESS_EVERY == 0 {
+                ops.edit(
+                    progress_channel,
+                    progress.message_id,
+                    &progress_text(checked, cleared, failed),
+                )
+                .await?;
+            }
+        }
+        ops.edit(
+            progress_channel,
+            progress.message_id,
+            &progress_text(checked, cleared, failed),
+        )
+        .await?;
+        if (batch.len() as u64) < MEMBER_BATCH {
+            break;
+        }
+    }
+    let mut done = format!("Removed spoiler roles from {} members.", cleared);
+    if failed > 0 {
+        done.push_str(&format!(
+            " Couldn't remove them from {} members, check that the bot's role is above \
+             the spoiler roles.",
+            failed
+        ));
+    }
+    ops.edit(progress_channel, progress.message_id, &done)
+        .await?;
+    info!(
+        "Cleared spoiler roles from {} members in group \"{}\" ({} failed)",
+        cleared, &group.group_name, failed
+    );
+
+    Ok(())
+}
+
+fn progress_text(checked: usize, cleared: usize, failed: usize) -> String {
+    let mut text = format!(
+        "Clearing spoiler roles... {} members checked, {} cleared",
+        checked, cleared
+    );
+    if failed > 0 {
+        text.push_str(&format!(", {} failed", failed));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_spoiler_role_is_cleared_once() {
+        let rule = |role_id| SpoilerRole {
+            channel_group_id: vec![0; 16],
+            role_id,
+            race_game: None,
+            division_role_id: None,
+        };
+        let rules = vec![rule(7), rule(5), rule(6)];
+        assert_eq!(
+            clearable_roles(Some(5), &rules),
+            vec![RoleId::from(5), RoleId::from(6), RoleId::from(7)]
+        );
+        assert!(clearable_roles(None, &[]).is_empty());
+    }
+}
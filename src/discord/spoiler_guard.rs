@@ -0,0 +1,89 @@
+use anyhow::Result;
+use diesel::{dsl::exists, prelude::*, select};
+use serenity::{
+    builder::CreateMessage,
+    client::Context,
+    model::{channel::Message, id::GuildId},
+};
+
+use crate::{
+    discord::{
+        channel_groups::{get_spoiler_group, ChannelGroup},
+        servers::{check_permissions, Permission},
+    },
+    games::get_maybe_active_race,
+    helpers::*,
+    schema::submissions,
+};
+
+// groups with guard_spoiler set only let runners who've submitted to the active race
+// (access grants count since they're stored as submissions) and mods talk in the
+// spoiler channel. this is a backstop for channel permissions that let too many people
+// see it, so it doesn't do anything while there's no race to spoil.
+pub async fn guard_spoiler_channel(ctx: &Context, msg: &Message) {
+    if msg.author.bot {
+        return;
+    }
+    let group = match get_spoiler_group(ctx, msg.channel_id).await {
+        Some(g) if g.guard_spoiler => g,
+        _ => return,
+    };
+    match may_post(ctx, msg, &group).await {
+        Ok(true) => (),
+        Ok(false) => remove_post(ctx, msg, &group).await,
+        Err(e) => warn!("Error checking spoiler channel access: {}", e),
+    }
+}
+
+async fn may_post(ctx: &Context, msg: &Message, group: &ChannelGroup) -> Result<bool> {
+    let user_roles = msg.member.as_ref().map_or(&[][..], |m| &m.roles[..]);
+    let guild_id = GuildId::from(group.server_id);
+    if check_permissions(ctx, guild_id, &msg.author, user_roles, Permission::Mod)
+        .await
+        .is_ok()
+    {
+        return Ok(true);
+    }
+    let conn = get_connection(ctx).await;
+    let race = match get_maybe_active_race(&conn, group) {
+        Some(r) => r,
+        None => return Ok(true),
+    };
+    let submitted: bool = select(exists(
+        submissions::table
+            .filter(submissions::race_id.eq(race.race_id))
+            .filter(submissions::runner_id.eq(msg.author.id.get())),
+    ))
+    .get_result(&conn)?;
+
+    Ok(submitted)
+}
+
+async fn remove_post(ctx: &Context, msg: &Message, group: &ChannelGroup) {
+    if let Err(e) = msg.delete(ctx).await {
+        warn!(
+            "Couldn't delete spoiler channel message from \"{}\" in group \"{}\": {}",
+            msg.author.name, group.group_name, e
+        );
+        return;
+    }
+    info!(
+        "Deleted spoiler channel message from \"{}\" in group \"{}\", who hasn't submitted",
+        msg.author.name, group.group_name
+    );
+    let text = format!(
+        "Your message in <#{}> was removed because only runners who have submitted a time \
+        for the current race can post there. Submit in <#{}> to join the discussion.",
+        group.spoiler, group.submission
+    );
+    if let Err(e) = msg
+        .author
+        .direct_message(ctx, CreateMessage::new().content(text))
+        .await
+    {
+        info!(
+            "Couldn't tell \"{}\" why their message was removed: {}",
+            msg.author.name, e
+        );
+    }
+}
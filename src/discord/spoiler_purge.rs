@@ -0,0 +1,123 @@
+use serenity::{
+    builder::GetMessages,
+    client::Context,
+    model::{
+        channel::Message,
+        id::{ChannelId, MessageId},
+    },
+};
+
+use crate::{
+    discord::{channel_groups::ChannelGroup, ops::DiscordOps},
+    error::MurahdahlaError,
+    helpers::*,
+};
+
+// how many messages we look at for each request, the most discord gives at once
+const PURGE_BATCH: u8 = 100;
+// discord won't bulk delete messages older than two weeks. we leave an hour to spare so
+// a batch doesn't age past it while we're working through the channel.
+const BULK_DELETE_MAX_AGE_SECS: i64 = 14 * 24 * 60 * 60 - 60 * 60;
+
+// groups with purge_spoiler set have their spoiler channel cleared when a new race
+// starts so early finishers don't read last race's discussion. only messages from
+// before the progress post are deleted, so runners who finish while this is still
+// going don't lose what they write. pinned messages are left alone.
+pub async fn purge_spoiler_channel(
+    ctx: &Context,
+    group: &ChannelGroup,
+) -> Result<(), MurahdahlaError> {
+    let ops: &dyn DiscordOps = &*ctx.http;
+    let channel = ChannelId::from(group.spoiler);
+    let progress = ops
+        .say(channel, "Clearing the previous race's discussion...")
+        .await?;
+    let mut before = progress.message_id;
+    let mut deleted: usize = 0;
+    loop {
+        let batch: Vec<Message> = discord_with_retry(|| {
+            channel.messages(&ctx, GetMessages::new().before(before).limit(PURGE_BATCH))
+        })
+        .await?;
+        // messages come newest first
+        match batch.last() {
+            Some(m) => before = m.id,
+            None => break,
+        }
+        let ids: Vec<MessageId> = batch.iter().filter(|m| !m.pinned).map(|m| m.id).collect();
+        let (recent, old) = split_bulk_deletable(&ids, chrono::Utc::now().timestamp());
+        // bulk deletes take 2-100 messages and anything older has to go one at a time.
+        // both go through the same retries as the rest of the bot so a rate limit only
+        // slows us down.
+        match recent.len() {
+            0 => (),
+            1 => ops.delete(channel, recent[0]).await?,
+            _ => {
+                discord_with_retry(|| channel.delete_messages(&ctx, recent.iter())).await?;
+            }
+        }
+        for id in old.iter() {
+            ops.delete(channel, *id).await?;
+        }
+        deleted += recent.len() + old.len();
+        if deleted > 0 {
+            ops.edit(
+                channel,
+                progress.message_id,
+                &format!(
+                    "Clearing the previous race's discussion... {} deleted",
+                    deleted
+                ),
+            )
+            .await?;
+        }
+        if batch.len() < PURGE_BATCH as usize {
+            break;
+        }
+    }
+    ops.edit(
+        channel,
+        progress.message_id,
+        &format!("Cleared {} messages from the previous race.", deleted),
+    )
+    .await?;
+    info!(
+        "Purged {} messages from the spoiler channel of group \"{}\"",
+        deleted, &group.group_name
+    );
+
+    Ok(())
+}
+
+fn split_bulk_deletable(ids: &[MessageId], now: i64) -> (Vec<MessageId>, Vec<MessageId>) {
+    // a message's age comes from its id so we don't need the message itself
+    ids.iter()
+        .copied()
+        .partition(|id| now - id.created_at().unix_timestamp() < BULK_DELETE_MAX_AGE_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // discord ids count milliseconds from the start of 2015
+    const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+    fn id_at(secs: i64) -> MessageId {
+        MessageId::from(((secs * 1000 - DISCORD_EPOCH_MS) as u64) << 22)
+    }
+
+    #[test]
+    fn old_messages_are_deleted_one_at_a_time() {
+        let now = 1_790_000_000;
+        let day = 24 * 60 * 60;
+        let ids = [
+            id_at(now - 60),
+            id_at(now - 13 * day),
+            id_at(now - 14 * day),
+        ];
+        let (recent, old) = split_bulk_deletable(&ids, now);
+        assert_eq!(recent, ids[..2].to_vec());
+        assert_eq!(old, vec![ids[2]]);
+    }
+}
@@ -0,0 +1,374 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use diesel::{
+    backend::Backend, deserialize, deserialize::FromSql, expression::AsExpression,
+    helper_types::AsExprOf, prelude::*, sql_types::Text,
+};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::EditRole,
+    model::{
+        channel::{PermissionOverwrite, PermissionOverwriteType},
+        guild::Guild,
+        id::{ChannelId, GuildId, RoleId, UserId},
+        permissions::Permissions,
+    },
+    prelude::*,
+};
+
+use crate::{
+    discord::{
+        channel_groups::ChannelGroup,
+        ops::DiscordOps,
+        servers::{check_bot_can_assign, resolve_role},
+        submissions::Submission,
+    },
+    error::MurahdahlaError,
+    games::{AsyncRaceData, GameName},
+    helpers::*,
+    schema::spoiler_roles,
+};
+
+// how runners are let into the spoiler and leaderboard channels after they submit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+pub enum SpoilerAccess {
+    // give them the group's spoiler role(s)
+    Role,
+    // add a permission overwrite for them on each channel. this is for servers that are
+    // close to discord's role limit.
+    Overwrite,
+    // make a new role for each race and delete it when the race stops
+    RaceRole,
+}
+
+impl Default for SpoilerAccess {
+    fn default() -> Self {
+        SpoilerAccess::Role
+    }
+}
+
+impl<DB> FromSql<Text, DB> for SpoilerAccess
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "role" => Ok(SpoilerAccess::Role),
+            "overwrite" => Ok(SpoilerAccess::Overwrite),
+            "race_role" => Ok(SpoilerAccess::RaceRole),
+            x => Err(format!("Unrecognized spoiler access: {}", x).into()),
+        }
+    }
+}
+
+impl AsExpression<Text> for SpoilerAccess {
+    type Expression = AsExprOf<String, Text>;
+
+    fn as_expression(self) -> Self::Expression {
+        <String as AsExpression<Text>>::as_expression(self.to_string())
+    }
+}
+
+impl<'a> AsExpression<Text> for &'a SpoilerAccess {
+    type Expression = AsExprOf<String, Text>;
+
+    fn as_expression(self) -> Self::Expression {
+        <String as AsExpression<Text>>::as_expression(self.to_string())
+    }
+}
+
+impl fmt::Display for SpoilerAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SpoilerAccess::Role => write!(f, "role"),
+            SpoilerAccess::Overwrite => write!(f, "overwrite"),
+            SpoilerAccess::RaceRole => write!(f, "race_role"),
+        }
+    }
+}
+
+// groups can hand out different spoiler roles depending on the game being raced or a
+// "division" role the runner already has. the group's own spoiler role is used when none
+// of these match.
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(parent = "ChannelGroup", foreign_key = "channel_group_id")]
+#[table_name = "spoiler_roles"]
+#[primary_key(channel_group_id, role_id)]
+pub struct SpoilerRole {
+    pub channel_group_id: Vec<u8>,
+    pub role_id: u64,
+    // only given out for races of this game
+    pub race_game: Option<String>,
+    // only given to runners who have this role
+    pub division_role_id: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpoilerRoleYaml {
+    pub role: String,
+    #[serde(default)]
+    pub game: Option<String>,
+    #[serde(default)]
+    pub division: Option<String>,
+}
+
+impl SpoilerRole {
+    fn matches_game(&self, game: GameName) -> bool {
+        self.race_game
+            .as_deref()
+            .map_or(true, |g| g == game.to_string())
+    }
+
+    fn matches_division(&self, member_roles: &[RoleId]) -> bool {
+        self.division_role_id
+            .map_or(true, |d| member_roles.iter().any(|r| r.get() == d))
+    }
+}
+
+pub async fn spoiler_roles_from_yaml(
+    ctx: &Context,
+    server: &Guild,
+    group_id: &[u8],
+    default_role_id: Option<RoleId>,
+    yaml: &[SpoilerRoleYaml],
+) -> Result<Vec<SpoilerRole>> {
+    let mut seen: HashSet<RoleId> = HashSet::new();
+    seen.extend(default_role_id);
+    let mut rules: Vec<SpoilerRole> = Vec::with_capacity(yaml.len());
+    for y in yaml {
+        let role_id = resolve_role(server, &y.role)
+            .map_err(|e| anyhow!("Could not get spoiler role from yaml: {}", e))?;
+        if !seen.insert(role_id) {
+            return Err(anyhow!(
+                "Spoiler role \"{}\" is listed more than once in yaml",
+                &y.role
+            ));
+        }
+        check_bot_can_assign(ctx, server, role_id).await?;
+        let race_game = match &y.game {
+            Some(g) => Some(GameName::from_str(g)?.to_string()),
+            None => None,
+        };
+        let division_role_id = match &y.division {
+            Some(d) => Some(
+                resolve_role(server, d)
+                    .map_err(|e| anyhow!("Could not get division role from yaml: {}", e))?
+                    .get(),
+            ),
+            None => None,
+        };
+        rules.push(SpoilerRole {
+            channel_group_id: group_id.to_vec(),
+            role_id: role_id.get(),
+            race_game,
+            division_role_id,
+        });
+    }
+
+    Ok(rules)
+}
+
+pub fn get_spoiler_roles(conn: &PooledConn, group: &ChannelGroup) -> Result<Vec<SpoilerRole>> {
+    Ok(SpoilerRole::belonging_to(group).load::<SpoilerRole>(conn)?)
+}
+
+pub fn select_spoiler_roles(
+    group: &ChannelGroup,
+    rules: &[SpoilerRole],
+    game: GameName,
+    member_roles: &[RoleId],
+) -> Vec<RoleId> {
+    // the roles a runner should get when they submit
+    let selected: Vec<RoleId> = rules
+        .iter()
+        .filter(|r| r.matches_game(game) && r.matches_division(member_roles))
+        .map(|r| RoleId::from(r.role_id))
+        .collect();
+    match selected.is_empty() {
+        true => group
+            .spoiler_role_id
+            .map(RoleId::from)
+            .into_iter()
+            .collect(),
+        false => selected,
+    }
+}
+
+pub fn removable_spoiler_roles(
+    group: &ChannelGroup,
+    rules: &[SpoilerRole],
+    game: GameName,
+    member_roles: &[RoleId],
+) -> Vec<RoleId> {
+    // the roles to take away when a race ends. we don't check divisions here since the
+    // runner's roles could have changed since they submitted.
+    let mut candidates: Vec<RoleId> = rules
+        .iter()
+        .filter(|r| r.matches_game(game))
+        .map(|r| RoleId::from(r.role_id))
+        .collect();
+    candidates.extend(group.spoiler_role_id.map(RoleId::from));
+    candidates.retain(|r| member_roles.contains(r));
+
+    candidates
+}
+
+// what runners are allowed to do in the spoiler and leaderboard channels
+pub const SPOILER_PERMISSIONS: Permissions =
+    Permissions::VIEW_CHANNEL.union(Permissions::READ_MESSAGE_HISTORY);
+
+pub async fn grant_spoiler_access(
+    ops: &dyn DiscordOps,
+    group: &ChannelGroup,
+    rules: &[SpoilerRole],
+    race: &AsyncRaceData,
+    user_id: UserId,
+) -> Result<(), MurahdahlaError> {
+    let guild_id = GuildId::from(group.server_id);
+    match group.spoiler_access {
+        SpoilerAccess::Role => {
+            let member_roles = ops.member_roles(guild_id, user_id).await?;
+            let role_ids = select_spoiler_roles(group, rules, race.race_game, &member_roles);
+            for r in role_ids {
+                ops.add_role(guild_id, user_id, r).await?;
+            }
+        }
+        SpoilerAccess::Overwrite => {
+            for c in [group.spoiler, group.leaderboard] {
+                ops.allow_member(ChannelId::from(c), user_id, SPOILER_PERMISSIONS)
+                    .await?;
+            }
+        }
+        SpoilerAccess::RaceRole => {
+            let role_id = race
+                .race_role_id
+                .ok_or_else(|| anyhow!("Race {} has no race role", race.race_id))?;
+            ops.add_role(guild_id, user_id, RoleId::from(role_id))
+                .await?;
+        }
+    };
+
+    Ok(())
+}
+
+pub async fn revoke_spoiler_access(
+    ops: &dyn DiscordOps,
+    group: &ChannelGroup,
+    rules: &[SpoilerRole],
+    race: &AsyncRaceData,
+    user_id: UserId,
+) -> Result<(), MurahdahlaError> {
+    let guild_id = GuildId::from(group.server_id);
+    match group.spoiler_access {
+        SpoilerAccess::Role => {
+            let member_roles = ops.member_roles(guild_id, user_id).await?;
+            let role_ids = removable_spoiler_roles(group, rules, race.race_game, &member_roles);
+            for r in role_ids {
+                ops.remove_role(guild_id, user_id, r).await?;
+            }
+        }
+        SpoilerAccess::Overwrite => {
+            for c in [group.spoiler, group.leaderboard] {
+                ops.remove_member_overwrite(ChannelId::from(c), user_id)
+                    .await?;
+            }
+        }
+        SpoilerAccess::RaceRole => {
+            if let Some(role_id) = race.race_role_id {
+                ops.remove_role(guild_id, user_id, RoleId::from(role_id))
+                    .await?;
+            }
+        }
+    };
+
+    Ok(())
+}
+
+pub async fn revoke_runners_access(
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+) -> Result<(), MurahdahlaError> {
+    // takes spoiler access away from everyone with a submission in this race once it
+    // has stopped. races with their own role just delete the role instead.
+    use crate::schema::submissions::columns::runner_id;
+
+    let (user_ids, rules) = {
+        let conn = pool.get()?;
+        let user_ids = Submission::belonging_to(race)
+            .select(runner_id)
+            .load::<u64>(&conn)?;
+        (user_ids, get_spoiler_roles(&conn, group)?)
+    };
+    let mut failures: Vec<String> = Vec::new();
+    for id in user_ids {
+        match revoke_spoiler_access(ops, group, &rules, race, UserId::from(id)).await {
+            Ok(()) => (),
+            // runners who have left the server don't have anything to take away
+            Err(e) if is_not_found(&e) => (),
+            Err(e) => failures.push(format!("user id {}: {}", id, e)),
+        };
+    }
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Failed to remove spoiler access for {} runners: {}",
+            failures.len(),
+            failures.join("; ")
+        )
+        .into()),
+    }
+}
+
+pub async fn create_race_role(
+    ctx: &Context,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+) -> Result<RoleId, MurahdahlaError> {
+    let mut role = GuildId::from(group.server_id)
+        .create_role(
+            &ctx,
+            EditRole::new()
+                .name(format!("race-{}-{}", race.race_id, race.race_date))
+                .mentionable(false),
+        )
+        .await?;
+    let overwrite = PermissionOverwrite {
+        allow: SPOILER_PERMISSIONS,
+        deny: Permissions::empty(),
+        kind: PermissionOverwriteType::Role(role.id),
+    };
+    for c in [group.spoiler, group.leaderboard] {
+        if let Err(e) = ChannelId::from(c)
+            .create_permission(&ctx, overwrite.clone())
+            .await
+        {
+            // don't leave a role around that doesn't do anything
+            let _ = role.delete(&ctx).await;
+            return Err(e.into());
+        }
+    }
+
+    Ok(role.id)
+}
+
+pub async fn delete_race_role(
+    ctx: &Context,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+) -> Result<(), MurahdahlaError> {
+    // the role's channel overwrites go with it
+    if let Some(role_id) = race.race_role_id {
+        GuildId::from(group.server_id)
+            .delete_role(&ctx, role_id)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,480 @@
+use thiserror::Error;
+
+use crate::discord::submissions::RunTime;
+
+// the grammar a message in a submission channel is read with. bump this whenever a
+// message that used to parse would now parse differently.
+//
+//   submission := [unranked] (forfeit | finish | done) [comment]
+//   unranked   := "nc"                              (any case)
+//   forfeit    := forfeit word, then anything       (the rest is ignored)
+//   finish     := time [collection] extra*
+//   done       := ["."] "done" [collection] extra*  (any case, for races the bot times)
+//   time       := [[h ":"] m ":"] s [fraction]
+//               | [h ("h"|"H")] [m ("m"|"M")] [s [fraction] ["s"|"S"]]
+//   fraction   := ("." | ",") 1-3 digits
+//   collection := digits
+//   extra      := any word
+//   comment    := "#" at the start of a word, then anything
+//
+// words are separated by whitespace. backslashes in the time are dropped because some
+// servers turn numbers into emotes and people escape them. what the collection and the
+// extras mean is up to the race's game, template or sort.
+pub const GRAMMAR_VERSION: u32 = 2;
+
+// some strings we'll compare with to check if a user has forfeited
+const FORFEIT: [&str; 4] = ["ff", "FF", "forfeit", "Forfeit"];
+// runners who aren't competing start their submission with this
+const UNRANKED_PREFIX: &str = "nc";
+// runners in races the bot times send this instead of a time since theirs is already
+// known. live races usually use ".done" like other race bots.
+pub const DONE_WORD: &str = "done";
+const COMMENT_MARKER: char = '#';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedSubmission<'a> {
+    pub unranked: bool,
+    pub entry: Entry<'a>,
+    // comments aren't stored yet, they're only taken out so they don't get read as
+    // collection rates or fields
+    pub comment: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<'a> {
+    Forfeit,
+    Finish {
+        time: RunTime,
+        collection: Option<&'a str>,
+        extras: Vec<&'a str>,
+    },
+    // the runner finished a race that times them itself
+    Done {
+        collection: Option<&'a str>,
+        extras: Vec<&'a str>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParseError {
+    #[error("The submission has no text")]
+    Empty,
+    #[error("Expected a time after \"{0}\"")]
+    MissingTime(String),
+    #[error("\"{token}\" isn't a time: {source}")]
+    Time { token: String, source: TimeError },
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TimeError {
+    #[error("Empty submission time")]
+    Empty,
+    #[error("\"{0}\" is not a number")]
+    NotANumber(String),
+    #[error("\"{0}\" is not a time unit")]
+    NotAUnit(char),
+    #[error("Tried to parse malformed time")]
+    Malformed,
+    #[error("Only seconds can have a fraction")]
+    MisplacedFraction,
+    #[error("Minutes and seconds must be under 60")]
+    OutOfRange,
+    #[error("Fractions of a second can have at most 3 digits")]
+    LongFraction,
+    #[error("Time is too long")]
+    TooLong,
+}
+
+pub fn parse_submission<'a>(
+    content: &'a str,
+    forfeit_words: &[&str],
+) -> Result<ParsedSubmission<'a>, ParseError> {
+    let (text, comment) = split_comment(content);
+    let mut words = text.split_whitespace().peekable();
+    let first = words.next().ok_or(ParseError::Empty)?;
+    let unranked = first.eq_ignore_ascii_case(UNRANKED_PREFIX);
+    let maybe_time = match unranked {
+        true => words
+            .next()
+            .ok_or_else(|| ParseError::MissingTime(first.to_owned()))?,
+        false => first,
+    };
+    if is_forfeit(maybe_time, forfeit_words) {
+        return Ok(ParsedSubmission {
+            unranked,
+            entry: Entry::Forfeit,
+            comment,
+        });
+    }
+    let time =
+        match maybe_time
+            .strip_prefix('.')
+            .unwrap_or(maybe_time)
+            .eq_ignore_ascii_case(DONE_WORD)
+        {
+            true => None,
+            false => Some(parse_variable_time(&maybe_time.replace('\\', "")).map_err(
+                |source| ParseError::Time {
+                    token: maybe_time.to_owned(),
+                    source,
+                },
+            )?),
+        };
+    let collection = words.next_if(|w| w.chars().all(|c| c.is_ascii_digit()));
+    let extras: Vec<&str> = words.collect();
+    let entry = match time {
+        Some(time) => Entry::Finish {
+            time,
+            collection,
+            extras,
+        },
+        None => Entry::Done { collection, extras },
+    };
+
+    Ok(ParsedSubmission {
+        unranked,
+        entry,
+        comment,
+    })
+}
+
+fn split_comment(content: &str) -> (&str, Option<&str>) {
+    // a marker only starts a comment at the beginning of a word so something like
+    // "<#1234>" or "a#b" stays as it is
+    let start = content.char_indices().find(|&(i, c)| {
+        c == COMMENT_MARKER
+            && content[..i]
+                .chars()
+                .next_back()
+                .map_or(true, char::is_whitespace)
+    });
+    match start {
+        Some((i, _)) => {
+            let comment = content[i + COMMENT_MARKER.len_utf8()..].trim();
+            (&content[..i], Some(comment).filter(|c| !c.is_empty()))
+        }
+        None => (content, None),
+    }
+}
+
+pub fn is_forfeit(word: &str, forfeit_words: &[&str]) -> bool {
+    // a group's own forfeit words are stored lowercase and match in any case
+    FORFEIT.contains(&word) || forfeit_words.contains(&word.to_lowercase().as_str())
+}
+
+fn parse_time_number(p: &str) -> Result<u64, TimeError> {
+    if p.is_empty() {
+        return Err(TimeError::Malformed);
+    }
+    match p.chars().all(|c| c.is_ascii_digit()) {
+        true => p.parse::<u64>().map_err(|_| TimeError::TooLong),
+        false => Err(TimeError::NotANumber(p.to_owned())),
+    }
+}
+
+fn split_suffixed_time(whole: &str, has_fraction: bool) -> Result<(u64, u64, u64), TimeError> {
+    // "1h23m45", "23m", "45s" etc. the units have to be in order and a number after the
+    // last unit counts as the next smaller one, so "1h23" is an hour and 23 minutes.
+    let mut values: [Option<u64>; 3] = [None; 3];
+    let mut next_unit = 0usize;
+    let mut rest = whole;
+    while !rest.is_empty() {
+        let digits_end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits_end == 0 {
+            return Err(TimeError::NotANumber(rest.to_owned()));
+        }
+        let number = parse_time_number(&rest[..digits_end])?;
+        let unit = match rest[digits_end..].chars().next() {
+            Some(u) => match u.to_ascii_lowercase() {
+                'h' => 0,
+                'm' => 1,
+                's' => 2,
+                _ => return Err(TimeError::NotAUnit(u)),
+            },
+            None => next_unit,
+        };
+        if unit < next_unit || unit > 2 {
+            return Err(TimeError::Malformed);
+        }
+        values[unit] = Some(number);
+        next_unit = unit + 1;
+        rest = rest.get(digits_end + 1..).unwrap_or_default();
+    }
+    if has_fraction && next_unit != 3 {
+        return Err(TimeError::MisplacedFraction);
+    }
+
+    Ok((
+        values[0].unwrap_or(0),
+        values[1].unwrap_or(0),
+        values[2].unwrap_or(0),
+    ))
+}
+
+pub fn parse_variable_time(maybe_time: &str) -> Result<RunTime, TimeError> {
+    // accepts [[h:]m:]s with an optional fraction of a second, e.g. "1:23:45.678", or
+    // the same with unit suffixes, e.g. "1h23m45.678s". a comma works as the decimal
+    // separator as well. hours aren't capped so long runs can be submitted.
+    let maybe_time = maybe_time.replace(',', ".");
+    let (whole, fraction) = match maybe_time.split_once('.') {
+        Some((w, f)) => (w, Some(f.strip_suffix(['s', 'S']).unwrap_or(f))),
+        None => (maybe_time.as_str(), None),
+    };
+    if whole.is_empty() {
+        return Err(TimeError::Empty);
+    }
+    let (hours, minutes, seconds) = match whole.contains(|c: char| c.is_ascii_alphabetic()) {
+        true => split_suffixed_time(whole, fraction.is_some())?,
+        false => {
+            let parts: Vec<u64> = whole
+                .split(':')
+                .map(parse_time_number)
+                .collect::<Result<Vec<u64>, TimeError>>()?;
+            match parts[..] {
+                [s] => (0, 0, s),
+                [m, s] => (0, m, s),
+                [h, m, s] => (h, m, s),
+                _ => return Err(TimeError::Malformed),
+            }
+        }
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return Err(TimeError::OutOfRange);
+    }
+    let ms = match fraction {
+        None => 0,
+        Some(f) if !f.is_empty() && f.len() <= 3 && f.chars().all(|c| c.is_ascii_digit()) => {
+            // ".5" is half a second, not five milliseconds
+            parse_time_number(f)? * 10u64.pow(3 - f.len() as u32)
+        }
+        Some(_) => return Err(TimeError::LongFraction),
+    };
+    let total = hours
+        .checked_mul(3600)
+        .and_then(|s| s.checked_add(minutes * 60 + seconds))
+        .and_then(|s| s.checked_mul(1000))
+        .and_then(|ms_total| ms_total.checked_add(ms))
+        .ok_or(TimeError::TooLong)?;
+
+    Ok(RunTime::from_millis(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finish<'a>(millis: u64, collection: Option<&'a str>, extras: Vec<&'a str>) -> Entry<'a> {
+        Entry::Finish {
+            time: RunTime::from_millis(millis),
+            collection,
+            extras,
+        }
+    }
+
+    fn parse(content: &str) -> Result<ParsedSubmission<'_>, ParseError> {
+        parse_submission(content, &[])
+    }
+
+    #[test]
+    fn parses_time_collection_and_extras() {
+        let p = parse("1:23:45").unwrap();
+        assert_eq!(p.entry, finish(5_025_000, None, vec![]));
+        assert!(!p.unranked);
+        assert_eq!(p.comment, None);
+
+        let p = parse("  1:23:45   216 ").unwrap();
+        assert_eq!(p.entry, finish(5_025_000, Some("216"), vec![]));
+
+        let p = parse("1:23:45 216 clean run").unwrap();
+        assert_eq!(
+            p.entry,
+            finish(5_025_000, Some("216"), vec!["clean", "run"])
+        );
+
+        // something that isn't a number is never a collection rate
+        let p = parse("1:23:45 216% 3").unwrap();
+        assert_eq!(p.entry, finish(5_025_000, None, vec!["216%", "3"]));
+    }
+
+    #[test]
+    fn parses_unranked_prefix() {
+        let p = parse("nc 1:23:45 167").unwrap();
+        assert!(p.unranked);
+        assert_eq!(p.entry, finish(5_025_000, Some("167"), vec![]));
+        assert!(parse("NC 1:23").unwrap().unranked);
+        assert_eq!(parse("nc"), Err(ParseError::MissingTime("nc".to_owned())));
+        assert_eq!(
+            parse("Nc # routed early"),
+            Err(ParseError::MissingTime("Nc".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parses_forfeits() {
+        assert_eq!(parse("ff").unwrap().entry, Entry::Forfeit);
+        assert_eq!(parse("Forfeit").unwrap().entry, Entry::Forfeit);
+        // anything after a forfeit word doesn't matter
+        assert_eq!(parse("ff 1:23:45 216").unwrap().entry, Entry::Forfeit);
+        let p = parse("nc ff").unwrap();
+        assert!(p.unranked);
+        assert_eq!(p.entry, Entry::Forfeit);
+        let p = parse_submission("Aufgeben # zu schwer", &["aufgeben"]).unwrap();
+        assert_eq!(p.entry, Entry::Forfeit);
+        assert_eq!(p.comment, Some("zu schwer"));
+        assert!(parse("aufgeben").is_err());
+    }
+
+    #[test]
+    fn group_forfeit_words() {
+        assert!(is_forfeit("ff", &[]));
+        assert!(is_forfeit("Aufgeben", &["aufgeben", "abandon"]));
+        assert!(!is_forfeit("aufgeben", &[]));
+        assert!(!is_forfeit("fF", &[]));
+    }
+
+    #[test]
+    fn parses_done() {
+        let p = parse("done 216 # no bonks").unwrap();
+        assert_eq!(
+            p.entry,
+            Entry::Done {
+                collection: Some("216"),
+                extras: vec![]
+            }
+        );
+        assert_eq!(p.comment, Some("no bonks"));
+        let p = parse("nc Done clean").unwrap();
+        assert!(p.unranked);
+        assert_eq!(
+            p.entry,
+            Entry::Done {
+                collection: None,
+                extras: vec!["clean"]
+            }
+        );
+        let p = parse(".done").unwrap();
+        assert_eq!(
+            p.entry,
+            Entry::Done {
+                collection: None,
+                extras: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn comments_are_split_off() {
+        let p = parse("1:23:45 216 # died to moldorm twice").unwrap();
+        assert_eq!(p.entry, finish(5_025_000, Some("216"), vec![]));
+        assert_eq!(p.comment, Some("died to moldorm twice"));
+
+        let p = parse("1:23:45 #gg").unwrap();
+        assert_eq!(p.comment, Some("gg"));
+        // an empty comment is the same as none
+        assert_eq!(parse("1:23:45 #").unwrap().comment, None);
+        // a marker inside a word isn't a comment
+        let p = parse("1:23:45 run#2 <#1234>").unwrap();
+        assert_eq!(p.entry, finish(5_025_000, None, vec!["run#2", "<#1234>"]));
+        assert_eq!(p.comment, None);
+    }
+
+    #[test]
+    fn empty_submissions_are_rejected() {
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse(" \n\t "), Err(ParseError::Empty));
+        assert_eq!(parse("# just a comment"), Err(ParseError::Empty));
+    }
+
+    #[test]
+    fn bad_times_name_the_token() {
+        let e = parse("1:75 216").unwrap_err();
+        assert_eq!(
+            e,
+            ParseError::Time {
+                token: "1:75".to_owned(),
+                source: TimeError::OutOfRange
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "\"1:75\" isn't a time: Minutes and seconds must be under 60"
+        );
+        assert_eq!(
+            parse("nc hello").unwrap_err(),
+            ParseError::Time {
+                token: "hello".to_owned(),
+                source: TimeError::NotANumber("hello".to_owned())
+            }
+        );
+        assert_eq!(
+            parse("1:2a").unwrap_err(),
+            ParseError::Time {
+                token: "1:2a".to_owned(),
+                source: TimeError::NotAUnit(':')
+            }
+        );
+    }
+
+    #[test]
+    fn backslashes_are_dropped_from_times() {
+        assert_eq!(
+            parse("1\\:23\\:45").unwrap().entry,
+            finish(5_025_000, None, vec![])
+        );
+        // the error still shows what was sent
+        match parse("1\\:99").unwrap_err() {
+            ParseError::Time { token, .. } => assert_eq!(token, "1\\:99"),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn parses_short_times() {
+        assert_eq!(parse_variable_time("45").unwrap().millis(), 45_000);
+        assert_eq!(parse_variable_time("1:23").unwrap().millis(), 83_000);
+        assert_eq!(parse_variable_time("1:23:45").unwrap().millis(), 5_025_000);
+        assert_eq!(parse_variable_time("1:75"), Err(TimeError::OutOfRange));
+        assert_eq!(parse_variable_time("1:2:3:4"), Err(TimeError::Malformed));
+        assert_eq!(parse_variable_time(""), Err(TimeError::Empty));
+        assert_eq!(parse_variable_time("1::2"), Err(TimeError::Malformed));
+    }
+
+    #[test]
+    fn parses_milliseconds_and_long_runs() {
+        assert_eq!(parse_variable_time("1:23.5").unwrap().millis(), 83_500);
+        assert_eq!(parse_variable_time("0:00:01.042").unwrap().millis(), 1_042);
+        assert_eq!(
+            parse_variable_time("26:00:00").unwrap().millis(),
+            26 * 3_600_000
+        );
+        assert_eq!(parse_variable_time("1.2345"), Err(TimeError::LongFraction));
+        assert_eq!(parse_variable_time("1.x"), Err(TimeError::LongFraction));
+        assert_eq!(
+            parse_variable_time("99999999999999999999"),
+            Err(TimeError::TooLong)
+        );
+        assert_eq!(
+            parse_variable_time("9999999999999999:00:00"),
+            Err(TimeError::TooLong)
+        );
+    }
+
+    #[test]
+    fn parses_localized_times() {
+        assert_eq!(parse_variable_time("1:23,5").unwrap().millis(), 83_500);
+        assert_eq!(parse_variable_time("1h23m45s").unwrap().millis(), 5_025_000);
+        assert_eq!(parse_variable_time("1H23M").unwrap().millis(), 4_980_000);
+        assert_eq!(parse_variable_time("1h23").unwrap().millis(), 4_980_000);
+        assert_eq!(parse_variable_time("23m45,5s").unwrap().millis(), 1_425_500);
+        assert_eq!(parse_variable_time("45s").unwrap().millis(), 45_000);
+        assert_eq!(
+            parse_variable_time("1h30.5"),
+            Err(TimeError::MisplacedFraction)
+        );
+        assert_eq!(parse_variable_time("23m1h"), Err(TimeError::Malformed));
+        assert_eq!(parse_variable_time("1x2"), Err(TimeError::NotAUnit('x')));
+        assert_eq!(parse_variable_time("90m"), Err(TimeError::OutOfRange));
+    }
+}
@@ -1,27 +1,96 @@
-use std::{default::Default, fmt, future::Future};
+use std::{
+    collections::HashMap,
+    default::Default,
+    fmt::{self, Write},
+    future::Future,
+    io,
+    sync::{atomic::Ordering, Arc, Mutex},
+};
 
 use anyhow::{anyhow, Result};
-use chrono::{Duration, NaiveDateTime, NaiveTime, Utc};
-use diesel::prelude::*;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    prelude::*,
+    serialize::{self, Output, ToSql},
+    sql_types::{BigInt, Unsigned},
+};
+use serde::{Deserialize, Serialize};
 use serenity::{
     client::Context,
-    model::{channel::Message, id::ChannelId},
+    model::{
+        channel::Message,
+        id::{ChannelId, GuildId, MessageId},
+    },
+    prelude::TypeMapKey,
 };
+use tokio::sync::{Mutex as AsyncMutex, Semaphore};
+use uuid::Uuid;
 
 use crate::{
+    config::get_config,
     discord::{
+        branding::edit_branded,
         channel_groups::{ChannelGroup, ChannelType},
-        messages::BotMessage,
+        flair::get_flairs,
+        leader::is_leader,
+        messages::{message_maintenance_user, BotMessage},
+        ops::DiscordOps,
+        secrets::CLIENT_GENERATION,
+        server_settings::{get_setting_parsed, ServerSetting},
+        submission_parser::{parse_submission, Entry},
+        templates::{parse_template_fields, split_collection_field},
+    },
+    error::MurahdahlaError,
+    games::{
+        get_maybe_active_race,
+        registry::{format_untimed, provider},
+        scoring::{scoring_for, RaceScoring},
+        AsyncRaceData, DataDisplay, GameName, LeaderboardSort,
     },
-    games::{smtotal, smvaria, smz3, z3r, AsyncRaceData, DataDisplay, GameName},
     helpers::*,
     schema::*,
 };
 
-// some strings we'll compare with to check if a user has forfeited
-const FORFEIT: [&str; 4] = ["ff", "FF", "forfeit", "Forfeit"];
+// discord's limit on the length of a message in characters
+const MAX_POST_CHARS: usize = 2000;
+// how often the leaderboard refresh task checks whether it has been turned on
+const REFRESH_DISABLED_CHECK_SECS: u64 = 60;
+// the unique index from the source_message_ids migration
+const SOURCE_MESSAGE_INDEX: &str = "submissions_source_message";
+pub const BLIND_NOTE: &str = "Times are hidden until the race is stopped.";
+
+// leaderboard rebuilds triggered by submissions run in the background so a race with
+// a huge leaderboard doesn't hold up the others. a race only ever has one rebuild
+// running and any requests that come in while it runs are folded into a single rerun.
+pub struct LeaderboardQueue {
+    permits: Arc<Semaphore>,
+    // race id -> whether another rebuild was requested while one was running
+    running: Arc<Mutex<HashMap<u32, bool>>>,
+    // race id -> held while a rebuild is adding or removing the race's posts. commands
+    // like !refresh build the leaderboard directly so this is what keeps them from
+    // resizing it at the same time as a queued rebuild.
+    locks: Arc<Mutex<HashMap<u32, Arc<AsyncMutex<()>>>>>,
+}
+
+impl LeaderboardQueue {
+    pub fn new(max_concurrent: usize) -> Self {
+        LeaderboardQueue {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            running: Arc::new(Mutex::new(HashMap::new())),
+            locks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+pub struct LeaderboardQueueContainer;
 
-#[derive(Debug, Insertable, Queryable, Identifiable, Associations)]
+impl TypeMapKey for LeaderboardQueueContainer {
+    type Value = LeaderboardQueue;
+}
+
+#[derive(Debug, Clone, Insertable, Queryable, Identifiable, Associations)]
 #[belongs_to(parent = "AsyncRaceData", foreign_key = "race_id")]
 #[table_name = "submissions"]
 #[primary_key(submission_id)]
@@ -32,46 +101,36 @@ pub struct Submission {
     pub race_game: GameName,
     pub submission_datetime: NaiveDateTime,
     pub runner_name: String,
-    pub runner_time: Option<NaiveTime>,
     pub runner_collection: Option<u16>,
     pub option_number: Option<u32>,
     pub option_text: Option<String>,
     pub runner_forfeit: bool,
+    // waiting on a mod to look at it. these aren't shown on the leaderboard.
+    pub pending: bool,
+    // from someone who isn't competing, like a restreamer who routed the seed early.
+    // these get their own section on the leaderboard and aren't given a place.
+    pub unranked: bool,
+    #[column_name = "runner_time_ms"]
+    pub runner_time: Option<RunTime>,
+    // the message this was submitted with, if there was one. unique so replaying or
+    // backfilling a channel can't insert the same submission twice.
+    pub source_message_id: Option<u64>,
+    // the message as it was sent. the message itself is deleted so this is what lets us
+    // re-parse submissions after the parser changes.
+    pub submission_text: Option<String>,
 }
 
 impl fmt::Display for Submission {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.race_game {
-            GameName::ALTTPR => write!(
-                f,
-                "{} - {} - {}/216",
-                self.runner_name,
-                self.runner_time.unwrap(),
-                self.runner_collection.unwrap()
-            ),
-            GameName::SMZ3 => write!(
-                f,
-                "{} - {} - {}/316",
-                self.runner_name,
-                self.runner_time.unwrap(),
-                self.runner_collection.unwrap()
-            ),
-            GameName::FF4FE => write!(f, "{} - {}", self.runner_name, self.runner_time.unwrap()),
-            GameName::SMVARIA => write!(
-                f,
-                "{} - {} - {}%",
-                self.runner_name,
-                self.runner_time.unwrap(),
-                self.runner_collection.unwrap()
-            ),
-            GameName::SMTotal => write!(
-                f,
-                "{} - {} - {}%",
-                self.runner_name,
-                self.runner_time.unwrap(),
-                self.runner_collection.unwrap()
-            ),
-            GameName::Other => write!(f, "{} - {}", self.runner_name, self.runner_time.unwrap()),
+        match self.runner_time {
+            Some(_) => provider(self.race_game).format_submission(self, f)?,
+            // only completion races have finishers without a time
+            None => format_untimed(self, f)?,
+        }
+        // no game uses the optional number yet so it's only set for score races
+        match self.option_number {
+            Some(score) => write!(f, " - {} points", score),
+            None => Ok(()),
         }
     }
 }
@@ -84,13 +143,18 @@ pub struct NewSubmission {
     pub race_game: GameName,
     pub submission_datetime: NaiveDateTime,
     pub runner_name: String,
-    pub runner_time: Option<NaiveTime>,
     pub runner_collection: Option<u16>,
     // we will put some optional fields here just in case a future module uses them
     // or somebody wants to extend an existing game with, say, a bonk counter
     pub option_number: Option<u32>,
     pub option_text: Option<String>,
     pub runner_forfeit: bool,
+    pub pending: bool,
+    pub unranked: bool,
+    #[column_name = "runner_time_ms"]
+    pub runner_time: Option<RunTime>,
+    pub source_message_id: Option<u64>,
+    pub submission_text: Option<String>,
 }
 
 impl NewSubmission {
@@ -112,12 +176,18 @@ impl NewSubmission {
         self
     }
 
-    fn set_time(&mut self, time: Option<NaiveTime>) -> &mut Self {
+    fn set_time(&mut self, time: Option<RunTime>) -> &mut Self {
         self.runner_time = time;
 
         self
     }
 
+    fn set_unranked(&mut self, unranked: bool) -> &mut Self {
+        self.unranked = unranked;
+
+        self
+    }
+
     pub fn set_collection<T: Into<u16>>(&mut self, cr: Option<T>) -> &mut Self {
         self.runner_collection = cr.map(|cr| cr.into());
 
@@ -139,8 +209,8 @@ impl NewSubmission {
     pub fn set_game_info(
         &mut self,
         game: GameName,
-        submission_msg: &Vec<&str>,
-    ) -> Result<Self, BoxedError> {
+        submission_msg: &[&str],
+    ) -> Result<Self, MurahdahlaError> {
         // pass this off to a game-specific function defined in a game's module
         // this can fail if the message does not have correct amount or type of args
         // also we should be preventing a game that's not implemented from starting
@@ -148,14 +218,9 @@ impl NewSubmission {
         // with a non-mutable cloned Self since this will be the final building method
 
         self.race_game = game;
-        match game {
-            GameName::ALTTPR => Ok(z3r::game_info(self, submission_msg)?.clone()),
-            GameName::SMZ3 => Ok(smz3::game_info(self, submission_msg)?.clone()),
-            GameName::SMTotal => Ok(smtotal::game_info(self, submission_msg)?.clone()),
-            GameName::SMVARIA => Ok(smvaria::game_info(self, submission_msg)?.clone()),
-            GameName::Other => Ok(self.clone()),
-            _ => Err(anyhow!("Game not yet implemented").into()),
-        }
+        Ok(provider(game)
+            .parse_submission(self, submission_msg)?
+            .clone())
     }
 }
 
@@ -172,6 +237,10 @@ impl Default for NewSubmission {
             option_number: None,
             option_text: None,
             runner_forfeit: false,
+            pending: false,
+            unranked: false,
+            source_message_id: None,
+            submission_text: None,
         }
     }
 }
@@ -179,78 +248,210 @@ impl Default for NewSubmission {
 pub fn process_submission(
     msg: &Message,
     race: &AsyncRaceData,
-) -> Result<NewSubmission, BoxedError> {
+    group: &ChannelGroup,
+    claim_time: Option<RunTime>,
+) -> Result<NewSubmission, MurahdahlaError> {
+    let mut submission = build_submission(
+        msg.author.id.get(),
+        &msg.author.name,
+        &msg.content,
+        race,
+        &group.forfeit_words(),
+        claim_time,
+    )?;
+    submission.source_message_id = Some(msg.id.get());
+    submission.submission_text = Some(msg.content.clone());
+
+    Ok(submission)
+}
+
+pub fn build_submission(
+    runner_id: u64,
+    runner_name: &str,
+    content: &str,
+    race: &AsyncRaceData,
+    forfeit_words: &[&str],
+    // how long the runner's timer has been running, if they claimed the seed or
+    // started one with !startrun
+    claim_time: Option<RunTime>,
+) -> Result<NewSubmission, MurahdahlaError> {
     // in some cases this will return Ok despite not successfully inserting a submission
     // ie when a submission is malformed. the submitter is expected to know and recognize
     // that the submission was malformed when their message is deleted and they dont
     // have access to the leaderboard and spoilers channel
 
-    let mut maybe_submission_text: Vec<&str> = msg.content.as_str().split_whitespace().collect();
-    if maybe_submission_text.is_empty() {
-        return Err(anyhow!("Received submission with no text.").into());
-    }
-    // first check to see if the user has forfeited
-    // the length check here should short circuit so we don't have to worry
-    // about panicking if there's no text
-    if !maybe_submission_text.is_empty() && FORFEIT.iter().any(|&x| x == maybe_submission_text[0]) {
-        let ff_submission = forfeit(msg, race)?;
-        return Ok(ff_submission);
-    }
-
-    // lets start with a default submission struct and add in what can here. then we'll
-    // pass it to a game-specific function that will add its own info. when these
-    // rows are pulled from the db, each game will have its own submission formatter as
-    // well that knows which info that game has and how to display it
-
-    // remove backslashes because *some servers* use numbers as emotes
-    // we are also REMOVING the first element of the vector here
-    let maybe_time: &str = &maybe_submission_text.remove(0).replace('\\', "");
-    let time = match parse_variable_time(maybe_time) {
-        Ok(t) => t,
-        Err(e) => {
+    let parsed = parse_submission(content, forfeit_words)
+        .map_err(|e| anyhow!("Malformed submission from user \"{}\": {}", runner_name, e))?;
+    let (time, collection, extras) = match (parsed.entry, claim_time) {
+        (Entry::Forfeit, _) => {
+            let mut ff_submission = forfeit(runner_id, runner_name, race);
+            ff_submission.unranked = parsed.unranked;
+            return Ok(ff_submission);
+        }
+        // completion races are untimed and rank runners by when they sent "done"
+        (Entry::Finish { .. }, _) if race.race_sort == LeaderboardSort::Completion => {
+            return Err(anyhow!(
+                "Submission from user \"{}\" has a time but this race is untimed",
+                runner_name
+            )
+            .into())
+        }
+        // an unranked finish without a time would look like someone given spoiler access
+        (Entry::Done { .. }, _)
+            if race.race_sort == LeaderboardSort::Completion && parsed.unranked =>
+        {
+            return Err(anyhow!(
+                "User \"{}\" sent an unranked submission to an untimed race",
+                runner_name
+            )
+            .into())
+        }
+        (Entry::Done { collection, extras }, _)
+            if race.race_sort == LeaderboardSort::Completion =>
+        {
+            (None, collection, extras)
+        }
+        // claim and live races time runners themselves so a time typed in is never used
+        (Entry::Finish { .. }, _) if race.race_claim || race.race_live_at.is_some() => {
+            return Err(anyhow!(
+                "Submission from user \"{}\" has a time but this race is timed by the bot",
+                runner_name
+            )
+            .into())
+        }
+        (
+            Entry::Finish {
+                time,
+                collection,
+                extras,
+            },
+            _,
+        ) => (Some(time), collection, extras),
+        (Entry::Done { collection, extras }, Some(time)) => (Some(time), collection, extras),
+        (Entry::Done { .. }, None) if race.race_live_at.is_some() => {
+            return Err(anyhow!(
+                "User \"{}\" sent \"done\" before the live race started",
+                runner_name
+            )
+            .into())
+        }
+        (Entry::Done { .. }, None) if race.race_claim => {
+            return Err(anyhow!(
+                "User \"{}\" submitted without claiming the seed first",
+                runner_name
+            )
+            .into())
+        }
+        (Entry::Done { .. }, None) => {
+            return Err(anyhow!(
+                "Submission from user \"{}\" has no time and no timer was started with !startrun",
+                runner_name
+            )
+            .into())
+        }
+    };
+    // the game, template or sort decides what the words after the time mean so they
+    // get them back in the order they were sent
+    let mut maybe_submission_text = collection.into_iter().chain(extras).collect::<Vec<&str>>();
+    let unranked = parsed.unranked;
+
+    // score races take the score off the end so the game still gets what it expects
+    let score: Option<u32> = match race.race_sort {
+        LeaderboardSort::Score => match maybe_submission_text.pop().map(str::parse::<u32>) {
+            Some(Ok(s)) => Some(s),
+            _ => {
+                return Err(anyhow!(
+                    "Submission from user \"{}\" is missing a score",
+                    runner_name
+                )
+                .into())
+            }
+        },
+        _ => None,
+    };
+
+    // races started from a template or an added game check their fields here instead
+    // of in the game
+    let (takes_collection, fields) = match &race.race_fields {
+        Some(f) => split_collection_field(f),
+        None => (false, None),
+    };
+    let field_collection: Option<u16> = match takes_collection {
+        true if maybe_submission_text.is_empty() => {
             return Err(anyhow!(
-                "Malformed time from user \"{}\": {} - {}",
-                &msg.author.name,
-                &maybe_time,
-                e
+                "Submission from user \"{}\" is missing a collection rate",
+                runner_name
             )
-            .into());
+            .into())
         }
+        true => Some(
+            maybe_submission_text
+                .remove(0)
+                .parse::<u16>()
+                .map_err(|e| anyhow!("Error processing submission for {}: {}", runner_name, e))?,
+        ),
+        false => None,
+    };
+    let field_text: Option<String> = match fields {
+        Some(fields) => Some(
+            parse_template_fields(fields, &maybe_submission_text)
+                .map_err(|e| anyhow!("Error processing submission for {}: {}", runner_name, e))?,
+        ),
+        None => None,
     };
 
-    let submission = NewSubmission::default()
-        .set_runner_id(msg.author.id)
+    let mut submission = NewSubmission::default()
+        .set_runner_id(runner_id)
         .set_race_id(race.race_id)
-        .name(&msg.author.name)
-        .set_time(Some(time))
+        .name(runner_name)
+        .set_time(time)
+        .set_optional_number(score)
+        .set_unranked(unranked)
         .set_game_info(race.race_game, &maybe_submission_text)
-        .map_err(|e| {
-            anyhow!(
-                "Error processing submission for {}: {}",
-                &msg.author.name,
-                e
-            )
-        })?;
+        .map_err(|e| anyhow!("Error processing submission for {}: {}", runner_name, e))?;
+    if field_text.is_some() {
+        submission.option_text = field_text;
+    }
+    if field_collection.is_some() {
+        submission.runner_collection = field_collection;
+    }
 
     Ok(submission)
 }
 
 #[inline]
-fn forfeit(msg: &Message, race: &AsyncRaceData) -> Result<NewSubmission> {
-    let submission = NewSubmission {
-        runner_id: *msg.author.id.as_u64(),
+fn forfeit(runner_id: u64, runner_name: &str, race: &AsyncRaceData) -> NewSubmission {
+    NewSubmission {
+        runner_id,
         race_id: race.race_id,
         race_game: race.race_game,
         submission_datetime: Utc::now().naive_utc(),
-        runner_name: msg.author.name.clone(),
+        runner_name: runner_name.to_owned(),
         runner_time: None,
         runner_collection: None,
         option_number: None,
         option_text: None,
         runner_forfeit: true,
-    };
+        pending: false,
+        unranked: false,
+        source_message_id: None,
+        submission_text: None,
+    }
+}
 
-    Ok(submission)
+pub fn access_grant(runner_id: u64, runner_name: &str, race: &AsyncRaceData) -> NewSubmission {
+    // someone given spoiler access without running the seed. they're recorded as an
+    // unranked submission with no time so they lose access when the race stops like
+    // everyone else, but they never show up on the leaderboard.
+    NewSubmission {
+        runner_id,
+        race_id: race.race_id,
+        race_game: race.race_game,
+        submission_datetime: Utc::now().naive_utc(),
+        runner_name: runner_name.to_owned(),
+        unranked: true,
+        ..Default::default()
+    }
 }
 
 pub async fn build_leaderboard(
@@ -258,54 +459,87 @@ pub async fn build_leaderboard(
     group: &ChannelGroup,
     race: &AsyncRaceData,
     target: ChannelType,
-) -> Result<(), BoxedError> {
+) -> Result<(), MurahdahlaError> {
     // the caller needs to have checked if there is currently an active race
     // which means we have a leaderboard message to work with
+    let highlight_secs: i64 = get_setting_parsed(
+        ctx,
+        GuildId::from(group.server_id),
+        ServerSetting::HighlightSecs,
+    )
+    .await
+    .unwrap_or(get_config(ctx).await.leaderboard_highlight_secs);
+    let pool = get_db_pool(ctx).await;
+    let locks = {
+        let data = ctx.data.read().await;
+        Arc::clone(
+            &data
+                .get::<LeaderboardQueueContainer>()
+                .expect("No leaderboard queue in share map")
+                .locks,
+        )
+    };
+    let race_lock = Arc::clone(locks.lock().unwrap().entry(race.race_id).or_default());
+    let result = {
+        let _guard = race_lock.lock().await;
+        post_leaderboard(&*ctx.http, &pool, group, race, target, highlight_secs).await
+    };
+    // the map and this function are the only holders when nothing else is waiting
+    let mut locks = locks.lock().unwrap();
+    if Arc::strong_count(&race_lock) == 2 {
+        locks.remove(&race.race_id);
+    }
+
+    result
+}
+
+pub async fn post_leaderboard(
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    target: ChannelType,
+    highlight_secs: i64,
+) -> Result<(), MurahdahlaError> {
     use crate::schema::messages::columns::*;
-    use crate::schema::submissions::columns::runner_forfeit;
 
     let target_channel_id: u64 = match target {
         ChannelType::Leaderboard => group.leaderboard,
         ChannelType::Submission => group.submission,
         _ => return Err(anyhow!("Did not specify a target channel to put leaderboard in").into()),
     };
-    let conn = get_connection(ctx).await;
-    // collect a vector of submissions for this race and sort it
-    let mut leaderboard: Vec<Submission> = Submission::belonging_to(race)
-        .filter(runner_forfeit.eq(false))
-        .load::<Submission>(&conn)?;
-    leaderboard.sort_by(|a, b| {
-        b.runner_time
-            .cmp(&a.runner_time)
-            .reverse()
-            .then(b.runner_collection.cmp(&a.runner_collection).reverse())
-            .then(b.option_number.cmp(&a.option_number).reverse())
-    });
+    let (leaderboard, mut lb_posts_data) = {
+        let conn = pool.get()?;
+        let leaderboard = load_leaderboard(&conn, group, race)?;
+        let lb_posts_data: Vec<BotMessage> = BotMessage::belonging_to(race)
+            .filter(channel_type.eq(target))
+            .load::<BotMessage>(&conn)?;
+        (leaderboard, lb_posts_data)
+    };
+    let highlight_window = Duration::seconds(highlight_secs);
     let time_now = Utc::now().naive_utc();
-    let mut lb_posts_data: Vec<BotMessage> = BotMessage::belonging_to(race)
-        .filter(channel_type.eq(target))
-        .load::<BotMessage>(&conn)?;
     lb_posts_data.sort_by(|a, b| b.message_datetime.cmp(&a.message_datetime).reverse());
-    let leaderboard_header = race.leaderboard_string();
-    // approximating how much to allocate here
-    let mut lb_string = String::with_capacity(leaderboard.len() * 40 + 150);
-    let mut count: u32 = 1;
-    lb_string.push_str(format!("{}\n", leaderboard_header).as_str());
-    leaderboard.iter().for_each(|s| {
-        // we italicize more recent submissions, but only in the leaderboard channel
-        if (time_now - s.submission_datetime < Duration::seconds(21600i64))
-            && target == ChannelType::Leaderboard
-        {
-            lb_string.push_str(format!("\n{}) *{}*", count, &s).as_str());
-            count += 1;
-        } else {
-            lb_string.push_str(format!("\n{}) {}", count, &s).as_str());
-            count += 1;
+    // we italicize more recent submissions, but only in the leaderboard channel
+    let highlight_cutoff = match target {
+        ChannelType::Leaderboard => Some(time_now - highlight_window),
+        _ => None,
+    };
+    // blind races only show times once they're stopped, and the final results go in the
+    // submission channel
+    let header = race.leaderboard_string(group.max_settings_chars());
+    let lb_string = match target {
+        ChannelType::Leaderboard if race.race_blind => {
+            blind_leaderboard_text(&header, &leaderboard)
         }
-    });
+        ChannelType::Submission if race.race_shuffle => {
+            shuffled_results_text(&header, &leaderboard)
+        }
+        _ => leaderboard_text(&header, &leaderboard, highlight_cutoff),
+    };
 
     fill_leaderboard(
-        ctx,
+        ops,
+        pool,
         &mut lb_posts_data,
         &lb_string,
         group,
@@ -317,18 +551,257 @@ pub async fn build_leaderboard(
     Ok(())
 }
 
+pub fn load_leaderboard(
+    conn: &PooledConn,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+) -> Result<Vec<Submission>> {
+    load_leaderboard_sorted(conn, group, race, scoring_for(race))
+}
+
+pub fn load_leaderboard_sorted(
+    conn: &PooledConn,
+    group: &ChannelGroup,
+    race: &AsyncRaceData,
+    scoring: &dyn RaceScoring,
+) -> Result<Vec<Submission>> {
+    // the race's ranked and unranked submissions in the given order with names ready to
+    // be posted
+    use crate::schema::submissions::columns::{pending, runner_forfeit, runner_time_ms, unranked};
+
+    let query = Submission::belonging_to(race)
+        .filter(runner_forfeit.eq(false))
+        .filter(pending.eq(false))
+        .into_boxed();
+    // submissions without a time are people given spoiler access, except in completion
+    // races where nobody has a time and those people are the only unranked ones
+    let query = match race.race_sort {
+        LeaderboardSort::Completion => query.filter(unranked.eq(false)),
+        _ => query.filter(runner_time_ms.is_not_null()),
+    };
+    let mut leaderboard: Vec<Submission> = query.load::<Submission>(conn)?;
+    let flairs = match group.show_flair {
+        true => {
+            let runner_ids: Vec<u64> = leaderboard.iter().map(|s| s.runner_id).collect();
+            get_flairs(conn, &runner_ids)?
+        }
+        false => HashMap::new(),
+    };
+    scoring.sort(&mut leaderboard);
+    leaderboard.iter_mut().for_each(|s| {
+        s.runner_name = render_runner_name(&s.runner_name, group);
+        if let Some(f) = flairs.get(&s.runner_id) {
+            s.runner_name.push(' ');
+            s.runner_name.push_str(&escape_markdown(f.chars()));
+        }
+    });
+
+    Ok(leaderboard)
+}
+
+pub fn render_runner_name(name: &str, group: &ChannelGroup) -> String {
+    // names go straight into the leaderboard posts so escape anything discord would
+    // treat as markdown and break up mentions so a name can't ping anyone
+    let mut chars: Vec<char> = match group.ascii_names {
+        true => name.chars().filter(char::is_ascii).collect(),
+        false => name.chars().collect(),
+    };
+    if let Some(max_len) = group.max_name_length {
+        chars.truncate(max_len as usize);
+    }
+    let mut rendered = escape_markdown(chars.into_iter());
+    if rendered.trim().is_empty() {
+        rendered = String::from("(unnamed runner)");
+    }
+
+    rendered
+}
+
+pub(crate) fn escape_markdown(chars: impl Iterator<Item = char>) -> String {
+    let mut escaped = String::new();
+    for c in chars.filter(|c| !c.is_control()) {
+        match c {
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '<' | ':' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // a zero width space after the @ stops @everyone and friends from working
+            '@' => escaped.push_str("@\u{200B}"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+pub fn leaderboard_text(
+    header: &str,
+    leaderboard: &[Submission],
+    highlight_cutoff: Option<NaiveDateTime>,
+) -> String {
+    // the leaderboard is rebuilt on every submission so write everything straight into
+    // one buffer instead of formatting each line into its own string first
+    // approximating how much to allocate here
+    let mut lb_string = String::with_capacity(leaderboard.len() * 40 + header.len() + 2);
+    lb_string.push_str(header);
+    lb_string.push('\n');
+    let highlighted = |s: &Submission| match highlight_cutoff {
+        Some(cutoff) => s.submission_datetime > cutoff,
+        None => false,
+    };
+    for (count, s) in (1u32..).zip(leaderboard.iter().filter(|s| !s.unranked)) {
+        // writing to a string can't fail
+        let _ = match highlighted(s) {
+            true => write!(lb_string, "\n{}) *{}*", count, s),
+            false => write!(lb_string, "\n{}) {}", count, s),
+        };
+    }
+    // unranked submissions go underneath without a place
+    let mut unranked = leaderboard.iter().filter(|s| s.unranked).peekable();
+    if unranked.peek().is_some() {
+        lb_string.push_str("\n\nUnranked:");
+    }
+    for s in unranked {
+        let _ = match highlighted(s) {
+            true => write!(lb_string, "\n- *{}*", s),
+            false => write!(lb_string, "\n- {}", s),
+        };
+    }
+
+    lb_string
+}
+
+pub fn blind_leaderboard_text(header: &str, leaderboard: &[Submission]) -> String {
+    let finished = leaderboard.len();
+    let plural = match finished {
+        1 => "runner has",
+        _ => "runners have",
+    };
+
+    format!(
+        "{}\n\n{} {} finished. {}",
+        header, finished, plural, BLIND_NOTE
+    )
+}
+
+pub fn shuffled_results_text(header: &str, leaderboard: &[Submission]) -> String {
+    // for races where people guess the standings. runners who finished are listed in a
+    // random order with no places or times.
+    let mut names = leaderboard
+        .iter()
+        .filter(|s| !s.unranked)
+        .map(|s| s.runner_name.as_str())
+        .collect::<Vec<&str>>();
+    names.sort_by_cached_key(|_| Uuid::new_v4());
+    let mut lb_string = format!("{}\n\nFinished, in random order:", header);
+    for name in names {
+        lb_string.push_str("\n- ");
+        lb_string.push_str(name);
+    }
+
+    lb_string
+}
+
+pub async fn queue_leaderboard(ctx: &Context, group: &ChannelGroup, race: &AsyncRaceData) {
+    let (permits, running) = {
+        let data = ctx.data.read().await;
+        let queue = data
+            .get::<LeaderboardQueueContainer>()
+            .expect("No leaderboard queue in share map");
+        (Arc::clone(&queue.permits), Arc::clone(&queue.running))
+    };
+    {
+        let mut running = running.lock().unwrap();
+        if let Some(rerun) = running.get_mut(&race.race_id) {
+            *rerun = true;
+            return;
+        }
+        running.insert(race.race_id, false);
+    }
+
+    let ctx = ctx.clone();
+    let group = group.clone();
+    let race = race.clone();
+    tokio::spawn(async move {
+        loop {
+            {
+                // the semaphore is never closed so this can't fail
+                let _permit = permits.acquire().await.unwrap();
+                if let Err(e) =
+                    build_leaderboard(&ctx, &group, &race, ChannelType::Leaderboard).await
+                {
+                    warn!(
+                        "Error rebuilding leaderboard for race {}: {}",
+                        race.race_id, e
+                    );
+                    message_maintenance_user(&ctx, e).await;
+                }
+            }
+            let mut running = running.lock().unwrap();
+            match running.get(&race.race_id) {
+                Some(true) => {
+                    running.insert(race.race_id, false);
+                }
+                _ => {
+                    running.remove(&race.race_id);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+pub async fn refresh_leaderboards_task(ctx: Context) {
+    // periodically rebuild every active leaderboard so missed edits or posts someone
+    // tampered with fix themselves without waiting for a !refresh. the interval is read
+    // each time around so it can be changed with !reloadconfig. this context dies with
+    // its client, so stop once a rotated token has replaced it.
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    loop {
+        let refresh_secs = get_config(&ctx).await.leaderboard_refresh_secs;
+        if refresh_secs == 0 {
+            tokio::time::sleep(std::time::Duration::from_secs(REFRESH_DISABLED_CHECK_SECS)).await;
+            continue;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(refresh_secs)).await;
+        if CLIENT_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if !is_leader() {
+            continue;
+        }
+
+        let groups: Vec<ChannelGroup> = {
+            let data = ctx.data.read().await;
+            data.get::<GroupContainer>()
+                .expect("No group container in share map")
+                .values()
+                .cloned()
+                .collect()
+        };
+        let conn = get_connection(&ctx).await;
+        for group in groups.iter() {
+            if let Some(race) = get_maybe_active_race(&conn, group) {
+                queue_leaderboard(&ctx, group, &race).await;
+            }
+        }
+    }
+}
+
 async fn fill_leaderboard(
-    ctx: &Context,
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
     mut lb_posts_data: &mut Vec<BotMessage>,
-    lb_string: &String,
+    lb_string: &str,
     group: &ChannelGroup,
     target: ChannelType,
     target_channel_id: u64,
-) -> Result<(), BoxedError> {
-    let necessary_posts: usize = lb_string.len() / 2000 + 1;
-    if necessary_posts > lb_posts_data.len() {
+) -> Result<(), MurahdahlaError> {
+    let posts = split_leaderboard(lb_string);
+    while posts.len() > lb_posts_data.len() {
         lb_posts_data = resize_leaderboard(
-            ctx,
+            ops,
+            pool,
             group.server_id,
             target,
             target_channel_id,
@@ -336,64 +809,88 @@ async fn fill_leaderboard(
         )
         .await?;
     }
-    // fill buffer then send the post until there's no more
-    let mut post_buffer = String::with_capacity(2000);
-    let mut post_iterator = lb_posts_data.iter_mut().peekable();
-    let mut submission_iterator = lb_string
-        .split('\n')
-        .collect::<Vec<&str>>()
-        .into_iter()
-        .peekable();
-
-    loop {
-        if post_iterator.peek().is_none() {
-            return Err(anyhow!("Ran out of space for leaderboard").into());
+    // keep going when a post fails so one bad edit doesn't leave the rest of the
+    // leaderboard stale. the failures are reported together at the end.
+    let channel = ChannelId::from(target_channel_id);
+    let mut failures: Vec<String> = Vec::new();
+    for (content, post_data) in posts.iter().zip(lb_posts_data.iter()) {
+        let edit = edit_branded(
+            ops,
+            group,
+            channel,
+            MessageId::from(post_data.message_id),
+            content,
+        )
+        .await;
+        if let Err(e) = edit {
+            failures.push(format!("message {}: {}", post_data.message_id, e));
         }
+    }
 
-        match submission_iterator.peek() {
-            Some(line) => {
-                if line.len() + post_buffer.len() <= 2000 {
-                    post_buffer
-                        .push_str(format!("\n{}", submission_iterator.next().unwrap()).as_str())
-                } else if line.len() + post_buffer.len() > 2000 {
-                    let mut post = ctx
-                        .http
-                        .get_message(target_channel_id, post_iterator.next().unwrap().message_id)
-                        .await?;
-                    post.edit(ctx, |x| x.content(&post_buffer)).await?;
-                    post_buffer.clear();
-                }
-            }
-            None => {
-                let mut post = ctx
-                    .http
-                    .get_message(target_channel_id, post_iterator.next().unwrap().message_id)
-                    .await?;
-                post.edit(ctx, |x| x.content(post_buffer)).await?;
-                break;
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Failed to update {} of {} leaderboard posts in channel {}: {}",
+            failures.len(),
+            posts.len(),
+            target_channel_id,
+            failures.join("; ")
+        )
+        .into()),
+    }
+}
+
+pub fn split_leaderboard(lb_string: &str) -> Vec<String> {
+    // splits the leaderboard into posts on line boundaries. discord's limit is in
+    // characters rather than bytes and the newlines between lines count towards it.
+    let mut posts: Vec<String> = Vec::new();
+    let mut post = String::with_capacity(MAX_POST_CHARS);
+    let mut post_chars: usize = 0;
+    for line in lb_string.split('\n') {
+        let line_chars = line.chars().count();
+        if post_chars > 0 && post_chars + 1 + line_chars > MAX_POST_CHARS {
+            posts.push(std::mem::take(&mut post));
+            post_chars = 0;
+        }
+        if post_chars > 0 {
+            post.push('\n');
+            post_chars += 1;
+        }
+        // a single line should never come close to the limit but if it does we have to
+        // break it up rather than have discord reject the post
+        for c in line.chars() {
+            if post_chars == MAX_POST_CHARS {
+                posts.push(std::mem::take(&mut post));
+                post_chars = 0;
             }
-        };
+            post.push(c);
+            post_chars += 1;
+        }
+    }
+    if post_chars > 0 || posts.is_empty() {
+        posts.push(post);
     }
 
-    Ok(())
+    posts
 }
 
 async fn resize_leaderboard<'a>(
-    ctx: &'a Context,
+    ops: &dyn DiscordOps,
+    pool: &MysqlPool,
     this_server_id: u64,
     target: ChannelType,
     target_channel_id: u64,
     lb_posts: &'a mut Vec<BotMessage>,
-) -> Result<&'a mut Vec<BotMessage>, BoxedError> {
+) -> Result<&'a mut Vec<BotMessage>, MurahdahlaError> {
     use crate::schema::messages::dsl::*;
     // we only ever need one more post than we have to hold all submissions
-    let conn = get_connection(ctx).await;
-    let new_message: Message = ChannelId::from(target_channel_id)
-        .say(&ctx, "Placeholder")
+    let new_message = ops
+        .say(ChannelId::from(target_channel_id), "Placeholder")
         .await?;
     let new_msg_data =
-        BotMessage::from_serenity_msg(&new_message, this_server_id, lb_posts[0].race_id, target);
+        BotMessage::from_posted(&new_message, this_server_id, lb_posts[0].race_id, target);
 
+    let conn = pool.get()?;
     diesel::insert_into(messages)
         .values(&new_msg_data)
         .execute(&conn)?;
@@ -402,42 +899,174 @@ async fn resize_leaderboard<'a>(
     Ok(lb_posts)
 }
 
-pub fn parse_variable_time(maybe_time: &str) -> Result<NaiveTime> {
-    // technically NaiveTime represents a time of day but it works for our purposes
-    let mut time_string = String::with_capacity(9);
-    let split_time = maybe_time.split(':');
-    match split_time.count() {
-        0 => return Err(anyhow!("Empty submission time")),
-        1 => {
-            time_string.push_str("00:00:");
-            time_string.push_str(maybe_time);
-        }
-        2 => {
-            time_string.push_str("00:");
-            time_string.push_str(maybe_time);
-        }
-        3 => {
-            time_string.push_str(maybe_time);
-        }
-        _ => return Err(anyhow!("Tried to parse malformed time")),
-    };
+// a runner's time, stored as milliseconds. unlike NaiveTime this isn't a time of day so
+// it can go past 24 hours.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+)]
+#[sql_type = "Unsigned<BigInt>"]
+pub struct RunTime(u64);
+
+impl RunTime {
+    pub fn from_millis(ms: u64) -> Self {
+        RunTime(ms)
+    }
+
+    pub fn millis(self) -> u64 {
+        self.0
+    }
+}
+
+impl<DB> FromSql<Unsigned<BigInt>, DB> for RunTime
+where
+    DB: Backend,
+    u64: FromSql<Unsigned<BigInt>, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        u64::from_sql(bytes).map(RunTime)
+    }
+}
+
+impl<DB> ToSql<Unsigned<BigInt>, DB> for RunTime
+where
+    DB: Backend,
+    u64: ToSql<Unsigned<BigInt>, DB>,
+{
+    fn to_sql<W: io::Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        self.0.to_sql(out)
+    }
+}
 
-    NaiveTime::parse_from_str(&time_string, "%H:%M:%S").map_err(|e| anyhow!("{}", e))
+impl fmt::Display for RunTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0 / 1000;
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        )?;
+        match self.0 % 1000 {
+            0 => Ok(()),
+            ms => write!(f, ".{:03}", ms),
+        }
+    }
 }
 
 pub async fn write_submission_add_role(
-    ctx: &Context,
+    pool: &MysqlPool,
     s: &NewSubmission,
-    role_fut: impl Future<Output = Result<(), BoxedError>>,
-) -> Result<(), BoxedError> {
+    role_fut: impl Future<Output = Result<(), MurahdahlaError>>,
+) -> Result<bool, MurahdahlaError> {
+    // returns false when the message this came from already has a submission stored, in
+    // which case there's nothing left to do for it
     use crate::schema::submissions::dsl::*;
 
-    let conn = get_connection(ctx).await;
     match role_fut.await {
         Ok(_) => (),
         Err(e) => return Err(anyhow!("Could not add role: {}", e).into()),
     }
-    diesel::insert_into(submissions).values(s).execute(&conn)?;
+    let conn = pool.get()?;
+    match diesel::insert_into(submissions).values(s).execute(&conn) {
+        Ok(_) => Ok(true),
+        Err(e) if is_duplicate_key(&e, SOURCE_MESSAGE_INDEX) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{discord::submission_parser::parse_variable_time, testing::test_submission};
+
+    fn line(c: char, len: usize) -> String {
+        std::iter::repeat(c).take(len).collect()
+    }
+
+    #[test]
+    fn post_at_limit_fits_in_one_post() {
+        // 999 + the newline + 1000 is exactly the limit
+        let lb = format!("{}\n{}", line('a', 999), line('b', 1000));
+        let posts = split_leaderboard(&lb);
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].chars().count(), MAX_POST_CHARS);
+    }
+
+    #[test]
+    fn newline_counts_towards_limit() {
+        let lb = format!("{}\n{}", line('a', 1000), line('b', 1000));
+        let posts = split_leaderboard(&lb);
+        assert_eq!(posts, vec![line('a', 1000), line('b', 1000)]);
+    }
+
+    #[test]
+    fn limit_is_counted_in_chars_not_bytes() {
+        // two byte characters, so this is well over 2000 bytes but exactly 2000 chars
+        let lb = format!("{}\n{}", line('é', 999), line('é', 1000));
+        assert!(lb.len() > MAX_POST_CHARS);
+        assert_eq!(split_leaderboard(&lb).len(), 1);
+
+        let lb = format!("{}\n{}", line('é', 1000), line('é', 1000));
+        assert_eq!(split_leaderboard(&lb).len(), 2);
+    }
+
+    #[test]
+    fn overlong_line_is_broken_up() {
+        let posts = split_leaderboard(&line('a', MAX_POST_CHARS * 2 + 1));
+        assert_eq!(posts.len(), 3);
+        assert!(posts.iter().all(|p| p.chars().count() <= MAX_POST_CHARS));
+    }
+
+    #[test]
+    fn run_times_display_like_before() {
+        assert_eq!(
+            parse_variable_time("1:30:00").unwrap().to_string(),
+            "01:30:00"
+        );
+        assert_eq!(
+            parse_variable_time("26:01:02.3").unwrap().to_string(),
+            "26:01:02.300"
+        );
+        assert_eq!(RunTime::from_millis(1_042).to_string(), "00:00:01.042");
+    }
+
+    #[test]
+    fn lines_are_split_on_boundaries() {
+        let lines: Vec<String> = (0..300)
+            .map(|i| format!("{}) runner{} - 1:30:00", i, i))
+            .collect();
+        let lb = lines.join("\n");
+        let posts = split_leaderboard(&lb);
+        assert!(posts.len() > 1);
+        assert!(posts.iter().all(|p| p.chars().count() <= MAX_POST_CHARS));
+        assert_eq!(posts.join("\n"), lb);
+    }
+
+    #[test]
+    fn shuffled_results_have_no_places_or_times() {
+        let runner = |name: &str, unranked: bool| Submission {
+            runner_name: name.to_owned(),
+            unranked,
+            runner_time: Some(RunTime::from_millis(3_723_000)),
+            ..test_submission()
+        };
+        let leaderboard = vec![runner("a", false), runner("b", false), runner("c", true)];
+        let text = shuffled_results_text("Header", &leaderboard);
+        assert!(text.starts_with("Header\n\nFinished, in random order:"));
+        let mut names: Vec<&str> = text.lines().filter_map(|l| l.strip_prefix("- ")).collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["a", "b"]);
+        assert!(!text.contains("01:02:03"));
+    }
 }
@@ -0,0 +1,198 @@
+use anyhow::{anyhow, Result};
+use diesel::{prelude::*, replace_into};
+
+use crate::{helpers::*, schema::game_templates};
+
+pub const MAX_TEMPLATE_NAME_CHARS: usize = 32;
+pub const MAX_TEMPLATE_FIELDS: usize = 5;
+pub const MAX_FORMAT_CHARS: usize = 300;
+// where the text given to the start command goes in a template's format
+pub const TEXT_PLACEHOLDER: &str = "{text}";
+// stands for the collection rate in a race's fields. added games put it first when
+// runners submit one, templates can't use it.
+pub const COLLECTION_FIELD: &str = "#collection";
+// submission fields are kept in option_text, which is a tinytext
+const MAX_FIELD_TEXT_CHARS: usize = 255;
+
+// a saved setup for a game the bot doesn't know about. races started with one get a
+// consistent header and runners have to fill in the template's fields after their time.
+#[derive(Debug, Clone, Insertable, Queryable)]
+#[table_name = "game_templates"]
+pub struct GameTemplate {
+    pub server_id: u64,
+    pub template_name: String,
+    // comma separated, empty if runners only submit a time
+    pub template_fields: String,
+    pub template_format: String,
+}
+
+impl GameTemplate {
+    pub fn new(server_id: u64, name: &str, fields: &str, format: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty()
+            || name.chars().count() > MAX_TEMPLATE_NAME_CHARS
+            || name.contains(char::is_whitespace)
+        {
+            return Err(anyhow!(
+                "Template names must be 1-{} characters with no whitespace",
+                MAX_TEMPLATE_NAME_CHARS
+            ));
+        }
+        let fields = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .collect::<Vec<&str>>();
+        if fields.iter().any(|f| f.starts_with('#')) {
+            return Err(anyhow!("Template fields can't start with \"#\""));
+        }
+        if fields.len() > MAX_TEMPLATE_FIELDS {
+            return Err(anyhow!(
+                "Templates can have at most {} fields",
+                MAX_TEMPLATE_FIELDS
+            ));
+        }
+        let format = format.trim();
+        if format.is_empty()
+            || format.chars().count() > MAX_FORMAT_CHARS
+            || format.chars().any(char::is_control)
+        {
+            return Err(anyhow!(
+                "Template formats must be 1-{} characters on one line",
+                MAX_FORMAT_CHARS
+            ));
+        }
+
+        Ok(GameTemplate {
+            server_id,
+            template_name: name,
+            template_fields: fields.join(","),
+            template_format: format.to_owned(),
+        })
+    }
+
+    pub fn render(&self, text: &str) -> String {
+        // a format without the placeholder is used as is
+        self.template_format.replace(TEXT_PLACEHOLDER, text.trim())
+    }
+
+    pub fn fields(&self) -> Option<String> {
+        match self.template_fields.is_empty() {
+            true => None,
+            false => Some(self.template_fields.clone()),
+        }
+    }
+}
+
+pub fn split_collection_field(fields: &str) -> (bool, Option<&str>) {
+    // the rest of the fields, if there are any, are parsed like a template's
+    match fields.strip_prefix(COLLECTION_FIELD) {
+        Some(rest) => match rest.trim_start_matches(',') {
+            "" => (true, None),
+            r => (true, Some(r)),
+        },
+        None => (false, Some(fields)),
+    }
+}
+
+pub fn parse_template_fields(fields: &str, values: &[&str]) -> Result<String> {
+    // each field takes one word except the last, which takes whatever is left, so the
+    // last field can be something like a comment
+    let fields = fields.split(',').collect::<Vec<&str>>();
+    if values.len() < fields.len() {
+        return Err(anyhow!(
+            "Submissions for this race need {} after the time",
+            fields.join(", ")
+        ));
+    }
+    let last = fields.len() - 1;
+    let field_text = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match i == last {
+            true => format!("{}: {}", f, values[i..].join(" ")),
+            false => format!("{}: {}", f, values[i]),
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    if field_text.chars().count() > MAX_FIELD_TEXT_CHARS {
+        return Err(anyhow!("Submission fields are too long"));
+    }
+
+    Ok(field_text)
+}
+
+pub fn set_template(conn: &PooledConn, template: &GameTemplate) -> Result<()> {
+    replace_into(game_templates::table)
+        .values(template)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn remove_template(conn: &PooledConn, this_server_id: u64, name: &str) -> Result<()> {
+    use crate::schema::game_templates::columns::*;
+
+    let removed = diesel::delete(
+        game_templates::table
+            .filter(server_id.eq(this_server_id))
+            .filter(template_name.eq(name.to_lowercase())),
+    )
+    .execute(conn)?;
+    match removed {
+        0 => Err(anyhow!("No template named \"{}\"", name)),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_template(conn: &PooledConn, this_server_id: u64, name: &str) -> Result<GameTemplate> {
+    use crate::schema::game_templates::columns::*;
+
+    game_templates::table
+        .filter(server_id.eq(this_server_id))
+        .filter(template_name.eq(name.to_lowercase()))
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("No template named \"{}\"", name))
+}
+
+pub fn get_templates(conn: &PooledConn, this_server_id: u64) -> Result<Vec<GameTemplate>> {
+    use crate::schema::game_templates::columns::*;
+
+    let templates = game_templates::table
+        .filter(server_id.eq(this_server_id))
+        .order(template_name.asc())
+        .load(conn)?;
+
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_are_validated() {
+        let t = GameTemplate::new(1, "SMB3R", " rank, seed ,", "SMB3 Rando - {text}").unwrap();
+        assert_eq!(t.template_name, "smb3r");
+        assert_eq!(t.fields().as_deref(), Some("rank,seed"));
+        assert_eq!(t.render(" hard mode "), "SMB3 Rando - hard mode");
+        assert!(GameTemplate::new(1, "", "", "x").is_err());
+        assert!(GameTemplate::new(1, "two words", "", "x").is_err());
+        assert!(GameTemplate::new(1, "t", "a,b,c,d,e,f", "x").is_err());
+        assert!(GameTemplate::new(1, "t", "", "").is_err());
+        assert!(GameTemplate::new(1, "t", "", "x")
+            .unwrap()
+            .fields()
+            .is_none());
+    }
+
+    #[test]
+    fn submission_fields() {
+        assert_eq!(
+            parse_template_fields("rank,comment", &["3", "clean", "run"]).unwrap(),
+            "rank: 3, comment: clean run"
+        );
+        assert!(parse_template_fields("rank,comment", &["3"]).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    builder::CreateMessage,
+    client::Context,
+    model::{channel::Message, user::User},
+    prelude::*,
+};
+
+use crate::config::get_config;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Attempt {
+    Allowed,
+    // first is only true the first time someone goes over in a window so they're told
+    // once instead of for every message
+    Throttled { first: bool },
+}
+
+// recent messages from each runner in submission channels so one person can't flood a
+// channel with messages that each have to be parsed, deleted and answered
+#[derive(Debug, Default)]
+pub struct SubmissionThrottle {
+    attempts: HashMap<u64, VecDeque<Instant>>,
+}
+
+impl SubmissionThrottle {
+    pub fn attempt(
+        &mut self,
+        runner_id: u64,
+        now: Instant,
+        limit: u32,
+        window: Duration,
+    ) -> Attempt {
+        if limit == 0 {
+            return Attempt::Allowed;
+        }
+        self.attempts.retain(|_, times| {
+            while times
+                .front()
+                .map_or(false, |t| now.duration_since(*t) >= window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let times = self.attempts.entry(runner_id).or_default();
+        let before = times.len();
+        // throttled messages count too so someone who keeps spamming stays throttled
+        times.push_back(now);
+        match before.cmp(&(limit as usize)) {
+            std::cmp::Ordering::Less => Attempt::Allowed,
+            std::cmp::Ordering::Equal => Attempt::Throttled { first: true },
+            std::cmp::Ordering::Greater => Attempt::Throttled { first: false },
+        }
+    }
+}
+
+pub struct ThrottleContainer;
+
+impl TypeMapKey for ThrottleContainer {
+    type Value = SubmissionThrottle;
+}
+
+pub async fn check_throttle(ctx: &Context, msg: &Message) -> Attempt {
+    let config = get_config(ctx).await;
+    let attempt = {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<ThrottleContainer>()
+            .expect("No submission throttle in share map")
+            .attempt(
+                msg.author.id.get(),
+                Instant::now(),
+                config.submission_attempt_limit,
+                Duration::from_secs(config.submission_attempt_window_secs),
+            )
+    };
+    if attempt == (Attempt::Throttled { first: true }) {
+        let text = format!(
+            "You're sending messages to a submission channel too quickly, so your last one \
+            wasn't counted. Please wait {} seconds and try again.",
+            config.submission_attempt_window_secs
+        );
+        if let Err(e) = msg
+            .author
+            .direct_message(ctx, CreateMessage::new().content(text))
+            .await
+        {
+            info!(
+                "Couldn't tell \"{}\" they were throttled: {}",
+                msg.author.name, e
+            );
+        }
+    }
+
+    attempt
+}
+
+// when each user last started races, for the commands that start one. they fetch a seed
+// and post in several channels so nobody should be able to run them back to back.
+#[derive(Debug, Default)]
+pub struct StartRaceLimiter {
+    starts: HashMap<u64, VecDeque<Instant>>,
+}
+
+impl StartRaceLimiter {
+    // returns how long the user has to wait, or None if they can start a race now. only
+    // starts that were allowed count towards the limit.
+    pub fn attempt(
+        &mut self,
+        user_id: u64,
+        now: Instant,
+        delay: Duration,
+        limit: u32,
+        span: Duration,
+    ) -> Option<Duration> {
+        let kept = delay.max(span);
+        self.starts.retain(|_, times| {
+            while times
+                .front()
+                .map_or(false, |t| now.duration_since(*t) >= kept)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let times = self.starts.entry(user_id).or_default();
+        if let Some(since) = times.back().map(|t| now.duration_since(*t)) {
+            if since < delay {
+                return Some(delay - since);
+            }
+        }
+        let mut in_span = times.iter().filter(|t| now.duration_since(**t) < span);
+        if limit > 0 && in_span.clone().count() >= limit as usize {
+            let oldest = in_span.next().unwrap();
+            return Some(span - now.duration_since(*oldest));
+        }
+        times.push_back(now);
+
+        None
+    }
+}
+
+pub struct StartLimiterContainer;
+
+impl TypeMapKey for StartLimiterContainer {
+    type Value = StartRaceLimiter;
+}
+
+pub async fn check_start_limit(ctx: &Context, user: &User) -> Option<Duration> {
+    let config = get_config(ctx).await;
+    let mut data = ctx.data.write().await;
+    data.get_mut::<StartLimiterContainer>()
+        .expect("No start race limiter in share map")
+        .attempt(
+            user.id.get(),
+            Instant::now(),
+            Duration::from_secs(config.start_race_delay_secs),
+            config.start_race_limit,
+            Duration::from_secs(config.start_race_limit_secs),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attempts_over_limit_are_throttled() {
+        let mut throttle = SubmissionThrottle::default();
+        let start = Instant::now();
+        let window = Duration::from_secs(60);
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(throttle.attempt(1, at(0), 2, window), Attempt::Allowed);
+        assert_eq!(throttle.attempt(1, at(1), 2, window), Attempt::Allowed);
+        assert_eq!(
+            throttle.attempt(1, at(2), 2, window),
+            Attempt::Throttled { first: true }
+        );
+        assert_eq!(
+            throttle.attempt(1, at(3), 2, window),
+            Attempt::Throttled { first: false }
+        );
+        // someone else isn't affected
+        assert_eq!(throttle.attempt(2, at(3), 2, window), Attempt::Allowed);
+        // everything from the first runner has aged out by now
+        assert_eq!(throttle.attempt(1, at(64), 2, window), Attempt::Allowed);
+        assert_eq!(throttle.attempt(1, at(64), 0, window), Attempt::Allowed);
+    }
+
+    #[test]
+    fn race_starts_are_limited() {
+        let mut limiter = StartRaceLimiter::default();
+        let start = Instant::now();
+        let delay = Duration::from_secs(5);
+        let span = Duration::from_secs(60);
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(limiter.attempt(1, at(0), delay, 2, span), None);
+        // too soon after the last one, and the wait is until the delay is up
+        assert_eq!(
+            limiter.attempt(1, at(2), delay, 2, span),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(limiter.attempt(1, at(10), delay, 2, span), None);
+        // past the delay but at the limit for the span
+        assert_eq!(
+            limiter.attempt(1, at(20), delay, 2, span),
+            Some(Duration::from_secs(40))
+        );
+        // someone else isn't affected
+        assert_eq!(limiter.attempt(2, at(20), delay, 2, span), None);
+        assert_eq!(limiter.attempt(1, at(60), delay, 2, span), None);
+    }
+}
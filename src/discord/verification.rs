@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateMessage,
+    },
+    model::{
+        application::{ButtonStyle, ComponentInteraction},
+        id::{ChannelId, UserId},
+    },
+    prelude::*,
+    utils::MessageBuilder,
+};
+
+use crate::{
+    discord::{
+        channel_groups::{ChannelGroup, ChannelType},
+        events::{emit, RaceEvent},
+        servers::{has_permission, Permission},
+        spoiler_roles::{get_spoiler_roles, revoke_spoiler_access},
+        submissions::{build_leaderboard, NewSubmission, Submission},
+    },
+    error::MurahdahlaError,
+    games::AsyncRaceData,
+    helpers::*,
+    schema::{async_races, submissions},
+};
+
+// button ids look like "verify_approve:123" where the number is the submission id
+pub const VERIFY_PREFIX: &str = "verify_";
+const APPROVE_ACTION: &str = "approve";
+const REJECT_ACTION: &str = "reject";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Verdict {
+    Approve,
+    Reject,
+}
+
+pub fn needs_verification(group: &ChannelGroup, submission: &NewSubmission) -> bool {
+    // forfeits and unranked submissions never need a look since they aren't ranked
+    match (group.verify_under, submission.runner_time) {
+        (Some(threshold), Some(time)) => {
+            !submission.runner_forfeit && !submission.unranked && time < threshold
+        }
+        _ => false,
+    }
+}
+
+pub fn parse_verify_id(custom_id: &str) -> Result<(Verdict, u32)> {
+    let (action, id) = custom_id
+        .strip_prefix(VERIFY_PREFIX)
+        .and_then(|s| s.split_once(':'))
+        .ok_or_else(|| anyhow!("Malformed verification button id: {}", custom_id))?;
+    let verdict = match action {
+        APPROVE_ACTION => Verdict::Approve,
+        REJECT_ACTION => Verdict::Reject,
+        x => return Err(anyhow!("Unrecognized verification action: {}", x)),
+    };
+
+    Ok((verdict, id.parse::<u32>()?))
+}
+
+pub async fn post_verification_request(
+    ctx: &Context,
+    group: &ChannelGroup,
+    submission: &Submission,
+) -> Result<(), MurahdahlaError> {
+    let channel_id = match group.verification_channel {
+        Some(c) => ChannelId::from(c),
+        None => return Err(anyhow!("Group has no verification channel").into()),
+    };
+    let content = MessageBuilder::new()
+        .push("Submission from ")
+        .mention(&UserId::from(submission.runner_id))
+        .push(" in ")
+        .push_bold_safe(&group.group_name)
+        .push(" needs verification:")
+        .push_codeblock_safe(submission.to_string(), None)
+        .build();
+    let approve_id = format!(
+        "{}{}:{}",
+        VERIFY_PREFIX, APPROVE_ACTION, submission.submission_id
+    );
+    let reject_id = format!(
+        "{}{}:{}",
+        VERIFY_PREFIX, REJECT_ACTION, submission.submission_id
+    );
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(approve_id)
+            .label("Approve")
+            .style(ButtonStyle::Success),
+        CreateButton::new(reject_id)
+            .label("Reject")
+            .style(ButtonStyle::Danger),
+    ]);
+    channel_id
+        .send_message(
+            &ctx,
+            CreateMessage::new()
+                .content(content)
+                .components(vec![buttons]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn handle_verification(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+) -> Result<(), MurahdahlaError> {
+    // the queue message sticks around until someone clicks a button so this doesn't go
+    // through a collector. we check permissions here since the framework's hooks only
+    // see commands.
+    let (verdict, this_submission_id) = parse_verify_id(&interaction.data.custom_id)?;
+    let guild_id = interaction
+        .guild_id
+        .ok_or_else(|| anyhow!("Verification button used outside of a server"))?;
+    let user_roles = interaction
+        .member
+        .as_ref()
+        .map_or(&[][..], |m| &m.roles[..]);
+    if !has_permission(
+        ctx,
+        guild_id,
+        interaction.user.id,
+        user_roles,
+        Permission::Mod,
+    )
+    .await
+    {
+        let response = CreateInteractionResponseMessage::new()
+            .content("Only mods can verify submissions.")
+            .ephemeral(true);
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+            .await?;
+        return Ok(());
+    }
+
+    let conn = get_connection(ctx).await;
+    let submission: Option<Submission> = submissions::table
+        .find(this_submission_id)
+        .first(&conn)
+        .optional()?;
+    let submission = match submission {
+        Some(s) if s.pending => s,
+        _ => {
+            return update_queue_message(ctx, interaction, "This submission was already reviewed.")
+                .await
+        }
+    };
+    let race: AsyncRaceData = async_races::table.find(submission.race_id).first(&conn)?;
+    let group: ChannelGroup = {
+        let data = ctx.data.read().await;
+        data.get::<GroupContainer>()
+            .expect("No group container in share map")
+            .values()
+            .find(|g| g.channel_group_id == race.channel_group_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("No group found for race {}", race.race_id))?
+    };
+
+    match verdict {
+        Verdict::Approve => {
+            diesel::update(&submission)
+                .set(submissions::pending.eq(false))
+                .execute(&conn)?;
+        }
+        Verdict::Reject => {
+            diesel::delete(&submission).execute(&conn)?;
+            // spoiler access was already taken away if the race has stopped
+            if race.race_active {
+                let rules = get_spoiler_roles(&conn, &group)?;
+                let runner = UserId::from(submission.runner_id);
+                if let Err(e) =
+                    revoke_spoiler_access(&*ctx.http, &group, &rules, &race, runner).await
+                {
+                    warn!(
+                        "Error removing spoiler access for \"{}\": {}",
+                        &submission.runner_name, e
+                    );
+                }
+            }
+        }
+    };
+    let outcome = match verdict {
+        Verdict::Approve => "approved",
+        Verdict::Reject => "rejected",
+    };
+    let content = MessageBuilder::new()
+        .push_codeblock_safe(submission.to_string(), None)
+        .push(format!("{} by ", outcome))
+        .mention(&interaction.user.id)
+        .build();
+    update_queue_message(ctx, interaction, &content).await?;
+
+    // approved submissions show up on the leaderboard now. rejected ones were never on it.
+    match (verdict, race.race_active) {
+        (Verdict::Approve, true) => {
+            emit(
+                ctx,
+                RaceEvent::SubmissionAccepted {
+                    runner_id: submission.runner_id,
+                    group,
+                    race,
+                },
+            )
+            .await
+        }
+        (Verdict::Approve, false) => {
+            build_leaderboard(ctx, &group, &race, ChannelType::Submission).await?
+        }
+        (Verdict::Reject, _) => (),
+    };
+
+    Ok(())
+}
+
+async fn update_queue_message(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), MurahdahlaError> {
+    let response = CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(vec![]);
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(response),
+        )
+        .await?;
+
+    Ok(())
+}
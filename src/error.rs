@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use diesel::r2d2::PoolError;
+use serenity::http::{HttpError, StatusCode};
+use thiserror::Error;
+
+use crate::games::SeedError;
+
+// commands and everything they call return one of these. the framework's error handler
+// uses its kind to decide who needs to hear about it.
+#[derive(Debug, Error)]
+pub enum MurahdahlaError {
+    // something the person running the command can fix, like a typo or a missing role
+    #[error("{0}")]
+    User(String),
+    // talking to discord failed. only outages, rate limits and dropped connections count
+    // as the kind of discord error that goes away on its own, see is_transient.
+    #[error("Discord error: {0}")]
+    Discord(Box<serenity::Error>),
+    // we recognized a seed url but couldn't get its data. commands that start races
+    // offer to go ahead with just the url.
+    #[error(transparent)]
+    Seed(#[from] SeedError),
+    // a bug, a database problem, or anything else the maintenance user should see
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+// the database, seed sites and files we read are all ours to sort out when they fail
+macro_rules! internal_from {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for MurahdahlaError {
+                fn from(e: $t) -> Self {
+                    MurahdahlaError::Internal(e.into())
+                }
+            }
+        )*
+    };
+}
+
+internal_from!(
+    diesel::result::Error,
+    PoolError,
+    reqwest::Error,
+    serde_json::Error,
+    serde_yaml::Error,
+    std::io::Error
+);
+
+// serenity's error is big enough that it's boxed to keep every Result small
+impl From<serenity::Error> for MurahdahlaError {
+    fn from(e: serenity::Error) -> Self {
+        MurahdahlaError::Discord(Box::new(e))
+    }
+}
+
+// builds a MurahdahlaError::User the same way anyhow! builds an error
+#[macro_export]
+macro_rules! user_error {
+    ($($arg:tt)*) => {
+        $crate::error::MurahdahlaError::User(format!($($arg)*))
+    };
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ErrorKind {
+    User,
+    Discord,
+    Internal,
+}
+
+impl MurahdahlaError {
+    pub fn user<T: Display>(e: T) -> Self {
+        MurahdahlaError::User(e.to_string())
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            MurahdahlaError::User(_) => ErrorKind::User,
+            MurahdahlaError::Discord(e) => discord_error_kind(e),
+            MurahdahlaError::Seed(_) | MurahdahlaError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+pub fn discord_error_kind(e: &serenity::Error) -> ErrorKind {
+    match is_transient(e) {
+        true => ErrorKind::Discord,
+        false => ErrorKind::Internal,
+    }
+}
+
+pub fn is_transient(e: &serenity::Error) -> bool {
+    // retrying only helps when discord is down, we're being rate limited, or the
+    // connection dropped. anything else, like missing permissions or a deleted channel,
+    // won't fix itself and the maintenance user needs to hear about it.
+    match e {
+        serenity::Error::Http(e) => match e {
+            HttpError::UnsuccessfulRequest(r) => status_is_transient(r.status_code),
+            HttpError::Request(r) => r.is_timeout() || r.is_connect(),
+            HttpError::RateLimitI64F64 | HttpError::RateLimitUtf8 => true,
+            _ => false,
+        },
+        serenity::Error::Gateway(_) | serenity::Error::Io(_) | serenity::Error::Tungstenite(_) => {
+            true
+        }
+        _ => false,
+    }
+}
+
+fn status_is_transient(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use reqwest::Method;
+    use serde_json::json;
+    use serenity::http::ErrorResponse;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn errors_keep_their_kind() {
+        let user = user_error!("\"{}\" isn't a race type", "xyz");
+        assert_eq!(user.kind(), ErrorKind::User);
+        assert_eq!(user.to_string(), "\"xyz\" isn't a race type");
+
+        assert_eq!(
+            MurahdahlaError::from(http_error(429).await).kind(),
+            ErrorKind::Discord
+        );
+        let io = serenity::Error::Io(std::io::ErrorKind::ConnectionReset.into());
+        assert_eq!(MurahdahlaError::from(io).kind(), ErrorKind::Discord);
+
+        assert_eq!(
+            MurahdahlaError::from(anyhow!("connection refused")).kind(),
+            ErrorKind::Internal
+        );
+        let database = MurahdahlaError::from(diesel::result::Error::NotFound);
+        assert_eq!(database.kind(), ErrorKind::Internal);
+    }
+
+    async fn http_error(status: u16) -> serenity::Error {
+        let body = json!({"code": 0, "message": "test"}).to_string();
+        let response = http::Response::builder().status(status).body(body).unwrap();
+        let response = ErrorResponse::from_response(response.into(), Method::POST).await;
+
+        HttpError::UnsuccessfulRequest(response).into()
+    }
+
+    #[tokio::test]
+    async fn only_transient_discord_errors_count_as_discord_errors() {
+        // a missing permission or a deleted channel needs someone to fix it
+        for status in [403, 404, 400] {
+            let e = http_error(status).await;
+            assert_eq!(discord_error_kind(&e), ErrorKind::Internal);
+        }
+        for status in [500, 502, 503, 429] {
+            let e = http_error(status).await;
+            assert_eq!(discord_error_kind(&e), ErrorKind::Discord);
+        }
+        let model = serenity::Error::Model(serenity::model::ModelError::InvalidPermissions {
+            required: Default::default(),
+            present: Default::default(),
+        });
+        assert_eq!(discord_error_kind(&model), ErrorKind::Internal);
+        assert_eq!(
+            discord_error_kind(&serenity::Error::Other("gateway closed")),
+            ErrorKind::Internal
+        );
+    }
+}
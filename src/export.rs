@@ -0,0 +1,81 @@
+use anyhow::Result;
+use diesel::prelude::*;
+use serde_json::{json, Value};
+
+use crate::{
+    discord::{channel_groups::ChannelGroup, submissions::Submission},
+    games::AsyncRaceData,
+    helpers::*,
+};
+
+pub fn export_server(conn: &PooledConn, this_server_id: u64) -> Result<Value> {
+    // every group on a server with its races and submissions, for backups or moving a
+    // server's history somewhere else without going through the database by hand
+    use crate::schema::async_races::columns::race_id;
+    use crate::schema::channels::columns::{group_name, server_id};
+    use crate::schema::channels::dsl::channels;
+
+    let groups: Vec<ChannelGroup> = channels
+        .filter(server_id.eq(this_server_id))
+        .order(group_name.asc())
+        .load(conn)?;
+    let mut group_values: Vec<Value> = Vec::with_capacity(groups.len());
+    for group in &groups {
+        let races: Vec<AsyncRaceData> = AsyncRaceData::belonging_to(group)
+            .order(race_id.asc())
+            .load(conn)?;
+        let submissions: Vec<Vec<Submission>> = Submission::belonging_to(&races)
+            .load::<Submission>(conn)?
+            .grouped_by(&races);
+        let race_values: Vec<Value> = races
+            .iter()
+            .zip(submissions)
+            .map(|(r, subs)| race_value(r, &subs))
+            .collect();
+        group_values.push(json!({
+            "group_name": group.group_name,
+            "submission_channel": group.submission,
+            "leaderboard_channel": group.leaderboard,
+            "spoiler_channel": group.spoiler,
+            "races": race_values,
+        }));
+    }
+
+    Ok(json!({
+        "server_id": this_server_id,
+        "groups": group_values,
+    }))
+}
+
+fn race_value(race: &AsyncRaceData, submissions: &[Submission]) -> Value {
+    let submission_values: Vec<Value> = submissions
+        .iter()
+        .map(|s| {
+            json!({
+                "runner_id": s.runner_id,
+                "runner_name": s.runner_name,
+                "submitted": s.submission_datetime.to_string(),
+                "time": s.runner_time.map(|t| t.to_string()),
+                "time_ms": s.runner_time.map(|t| t.millis()),
+                "collection": s.runner_collection,
+                "score": s.option_number,
+                "fields": s.option_text,
+                "forfeit": s.runner_forfeit,
+                "pending": s.pending,
+                "unranked": s.unranked,
+            })
+        })
+        .collect();
+
+    json!({
+        "race_id": race.race_id,
+        "active": race.race_active,
+        "date": race.race_date.to_string(),
+        "game": race.race_game.to_string(),
+        "type": race.race_type.to_string(),
+        "info": race.race_info,
+        "url": race.race_url,
+        "deadline": race.race_deadline.map(|d| d.to_string()),
+        "submissions": submission_values,
+    })
+}
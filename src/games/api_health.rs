@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use serenity::{client::Context, prelude::TypeMapKey};
+
+use crate::{
+    config::get_config,
+    discord::secrets::CLIENT_GENERATION,
+    error::MurahdahlaError,
+    games::{is_transient, BoxedGame, GameName},
+};
+
+// how often to check whether stats logging was turned back on
+const STATS_DISABLED_CHECK_SECS: u64 = 300;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ApiOutcome {
+    Ok,
+    // the site answered but didn't like the request, like a seed that doesn't exist
+    Rejected,
+    // timeouts, connection errors and 5xx responses after every retry
+    Unavailable,
+}
+
+impl ApiOutcome {
+    pub fn of(result: &Result<BoxedGame, MurahdahlaError>) -> Self {
+        match result {
+            Ok(_) => ApiOutcome::Ok,
+            Err(e) if upstream_failure(e) => ApiOutcome::Unavailable,
+            Err(_) => ApiOutcome::Rejected,
+        }
+    }
+}
+
+fn upstream_failure(e: &MurahdahlaError) -> bool {
+    // the request error can be anywhere in the chain depending on how the game wrapped it
+    match e {
+        MurahdahlaError::Internal(e) => e
+            .chain()
+            .find_map(|err| err.downcast_ref::<reqwest::Error>())
+            .map_or(false, is_transient),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Default)]
+struct ApiStats {
+    // reset every time the stats are logged
+    requests: u32,
+    errors: u32,
+    total_latency: Duration,
+    max_latency: Duration,
+    // these make up the circuit breaker and aren't reset
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+// latency and errors of each seed site's api, and a circuit breaker for each one. a
+// site that keeps timing out gets left alone for a while and races for it start with
+// just their url, the same as "--other", instead of making everyone wait on retries.
+#[derive(Debug, Default)]
+pub struct ApiHealth {
+    games: HashMap<GameName, ApiStats>,
+}
+
+impl ApiHealth {
+    pub fn is_open(&self, game: GameName, now: Instant) -> bool {
+        self.games
+            .get(&game)
+            .and_then(|s| s.open_until)
+            .map_or(false, |until| now < until)
+    }
+
+    // returns true when this call opened the game's breaker. after the cooldown the next
+    // call goes through and one more failure opens it again right away.
+    pub fn record(
+        &mut self,
+        game: GameName,
+        latency: Duration,
+        outcome: ApiOutcome,
+        now: Instant,
+        failures_to_open: u32,
+        cooldown: Duration,
+    ) -> bool {
+        let stats = self.games.entry(game).or_default();
+        stats.requests += 1;
+        stats.total_latency += latency;
+        stats.max_latency = stats.max_latency.max(latency);
+        if outcome != ApiOutcome::Ok {
+            stats.errors += 1;
+        }
+        match outcome {
+            ApiOutcome::Unavailable => stats.consecutive_failures += 1,
+            // any answer at all means the site is up
+            ApiOutcome::Ok | ApiOutcome::Rejected => {
+                stats.consecutive_failures = 0;
+                stats.open_until = None;
+            }
+        }
+        if failures_to_open > 0 && stats.consecutive_failures >= failures_to_open {
+            stats.open_until = Some(now + cooldown);
+            return true;
+        }
+
+        false
+    }
+
+    pub fn take_summary(&mut self) -> Option<String> {
+        let mut summary = String::new();
+        let mut games = self
+            .games
+            .iter_mut()
+            .filter(|(_, s)| s.requests > 0)
+            .collect::<Vec<(&GameName, &mut ApiStats)>>();
+        games.sort_by_key(|(g, _)| g.as_str());
+        for (game, stats) in games {
+            let _ = write!(
+                summary,
+                "{}: {} requests, {} failed ({}%), avg {}ms, max {}ms",
+                game,
+                stats.requests,
+                stats.errors,
+                stats.errors * 100 / stats.requests,
+                stats.total_latency.as_millis() / u128::from(stats.requests),
+                stats.max_latency.as_millis()
+            );
+            if stats.open_until.is_some() {
+                summary.push_str(", breaker open");
+            }
+            summary.push_str("; ");
+            stats.requests = 0;
+            stats.errors = 0;
+            stats.total_latency = Duration::ZERO;
+            stats.max_latency = Duration::ZERO;
+        }
+        match summary.is_empty() {
+            true => None,
+            false => Some(summary.trim_end_matches("; ").to_owned()),
+        }
+    }
+}
+
+pub struct ApiHealthContainer;
+
+impl TypeMapKey for ApiHealthContainer {
+    type Value = ApiHealth;
+}
+
+pub async fn api_available(ctx: &Context, game: GameName) -> bool {
+    let data = ctx.data.read().await;
+    !data
+        .get::<ApiHealthContainer>()
+        .expect("No api health in share map")
+        .is_open(game, Instant::now())
+}
+
+pub async fn record_api_call(
+    ctx: &Context,
+    game: GameName,
+    latency: Duration,
+    result: &Result<BoxedGame, MurahdahlaError>,
+) {
+    let config = get_config(ctx).await;
+    let outcome = ApiOutcome::of(result);
+    debug!(
+        "{} API call took {}ms ({:?})",
+        game,
+        latency.as_millis(),
+        outcome
+    );
+    let opened = {
+        let mut data = ctx.data.write().await;
+        data.get_mut::<ApiHealthContainer>()
+            .expect("No api health in share map")
+            .record(
+                game,
+                latency,
+                outcome,
+                Instant::now(),
+                config.api_breaker_failures,
+                Duration::from_secs(config.api_breaker_cooldown_secs),
+            )
+    };
+    if opened {
+        warn!(
+            "{} API failed {} times in a row, starting its races without it for {}s",
+            game, config.api_breaker_failures, config.api_breaker_cooldown_secs
+        );
+    }
+}
+
+pub async fn api_stats_task(ctx: Context) {
+    // logs each api's stats since the last time around. the interval is read each time
+    // so it can be changed with !reloadconfig.
+    let generation = CLIENT_GENERATION.load(Ordering::SeqCst);
+    loop {
+        let log_secs = get_config(&ctx).await.api_stats_log_secs;
+        if log_secs == 0 {
+            tokio::time::sleep(Duration::from_secs(STATS_DISABLED_CHECK_SECS)).await;
+            continue;
+        }
+        tokio::time::sleep(Duration::from_secs(log_secs)).await;
+        if CLIENT_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let summary = {
+            let mut data = ctx.data.write().await;
+            data.get_mut::<ApiHealthContainer>()
+                .expect("No api health in share map")
+                .take_summary()
+        };
+        if let Some(s) = summary {
+            info!("Seed API stats for the last {}s: {}", log_secs, s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_opens_after_failures_and_closes_on_success() {
+        let mut health = ApiHealth::default();
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(300);
+        let ms = Duration::from_millis;
+
+        assert!(!health.record(
+            GameName::ALTTPR,
+            ms(100),
+            ApiOutcome::Unavailable,
+            now,
+            2,
+            cooldown
+        ));
+        // a bad seed doesn't count against the site
+        assert!(!health.record(
+            GameName::ALTTPR,
+            ms(300),
+            ApiOutcome::Rejected,
+            now,
+            2,
+            cooldown
+        ));
+        assert!(!health.record(
+            GameName::ALTTPR,
+            ms(100),
+            ApiOutcome::Unavailable,
+            now,
+            2,
+            cooldown
+        ));
+        assert!(health.record(
+            GameName::ALTTPR,
+            ms(500),
+            ApiOutcome::Unavailable,
+            now,
+            2,
+            cooldown
+        ));
+        assert!(health.is_open(GameName::ALTTPR, now));
+        assert!(!health.is_open(GameName::SMZ3, now));
+        assert!(!health.is_open(GameName::ALTTPR, now + cooldown));
+
+        // half open after the cooldown, one more failure opens it again
+        let later = now + cooldown;
+        assert!(health.record(
+            GameName::ALTTPR,
+            ms(100),
+            ApiOutcome::Unavailable,
+            later,
+            2,
+            cooldown
+        ));
+        let later = later + cooldown;
+        assert!(!health.record(
+            GameName::ALTTPR,
+            ms(100),
+            ApiOutcome::Ok,
+            later,
+            2,
+            cooldown
+        ));
+        assert!(!health.is_open(GameName::ALTTPR, later));
+
+        assert_eq!(
+            health.take_summary().unwrap(),
+            "ALTTPR: 6 requests, 5 failed (83%), avg 200ms, max 500ms"
+        );
+        assert!(health.take_summary().is_none());
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{anyhow, Result};
+use reqwest::get;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::{form_urlencoded, Url};
+
+use crate::{
+    discord::templates::{COLLECTION_FIELD, MAX_TEMPLATE_FIELDS, MAX_TEMPLATE_NAME_CHARS},
+    error::MurahdahlaError,
+    games::{fetch_with_retry, AsyncGame, GameName},
+};
+
+const MAX_DISPLAY_NAME_CHARS: usize = 64;
+const MAX_HOSTS: usize = 5;
+const MAX_SETTINGS_PATHS: usize = 5;
+const MAX_PATH_CHARS: usize = 100;
+// what's replaced in an api url before it's requested
+const SEED_PLACEHOLDER: &str = "{seed}";
+const URL_PLACEHOLDER: &str = "{url}";
+const UNKNOWN_SETTINGS: &str = "(settings unavailable)";
+const SETTINGS_SEPARATOR: &str = " / ";
+
+// a game a server defined for itself with !addgame. races for it are "other" races so
+// nothing in the database needs to know about it besides the definition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomGameDefinition {
+    pub name: String,
+    pub display_name: String,
+    // "example.com" or "*.example.com" for the site and any of its subdomains
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    // whether runners submit a collection rate right after their time
+    #[serde(default)]
+    pub collection: bool,
+    // anything else runners fill in, the same as a template's fields
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub api: Option<CustomGameApi>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomGameApi {
+    pub url: String,
+    // json paths like "$.settings.preset" or "$.players[0].name". whatever they find is
+    // joined together for the race's settings.
+    pub settings: Vec<String>,
+}
+
+impl CustomGameDefinition {
+    pub fn from_yaml(yaml: &[u8]) -> Result<Self> {
+        let mut definition: CustomGameDefinition = serde_yaml::from_slice(yaml)?;
+        definition.validate()?;
+
+        Ok(definition)
+    }
+
+    pub fn validate(&mut self) -> Result<()> {
+        self.name = self.name.trim().to_lowercase();
+        if self.name.is_empty()
+            || self.name.chars().count() > MAX_TEMPLATE_NAME_CHARS
+            || self.name.contains(char::is_whitespace)
+        {
+            return Err(anyhow!(
+                "Game names must be 1-{} characters with no whitespace",
+                MAX_TEMPLATE_NAME_CHARS
+            ));
+        }
+        self.display_name = self.display_name.trim().to_owned();
+        if self.display_name.is_empty()
+            || self.display_name.chars().count() > MAX_DISPLAY_NAME_CHARS
+            || self.display_name.chars().any(char::is_control)
+        {
+            return Err(anyhow!(
+                "Display names must be 1-{} characters on one line",
+                MAX_DISPLAY_NAME_CHARS
+            ));
+        }
+        if self.hosts.is_empty() || self.hosts.len() > MAX_HOSTS {
+            return Err(anyhow!("Games need 1-{} hosts", MAX_HOSTS));
+        }
+        for host in self.hosts.iter_mut() {
+            *host = host.trim().to_lowercase();
+            if !valid_host_pattern(host) {
+                return Err(anyhow!("\"{}\" isn't a host name", host));
+            }
+        }
+        if let Some(prefix) = &self.path_prefix {
+            if !prefix.starts_with('/') {
+                return Err(anyhow!("Path prefixes have to start with \"/\""));
+            }
+        }
+        let field_count = self.fields.len() + usize::from(self.collection);
+        if field_count > MAX_TEMPLATE_FIELDS {
+            return Err(anyhow!(
+                "Games can have at most {} fields counting collection",
+                MAX_TEMPLATE_FIELDS
+            ));
+        }
+        for field in self.fields.iter_mut() {
+            *field = field.trim().to_owned();
+            if field.is_empty() || field.contains(',') || field.starts_with('#') {
+                return Err(anyhow!("\"{}\" can't be used as a field name", field));
+            }
+        }
+        if let Some(api) = &self.api {
+            // the url is checked with its placeholders filled in since that's what the
+            // bot will request
+            let api_url = Url::parse(
+                &api.url
+                    .replace(SEED_PLACEHOLDER, "seed")
+                    .replace(URL_PLACEHOLDER, "url"),
+            )?;
+            if api_url.scheme() != "https" {
+                return Err(anyhow!("API urls have to use https"));
+            }
+            if api.settings.is_empty() || api.settings.len() > MAX_SETTINGS_PATHS {
+                return Err(anyhow!("APIs need 1-{} settings paths", MAX_SETTINGS_PATHS));
+            }
+            for path in api.settings.iter() {
+                if path.chars().count() > MAX_PATH_CHARS {
+                    return Err(anyhow!(
+                        "Settings paths can't be over {} characters",
+                        MAX_PATH_CHARS
+                    ));
+                }
+                parse_json_path(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn matches_url(&self, game_url: &Url) -> bool {
+        let host = match game_url.host_str() {
+            Some(h) => h.to_lowercase(),
+            None => return false,
+        };
+        let prefix = self.path_prefix.as_deref().unwrap_or("/");
+
+        self.hosts.iter().any(|p| host_matches(p, &host)) && game_url.path().starts_with(prefix)
+    }
+
+    pub fn race_fields(&self) -> Option<String> {
+        // collection goes first since that's where runners put it for built in games
+        let fields = match self.collection {
+            true => std::iter::once(COLLECTION_FIELD)
+                .chain(self.fields.iter().map(String::as_str))
+                .collect::<Vec<&str>>(),
+            false => self.fields.iter().map(String::as_str).collect(),
+        };
+        match fields.is_empty() {
+            true => None,
+            false => Some(fields.join(",")),
+        }
+    }
+}
+
+fn valid_host_pattern(pattern: &str) -> bool {
+    let host = pattern.strip_prefix("*.").unwrap_or(pattern);
+    host.contains('.')
+        && host
+            .split('.')
+            .all(|l| !l.is_empty() && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+        None => host == pattern,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathStep {
+    Key(String),
+    Index(usize),
+}
+
+fn parse_json_path(path: &str) -> Result<Vec<PathStep>> {
+    // only the part of jsonpath that picks out a single value: "$", ".key", "['key']"
+    // and "[0]". nothing here needs filters or wildcards.
+    let rest = path
+        .trim()
+        .strip_prefix('$')
+        .ok_or_else(|| anyhow!("Settings path \"{}\" has to start with \"$\"", path))?;
+    let mut steps = Vec::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+                while let Some(&k) = chars.peek() {
+                    if k == '.' || k == '[' {
+                        break;
+                    }
+                    key.push(k);
+                    chars.next();
+                }
+                if key.is_empty() {
+                    return Err(anyhow!("Settings path \"{}\" has an empty key", path));
+                }
+                steps.push(PathStep::Key(key));
+            }
+            '[' => {
+                let mut inner = String::new();
+                for k in chars.by_ref() {
+                    if k == ']' {
+                        break;
+                    }
+                    inner.push(k);
+                }
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|i| i.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|i| i.strip_suffix('"')));
+                let step = match quoted {
+                    Some(key) => PathStep::Key(key.to_owned()),
+                    None => PathStep::Index(inner.parse::<usize>().map_err(|_| {
+                        anyhow!("Settings path \"{}\" has a bad index \"{}\"", path, inner)
+                    })?),
+                };
+                steps.push(step);
+            }
+            _ => return Err(anyhow!("Settings path \"{}\" isn't a json path", path)),
+        }
+    }
+
+    Ok(steps)
+}
+
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    parse_json_path(path)
+        .ok()?
+        .iter()
+        .try_fold(value, |v, step| match step {
+            PathStep::Key(k) => v.get(k),
+            PathStep::Index(i) => v.get(i),
+        })
+}
+
+fn settings_from_json(paths: &[String], seed: &Value) -> Option<String> {
+    // paths that find nothing or find a whole object are left out
+    let values = paths
+        .iter()
+        .filter_map(|p| match json_path(seed, p)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    match values.is_empty() {
+        true => None,
+        false => Some(values.join(SETTINGS_SEPARATOR)),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomGame {
+    display_name: String,
+    settings: Option<String>,
+    url: String,
+}
+
+impl CustomGame {
+    pub async fn new_from_url(
+        definition: &CustomGameDefinition,
+        args_str: &str,
+    ) -> Result<Self, MurahdahlaError> {
+        let url = args_str.trim().to_owned();
+        let settings = match &definition.api {
+            Some(api) => {
+                let api_url = api_url(&api.url, &url)?;
+                match get_seed_json(&api_url).await {
+                    Ok(seed) => Some(
+                        settings_from_json(&api.settings, &seed)
+                            .unwrap_or_else(|| UNKNOWN_SETTINGS.to_owned()),
+                    ),
+                    Err(e) => {
+                        warn!("Starting {} race without settings: {}", definition.name, e);
+                        Some(UNKNOWN_SETTINGS.to_owned())
+                    }
+                }
+            }
+            None => None,
+        };
+
+        Ok(CustomGame {
+            display_name: definition.display_name.clone(),
+            settings,
+            url,
+        })
+    }
+}
+
+fn api_url(template: &str, seed_url: &str) -> Result<String> {
+    // the seed is the last part of the url path, the way every built in game finds it
+    let parsed = Url::parse(seed_url)?;
+    let seed = parsed
+        .path_segments()
+        .and_then(|mut s| s.rfind(|s| !s.is_empty()))
+        .unwrap_or("");
+    if template.contains(SEED_PLACEHOLDER) && seed.is_empty() {
+        return Err(anyhow!("Couldn't find a seed in \"{}\"", seed_url));
+    }
+    let encoded_url = form_urlencoded::byte_serialize(seed_url.as_bytes()).collect::<String>();
+
+    Ok(template
+        .replace(SEED_PLACEHOLDER, seed)
+        .replace(URL_PLACEHOLDER, &encoded_url))
+}
+
+async fn get_seed_json(api_url: &str) -> Result<Value> {
+    let seed = fetch_with_retry(|| get(api_url))
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+
+    Ok(seed)
+}
+
+impl AsyncGame for CustomGame {
+    fn game_name(&self) -> GameName {
+        GameName::Other
+    }
+
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        match &self.settings {
+            Some(s) => Ok(format!("{} - {}", self.display_name, s)),
+            None => Ok(self.display_name.clone()),
+        }
+    }
+
+    fn has_url(&self) -> bool {
+        true
+    }
+
+    fn game_url(&self) -> Option<&str> {
+        Some(&self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITION: &str = r#"
+name: SMWR
+display_name: SMW Randomizer
+hosts: ["*.smwrando.example"]
+path_prefix: /seed/
+collection: true
+fields: [comment]
+api:
+  url: https://api.smwrando.example/seeds/{seed}
+  settings: ["$.settings.preset", "$['goal']", "$.flags[1]", "$.missing"]
+"#;
+
+    #[test]
+    fn definitions_match_urls_and_build_settings() {
+        let definition = CustomGameDefinition::from_yaml(DEFINITION.as_bytes()).unwrap();
+        assert_eq!(definition.name, "smwr");
+        assert_eq!(
+            definition.race_fields().as_deref(),
+            Some("#collection,comment")
+        );
+
+        let seed_url = Url::parse("https://www.smwrando.example/seed/AbC123").unwrap();
+        assert!(definition.matches_url(&seed_url));
+        assert!(definition.matches_url(&Url::parse("https://smwrando.example/seed/x").unwrap()));
+        assert!(!definition.matches_url(&Url::parse("https://smwrando.example/about").unwrap()));
+        assert!(!definition.matches_url(&Url::parse("https://notsmwrando.example/seed/x").unwrap()));
+        assert_eq!(
+            api_url(&definition.api.as_ref().unwrap().url, seed_url.as_str()).unwrap(),
+            "https://api.smwrando.example/seeds/AbC123"
+        );
+
+        let seed = serde_json::json!({
+            "settings": {"preset": "Hard"},
+            "goal": "Bowser",
+            "flags": ["a", 96],
+        });
+        assert_eq!(
+            settings_from_json(&definition.api.unwrap().settings, &seed).as_deref(),
+            Some("Hard / Bowser / 96")
+        );
+
+        let bad = DEFINITION.replace("https://api", "http://api");
+        assert!(CustomGameDefinition::from_yaml(bad.as_bytes()).is_err());
+        let bad = DEFINITION.replace("$.flags[1]", "flags.1");
+        assert!(CustomGameDefinition::from_yaml(bad.as_bytes()).is_err());
+        let bad = DEFINITION.replace("*.smwrando.example", "*");
+        assert!(CustomGameDefinition::from_yaml(bad.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Result};
+use reqwest;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    config::read_env,
+    discord::submissions::NewSubmission,
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider},
+        AsyncGame, BoxedGame, GameName,
+    },
+};
+
+const API_URL: &str = "https://ff4fe.com/api/seed";
+// the fe api only answers requests with a key. without one, races still start with
+// just the url.
+pub const API_KEY_VAR: &str = "FF4FE_API_KEY";
+const UNKNOWN_SETTINGS: &str = "(settings unavailable)";
+
+#[derive(Debug, Clone)]
+pub struct FF4FEGame {
+    // None when there's no api key to ask with
+    seed: Option<FF4FESeed>,
+    url: String,
+}
+
+// the parts of the api's seed response we show. the verification is the list of items
+// shown on the title screen so runners can check they have the right seed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FF4FESeed {
+    version: String,
+    flags: String,
+    #[serde(default)]
+    verification: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    status: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(flatten)]
+    seed: Option<FF4FESeed>,
+}
+
+impl FF4FEGame {
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let id = ff4fe_seed_id(args_str)?;
+        let url = args_str.to_string();
+        let seed = match read_env(API_KEY_VAR)?.filter(|k| !k.is_empty()) {
+            Some(key) => Some(get_seed(&key, &id).await?),
+            None => {
+                warn!(
+                    "Starting FF4FE race without settings: {} isn't set",
+                    API_KEY_VAR
+                );
+                None
+            }
+        };
+
+        Ok(FF4FEGame { seed, url })
+    }
+}
+
+fn ff4fe_seed_id(args_str: &str) -> Result<String> {
+    // fe seed links put the id in the query string (ff4fe.com/get?id=...) instead of
+    // the path so we can't use games::seed_id here
+    let seed_url = Url::parse(args_str).map_err(|e| anyhow!("Invalid seed url: {}", e))?;
+    let id = seed_url
+        .query_pairs()
+        .find(|(k, _)| k == "id")
+        .map(|(_, v)| v.into_owned())
+        .ok_or_else(|| anyhow!("Couldn't find a seed id in the url"))?;
+    match valid_seed_id(&id) {
+        true => Ok(id),
+        false => Err(anyhow!("\"{}\" isn't a valid FF4FE seed id", id)),
+    }
+}
+
+async fn get_seed(key: &str, id: &str) -> Result<FF4FESeed> {
+    let params = [("key", key), ("id", id)];
+    let client = reqwest::Client::new();
+    let response = fetch_with_retry(|| client.get(API_URL).query(&params).send())
+        .await?
+        .json::<ApiResponse>()
+        .await?;
+
+    parse_response(response)
+}
+
+fn parse_response(response: ApiResponse) -> Result<FF4FESeed> {
+    match (response.status.as_str(), response.seed) {
+        ("ok", Some(seed)) => Ok(seed),
+        ("ok", None) => Err(anyhow!("Unrecognized FF4FE API response")),
+        (status, _) => Err(anyhow!(
+            "FF4FE API returned \"{}\": {}",
+            status,
+            response.error.as_deref().unwrap_or("no error given")
+        )),
+    }
+}
+
+impl AsyncGame for FF4FEGame {
+    fn game_name(&self) -> GameName {
+        GameName::FF4FE
+    }
+
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        let seed = match &self.seed {
+            Some(s) => s,
+            None => return Ok(UNKNOWN_SETTINGS.to_owned()),
+        };
+        let mut settings = format!("v{} {}", seed.version.trim_start_matches('v'), seed.flags);
+        if let Some(v) = seed.verification.as_deref().filter(|v| !v.is_empty()) {
+            settings.push_str(&format!(" ({})", v));
+        }
+
+        Ok(settings)
+    }
+
+    fn has_url(&self) -> bool {
+        true
+    }
+
+    fn game_url(&self) -> Option<&str> {
+        Some(&self.url)
+    }
+}
+
+pub struct FF4FEProvider;
+
+impl GameProvider for FF4FEProvider {
+    fn name(&self) -> GameName {
+        GameName::FF4FE
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        valid_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        game_info(submission, msg)
+    }
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    matches!(
+        game_url.host_str(),
+        Some("ff4fe.com") | Some("www.ff4fe.com")
+    ) && game_url.path() == "/get"
+}
+
+fn valid_seed_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(async move { Ok(Box::new(FF4FEGame::new_from_str(&args_str).await?) as BoxedGame) })
+}
+
+pub fn game_info<'a>(
+    submission: &'a mut NewSubmission,
+    msg: &[&str],
+) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+    // fe races are ranked on the in-game time alone so there's nothing else to read
+    if !msg.is_empty() {
+        return Err(anyhow!("FF4FE submissions only take the in-game time.").into());
+    }
+
+    Ok(submission)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_from_api_response() {
+        let response: ApiResponse =
+            serde_json::from_str(include_str!("../../tests/data/ff4fe/seed.json")).unwrap();
+        let game = FF4FEGame {
+            seed: Some(parse_response(response).unwrap()),
+            url: "https://ff4fe.com/get?id=4PXB0H7QJL".to_owned(),
+        };
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "v4.6.0 Orandom:5,req:all/win:crystal Kmain/summon/moon Pkey Cstandard/nofree \
+            Tpro Sstandard Bstandard/alt:gauntlet Etoggle Glife/sylph/backrow -kit:basic \
+            (Bacchus Hourglass Zeus Phoenix)"
+        );
+        assert_eq!(
+            ff4fe_seed_id("https://ff4fe.com/get?id=4PXB0H7QJL").unwrap(),
+            "4PXB0H7QJL"
+        );
+
+        let error: ApiResponse =
+            serde_json::from_str(r#"{"status": "error", "error": "Invalid API key"}"#).unwrap();
+        assert!(parse_response(error).is_err());
+    }
+}
@@ -1,25 +1,44 @@
-use std::fmt;
+use std::{
+    fmt,
+    future::Future,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
-use chrono::{offset::Utc, NaiveDate};
+use chrono::{offset::Utc, Duration as ChronoDuration, NaiveDate, NaiveDateTime};
 use diesel::{
     backend::Backend, deserialize, deserialize::FromSql, expression::AsExpression,
     helper_types::AsExprOf, prelude::*, sql_types::Text,
 };
-use serenity::framework::standard::Args;
+use reqwest::Response;
+use serde_json::Value;
+use serenity::client::Context;
+use tokio::time::sleep;
 use url::Url;
 
 use crate::{
-    discord::channel_groups::ChannelGroup,
+    discord::{channel_groups::ChannelGroup, submissions::escape_markdown},
+    error::MurahdahlaError,
     games::{
-        other::OtherGame, smtotal::SMTotalGame, smvaria::SMVARIAGame, smz3::SMZ3Game, z3r::Z3rGame,
+        api_health::{api_available, record_api_call},
+        other::OtherGame,
+        registry::{
+            provider, provider_by_name, provider_for_json, provider_for_url, registered_games,
+        },
     },
     helpers::*,
     schema::*,
-    BoxedError,
 };
 
+pub mod api_health;
+pub mod custom;
+pub mod ff4fe;
+pub mod ootr;
 pub mod other;
+pub mod registry;
+pub mod scoring;
+pub mod smmaprando;
 pub mod smtotal;
 pub mod smvaria;
 pub mod smz3;
@@ -27,7 +46,33 @@ pub mod z3r;
 
 pub type BoxedGame = Box<dyn AsyncGame + Send + Sync>;
 
-#[derive(Debug, Queryable, Identifiable, Associations)]
+pub const OTHER_FLAG: &str = "--other";
+pub const SORT_FLAG: &str = "--sort";
+pub const TEMPLATE_FLAG: &str = "--template";
+pub const DEADLINE_FLAG: &str = "--deadline";
+pub const BLIND_FLAG: &str = "--blind";
+pub const NO_REMINDERS_FLAG: &str = "--no-reminders";
+pub const CLAIM_FLAG: &str = "--claim";
+pub const LIVE_FLAG: &str = "--live";
+pub const PRESET_FLAG: &str = "--preset";
+pub const SHUFFLE_FLAG: &str = "--shuffle-results";
+pub const TEAMS_FLAG: &str = "--teams";
+pub const MAX_DEADLINE_DAYS: i64 = 28;
+pub const MAX_SEED_JSON_BYTES: u32 = 8_000_000;
+pub const ANNOUNCEMENT_SEPARATOR: &str = " | ";
+pub const MAX_ANNOUNCEMENT_LENGTH: usize = 200;
+// the unique index from the source_message_ids migration
+const SOURCE_MESSAGE_INDEX: &str = "async_races_source_message";
+// how much of a race's settings are shown in its posts unless its group says otherwise
+pub const DEFAULT_MAX_SETTINGS_CHARS: usize = 300;
+const SETTINGS_ELLIPSIS: &str = "...";
+
+// seed sites intermittently fail so we give external api calls a few tries
+const API_ATTEMPTS: u32 = 3;
+const API_BACKOFF_MS: u64 = 500;
+const API_JITTER_MS: u64 = 250;
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
 #[belongs_to(parent = "ChannelGroup", foreign_key = "channel_group_id")]
 #[table_name = "async_races"]
 #[primary_key(race_id)]
@@ -40,6 +85,38 @@ pub struct AsyncRaceData {
     pub race_type: RaceType,
     pub race_info: String,
     pub race_url: Option<String>,
+    // the role made just for this race when its group uses race roles
+    pub race_role_id: Option<u64>,
+    // comma separated steps that failed when the race was stopped
+    pub stop_pending: Option<String>,
+    // when the race is meant to end, if it was given one
+    pub race_deadline: Option<NaiveDateTime>,
+    pub race_sort: LeaderboardSort,
+    // fields runners fill in after their time when the race uses a game template
+    pub race_fields: Option<String>,
+    // times are hidden from the leaderboard channel until the race is stopped
+    pub race_blind: bool,
+    // the command that started the race. unique so a message seen twice can't start two.
+    pub source_message_id: Option<u64>,
+    // whether runners are reminded before the deadline
+    pub race_reminders: bool,
+    // reminders that came due before this were already sent or came before the race
+    // started
+    pub last_reminder_at: Option<NaiveDateTime>,
+    // runners !claim the seed to get it in a DM and are timed from then
+    pub race_claim: bool,
+    // live races start together after a countdown at this time and runners are timed
+    // from it
+    pub race_live_at: Option<NaiveDateTime>,
+    pub live_countdown_sent: bool,
+    // text from after " | " in the start command, shown above the race post and
+    // leaderboard
+    pub race_announcement: Option<String>,
+    // the preset the seed was rolled with, shown in !raceinfo
+    pub race_preset: Option<String>,
+    // the final results list runners in random order without times and the real
+    // standings are only sent to the mod who stopped the race
+    pub race_shuffle: bool,
 }
 
 #[derive(Debug, Insertable)]
@@ -52,6 +129,19 @@ pub struct NewAsyncRaceData {
     pub race_type: RaceType,
     pub race_info: String,
     pub race_url: Option<String>,
+    pub race_deadline: Option<NaiveDateTime>,
+    pub race_sort: LeaderboardSort,
+    pub race_fields: Option<String>,
+    pub race_blind: bool,
+    pub source_message_id: Option<u64>,
+    pub race_reminders: bool,
+    pub last_reminder_at: Option<NaiveDateTime>,
+    pub race_claim: bool,
+    pub race_live_at: Option<NaiveDateTime>,
+    pub live_countdown_sent: bool,
+    pub race_announcement: Option<String>,
+    pub race_preset: Option<String>,
+    pub race_shuffle: bool,
 }
 
 impl NewAsyncRaceData {
@@ -59,8 +149,8 @@ impl NewAsyncRaceData {
         game: &BoxedGame,
         group_id: &[u8],
         race_type: RaceType,
-    ) -> Result<Self, BoxedError> {
-        let todays_date = Utc::now().date_naive();
+    ) -> Result<Self, MurahdahlaError> {
+        let now = Utc::now().naive_utc();
         let settings_string = game.settings_str()?;
         let maybe_url: Option<String> = match game.has_url() {
             true => Some(game.game_url().unwrap().to_owned()),
@@ -70,23 +160,53 @@ impl NewAsyncRaceData {
         Ok(NewAsyncRaceData {
             channel_group_id: group_id.to_vec(),
             race_active: true,
-            race_date: todays_date,
+            race_date: now.date(),
             race_game: game.game_name(),
             race_type,
             race_info: settings_string,
             race_url: maybe_url,
+            race_deadline: None,
+            race_sort: LeaderboardSort::default(),
+            race_fields: None,
+            race_blind: false,
+            source_message_id: None,
+            race_reminders: true,
+            last_reminder_at: Some(now),
+            race_claim: false,
+            race_live_at: None,
+            live_countdown_sent: false,
+            race_announcement: None,
+            race_preset: None,
+            race_shuffle: false,
         })
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, FromSqlRow)]
-pub enum GameName {
-    ALTTPR,
-    SMZ3,
-    FF4FE,
-    SMVARIA,
-    SMTotal,
-    Other,
+// a game as it's stored in the database and shown in race posts. everything else about
+// it comes from its provider, see registry.rs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, FromSqlRow)]
+pub struct GameName(&'static str);
+
+// named like the enum variants these used to be so matching on them reads the same
+#[allow(non_upper_case_globals)]
+impl GameName {
+    pub const ALTTPR: GameName = GameName::new("ALTTPR");
+    pub const SMZ3: GameName = GameName::new("SMZ3");
+    pub const FF4FE: GameName = GameName::new("FF4 FE");
+    pub const SMVARIA: GameName = GameName::new("SM VARIA");
+    pub const SMTotal: GameName = GameName::new("SM Total");
+    pub const OoTR: GameName = GameName::new("OoTR");
+    pub const SMMapRando: GameName = GameName::new("SM Map Rando");
+    pub const Other: GameName = GameName::new("Other");
+
+    // games other than these make their own name in their module for their provider
+    pub const fn new(display_name: &'static str) -> Self {
+        GameName(display_name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
 }
 
 impl<DB> FromSql<Text, DB> for GameName
@@ -95,14 +215,13 @@ where
     String: FromSql<Text, DB>,
 {
     fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
-        match String::from_sql(bytes)?.as_str() {
-            "ALTTPR" => Ok(GameName::ALTTPR),
-            "SMZ3" => Ok(GameName::SMZ3),
-            "FF4 FE" => Ok(GameName::FF4FE),
-            "SM VARIA" => Ok(GameName::SMVARIA),
-            "SM Total" => Ok(GameName::SMTotal),
-            "Other" => Ok(GameName::Other),
-            x => Err(format!("Unrecognized game name: {}", x).into()),
+        let name = String::from_sql(bytes)?;
+        match registered_games()
+            .iter()
+            .find(|p| p.name().as_str() == name)
+        {
+            Some(p) => Ok(p.name()),
+            None => Err(format!("Unrecognized game name: {}", name).into()),
         }
     }
 }
@@ -125,14 +244,18 @@ impl<'a> AsExpression<Text> for &'a GameName {
 
 impl fmt::Display for GameName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            GameName::ALTTPR => write!(f, "ALTTPR"),
-            GameName::SMZ3 => write!(f, "SMZ3"),
-            GameName::FF4FE => write!(f, "FF4 FE"),
-            GameName::SMVARIA => write!(f, "SM VARIA"),
-            GameName::SMTotal => write!(f, "SM Total"),
-            GameName::Other => write!(f, "Other"),
-        }
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for GameName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // accepts the names we display, ignoring case, so group yaml can say "alttpr"
+        provider_by_name(s)
+            .map(|p| p.name())
+            .ok_or_else(|| anyhow!("Unrecognized game name: {}", s))
     }
 }
 
@@ -181,12 +304,92 @@ impl fmt::Display for RaceType {
     }
 }
 
+// how a race's leaderboard is ordered
+#[derive(Debug, Copy, Clone, PartialEq, FromSqlRow)]
+pub enum LeaderboardSort {
+    // fastest first, ties go to the lower collection rate
+    Time,
+    // highest collection rate first, then fastest. for collect-a-thon events.
+    Collection,
+    // highest score first, then fastest. runners put their score at the end of their
+    // submission.
+    Score,
+    // untimed. runners send "done" when they finish and are ranked by when they sent it.
+    Completion,
+}
+
+impl Default for LeaderboardSort {
+    fn default() -> Self {
+        LeaderboardSort::Time
+    }
+}
+
+impl<DB> FromSql<Text, DB> for LeaderboardSort
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "time" => Ok(LeaderboardSort::Time),
+            "collection" => Ok(LeaderboardSort::Collection),
+            "score" => Ok(LeaderboardSort::Score),
+            "completion" => Ok(LeaderboardSort::Completion),
+            x => Err(format!("Unrecognized leaderboard sort: {}", x).into()),
+        }
+    }
+}
+
+impl AsExpression<Text> for LeaderboardSort {
+    type Expression = AsExprOf<String, Text>;
+
+    fn as_expression(self) -> Self::Expression {
+        <String as AsExpression<Text>>::as_expression(self.to_string())
+    }
+}
+
+impl<'a> AsExpression<Text> for &'a LeaderboardSort {
+    type Expression = AsExprOf<String, Text>;
+
+    fn as_expression(self) -> Self::Expression {
+        <String as AsExpression<Text>>::as_expression(self.to_string())
+    }
+}
+
+impl fmt::Display for LeaderboardSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LeaderboardSort::Time => write!(f, "time"),
+            LeaderboardSort::Collection => write!(f, "collection"),
+            LeaderboardSort::Score => write!(f, "score"),
+            LeaderboardSort::Completion => write!(f, "completion"),
+        }
+    }
+}
+
+impl FromStr for LeaderboardSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "time" => Ok(LeaderboardSort::Time),
+            "collection" => Ok(LeaderboardSort::Collection),
+            "score" => Ok(LeaderboardSort::Score),
+            "completion" => Ok(LeaderboardSort::Completion),
+            x => Err(anyhow!(
+                "Unrecognized leaderboard sort \"{}\", expected time, collection, score or completion",
+                x
+            )),
+        }
+    }
+}
+
 pub trait AsyncGame {
     // returns the name of the game played (eg ALTTPR, FF4 FE, SMZ3, etc)
     fn game_name(&self) -> GameName;
 
     // returns a string with some information about settings or full flags
-    fn settings_str(&self) -> Result<String, BoxedError>;
+    fn settings_str(&self) -> Result<String, MurahdahlaError>;
 
     // whether this game has an associated url.
     fn has_url(&self) -> bool;
@@ -195,44 +398,272 @@ pub trait AsyncGame {
     fn game_url(&self) -> Option<&str>;
 }
 
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StartFlags {
+    pub sort: LeaderboardSort,
+    pub template: Option<String>,
+    pub deadline: Option<ChronoDuration>,
+    pub blind: bool,
+    pub no_reminders: bool,
+    pub claim: bool,
+    // how long until a live race's countdown ends
+    pub live: Option<ChronoDuration>,
+    // the preset the seed was rolled with. !roll fills this in.
+    pub preset: Option<String>,
+    pub shuffle: bool,
+}
+
+impl StartFlags {
+    pub fn take(input: &str) -> Result<(Self, &str)> {
+        // flags go before the url or game info in any order. "--other" is left for
+        // get_game_boxed since it has to be the last thing before the game info.
+        let mut flags = StartFlags::default();
+        let mut rest = input.trim_start();
+        while let Some((flag, after)) = next_word(rest) {
+            rest = match flag {
+                SORT_FLAG => {
+                    let (sort, after) = next_word(after).ok_or_else(|| {
+                        anyhow!(
+                            "{} needs time, collection, score or completion after it",
+                            SORT_FLAG
+                        )
+                    })?;
+                    flags.sort = LeaderboardSort::from_str(sort)?;
+                    after
+                }
+                TEMPLATE_FLAG => {
+                    let (name, after) = next_word(after).ok_or_else(|| {
+                        anyhow!("{} needs a template name after it", TEMPLATE_FLAG)
+                    })?;
+                    flags.template = Some(name.to_owned());
+                    after
+                }
+                DEADLINE_FLAG => {
+                    let (deadline, after) = next_word(after).ok_or_else(|| {
+                        anyhow!("{} needs a length like 3d or 12h after it", DEADLINE_FLAG)
+                    })?;
+                    flags.deadline = Some(parse_deadline(deadline)?);
+                    after
+                }
+                BLIND_FLAG => {
+                    flags.blind = true;
+                    after
+                }
+                NO_REMINDERS_FLAG => {
+                    flags.no_reminders = true;
+                    after
+                }
+                CLAIM_FLAG => {
+                    flags.claim = true;
+                    after
+                }
+                SHUFFLE_FLAG => {
+                    flags.shuffle = true;
+                    after
+                }
+                LIVE_FLAG => {
+                    let (start_in, after) = next_word(after).ok_or_else(|| {
+                        anyhow!(
+                            "{} needs how long until the start like 10m after it",
+                            LIVE_FLAG
+                        )
+                    })?;
+                    flags.live = Some(parse_deadline(start_in)?);
+                    after
+                }
+                PRESET_FLAG => {
+                    let (name, after) = next_word(after)
+                        .ok_or_else(|| anyhow!("{} needs a preset name after it", PRESET_FLAG))?;
+                    flags.preset = Some(name.to_owned());
+                    after
+                }
+                TEAMS_FLAG => return Err(anyhow!("Team races aren't supported yet")),
+                _ => break,
+            };
+        }
+
+        Ok((flags, rest))
+    }
+}
+
+pub fn next_word(input: &str) -> Option<(&str, &str)> {
+    // splits off the first whitespace separated word and returns it with what's left
+    let input = input.trim_start();
+    match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => Some((word, rest.trim_start())),
+        None if input.is_empty() => None,
+        None => Some((input, "")),
+    }
+}
+
+pub fn split_announcement(args_str: &str) -> Result<(String, Option<String>)> {
+    // "[URL or game info] | [announcement]". everything after the first separator is the
+    // announcement so it can have its own pipes in it.
+    let (game_info, announcement) = match args_str.split_once(ANNOUNCEMENT_SEPARATOR) {
+        Some((g, a)) => (g.trim(), a.trim()),
+        None => return Ok((args_str.to_owned(), None)),
+    };
+    if announcement.chars().count() > MAX_ANNOUNCEMENT_LENGTH {
+        return Err(anyhow!(
+            "Race announcements can be at most {} characters",
+            MAX_ANNOUNCEMENT_LENGTH
+        ));
+    }
+    let announcement = Some(announcement.to_owned()).filter(|a| !a.is_empty());
+
+    Ok((game_info.to_owned(), announcement))
+}
+
+pub fn parse_deadline(deadline: &str) -> Result<ChronoDuration> {
+    // a number followed by m, h, d or w
+    let deadline = deadline.trim().to_lowercase();
+    let err = || {
+        anyhow!(
+            "\"{}\" isn't a deadline. Use a length like 90m, 12h, 3d or 1w, up to {} days",
+            deadline,
+            MAX_DEADLINE_DAYS
+        )
+    };
+    let unit = deadline.chars().last().ok_or_else(err)?;
+    let amount: i64 = deadline[..deadline.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| err())?;
+    // checked before building the duration since chrono panics on huge ones
+    if amount <= 0 || amount > MAX_DEADLINE_DAYS * 24 * 60 {
+        return Err(err());
+    }
+    let length = match unit {
+        'm' => ChronoDuration::minutes(amount),
+        'h' => ChronoDuration::hours(amount),
+        'd' => ChronoDuration::days(amount),
+        'w' => ChronoDuration::weeks(amount),
+        _ => return Err(err()),
+    };
+    if length > ChronoDuration::days(MAX_DEADLINE_DAYS) {
+        return Err(err());
+    }
+
+    Ok(length)
+}
+
 pub fn determine_game(args_str: &str) -> GameName {
     // we parse as a url here just to determine the game then discard the url
-    // TODO: if we have, say, a festive alttpr url without /h/, we could make it an
-    // other game
-    let game_url = match Url::parse(args_str) {
-        Ok(u) => u,
-        Err(_) => return GameName::Other,
+    match Url::parse(args_str) {
+        Ok(u) => provider_for_url(&u).map_or(GameName::Other, |p| p.name()),
+        Err(_) => GameName::Other,
+    }
+}
+
+pub fn seed_id(args_str: &str, game: GameName) -> Result<String> {
+    // every site we fetch from puts the seed id at the end of the url path. check that
+    // it at least looks like an id for that site so a mangled link gets a clear error
+    // instead of a failed request.
+    let seed_url = Url::parse(args_str).map_err(|e| anyhow!("Invalid seed url: {}", e))?;
+    let id = seed_url
+        .path_segments()
+        .and_then(|mut s| s.rfind(|p| !p.is_empty()))
+        .ok_or_else(|| anyhow!("Couldn't find a seed id in the url"))?;
+    match provider(game).valid_seed_id(id) {
+        true => Ok(id.to_owned()),
+        false => Err(anyhow!("\"{}\" isn't a valid {} seed id", id, game)),
+    }
+}
+
+pub async fn get_game_boxed(
+    ctx: &Context,
+    game_info: &str,
+    seed_json: Option<&[u8]>,
+) -> Result<BoxedGame, MurahdahlaError> {
+    // "--other" skips game detection entirely so a race can still be started with
+    // just the url when a seed site is down or doesn't know about the seed
+    if let Some(other_str) = game_info.strip_prefix(OTHER_FLAG) {
+        return Ok(Box::new(OtherGame::new_from_str(other_str.trim_start())?));
+    }
+    if let Some(json_bytes) = seed_json {
+        return get_game_from_json(game_info, json_bytes);
+    }
+    let game_category = determine_game(game_info);
+    if game_category == GameName::Other {
+        return Ok(Box::new(OtherGame::new_from_str(game_info)?));
+    }
+    let game_provider = provider(game_category);
+    if !game_provider.fetches_seeds() {
+        return Err(anyhow!("Tried to start unknown game").into());
+    }
+    if !api_available(ctx, game_category).await {
+        warn!(
+            "{} API breaker is open, starting race with just the url",
+            game_category
+        );
+        return Ok(Box::new(OtherGame::new_from_str(game_info)?));
+    }
+    let started = Instant::now();
+    let game: Result<BoxedGame, MurahdahlaError> = game_provider.fetch(game_info.to_owned()).await;
+    record_api_call(ctx, game_category, started.elapsed(), &game).await;
+
+    game.map_err(|e| {
+        SeedError {
+            game: game_category,
+            url: game_info.to_owned(),
+            message: e.to_string(),
+        }
+        .into()
+    })
+}
+
+fn get_game_from_json(args_str: &str, json_bytes: &[u8]) -> Result<BoxedGame, MurahdahlaError> {
+    // builds a game from seed data attached to the start command instead of fetching it.
+    // the url is optional here since locally generated seeds won't have one.
+    let seed: Value = serde_json::from_slice(json_bytes)
+        .map_err(|e| anyhow!("Error parsing attached seed JSON: {}", e))?;
+    let maybe_url: Option<String> = match args_str.trim() {
+        "" => None,
+        u => Some(u.to_owned()),
     };
-    match game_url.host_str() {
-        Some(g) if (g == "alttpr.com" && game_url.path().contains("/h/")) => GameName::ALTTPR,
-        Some(g) if (g == "samus.link" && game_url.path().contains("/seed")) => GameName::SMZ3,
-        Some(g) if (g == "sm.samus.link" && game_url.path().contains("/seed")) => GameName::SMTotal,
-        Some(g)
-            if ((g == "randommetroidsolver.pythonanywhere.com"
-                || g == "varia.run"
-                || g == "variabeta.pythonanywhere.com")
-                && game_url.path().contains("/customizer")) =>
-        {
-            GameName::SMVARIA
+    let game_category = match maybe_url.as_deref().map(determine_game) {
+        Some(GameName::Other) | None => {
+            provider_for_json(&seed).map_or(GameName::Other, |p| p.name())
+        }
+        Some(g) => g,
+    };
+    match provider(game_category) {
+        p if p.reads_seed_json() => p.game_from_json(seed, maybe_url),
+        _ => {
+            let supported: Vec<&str> = registered_games()
+                .iter()
+                .filter(|p| p.reads_seed_json())
+                .map(|p| p.name().as_str())
+                .collect();
+            Err(anyhow!(
+                "Attached seed JSON is only supported for {} games",
+                supported.join(" and ")
+            )
+            .into())
         }
-        // Some(g) if g == "ff4fe.com" => GameName::FF4FE,
-        Some(_) => GameName::Other,
-        None => GameName::Other,
     }
 }
 
-pub async fn get_game_boxed(args: &Args) -> Result<BoxedGame, BoxedError> {
-    let game_category = determine_game(args.rest());
-    match game_category {
-        GameName::ALTTPR => Ok(Box::new(Z3rGame::new_from_str(args.rest()).await?)),
-        GameName::SMZ3 => Ok(Box::new(SMZ3Game::new_from_str(args.rest()).await?)),
-        GameName::SMTotal => Ok(Box::new(SMTotalGame::new_from_str(args.rest()).await?)),
-        GameName::SMVARIA => Ok(Box::new(SMVARIAGame::new_from_str(args.rest()).await?)),
-        GameName::Other => Ok(Box::new(OtherGame::new_from_str(args.rest())?)),
-        _ => Err(anyhow!("Tried to start unknown game").into()),
+// error returned when we recognize a seed url but can't get or parse its data. we keep
+// the url around so we can offer to start the race without seed information.
+#[derive(Debug)]
+pub struct SeedError {
+    pub game: GameName,
+    pub url: String,
+    pub message: String,
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Error retrieving {} seed data for <{}>: {}",
+            self.game, self.url, self.message
+        )
     }
 }
 
+impl std::error::Error for SeedError {}
+
 pub fn get_maybe_active_race(conn: &PooledConn, group: &ChannelGroup) -> Option<AsyncRaceData> {
     use crate::schema::async_races::columns::*;
 
@@ -242,19 +673,98 @@ pub fn get_maybe_active_race(conn: &PooledConn, group: &ChannelGroup) -> Option<
         .ok()
 }
 
+pub fn insert_race(
+    conn: &PooledConn,
+    new_race: &NewAsyncRaceData,
+) -> Result<Option<AsyncRaceData>> {
+    // None when the message that started this race has already started one
+    use crate::schema::async_races::columns::*;
+    use crate::schema::async_races::dsl::async_races;
+
+    match diesel::insert_into(async_races)
+        .values(new_race)
+        .execute(conn)
+    {
+        Ok(_) => (),
+        Err(e) if is_duplicate_key(&e, SOURCE_MESSAGE_INDEX) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    // we need to pull this back out for the race id
+    let race: AsyncRaceData = async_races
+        .filter(channel_group_id.eq(&new_race.channel_group_id))
+        .filter(race_active.eq(true))
+        .get_result(conn)?;
+
+    Ok(Some(race))
+}
+
+pub fn race_started_by(conn: &PooledConn, message_id: u64) -> Result<bool> {
+    use crate::schema::async_races::columns::*;
+    use crate::schema::async_races::dsl::async_races;
+    use diesel::dsl::{exists, select};
+
+    Ok(select(exists(async_races.filter(source_message_id.eq(message_id)))).get_result(conn)?)
+}
+
+pub fn find_seed_reuse(
+    conn: &PooledConn,
+    server_id: u64,
+    seed_url: &str,
+) -> Result<Vec<AsyncRaceData>> {
+    // earlier races anywhere on the server that used the same seed. the url has the seed's
+    // hash in it so comparing urls is enough.
+    use crate::schema::{async_races, channels};
+
+    let races: Vec<AsyncRaceData> = async_races::table
+        .inner_join(channels::table)
+        .filter(channels::server_id.eq(server_id))
+        .filter(async_races::race_url.eq(seed_url))
+        .select(async_races::all_columns)
+        .order(async_races::race_date.desc())
+        .load(conn)?;
+
+    Ok(races)
+}
+
+pub fn mark_stopped(conn: &PooledConn, race: &AsyncRaceData) -> Result<()> {
+    // if this fails nothing else has happened yet and the race is still running
+    use crate::schema::async_races;
+
+    diesel::update(race)
+        .set(async_races::race_active.eq(false))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// the race's settings are cut down to max_settings characters in all of these, see
+// ChannelGroup::max_settings_chars
 pub trait DataDisplay {
-    fn base_string(&self) -> String;
+    fn base_string(&self, max_settings: usize) -> String;
 
-    fn leaderboard_string(&self) -> String;
+    fn leaderboard_string(&self, max_settings: usize) -> String;
+
+    // what's posted in the submission channel. claim races keep the seed out of it.
+    fn announcement_string(&self, max_settings: usize) -> String;
 }
 
 impl DataDisplay for NewAsyncRaceData {
-    fn base_string(&self) -> String {
+    // this needs to match the AsyncRaceData impl below so race previews show exactly
+    // what will be posted
+    fn base_string(&self, max_settings: usize) -> String {
         let mut base_game_string = format!("{} - ", self.race_date);
         if self.race_game != GameName::Other {
-            base_game_string.push_str(format!("{} - ", self.race_game).as_str());
+            base_game_string.push_str(format!("{} ", self.race_game).as_str());
         }
-        base_game_string.push_str(format!("({}) - {}", self.race_type, self.race_info).as_str());
+        base_game_string.push_str(
+            format!(
+                "({}) - {}",
+                self.race_type,
+                sanitize_settings(&self.race_info, max_settings)
+            )
+            .as_str(),
+        );
         if self.race_url.is_some() {
             base_game_string.push_str(format!(" - <{}>", self.race_url.as_ref().unwrap()).as_str());
         }
@@ -262,23 +772,42 @@ impl DataDisplay for NewAsyncRaceData {
         base_game_string
     }
 
-    fn leaderboard_string(&self) -> String {
-        let base_game_string = self.base_string();
-        let lb_string = format!("Leaderboard for {}", base_game_string);
+    fn leaderboard_string(&self, max_settings: usize) -> String {
+        let base_game_string = self.base_string(max_settings);
+        let mut lb_string = format!("Leaderboard for {}", base_game_string);
+        push_sort_note(&mut lb_string, self.race_sort);
+        push_deadline_note(&mut lb_string, self.race_deadline);
 
-        lb_string
+        with_announcement(lb_string, self.race_announcement.as_deref())
+    }
+
+    fn announcement_string(&self, max_settings: usize) -> String {
+        let mut announcement = match self.race_claim {
+            true => claim_announcement(self.race_date, self.race_game, self.race_type),
+            false => self.base_string(max_settings),
+        };
+        push_live_note(&mut announcement, self.race_live_at);
+
+        with_announcement(announcement, self.race_announcement.as_deref())
     }
 }
 
 impl DataDisplay for AsyncRaceData {
     // we could maybe return &str instead of Strings here and maybe save a bit of
     // memory?
-    fn base_string(&self) -> String {
+    fn base_string(&self, max_settings: usize) -> String {
         let mut base_game_string = format!("{} - ", self.race_date);
         if self.race_game != GameName::Other {
             base_game_string.push_str(format!("{} ", self.race_game).as_str());
         }
-        base_game_string.push_str(format!("({}) - {}", self.race_type, self.race_info).as_str());
+        base_game_string.push_str(
+            format!(
+                "({}) - {}",
+                self.race_type,
+                sanitize_settings(&self.race_info, max_settings)
+            )
+            .as_str(),
+        );
         if self.race_url.is_some() {
             base_game_string.push_str(format!(" - <{}>", self.race_url.as_ref().unwrap()).as_str());
         }
@@ -286,10 +815,208 @@ impl DataDisplay for AsyncRaceData {
         base_game_string
     }
 
-    fn leaderboard_string(&self) -> String {
-        let base_game_string = self.base_string();
-        let lb_string = format!("Leaderboard for {}", base_game_string);
+    fn leaderboard_string(&self, max_settings: usize) -> String {
+        let base_game_string = self.base_string(max_settings);
+        let mut lb_string = format!("Leaderboard for {}", base_game_string);
+        push_sort_note(&mut lb_string, self.race_sort);
+        push_deadline_note(&mut lb_string, self.race_deadline);
+
+        with_announcement(lb_string, self.race_announcement.as_deref())
+    }
+
+    fn announcement_string(&self, max_settings: usize) -> String {
+        let mut announcement = match self.race_claim {
+            true => claim_announcement(self.race_date, self.race_game, self.race_type),
+            false => self.base_string(max_settings),
+        };
+        push_live_note(&mut announcement, self.race_live_at);
+
+        with_announcement(announcement, self.race_announcement.as_deref())
+    }
+}
+
+fn claim_announcement(date: NaiveDate, game: GameName, race_type: RaceType) -> String {
+    let mut announcement = format!("{} - ", date);
+    if game != GameName::Other {
+        announcement.push_str(format!("{} ", game).as_str());
+    }
+    announcement.push_str(
+        format!(
+            "({}) - Use !claim here to get the seed. Your time starts when it's sent to you.",
+            race_type
+        )
+        .as_str(),
+    );
+
+    announcement
+}
+
+#[inline]
+fn push_sort_note(lb_string: &mut String, sort: LeaderboardSort) {
+    match sort {
+        LeaderboardSort::Time => (),
+        LeaderboardSort::Collection => lb_string.push_str(" - ranked by collection rate"),
+        LeaderboardSort::Score => lb_string.push_str(" - ranked by score"),
+        LeaderboardSort::Completion => lb_string.push_str(" - untimed, ranked by finish order"),
+    };
+}
+
+#[inline]
+fn push_deadline_note(lb_string: &mut String, deadline: Option<NaiveDateTime>) {
+    if let Some(d) = deadline {
+        lb_string.push_str(format!(" - ends {} UTC", d.format("%Y-%m-%d %H:%M")).as_str());
+    }
+}
+
+pub fn sanitize_settings(settings: &str, max_chars: usize) -> String {
+    // settings come from seed sites and whoever started the race, so they're put on one
+    // line, kept from pinging anyone and escaped so stray markdown doesn't run into the
+    // rest of the header. links are left alone since escaping them breaks them. long
+    // settings are cut short before escaping so the backslashes don't count.
+    let settings = settings.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let settings = match settings.chars().count() > max_chars {
+        true => {
+            let cut: String = settings
+                .chars()
+                .take(max_chars.saturating_sub(SETTINGS_ELLIPSIS.len()))
+                .collect();
+            format!("{}{}", cut.trim_end(), SETTINGS_ELLIPSIS)
+        }
+        false => settings,
+    };
+
+    settings
+        .split(' ')
+        .map(
+            |word| match word.starts_with("https://") || word.starts_with("http://") {
+                true => word.to_owned(),
+                false => escape_markdown(word.chars()),
+            },
+        )
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[inline]
+fn with_announcement(text: String, announcement: Option<&str>) -> String {
+    match announcement {
+        Some(a) => format!("**{}**\n{}", a, text),
+        None => text,
+    }
+}
+
+#[inline]
+fn push_live_note(announcement: &mut String, live_at: Option<NaiveDateTime>) {
+    // discord shows the timestamp in each reader's own timezone and counts down to it
+    if let Some(l) = live_at {
+        announcement.push_str(
+            format!(
+                " - live race starting <t:{0}:R> (<t:{0}:t>). Send \".done\" when you finish",
+                l.timestamp()
+            )
+            .as_str(),
+        );
+    }
+}
+
+pub async fn fetch_with_retry<F, Fut>(mut request: F) -> reqwest::Result<Response>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<Response>>,
+{
+    // retries a request with exponential backoff when the failure looks transient (timeouts,
+    // connection errors, 5xx responses.) anything else is returned immediately.
+    let mut attempt: u32 = 1;
+    loop {
+        let result = request().await.and_then(|r| r.error_for_status());
+        match result {
+            Err(e) if attempt < API_ATTEMPTS && is_transient(&e) => {
+                let delay = API_BACKOFF_MS * 2u64.pow(attempt - 1) + jitter_ms(API_JITTER_MS);
+                warn!(
+                    "Seed API request failed (attempt {}/{}), retrying in {}ms: {}",
+                    attempt, API_ATTEMPTS, delay, e
+                );
+                sleep(Duration::from_millis(delay)).await;
+                attempt += 1;
+            }
+            r => return r,
+        }
+    }
+}
+
+#[inline]
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.status().map_or(false, |s| s.is_server_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_flags_in_any_order() {
+        let (flags, rest) =
+            StartFlags::take("--blind --deadline 3d --sort collection https://alttpr.com/h/abc")
+                .unwrap();
+        assert_eq!(flags.sort, LeaderboardSort::Collection);
+        assert_eq!(flags.deadline, Some(ChronoDuration::days(3)));
+        assert!(flags.blind);
+        assert_eq!(rest, "https://alttpr.com/h/abc");
+
+        assert!(StartFlags::take("--teams https://alttpr.com/h/abc").is_err());
+        assert!(StartFlags::take("--sort").is_err());
+        let (flags, rest) = StartFlags::take("https://alttpr.com/h/abc --blind").unwrap();
+        assert!(!flags.blind);
+        assert_eq!(rest, "https://alttpr.com/h/abc --blind");
+    }
+
+    #[test]
+    fn announcement_after_separator() {
+        let (game, announcement) =
+            split_announcement("https://alttpr.com/h/abc | Week 7 | Enemizer Special!").unwrap();
+        assert_eq!(game, "https://alttpr.com/h/abc");
+        assert_eq!(announcement.as_deref(), Some("Week 7 | Enemizer Special!"));
+        let (game, announcement) = split_announcement("https://alttpr.com/h/abc").unwrap();
+        assert_eq!(game, "https://alttpr.com/h/abc");
+        assert_eq!(announcement, None);
+        assert!(split_announcement(&format!("abc | {}", "a".repeat(201))).is_err());
+    }
+
+    #[test]
+    fn settings_are_sanitized_and_cut() {
+        assert_eq!(
+            sanitize_settings("Open\n**Hard**  `ganon` @everyone", 100),
+            "Open \\*\\*Hard\\*\\* \\`ganon\\` @\u{200B}everyone"
+        );
+        // links keep their underscores
+        assert_eq!(
+            sanitize_settings("see https://example.com/a_b for_it", 100),
+            "see https://example.com/a_b for\\_it"
+        );
+        assert_eq!(
+            sanitize_settings("Normal Open Ganon", 17),
+            "Normal Open Ganon"
+        );
+        // the ellipsis counts towards the limit and trailing spaces are dropped first
+        assert_eq!(sanitize_settings("Normal Open Ganon", 15), "Normal Open...");
+        assert_eq!(sanitize_settings("日本語のプリセット", 7), "日本語の...");
+        assert_eq!(sanitize_settings("a_b_c_d_e", 8), "a\\_b\\_c...");
+
+        let game: BoxedGame = Box::new(OtherGame::new_from_str(&"x".repeat(50)).unwrap());
+        let race = NewAsyncRaceData::new_from_game(&game, &[], RaceType::RTA).unwrap();
+        assert!(race
+            .leaderboard_string(20)
+            .ends_with(&format!("(RTA) - {}...", "x".repeat(17))));
+    }
 
-        lb_string
+    #[test]
+    fn deadlines() {
+        assert_eq!(parse_deadline("90m").unwrap(), ChronoDuration::minutes(90));
+        assert_eq!(parse_deadline("1W").unwrap(), ChronoDuration::weeks(1));
+        assert!(parse_deadline("5w").is_err());
+        assert!(parse_deadline("0d").is_err());
+        assert!(parse_deadline("3 days").is_err());
+        assert!(parse_deadline("99999999999999h").is_err());
+        assert!(parse_deadline("").is_err());
     }
 }
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Result};
+use reqwest;
+use serde_json::Value;
+use url::Url;
+
+use crate::{
+    config::read_env,
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider},
+        AsyncGame, BoxedGame, GameName,
+    },
+};
+
+const API_URL: &str = "https://ootrandomizer.com/api/v2/seed/details";
+// like fe, the ootr api needs a key. races still start with just the url without one.
+pub const API_KEY_VAR: &str = "OOTR_API_KEY";
+const UNKNOWN_SETTINGS: &str = "(settings unavailable)";
+
+#[derive(Debug, Clone)]
+pub struct OoTRGame {
+    // the seed details from the api. None when there's no api key to ask with.
+    details: Option<Value>,
+    url: String,
+}
+
+impl OoTRGame {
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let id = ootr_seed_id(args_str)?;
+        let url = args_str.to_string();
+        let details = match read_env(API_KEY_VAR)?.filter(|k| !k.is_empty()) {
+            Some(key) => Some(get_details(&key, &id).await?),
+            None => {
+                warn!(
+                    "Starting OoTR race without settings: {} isn't set",
+                    API_KEY_VAR
+                );
+                None
+            }
+        };
+
+        Ok(OoTRGame { details, url })
+    }
+}
+
+fn ootr_seed_id(args_str: &str) -> Result<String> {
+    // seed links are ootrandomizer.com/seed/get?id=... so the id is in the query string
+    let seed_url = Url::parse(args_str).map_err(|e| anyhow!("Invalid seed url: {}", e))?;
+    let id = seed_url
+        .query_pairs()
+        .find(|(k, _)| k == "id")
+        .map(|(_, v)| v.into_owned())
+        .ok_or_else(|| anyhow!("Couldn't find a seed id in the url"))?;
+    match valid_seed_id(&id) {
+        true => Ok(id),
+        false => Err(anyhow!("\"{}\" isn't a valid OoTR seed id", id)),
+    }
+}
+
+async fn get_details(key: &str, id: &str) -> Result<Value> {
+    let params = [("key", key), ("id", id)];
+    let client = reqwest::Client::new();
+    let details = fetch_with_retry(|| client.get(API_URL).query(&params).send())
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+    if !details["settings"].is_object() {
+        return Err(anyhow!("Unrecognized OoTR API response"));
+    }
+
+    Ok(details)
+}
+
+fn bridge_condition(settings: &Value) -> String {
+    let count = |field: &str| {
+        settings[field]
+            .as_u64()
+            .map_or_else(|| "?".to_owned(), |c| c.to_string())
+    };
+    match settings["bridge"].as_str() {
+        Some("open") => "Open Bridge".to_owned(),
+        Some("vanilla") => "Vanilla Bridge".to_owned(),
+        Some("stones") => format!("{} Stones Bridge", count("bridge_stones")),
+        Some("medallions") => format!("{} Medallions Bridge", count("bridge_medallions")),
+        Some("dungeons") => format!("{} Dungeons Bridge", count("bridge_rewards")),
+        Some("tokens") => format!("{} Tokens Bridge", count("bridge_tokens")),
+        Some("hearts") => format!("{} Hearts Bridge", count("bridge_hearts")),
+        _ => "Unknown Bridge".to_owned(),
+    }
+}
+
+fn is_on(setting: &Value) -> bool {
+    // older versions use booleans for some options that are "off" or a mode name now
+    match setting {
+        Value::Bool(b) => *b,
+        Value::String(s) => s != "off",
+        _ => false,
+    }
+}
+
+impl AsyncGame for OoTRGame {
+    fn game_name(&self) -> GameName {
+        GameName::OoTR
+    }
+
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        let details = match &self.details {
+            Some(d) => d,
+            None => return Ok(UNKNOWN_SETTINGS.to_owned()),
+        };
+        let settings = &details["settings"];
+        let logic = match settings["logic_rules"].as_str() {
+            Some("glitchless") => "Glitchless",
+            Some("glitched") => "Glitched",
+            Some("none") => "No Logic",
+            _ => "Unknown Logic",
+        };
+        let version = details["version"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Error parsing OoTR version"))?;
+        let mut game_string = format!("v{} {} {}", version, logic, bridge_condition(settings));
+        if settings["triforce_hunt"].as_bool() == Some(true) {
+            game_string.push_str(" Triforce Hunt");
+        }
+        if is_on(&settings["shuffle_dungeon_entrances"]) {
+            game_string.push_str(" Dungeon ER");
+        }
+        if is_on(&settings["shuffle_overworld_entrances"]) {
+            game_string.push_str(" Overworld ER");
+        }
+
+        Ok(game_string)
+    }
+
+    fn has_url(&self) -> bool {
+        true
+    }
+
+    fn game_url(&self) -> Option<&str> {
+        Some(&self.url)
+    }
+}
+
+pub struct OoTRProvider;
+
+impl GameProvider for OoTRProvider {
+    fn name(&self) -> GameName {
+        GameName::OoTR
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        valid_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    matches!(
+        game_url.host_str(),
+        Some("ootrandomizer.com") | Some("www.ootrandomizer.com")
+    ) && game_url.path() == "/seed/get"
+}
+
+fn valid_seed_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(async move { Ok(Box::new(OoTRGame::new_from_str(&args_str).await?) as BoxedGame) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_from_seed_details() {
+        let details: Value =
+            serde_json::from_str(include_str!("../../tests/data/ootr/details.json")).unwrap();
+        let game = OoTRGame {
+            details: Some(details),
+            url: "https://ootrandomizer.com/seed/get?id=1482503".to_owned(),
+        };
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "v8.1.0 Glitchless 4 Medallions Bridge Dungeon ER"
+        );
+        assert_eq!(
+            ootr_seed_id("https://ootrandomizer.com/seed/get?id=1482503").unwrap(),
+            "1482503"
+        );
+        assert!(ootr_seed_id("https://ootrandomizer.com/seed/get?id=abc").is_err());
+    }
+}
@@ -1,17 +1,47 @@
+use std::fmt;
+
 use anyhow::{anyhow, Result};
 
 use crate::{
-    games::{AsyncGame, GameName},
-    helpers::BoxedError,
+    discord::submissions::Submission,
+    error::MurahdahlaError,
+    games::{registry::GameProvider, AsyncGame, GameName},
 };
 
+// other games are whatever we couldn't recognize so nothing matches them. they're
+// started directly in get_game_boxed instead of through a fetch.
+pub struct OtherProvider;
+
+impl GameProvider for OtherProvider {
+    fn name(&self) -> GameName {
+        GameName::Other
+    }
+
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_other(s, f)
+    }
+}
+
+fn format_other(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // races started from a template keep the runner's fields in option_text. added
+    // games can take a collection rate too.
+    write!(f, "{} - {}", s.runner_name, s.runner_time.unwrap())?;
+    if let Some(c) = s.runner_collection {
+        write!(f, " - {}", c)?;
+    }
+    match &s.option_text {
+        Some(t) => write!(f, " - {}", t),
+        None => Ok(()),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OtherGame {
     text: String,
 }
 
 impl OtherGame {
-    pub fn new_from_str(args_str: &str) -> Result<Self, BoxedError> {
+    pub fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
         // arbitrary but lets make sure the string here isn't *too* long
         if args_str.len() > 400usize {
             return Err(anyhow!("String for other game is too long").into());
@@ -28,7 +58,7 @@ impl AsyncGame for OtherGame {
         GameName::Other
     }
 
-    fn settings_str(&self) -> Result<String, BoxedError> {
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
         Ok(self.text.clone())
     }
 
@@ -0,0 +1,227 @@
+use std::{fmt, future::Future, pin::Pin};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use serde_json::Value;
+use url::Url;
+
+use crate::{
+    discord::submissions::{NewSubmission, Submission},
+    error::MurahdahlaError,
+    games::{
+        ff4fe::FF4FEProvider, ootr::OoTRProvider, other::OtherProvider,
+        smmaprando::SMMapRandoProvider, smtotal::SMTotalProvider, smvaria::SMVARIAProvider,
+        smz3::SMZ3Provider, z3r::Z3rProvider, BoxedGame, GameName,
+    },
+};
+
+pub type GameFuture = Pin<Box<dyn Future<Output = Result<BoxedGame, MurahdahlaError>> + Send>>;
+
+// resolves to the url of the new seed
+pub type RollFuture = Pin<Box<dyn Future<Output = Result<String, MurahdahlaError>> + Send>>;
+
+pub type BoxedProvider = Box<dyn GameProvider>;
+
+// everything the bot needs to know about a game outside of the game's own module. adding
+// a game means implementing this for it and registering it at startup, see
+// builtin_games. the defaults are for a game the bot can only show a link for.
+pub trait GameProvider: Send + Sync {
+    // also how the game is stored in the database and shown in race posts
+    fn name(&self) -> GameName;
+
+    // whether a seed url is from this game's site
+    fn matches_url(&self, _game_url: &Url) -> bool {
+        false
+    }
+
+    // whether the last segment of a seed url looks like one of this game's seed ids
+    fn valid_seed_id(&self, _id: &str) -> bool {
+        true
+    }
+
+    // whether fetch can look up seeds on the game's site
+    fn fetches_seeds(&self) -> bool {
+        false
+    }
+
+    // fetches a seed's information from the game's site
+    fn fetch(&self, _args_str: String) -> GameFuture {
+        let e = unsupported(self.name(), "look up");
+        Box::pin(async move { Err(e) })
+    }
+
+    // whether game_from_json can build a game from attached seed json
+    fn reads_seed_json(&self) -> bool {
+        false
+    }
+
+    // whether attached seed json looks like it came from this game
+    fn matches_json(&self, _seed: &Value) -> bool {
+        false
+    }
+
+    // builds a game from attached seed json and an optional url
+    fn game_from_json(
+        &self,
+        _seed: Value,
+        _url: Option<String>,
+    ) -> Result<BoxedGame, MurahdahlaError> {
+        Err(unsupported(self.name(), "read attached"))
+    }
+
+    // whether roll can generate seeds from a roll preset's settings
+    fn rolls_seeds(&self) -> bool {
+        false
+    }
+
+    // generates a new seed on the game's site from a roll preset's settings
+    fn roll(&self, _settings: Value) -> RollFuture {
+        let e = unsupported(self.name(), "roll");
+        Box::pin(async move { Err(e) })
+    }
+
+    // reads anything after the time in a submission, e.g. a collection rate
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        _msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        Ok(submission)
+    }
+
+    // writes a submission the way it's shown on the leaderboard
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_time_only(s, f)
+    }
+}
+
+fn unsupported(game: GameName, action: &str) -> MurahdahlaError {
+    anyhow!("The bot can't {} {} seeds", action, game).into()
+}
+
+static GAMES: OnceCell<Vec<BoxedProvider>> = OnceCell::new();
+
+// lookups take the first match so other has to go last, it's what's left when nothing
+// else matches
+pub fn builtin_games() -> Vec<BoxedProvider> {
+    vec![
+        Box::new(Z3rProvider),
+        Box::new(SMZ3Provider),
+        Box::new(FF4FEProvider),
+        Box::new(SMVARIAProvider),
+        Box::new(SMTotalProvider),
+        Box::new(OoTRProvider),
+        Box::new(SMMapRandoProvider),
+        Box::new(OtherProvider),
+    ]
+}
+
+pub fn register_games(providers: Vec<BoxedProvider>) -> Result<()> {
+    // called once at startup before anything reads from the database. two providers for a
+    // game or two games with the same name would make one of them unreachable.
+    for (i, p) in providers.iter().enumerate() {
+        let name = p.name().as_str();
+        if providers[i + 1..]
+            .iter()
+            .any(|o| o.name().as_str().eq_ignore_ascii_case(name))
+        {
+            return Err(anyhow!("{} was registered twice", name));
+        }
+    }
+    match providers.last().map(|p| p.name()) {
+        Some(GameName::Other) => (),
+        _ => {
+            return Err(anyhow!(
+                "{} has to be the last game registered",
+                GameName::Other
+            ))
+        }
+    }
+
+    GAMES
+        .set(providers)
+        .map_err(|_| anyhow!("Games were already registered"))
+}
+
+pub fn registered_games() -> &'static [BoxedProvider] {
+    // tests and anything else that never went through startup get the built in games
+    GAMES.get_or_init(builtin_games)
+}
+
+pub fn provider(game: GameName) -> &'static dyn GameProvider {
+    provider_by_name(game.as_str()).expect("Game missing from registry")
+}
+
+pub fn provider_by_name(name: &str) -> Option<&'static dyn GameProvider> {
+    registered_games()
+        .iter()
+        .find(|p| p.name().as_str().eq_ignore_ascii_case(name))
+        .map(|p| p.as_ref())
+}
+
+pub fn provider_for_url(game_url: &Url) -> Option<&'static dyn GameProvider> {
+    registered_games()
+        .iter()
+        .find(|p| p.matches_url(game_url))
+        .map(|p| p.as_ref())
+}
+
+pub fn provider_for_json(seed: &Value) -> Option<&'static dyn GameProvider> {
+    registered_games()
+        .iter()
+        .find(|p| p.reads_seed_json() && p.matches_json(seed))
+        .map(|p| p.as_ref())
+}
+
+// formats shared by more than one game
+
+pub fn format_time_only(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} - {}", s.runner_name, s.runner_time.unwrap())
+}
+
+pub fn format_untimed(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // completion races show when the runner finished instead of a time, whatever the game
+    write!(
+        f,
+        "{} - done {} UTC",
+        s.runner_name,
+        s.submission_datetime.format("%b %-d %H:%M")
+    )?;
+    if let Some(c) = s.runner_collection {
+        write!(f, " - {}", c)?;
+    }
+    match &s.option_text {
+        Some(t) => write!(f, " - {}", t),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_is_consistent() {
+        for p in registered_games() {
+            assert_eq!(provider(p.name()).name(), p.name());
+            assert_eq!(
+                provider_by_name(p.name().as_str()).unwrap().name(),
+                p.name()
+            );
+        }
+        assert!(register_games(builtin_games()).is_err());
+    }
+
+    #[test]
+    fn registration_is_checked() {
+        let mut twice = builtin_games();
+        twice.insert(0, Box::new(Z3rProvider));
+        assert_eq!(
+            register_games(twice).unwrap_err().to_string(),
+            "ALTTPR was registered twice"
+        );
+        let mut other_first = builtin_games();
+        other_first.rotate_right(1);
+        assert!(register_games(other_first).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use std::cmp::Ordering;
+
+use crate::{
+    discord::submissions::Submission,
+    games::{AsyncRaceData, LeaderboardSort},
+};
+
+// how a race ranks its finishers. the leaderboard, results and archive only ever ask a
+// race's scoring to sort, so a new way of ranking runners only needs an impl here and a
+// case in scoring_for.
+pub trait RaceScoring: Sync {
+    // what finishers are ranked on, best first
+    fn compare(&self, a: &Submission, b: &Submission) -> Ordering;
+
+    // settles finishers that compare equal. the faster time unless a scoring says otherwise
+    fn tiebreak(&self, a: &Submission, b: &Submission) -> Ordering {
+        a.runner_time.cmp(&b.runner_time)
+    }
+
+    fn sort(&self, leaderboard: &mut [Submission]) {
+        leaderboard.sort_by(|a, b| self.compare(a, b).then_with(|| self.tiebreak(a, b)));
+    }
+}
+
+// fastest first, ties go to the lower collection rate
+pub struct TimeScoring;
+
+impl RaceScoring for TimeScoring {
+    fn compare(&self, a: &Submission, b: &Submission) -> Ordering {
+        a.runner_time.cmp(&b.runner_time)
+    }
+
+    fn tiebreak(&self, a: &Submission, b: &Submission) -> Ordering {
+        a.runner_collection
+            .cmp(&b.runner_collection)
+            .then(a.option_number.cmp(&b.option_number))
+    }
+}
+
+// highest collection rate first, then fastest
+pub struct CollectionScoring;
+
+impl RaceScoring for CollectionScoring {
+    fn compare(&self, a: &Submission, b: &Submission) -> Ordering {
+        b.runner_collection.cmp(&a.runner_collection)
+    }
+}
+
+// highest score first, then fastest
+pub struct ScoreScoring;
+
+impl RaceScoring for ScoreScoring {
+    fn compare(&self, a: &Submission, b: &Submission) -> Ordering {
+        b.option_number.cmp(&a.option_number)
+    }
+}
+
+// whoever sent "done" first. completion races have no times to break ties with.
+pub struct CompletionScoring;
+
+impl RaceScoring for CompletionScoring {
+    fn compare(&self, a: &Submission, b: &Submission) -> Ordering {
+        a.submission_datetime.cmp(&b.submission_datetime)
+    }
+
+    fn tiebreak(&self, a: &Submission, b: &Submission) -> Ordering {
+        a.submission_id.cmp(&b.submission_id)
+    }
+}
+
+pub fn scoring_for(race: &AsyncRaceData) -> &'static dyn RaceScoring {
+    scoring_for_sort(race.race_sort)
+}
+
+pub fn scoring_for_sort(sort: LeaderboardSort) -> &'static dyn RaceScoring {
+    match sort {
+        LeaderboardSort::Time => &TimeScoring,
+        LeaderboardSort::Collection => &CollectionScoring,
+        LeaderboardSort::Score => &ScoreScoring,
+        LeaderboardSort::Completion => &CompletionScoring,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{discord::submissions::RunTime, testing::test_submission};
+
+    fn finisher(name: &str, time_ms: u64, collection: u16) -> Submission {
+        Submission {
+            runner_name: name.to_owned(),
+            runner_collection: Some(collection),
+            runner_time: Some(RunTime::from_millis(time_ms)),
+            ..test_submission()
+        }
+    }
+
+    #[test]
+    fn scorings_rank_with_their_tiebreaks() {
+        let mut lb = vec![
+            finisher("a", 2_000, 216),
+            finisher("b", 1_000, 200),
+            finisher("c", 1_000, 190),
+            finisher("d", 1_500, 216),
+        ];
+        let names = |lb: &[Submission]| -> Vec<String> {
+            lb.iter().map(|s| s.runner_name.clone()).collect()
+        };
+        scoring_for_sort(LeaderboardSort::Time).sort(&mut lb);
+        assert_eq!(names(&lb), vec!["c", "b", "d", "a"]);
+        scoring_for_sort(LeaderboardSort::Collection).sort(&mut lb);
+        assert_eq!(names(&lb), vec!["d", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn completion_ranks_by_finish_order_without_times() {
+        let mut lb = vec![
+            finisher("a", 0, 0),
+            finisher("b", 0, 0),
+            finisher("c", 0, 0),
+        ];
+        lb.iter_mut().enumerate().for_each(|(i, s)| {
+            s.submission_id = i as u32;
+            s.runner_time = None;
+            s.runner_collection = None;
+        });
+        lb[0].submission_datetime += chrono::Duration::minutes(5);
+        scoring_for_sort(LeaderboardSort::Completion).sort(&mut lb);
+        let names: Vec<&str> = lb.iter().map(|s| s.runner_name.as_str()).collect();
+        assert_eq!(names, vec!["b", "c", "a"]);
+        assert_eq!(lb[2].to_string(), "a - done Jan 1 00:05 UTC");
+    }
+}
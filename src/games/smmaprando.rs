@@ -0,0 +1,215 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use reqwest::get;
+use serde_json::Value;
+use url::Url;
+
+use crate::{
+    discord::submissions::{NewSubmission, Submission},
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider},
+        seed_id, AsyncGame, BoxedGame, GameName,
+    },
+};
+
+// every seed's settings are served next to the seed itself
+const BASE_URL: &str = "https://maprando.com/seed/";
+const SETTINGS_FILE: &str = "data/settings.json";
+const UNKNOWN_SETTINGS: &str = "(settings unavailable)";
+
+#[derive(Debug, Clone)]
+pub struct SMMapRandoGame {
+    // None for seeds from versions that didn't save their settings as json
+    settings: Option<Value>,
+    url: String,
+}
+
+impl SMMapRandoGame {
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let seed_name = seed_id(args_str, GameName::SMMapRando)?;
+        let url = args_str.to_string();
+        let settings = match get_settings(&seed_name).await {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn!("Starting SM Map Rando race without settings: {}", e);
+                None
+            }
+        };
+
+        Ok(SMMapRandoGame { settings, url })
+    }
+}
+
+async fn get_settings(seed_name: &str) -> Result<Value> {
+    let url = format!("{}{}/{}", BASE_URL, seed_name, SETTINGS_FILE);
+    let settings = fetch_with_retry(|| get(&url))
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+
+    Ok(settings)
+}
+
+pub struct SMMapRandoItemRate(u16);
+
+impl TryFrom<u16> for SMMapRandoItemRate {
+    type Error = MurahdahlaError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        if value > 100 {
+            Err(anyhow!("SM Map Rando item percentage not between 0 - 100").into())
+        } else {
+            Ok(SMMapRandoItemRate(value))
+        }
+    }
+}
+
+impl From<SMMapRandoItemRate> for u16 {
+    fn from(c: SMMapRandoItemRate) -> Self {
+        c.0
+    }
+}
+
+impl AsyncGame for SMMapRandoGame {
+    fn game_name(&self) -> GameName {
+        GameName::SMMapRando
+    }
+
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        let settings = match &self.settings {
+            Some(s) => s,
+            None => return Ok(UNKNOWN_SETTINGS.to_owned()),
+        };
+        // presets are the names shown on the site. a customized section has no preset.
+        let skill = settings["skill_assumption_settings"]["preset"]
+            .as_str()
+            .unwrap_or("Custom");
+        let progression = settings["item_progression_settings"]["preset"]
+            .as_str()
+            .unwrap_or("Custom");
+        let mut game_string = format!("{} Skill / {} Items", skill, progression);
+        if let Some(map) = settings["map_layout"].as_str() {
+            game_string.push_str(&format!(" / {} Map", map));
+        }
+        if let Some(doors) = settings["doors_mode"].as_str() {
+            game_string.push_str(&format!(" / {} Doors", doors));
+        }
+
+        Ok(game_string)
+    }
+
+    fn has_url(&self) -> bool {
+        true
+    }
+
+    fn game_url(&self) -> Option<&str> {
+        Some(&self.url)
+    }
+}
+
+pub struct SMMapRandoProvider;
+
+impl GameProvider for SMMapRandoProvider {
+    fn name(&self) -> GameName {
+        GameName::SMMapRando
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        valid_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        game_info(submission, msg)
+    }
+
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_submission(s, f)
+    }
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    matches!(
+        game_url.host_str(),
+        Some("maprando.com") | Some("www.maprando.com")
+    ) && game_url.path().starts_with("/seed/")
+}
+
+fn valid_seed_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(
+        async move { Ok(Box::new(SMMapRandoGame::new_from_str(&args_str).await?) as BoxedGame) },
+    )
+}
+
+pub fn game_info<'a>(
+    submission: &'a mut NewSubmission,
+    msg: &[&str],
+) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+    // runners give the item percentage from the end screen, with or without the "%"
+    if msg.len() != 1 {
+        return Err(anyhow!("SM Map Rando submission did not include item percentage.").into());
+    }
+
+    let number = u16::from_str(msg[0].trim_end_matches('%')).map_err(MurahdahlaError::user)?;
+    let collection = SMMapRandoItemRate::try_from(number)?;
+    submission.set_collection(Some(collection));
+
+    Ok(submission)
+}
+
+fn format_submission(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "{} - {} - {}%",
+        s.runner_name,
+        s.runner_time.unwrap(),
+        s.runner_collection.unwrap()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_from_seed_settings() {
+        let settings: Value =
+            serde_json::from_str(include_str!("../../tests/data/smmaprando/settings.json"))
+                .unwrap();
+        let game = SMMapRandoGame {
+            settings: Some(settings),
+            url: "https://maprando.com/seed/h2k7m1xq9e3b/".to_owned(),
+        };
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "Hard Skill / Tricky Items / Standard Map / Ammo Doors"
+        );
+        assert_eq!(
+            seed_id(&game.url, GameName::SMMapRando).unwrap(),
+            "h2k7m1xq9e3b"
+        );
+    }
+}
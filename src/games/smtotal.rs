@@ -1,16 +1,23 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use base64;
 use reqwest::get;
 use serde::Deserialize;
 use serde_json::{from_str, Value};
+use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    discord::submissions::NewSubmission,
-    games::{AsyncGame, GameName},
-    helpers::BoxedError,
+    discord::submissions::{NewSubmission, Submission},
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider},
+        seed_id,
+        smz3::samus_link_seed_id,
+        AsyncGame, BoxedGame, GameName,
+    },
 };
 
 const BASE_URL: &str = "https://sm.samus.link/api/seed/";
@@ -28,9 +35,9 @@ pub struct SMTotalSettings {
 }
 
 impl SMTotalGame {
-    pub async fn new_from_str(args_str: &str) -> Result<Self, BoxedError> {
-        let game_slug: &str = args_str.split('/').last().unwrap();
-        let map = get_seed(game_slug).await?;
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let game_slug = seed_id(args_str, GameName::SMTotal)?;
+        let map = get_seed(&game_slug).await?;
         let url = args_str.to_string(); // we've already parsed this as a url and should know it's good
         let game = SMTotalGame { map, url };
 
@@ -46,7 +53,7 @@ async fn get_seed(slug: &str) -> Result<Value> {
     let guid = Uuid::from_slice(&guid_vec)?;
     let guid_str = guid.as_simple().encode_lower(&mut buf);
     let url = format!("{}{}", BASE_URL, guid_str);
-    let seed = get(&url).await?.json().await?;
+    let seed = fetch_with_retry(|| get(&url)).await?.json().await?;
 
     Ok(seed)
 }
@@ -54,7 +61,7 @@ async fn get_seed(slug: &str) -> Result<Value> {
 pub struct SMTotalCollectionRate(u16);
 
 impl TryFrom<u16> for SMTotalCollectionRate {
-    type Error = BoxedError;
+    type Error = MurahdahlaError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         if value > 316 {
@@ -76,7 +83,7 @@ impl AsyncGame for SMTotalGame {
         GameName::SMTotal
     }
 
-    fn settings_str(&self) -> Result<String, BoxedError> {
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
         let settings_map = &self
             .map
             .as_object()
@@ -126,18 +133,72 @@ impl AsyncGame for SMTotalGame {
     }
 }
 
+pub struct SMTotalProvider;
+
+impl GameProvider for SMTotalProvider {
+    fn name(&self) -> GameName {
+        GameName::SMTotal
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        samus_link_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        game_info(submission, msg)
+    }
+
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_submission(s, f)
+    }
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    game_url.host_str() == Some("sm.samus.link") && game_url.path().contains("/seed")
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(async move { Ok(Box::new(SMTotalGame::new_from_str(&args_str).await?) as BoxedGame) })
+}
+
 pub fn game_info<'a>(
     submission: &'a mut NewSubmission,
-    msg: &Vec<&str>,
-) -> Result<&'a mut NewSubmission, BoxedError> {
+    msg: &[&str],
+) -> Result<&'a mut NewSubmission, MurahdahlaError> {
     // make sure there's enough elements in the vec to maybe use
     if msg.len() != 1 {
         return Err(anyhow!("SM (Total) submission did not include collection rate.").into());
     }
 
-    let number = u16::from_str(msg[0])?;
+    let number = u16::from_str(msg[0]).map_err(MurahdahlaError::user)?;
     let collection = SMTotalCollectionRate::try_from(number)?;
     submission.set_collection(Some(collection));
 
     Ok(submission)
 }
+
+fn format_submission(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "{} - {} - {}%",
+        s.runner_name,
+        s.runner_time.unwrap(),
+        s.runner_collection.unwrap()
+    )
+}
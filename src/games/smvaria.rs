@@ -1,60 +1,145 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use reqwest;
+use serde::Deserialize;
 use serde_json::Value;
+use url::Url;
+use uuid::Uuid;
 
 use crate::{
-    discord::submissions::NewSubmission,
-    games::{AsyncGame, GameName},
-    helpers::BoxedError,
+    discord::submissions::{NewSubmission, Submission},
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider},
+        seed_id, AsyncGame, BoxedGame, GameName,
+    },
 };
 
-// const BASE_URL: &'static str = "https://randommetroidsolver.pythonanywhere.com/customizer";
-const API_URL: &str = "https://variabeta.pythonanywhere.com/randoParamsWebServiceAPI";
+// each site that hosts the customizer has its own copy of the api
+const STABLE_API_URL: &str = "https://varia.run/randoParamsWebServiceAPI";
+const LEGACY_API_URL: &str =
+    "https://randommetroidsolver.pythonanywhere.com/randoParamsWebServiceAPI";
+const BETA_API_URL: &str = "https://variabeta.pythonanywhere.com/randoParamsWebServiceAPI";
+// what we show for the settings when we couldn't make sense of the api's response
+const UNKNOWN_SETTINGS: &str = "(settings unavailable)";
 
 #[derive(Debug, Clone)]
 pub struct SMVARIAGame {
-    map: Value,
+    // None when the api answered with something we don't understand. the race is still
+    // started with just the url.
+    settings: Option<VariaSettings>,
     url: String,
 }
 
+// the seed parameters we use. the older api sends every option as an "on"/"off" string
+// while newer versions can send booleans, so toggles accept either.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariaSettings {
+    preset: String,
+    majors_split: String,
+    #[serde(default)]
+    area_randomization: Toggle,
+    #[serde(default)]
+    boss_randomization: Toggle,
+    #[serde(default)]
+    doors_colors_rando: Toggle,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Toggle {
+    Bool(bool),
+    Text(String),
+}
+
+impl Default for Toggle {
+    fn default() -> Self {
+        Toggle::Bool(false)
+    }
+}
+
+impl Toggle {
+    fn is_on(&self) -> bool {
+        match self {
+            Toggle::Bool(b) => *b,
+            Toggle::Text(t) => t == "on" || t == "true",
+        }
+    }
+}
+
+// the first versions of the api return the parameters as a json string inside the
+// response. current ones return the object itself.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum VariaResponse {
+    Current(VariaSettings),
+    Legacy(String),
+}
+
 impl SMVARIAGame {
-    pub async fn new_from_str(args_str: &str) -> Result<Self, BoxedError> {
-        let game_slug: &str = args_str.split('/').last().unwrap();
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let game_slug = seed_id(args_str, GameName::SMVARIA)?;
         let url = args_str.to_string();
-        let map = get_seed(game_slug).await?;
-        let game = SMVARIAGame { map, url };
+        let response = get_seed(api_url(args_str), &game_slug).await?;
+        let settings = match parse_settings(response) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn!("Starting SM VARIA race without settings: {}", e);
+                None
+            }
+        };
+        let game = SMVARIAGame { settings, url };
 
         Ok(game)
     }
 }
 
-async fn get_seed(slug: &str) -> Result<Value> {
+fn api_url(args_str: &str) -> &'static str {
+    // ask the site the seed came from. anything we don't recognize goes to the stable
+    // site since that's where seeds are generated now.
+    match Url::parse(args_str).ok().as_ref().and_then(Url::host_str) {
+        Some("variabeta.pythonanywhere.com") => BETA_API_URL,
+        Some("randommetroidsolver.pythonanywhere.com") => LEGACY_API_URL,
+        _ => STABLE_API_URL,
+    }
+}
+
+async fn get_seed(api_url: &str, slug: &str) -> Result<Value> {
     let params = [("guid", &slug)];
     let client = reqwest::Client::new();
-    let json_str: String = client
-        .post(API_URL)
-        .header("Content-Type", "application/json")
-        .form(&params)
-        .send()
-        .await?
-        .json::<Value>()
-        .await?
-        .as_str()
-        .ok_or_else(|| anyhow!("Error parsing VARIA API response as str"))?
-        .to_owned();
+    let response = fetch_with_retry(|| {
+        client
+            .post(api_url)
+            .header("Content-Type", "application/json")
+            .form(&params)
+            .send()
+    })
+    .await?
+    .json::<Value>()
+    .await?;
 
-    // feel like there's a better way but I couldn't figure this out
-    let seed = Value::from_str(&json_str)?;
+    Ok(response)
+}
 
-    Ok(seed)
+pub fn parse_settings(response: Value) -> Result<VariaSettings> {
+    let settings = match serde_json::from_value::<VariaResponse>(response)
+        .map_err(|_| anyhow!("Unrecognized VARIA API response"))?
+    {
+        VariaResponse::Current(s) => s,
+        VariaResponse::Legacy(json_str) => serde_json::from_str::<VariaSettings>(&json_str)
+            .map_err(|e| anyhow!("Error parsing VARIA API response: {}", e))?,
+    };
+
+    Ok(settings)
 }
 
 pub struct SMVARIACollectionRate(u16);
 
 impl TryFrom<u16> for SMVARIACollectionRate {
-    type Error = BoxedError;
+    type Error = MurahdahlaError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         if value > 316 {
@@ -76,43 +161,25 @@ impl AsyncGame for SMVARIAGame {
         GameName::SMVARIA
     }
 
-    fn settings_str(&self) -> Result<String, BoxedError> {
-        let game_json = &self
-            .map
-            .as_object()
-            .ok_or_else(|| anyhow!("Error parsing sm.samus.link response as Object"))?;
-        let skill_preset = game_json["preset"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing VARIA response"))?;
-        let split: &str = match game_json["majorsSplit"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing VARIA response"))?
-        {
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        let settings = match &self.settings {
+            Some(s) => s,
+            None => return Ok(UNKNOWN_SETTINGS.to_owned()),
+        };
+        let split: &str = match settings.majors_split.as_str() {
             "Major" => "Major/Minor",
             "Full" => "Full",
             "Chozo" => "Chozo",
             _ => "Unknown Item Split",
         };
-        let mut base_settings = format!("\"{}\" {} ", skill_preset, split);
-        if game_json["areaRandomization"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing game state"))?
-            == "on"
-        {
+        let mut base_settings = format!("\"{}\" {} ", settings.preset, split);
+        if settings.area_randomization.is_on() {
             base_settings.push_str("Area Rando ")
         }
-        if game_json["bossRandomization"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing VARIA response"))?
-            == "on"
-        {
+        if settings.boss_randomization.is_on() {
             base_settings.push_str("Boss Rando ")
         }
-        if game_json["doorsColorsRando"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing VARIA response"))?
-            == "on"
-        {
+        if settings.doors_colors_rando.is_on() {
             base_settings.push_str("Door Color Rando ")
         }
 
@@ -128,18 +195,81 @@ impl AsyncGame for SMVARIAGame {
     }
 }
 
+pub struct SMVARIAProvider;
+
+impl GameProvider for SMVARIAProvider {
+    fn name(&self) -> GameName {
+        GameName::SMVARIA
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        valid_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        game_info(submission, msg)
+    }
+
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_submission(s, f)
+    }
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    matches!(
+        game_url.host_str(),
+        Some("randommetroidsolver.pythonanywhere.com")
+            | Some("varia.run")
+            | Some("variabeta.pythonanywhere.com")
+    ) && game_url.path().contains("/customizer")
+}
+
+fn valid_seed_id(id: &str) -> bool {
+    Uuid::parse_str(id).is_ok()
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(async move { Ok(Box::new(SMVARIAGame::new_from_str(&args_str).await?) as BoxedGame) })
+}
+
 pub fn game_info<'a>(
     submission: &'a mut NewSubmission,
-    msg: &Vec<&str>,
-) -> Result<&'a mut NewSubmission, BoxedError> {
+    msg: &[&str],
+) -> Result<&'a mut NewSubmission, MurahdahlaError> {
     // make sure there's enough elements in the vec to maybe use
     if msg.len() != 1 {
         return Err(anyhow!("SM VARIA submission did not include collection rate.").into());
     }
 
-    let number = u16::from_str(msg[0])?;
+    let number = u16::from_str(msg[0]).map_err(MurahdahlaError::user)?;
     let collection = SMVARIACollectionRate::try_from(number)?;
     submission.set_collection(Some(collection));
 
     Ok(submission)
 }
+
+fn format_submission(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "{} - {} - {}%",
+        s.runner_name,
+        s.runner_time.unwrap(),
+        s.runner_collection.unwrap()
+    )
+}
@@ -1,24 +1,31 @@
-use std::{default::Default, str::FromStr};
+use std::{default::Default, fmt, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use base64;
 use reqwest::get;
 use serde::Deserialize;
 use serde_json::{from_str, Value};
+use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    discord::submissions::NewSubmission,
-    games::{AsyncGame, GameName},
-    helpers::BoxedError,
+    discord::submissions::{NewSubmission, Submission},
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider, RollFuture},
+        seed_id, AsyncGame, BoxedGame, GameName,
+    },
 };
 
 const BASE_URL: &str = "https://samus.link/api/seed/";
+const ROLL_URL: &str = "https://samus.link/api/randomizers/smz3/generate";
+const SEED_URL: &str = "https://samus.link/seed/";
 
 #[derive(Debug, Clone)]
 pub struct SMZ3Game {
     map: Value,
-    url: String,
+    url: Option<String>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -44,10 +51,18 @@ pub struct SMZ3Settings {
 // }
 
 impl SMZ3Game {
-    pub async fn new_from_str(args_str: &str) -> Result<Self, BoxedError> {
-        let game_slug: &str = args_str.split('/').last().unwrap();
-        let map = get_seed(game_slug).await?;
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let game_slug = seed_id(args_str, GameName::SMZ3)?;
+        let map = get_seed(&game_slug).await?;
         let url = args_str.to_string(); // we've already parsed this as a url and should know it's good
+
+        SMZ3Game::new_from_json(map, Some(url))
+    }
+
+    pub fn new_from_json(map: Value, url: Option<String>) -> Result<Self, MurahdahlaError> {
+        if !map.is_object() {
+            return Err(anyhow!("SMZ3 seed data is not a JSON object").into());
+        }
         let game = SMZ3Game { map, url };
 
         Ok(game)
@@ -62,7 +77,7 @@ async fn get_seed(slug: &str) -> Result<Value> {
     let guid = Uuid::from_slice(&guid_vec)?;
     let guid_str = guid.as_simple().encode_lower(&mut buf);
     let url = format!("{}{}", BASE_URL, guid_str);
-    let seed = get(&url).await?.json().await?;
+    let seed = fetch_with_retry(|| get(&url)).await?.json().await?;
 
     Ok(seed)
 }
@@ -70,7 +85,7 @@ async fn get_seed(slug: &str) -> Result<Value> {
 pub struct SMZ3CollectionRate(u16);
 
 impl TryFrom<u16> for SMZ3CollectionRate {
-    type Error = BoxedError;
+    type Error = MurahdahlaError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         if value > 316 {
@@ -92,7 +107,7 @@ impl AsyncGame for SMZ3Game {
         GameName::SMZ3
     }
 
-    fn settings_str(&self) -> Result<String, BoxedError> {
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
         let settings_map = &self
             .map
             .as_object()
@@ -138,26 +153,163 @@ impl AsyncGame for SMZ3Game {
     }
 
     fn has_url(&self) -> bool {
-        true
+        self.url.is_some()
     }
 
     fn game_url(&self) -> Option<&str> {
-        Some(&self.url)
+        self.url.as_deref()
+    }
+}
+
+pub struct SMZ3Provider;
+
+impl GameProvider for SMZ3Provider {
+    fn name(&self) -> GameName {
+        GameName::SMZ3
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        samus_link_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+
+    fn reads_seed_json(&self) -> bool {
+        true
+    }
+
+    fn matches_json(&self, seed: &Value) -> bool {
+        matches_json(seed)
+    }
+
+    fn game_from_json(
+        &self,
+        seed: Value,
+        url: Option<String>,
+    ) -> Result<BoxedGame, MurahdahlaError> {
+        from_json(seed, url)
+    }
+
+    fn rolls_seeds(&self) -> bool {
+        true
     }
+
+    fn roll(&self, settings: Value) -> RollFuture {
+        roll(settings)
+    }
+
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        game_info(submission, msg)
+    }
+
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_submission(s, f)
+    }
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    game_url.host_str() == Some("samus.link") && game_url.path().contains("/seed")
+}
+
+pub fn samus_link_seed_id(id: &str) -> bool {
+    // samus.link uses url-safe base64 of a uuid with the padding cut off
+    id.len() == 22
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(async move { Ok(Box::new(SMZ3Game::new_from_str(&args_str).await?) as BoxedGame) })
+}
+
+fn roll(settings: Value) -> RollFuture {
+    Box::pin(async move {
+        let client = reqwest::Client::new();
+        let seed: Value = fetch_with_retry(|| client.post(ROLL_URL).json(&settings).send())
+            .await?
+            .json()
+            .await?;
+        let guid = seed["guid"]
+            .as_str()
+            .ok_or_else(|| anyhow!("samus.link didn't send back a seed"))?;
+
+        Ok(format!("{}{}", SEED_URL, seed_slug(guid)?))
+    })
+}
+
+fn seed_slug(guid: &str) -> Result<String> {
+    // the reverse of get_seed. seed links use the guid as url-safe base64 without padding.
+    let guid = Uuid::parse_str(guid)?;
+    let slug = base64::encode_config(guid.as_bytes(), base64::URL_SAFE);
+
+    Ok(slug.trim_end_matches('=').to_owned())
+}
+
+fn matches_json(seed: &Value) -> bool {
+    // samus.link seeds have a list of worlds with their settings
+    seed.get("worlds").is_some() && seed.get("hash").is_some()
+}
+
+fn from_json(seed: Value, url: Option<String>) -> Result<BoxedGame, MurahdahlaError> {
+    Ok(Box::new(SMZ3Game::new_from_json(seed, url)?))
 }
 
 pub fn game_info<'a>(
     submission: &'a mut NewSubmission,
-    msg: &Vec<&str>,
-) -> Result<&'a mut NewSubmission, BoxedError> {
+    msg: &[&str],
+) -> Result<&'a mut NewSubmission, MurahdahlaError> {
     // make sure there's enough elements in the vec to maybe use
     if msg.len() != 1 {
         return Err(anyhow!("SMZ3 submission did not include collection rate.").into());
     }
 
-    let number = u16::from_str(msg[0])?;
+    let number = u16::from_str(msg[0]).map_err(MurahdahlaError::user)?;
     let collection = SMZ3CollectionRate::try_from(number)?;
     submission.set_collection(Some(collection));
 
     Ok(submission)
 }
+
+fn format_submission(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "{} - {} - {}/316",
+        s.runner_name,
+        s.runner_time.unwrap(),
+        s.runner_collection.unwrap()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolled_guid_becomes_seed_slug() {
+        let slug = seed_slug("0f4b8fc9-8c2c-4b3e-9a1d-6f1e2d3c4b5a").unwrap();
+        assert_eq!(slug.len(), 22);
+        assert!(samus_link_seed_id(&slug));
+        // and back again the way get_seed reads it
+        let guid = base64::decode_config(format!("{}==", slug), base64::URL_SAFE).unwrap();
+        assert_eq!(
+            Uuid::from_slice(&guid).unwrap().to_string(),
+            "0f4b8fc9-8c2c-4b3e-9a1d-6f1e2d3c4b5a"
+        );
+        assert!(seed_slug("not a guid").is_err());
+    }
+}
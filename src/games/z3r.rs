@@ -1,16 +1,27 @@
-use std::{convert::TryFrom, str::FromStr};
+use std::{collections::HashMap, convert::TryFrom, fmt, fs, str::FromStr};
 
 use anyhow::{anyhow, Result};
 use reqwest::get;
 use serde_json::{Map, Value};
+use url::Url;
 
 use crate::{
-    discord::submissions::NewSubmission,
-    games::{AsyncGame, GameName},
-    helpers::BoxedError,
+    discord::submissions::{NewSubmission, Submission},
+    error::MurahdahlaError,
+    games::{
+        fetch_with_retry,
+        registry::{GameFuture, GameProvider, RollFuture},
+        seed_id, AsyncGame, BoxedGame, GameName,
+    },
 };
 
 const BASE_URL: &str = "https://alttpr-patch-data.s3.us-east-2.amazonaws.com/";
+// where seeds are generated and where the new seed can be found afterwards
+const ROLL_URL: &str = "https://alttpr.com/api/randomizer";
+const SEED_URL: &str = "https://alttpr.com/h/";
+// door randomizer seeds aren't hosted on alttpr.com. they're shared as links to the web
+// patcher with the url of the .bps patch in the query string.
+const DOORS_PATCHER_HOST: &str = "alttprpatch.synack.live";
 const FILE_SELECT_CODE: u64 = 0x180215; // tables.asm: 1007
 
 const fn code_map(value: u64) -> &'static str {
@@ -55,14 +66,35 @@ const fn code_map(value: u64) -> &'static str {
 pub struct Z3rGame {
     meta: Value,
     patches: Map<String, Value>,
-    url: String,
+    url: Option<String>,
 }
 
 impl Z3rGame {
-    pub async fn new_from_str(args_str: &str) -> Result<Self, BoxedError> {
-        let game_id = args_str.split('/').last().unwrap();
-        let mut meta = get_patch(game_id).await?;
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let game_id = seed_id(args_str, GameName::ALTTPR)?;
+        let meta = get_patch(&game_id).await?;
         let url = args_str.to_string(); // we've already parsed this as a url and should know it's good
+
+        Z3rGame::new_from_json(meta, Some(url))
+    }
+
+    pub fn file_select_code(&self) -> Result<Vec<&'static str>> {
+        get_code(&self.patches)
+    }
+
+    fn is_customizer(&self) -> bool {
+        // the customizer marks its seeds at the top level of the patch data or in the
+        // spoiler meta depending on when it was generated
+        self.meta["customizer"].as_bool() == Some(true)
+            || self.meta["spoiler"]["meta"]["customizer"].as_bool() == Some(true)
+    }
+
+    pub fn new_from_json(mut meta: Value, url: Option<String>) -> Result<Self, MurahdahlaError> {
+        // the json here might be a user-provided attachment so make sure it at least looks
+        // like patch data before we index into it
+        if !meta.is_object() {
+            return Err(anyhow!("ALTTPR seed data is not a JSON object").into());
+        }
         let mut patch_json: Value = meta["patch"].take();
         let patches = patch_to_map(&mut patch_json)?;
         let game = Z3rGame { meta, patches, url };
@@ -71,9 +103,23 @@ impl Z3rGame {
     }
 }
 
+pub async fn verify_code(source: &str) -> Result<String, MurahdahlaError> {
+    // backs the verify-code maintenance subcommand. takes a seed url or a path to a patch
+    // json file and gives back the file select code we'd post for it.
+    let game = match Url::parse(source) {
+        Ok(_) => Z3rGame::new_from_str(source).await?,
+        Err(_) => {
+            let json_str = fs::read_to_string(source)?;
+            Z3rGame::new_from_json(serde_json::from_str(&json_str)?, None)?
+        }
+    };
+
+    Ok(game.file_select_code()?.join("/"))
+}
+
 async fn get_patch(game_id: &str) -> Result<Value> {
     let url = format!("{}{}.json", BASE_URL, game_id);
-    let patch_json = get(&url).await?.json().await?;
+    let patch_json = fetch_with_retry(|| get(&url)).await?.json().await?;
 
     Ok(patch_json)
 }
@@ -81,7 +127,7 @@ async fn get_patch(game_id: &str) -> Result<Value> {
 pub struct Z3rCollectionRate(u16);
 
 impl TryFrom<u16> for Z3rCollectionRate {
-    type Error = BoxedError;
+    type Error = MurahdahlaError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         if value > 216 {
@@ -103,102 +149,162 @@ impl AsyncGame for Z3rGame {
         GameName::ALTTPR
     }
 
-    fn settings_str(&self) -> Result<String, BoxedError> {
-        // TODO: check for "special" here because we need to handle festives etc differently
-        let game_json = &self.meta;
-        let game_patches = &self.patches;
-        if let Ok("mystery") = game_json["spoiler"]["meta"]["spoilers"]
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        let meta = &self.meta["spoiler"]["meta"];
+        let code: Vec<&str> = get_code(&self.patches)?;
+        let code = format!(
+            "({}/{}/{}/{}/{})",
+            code[0], code[1], code[2], code[3], code[4]
+        );
+        if let Ok("mystery") = meta["spoilers"]
             .as_str()
             .ok_or_else(|| anyhow!("Error parsing spoiler meta information"))
         {
-            let code: Vec<&str> = get_code(game_patches)?;
-            return Ok(format!(
-                "Mystery ({}/{}/{}/{}/{})",
-                code[0], code[1], code[2], code[3], code[4]
-            ));
-        };
-        let state = match game_json["spoiler"]["meta"]["mode"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing game state"))?
-        {
-            "open" => "Open",
-            "standard" => "Standard",
-            "inverted" => "Inverted",
-            "retro" => "Retro",
-            _ => "Unknown State",
+            return Ok(format!("Mystery {}", code));
         };
-        let goal = match game_json["spoiler"]["meta"]["goal"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing goal"))?
-        {
-            "ganon" => "Defeat Ganon",
-            "fast_ganon" => "Fast Ganon",
-            "dungeons" => "All Dungeons",
-            "pedestal" => "Pedestal",
-            "triforce-hunt" => "Triforce Hunt",
-            _ => "Unknown Goal",
+        if !self.is_customizer() {
+            return Ok(format!("{}{}", standard_settings(meta)?, code));
+        }
+        // customizer seeds can set things the presets can't, so when we don't understand
+        // them we show the name the seed was given instead of failing to start the race
+        let game_string = match (standard_settings(meta), meta["name"].as_str()) {
+            (Ok(settings), _) => format!("Customizer {}{}", settings, code),
+            (Err(_), Some(name)) if !name.is_empty() => format!("Customizer \"{}\" {}", name, code),
+            (Err(_), _) => format!("Customizer {}", code),
         };
-        let gt_crystals = game_json["spoiler"]["meta"]["entry_crystals_tower"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing GT crystals"))?;
-        let ganon_crystals = game_json["spoiler"]["meta"]["entry_crystals_ganon"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing Ganon crystals"))?;
-        let code: Vec<&str> = get_code(game_patches)?;
 
-        let dungeon_items = match game_json["spoiler"]["meta"]["dungeon_items"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing dungeon item shuffle"))?
-        {
-            "standard" => "Standard ",
-            "mc" => "MC ",
-            "mcs" => "MCS ",
-            "full" => "Keysanity ",
-            _ => "Unknown Dungeon Item Shuffle ",
+        Ok(game_string)
+    }
+
+    fn has_url(&self) -> bool {
+        self.url.is_some()
+    }
+
+    fn game_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DoorRandoGame {
+    patch_name: String,
+    // settings from the spoiler log, which only exists when the seed was generated with
+    // spoilers on. race seeds usually aren't.
+    settings: Option<HashMap<String, String>>,
+    url: String,
+}
+
+impl DoorRandoGame {
+    pub async fn new_from_str(args_str: &str) -> Result<Self, MurahdahlaError> {
+        let patch_url = Url::parse(args_str)
+            .ok()
+            .and_then(|u| door_patch_url(&u))
+            .ok_or_else(|| anyhow!("Couldn't find a door randomizer patch in the url"))?;
+        let patch_name = patch_url
+            .path_segments()
+            .and_then(|mut s| s.next_back())
+            .and_then(|f| f.strip_suffix(".bps"))
+            .ok_or_else(|| anyhow!("Couldn't find a door randomizer patch in the url"))?
+            .to_owned();
+        let settings = match spoiler_url(&patch_url, &patch_name) {
+            Some(url) => match get_spoiler(&url).await {
+                Ok(s) => Some(spoiler_settings(&s)),
+                Err(e) => {
+                    info!("No spoiler log for door randomizer seed: {}", e);
+                    None
+                }
+            },
+            None => None,
         };
-        let mut shuffle = "Vanilla Shuffle ";
-        if game_json["spoiler"]["meta"].get("shuffle") != None {
-            shuffle = match game_json["spoiler"]["meta"]["shuffle"]
-                .as_str()
-                .ok_or_else(|| anyhow!("Error parsing entrance shuffle"))?
-            {
-                "simple" => "Simple Shuffle ",
-                "restricted" => "Restricted Shuffle ",
-                "full" => "Full Shuffle ",
-                "crossed" => "Crossed Shuffle ",
-                "insanity" => "Insanity Shuffle ",
-                _ => "Unknown Shuffle ",
-            };
+
+        Ok(DoorRandoGame {
+            patch_name,
+            settings,
+            url: args_str.to_string(),
+        })
+    }
+}
+
+fn door_patch_url(game_url: &Url) -> Option<Url> {
+    if game_url.host_str() != Some(DOORS_PATCHER_HOST) || game_url.path() != "/patcher.html" {
+        return None;
+    }
+    game_url
+        .query_pairs()
+        .find(|(k, _)| k == "patch")
+        .and_then(|(_, v)| Url::parse(&v).ok())
+        .filter(|u| u.path().ends_with(".bps"))
+}
+
+fn spoiler_url(patch_url: &Url, patch_name: &str) -> Option<String> {
+    // the bot that generates these puts spoilers next to the patches, .../patch/NAME.bps
+    // and .../spoiler/NAME_Spoiler.txt. for anywhere else we don't know where to look.
+    let path = patch_url.path();
+    let dir = path.strip_suffix(&format!("patch/{}.bps", patch_name))?;
+    let mut url = patch_url.clone();
+    url.set_path(&format!("{}spoiler/{}_Spoiler.txt", dir, patch_name));
+    url.set_query(None);
+
+    Some(url.to_string())
+}
+
+async fn get_spoiler(url: &str) -> Result<String> {
+    let spoiler = fetch_with_retry(|| get(url))
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    Ok(spoiler)
+}
+
+fn spoiler_settings(spoiler: &str) -> HashMap<String, String> {
+    // the settings are "Name:   value" lines after the version line at the top of the
+    // log, up to the first blank line after them
+    let mut settings = HashMap::new();
+    for line in spoiler.lines().skip(1) {
+        if line.trim().is_empty() && !settings.is_empty() {
+            break;
         }
-        let logic = match game_json["spoiler"]["meta"]["logic"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Error parsing logic"))?
-        {
-            "NoGlitches" => "No Glitches ",
-            "OverworldGlitches" => "Overworld Glitches ",
-            "Major Glitches" => "Major Glitches ",
-            "None" => "No Logic ",
-            _ => "Unknown Logic ",
-        };
+        if let Some((key, value)) = line.split_once(':') {
+            settings.insert(key.trim().to_lowercase(), value.trim().to_owned());
+        }
+    }
 
-        let mut game_string: String =
-            format!("{} {} {}/{} ", state, goal, gt_crystals, ganon_crystals);
-        if dungeon_items != "Standard " {
-            game_string.push_str(dungeon_items);
+    settings
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl AsyncGame for DoorRandoGame {
+    fn game_name(&self) -> GameName {
+        GameName::ALTTPR
+    }
+
+    fn settings_str(&self) -> Result<String, MurahdahlaError> {
+        let settings = match &self.settings {
+            Some(s) => s,
+            None => return Ok(format!("Door Randomizer ({})", self.patch_name)),
+        };
+        let mut game_string = String::from("Door Randomizer");
+        for (key, suffix) in [("mode", ""), ("goal", ""), ("door shuffle", " Doors")] {
+            if let Some(value) = settings.get(key) {
+                game_string.push_str(&format!(" {}{}", capitalize(value), suffix));
+            }
         }
-        if shuffle != "Vanilla Shuffle " {
-            game_string.push_str(shuffle);
+        match settings.get("entrance shuffle").map(String::as_str) {
+            None | Some("vanilla") => (),
+            Some(er) => game_string.push_str(&format!(" {} Shuffle", capitalize(er))),
         }
-        if logic != "No Glitches " {
-            game_string.push_str(logic);
+        if settings.get("key drop shuffle").map(String::as_str) == Some("Yes") {
+            game_string.push_str(" Keydrop");
         }
-        game_string.push_str(
-            format!(
-                "({}/{}/{}/{}/{})",
-                code[0], code[1], code[2], code[3], code[4]
-            )
-            .as_str(),
-        );
 
         Ok(game_string)
     }
@@ -212,6 +318,142 @@ impl AsyncGame for Z3rGame {
     }
 }
 
+fn standard_settings(meta: &Value) -> Result<String> {
+    // everything but the file select code, which the caller adds to the end
+    let state = match meta["mode"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Error parsing game state"))?
+    {
+        "open" => "Open",
+        "standard" => "Standard",
+        "inverted" => "Inverted",
+        "retro" => "Retro",
+        _ => "Unknown State",
+    };
+    let goal = match meta["goal"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Error parsing goal"))?
+    {
+        "ganon" => "Defeat Ganon",
+        "fast_ganon" => "Fast Ganon",
+        "dungeons" => "All Dungeons",
+        "pedestal" => "Pedestal",
+        "triforce-hunt" => "Triforce Hunt",
+        _ => "Unknown Goal",
+    };
+    let gt_crystals = meta["entry_crystals_tower"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Error parsing GT crystals"))?;
+    let ganon_crystals = meta["entry_crystals_ganon"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Error parsing Ganon crystals"))?;
+
+    let dungeon_items = match meta["dungeon_items"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Error parsing dungeon item shuffle"))?
+    {
+        "standard" => "Standard ",
+        "mc" => "MC ",
+        "mcs" => "MCS ",
+        "full" => "Keysanity ",
+        _ => "Unknown Dungeon Item Shuffle ",
+    };
+    let mut shuffle = "Vanilla Shuffle ";
+    if meta.get("shuffle").is_some() {
+        shuffle = match meta["shuffle"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Error parsing entrance shuffle"))?
+        {
+            "simple" => "Simple Shuffle ",
+            "restricted" => "Restricted Shuffle ",
+            "full" => "Full Shuffle ",
+            "crossed" => "Crossed Shuffle ",
+            "insanity" => "Insanity Shuffle ",
+            _ => "Unknown Shuffle ",
+        };
+    }
+    let logic = match meta["logic"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Error parsing logic"))?
+    {
+        "NoGlitches" => "No Glitches ",
+        "OverworldGlitches" => "Overworld Glitches ",
+        "Major Glitches" => "Major Glitches ",
+        "None" => "No Logic ",
+        _ => "Unknown Logic ",
+    };
+
+    let mut game_string: String = format!("{} {} {}/{} ", state, goal, gt_crystals, ganon_crystals);
+    // crossed entrances with keysanity is common enough to have its own name
+    if dungeon_items == "Keysanity " && shuffle == "Crossed Shuffle " {
+        game_string.push_str("Crosskeys ");
+    } else {
+        if dungeon_items != "Standard " {
+            game_string.push_str(dungeon_items);
+        }
+        if shuffle != "Vanilla Shuffle " {
+            game_string.push_str(shuffle);
+        }
+    }
+    if logic != "No Glitches " {
+        game_string.push_str(logic);
+    }
+    game_string.push_str(&extra_settings(meta));
+
+    Ok(game_string)
+}
+
+fn meta_setting<'a>(meta: &'a Value, group: &str, name: &str) -> Option<&'a str> {
+    // the spoiler meta flattens grouped settings to "enemizer.boss_shuffle" but the
+    // settings they came from nest them, so look for both
+    meta[format!("{}.{}", group, name).as_str()]
+        .as_str()
+        .or_else(|| meta[group][name].as_str())
+}
+
+fn extra_settings(meta: &Value) -> String {
+    // settings older seeds might not have. each is only shown when it isn't the default.
+    let mut extras = String::new();
+    let item_pool = meta["item_pool"]
+        .as_str()
+        .or_else(|| meta_setting(meta, "item", "pool"));
+    match item_pool {
+        Some("hard") => extras.push_str("Hard Item Pool "),
+        Some("expert") => extras.push_str("Expert Item Pool "),
+        _ => (),
+    }
+    let item_functionality = meta["item_functionality"]
+        .as_str()
+        .or_else(|| meta_setting(meta, "item", "functionality"));
+    match item_functionality {
+        Some("hard") => extras.push_str("Hard Functionality "),
+        Some("expert") => extras.push_str("Expert Functionality "),
+        _ => (),
+    }
+    match meta["weapons"].as_str() {
+        Some("assured") => extras.push_str("Assured Sword "),
+        Some("vanilla") => extras.push_str("Vanilla Swords "),
+        Some("swordless") => extras.push_str("Swordless "),
+        _ => (),
+    }
+    match meta_setting(meta, "enemizer", "boss_shuffle") {
+        Some("simple") => extras.push_str("Simple Boss Shuffle "),
+        Some("full") => extras.push_str("Full Boss Shuffle "),
+        Some("random") => extras.push_str("Random Boss Shuffle "),
+        _ => (),
+    }
+    match meta_setting(meta, "enemizer", "enemy_shuffle") {
+        Some("shuffled") => extras.push_str("Shuffled Enemies "),
+        Some("random") => extras.push_str("Random Enemies "),
+        _ => (),
+    }
+    if meta_setting(meta, "enemizer", "pot_shuffle") == Some("on") {
+        extras.push_str("Pot Shuffle ");
+    }
+
+    extras
+}
+
 #[inline]
 fn patch_to_map(patches: &mut Value) -> Result<Map<String, Value>> {
     // Converts the ROM patch data to serde_json's Map type and discards the "outer"
@@ -221,55 +463,183 @@ fn patch_to_map(patches: &mut Value) -> Result<Map<String, Value>> {
         .as_array_mut()
         .ok_or_else(|| anyhow!("Error parsing ALTTPR patches into vector"))?
         .iter_mut()
-        .map(|inner| inner.as_object_mut().unwrap())
-        .for_each(|m| {
-            let key: String = m.keys().last().unwrap().clone();
-            let value: Value = m.remove(&key).unwrap();
+        .try_for_each(|inner| {
+            let m = inner
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("Error parsing ALTTPR patch as object"))?;
+            let key: String = m
+                .keys()
+                .next_back()
+                .ok_or_else(|| anyhow!("Found empty ALTTPR patch"))?
+                .clone();
+            let value: Value = m.remove(&key).unwrap(); // we just got this key from the map
             patch_map.insert(key, value);
-        });
+
+            Ok::<(), anyhow::Error>(())
+        })?;
 
     Ok(patch_map)
 }
 
 #[inline]
 fn get_code(patch_map: &Map<String, Value>) -> Result<Vec<&'static str>> {
-    let mut code_vec: Vec<&'static str> = Vec::with_capacity(5);
-    let index_int = patch_map
-        .keys()
-        .map(|s| s.parse::<u64>().unwrap())
-        .reduce(|a, b| {
-            if (b == FILE_SELECT_CODE) || ((b > a) && (b < FILE_SELECT_CODE)) {
-                b
-            } else {
-                a
-            }
-        })
-        .ok_or_else(|| anyhow!("Error finding file select code patch index"))?;
-    let index_string = index_int.to_string();
-    let patch_slice = patch_map[&index_string]
+    // the code is five bytes at FILE_SELECT_CODE but that address is usually somewhere
+    // in the middle of a bigger patch, so find the last patch that starts at or before
+    // it and read from the right offset into that
+    let mut code_patch: Option<(u64, &String)> = None;
+    for key in patch_map.keys() {
+        let offset = key
+            .parse::<u64>()
+            .map_err(|_| anyhow!("Found non-numeric ALTTPR patch offset: {}", key))?;
+        if offset <= FILE_SELECT_CODE && code_patch.map_or(true, |(o, _)| offset > o) {
+            code_patch = Some((offset, key));
+        }
+    }
+    let (index_int, index_string) =
+        code_patch.ok_or_else(|| anyhow!("Error finding file select code patch index"))?;
+    let patch_slice = patch_map[index_string]
         .as_array()
         .ok_or_else(|| anyhow!("Error parsing file select code data"))?;
-    let mut code_offset = 0u64;
-    if index_int != FILE_SELECT_CODE {
-        code_offset = FILE_SELECT_CODE - index_int;
+    let code_offset = (FILE_SELECT_CODE - index_int) as usize;
+    // a patch that ends before the code means the code isn't in the patch data at all.
+    // we used to fall back to a made up code here which just posted the wrong code.
+    let code_bytes = patch_slice
+        .get(code_offset..code_offset + 5)
+        .ok_or_else(|| {
+            anyhow!(
+                "File select code patch at {} is too short to contain the code",
+                index_int
+            )
+        })?;
+    code_bytes
+        .iter()
+        .map(|b| {
+            b.as_u64()
+                .map(code_map)
+                .ok_or_else(|| anyhow!("Error parsing code byte as integer"))
+        })
+        .collect()
+}
+
+pub struct Z3rProvider;
+
+impl GameProvider for Z3rProvider {
+    fn name(&self) -> GameName {
+        GameName::ALTTPR
+    }
+
+    fn matches_url(&self, game_url: &Url) -> bool {
+        matches_url(game_url)
+    }
+
+    fn valid_seed_id(&self, id: &str) -> bool {
+        valid_seed_id(id)
+    }
+
+    fn fetches_seeds(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, args_str: String) -> GameFuture {
+        fetch(args_str)
+    }
+
+    fn reads_seed_json(&self) -> bool {
+        true
+    }
+
+    fn matches_json(&self, seed: &Value) -> bool {
+        matches_json(seed)
+    }
+
+    fn game_from_json(
+        &self,
+        seed: Value,
+        url: Option<String>,
+    ) -> Result<BoxedGame, MurahdahlaError> {
+        from_json(seed, url)
+    }
+
+    fn rolls_seeds(&self) -> bool {
+        true
+    }
+
+    fn roll(&self, settings: Value) -> RollFuture {
+        roll(settings)
+    }
+
+    fn parse_submission<'a>(
+        &self,
+        submission: &'a mut NewSubmission,
+        msg: &[&str],
+    ) -> Result<&'a mut NewSubmission, MurahdahlaError> {
+        game_info(submission, msg)
+    }
+
+    fn format_submission(&self, s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_submission(s, f)
     }
-    if patch_slice.len() < (code_offset + 5) as usize {
-        return Ok(vec!["Bow", "Boomerang", "Hookshot", "Bombs", "Mushroom"]);
+}
+
+fn matches_url(game_url: &Url) -> bool {
+    // TODO: if we have, say, a festive alttpr url without /h/, we could make it an
+    // other game
+    match game_url.host_str() {
+        Some("alttpr.com") | Some("www.alttpr.com") => game_url.path().contains("/h/"),
+        Some(DOORS_PATCHER_HOST) => door_patch_url(game_url).is_some(),
+        _ => false,
     }
-    for i in 0..5 {
-        let code_byte = patch_slice[(i + code_offset) as usize]
-            .as_u64()
-            .ok_or_else(|| anyhow!("Error parsing code byte as integer"))?;
-        code_vec.push(code_map(code_byte));
+}
+
+fn valid_seed_id(id: &str) -> bool {
+    // alttpr.com hashes are always ten alphanumeric characters
+    id.len() == 10 && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn fetch(args_str: String) -> GameFuture {
+    Box::pin(async move {
+        let is_doors = Url::parse(&args_str).map_or(false, |u| door_patch_url(&u).is_some());
+        let game: BoxedGame = match is_doors {
+            true => Box::new(DoorRandoGame::new_from_str(&args_str).await?),
+            false => Box::new(Z3rGame::new_from_str(&args_str).await?),
+        };
+
+        Ok(game)
+    })
+}
+
+fn roll(settings: Value) -> RollFuture {
+    Box::pin(async move {
+        let client = reqwest::Client::new();
+        let seed: Value = fetch_with_retry(|| client.post(ROLL_URL).json(&settings).send())
+            .await?
+            .json()
+            .await?;
+
+        Ok(rolled_seed_url(&seed)?)
+    })
+}
+
+fn rolled_seed_url(seed: &Value) -> Result<String> {
+    match seed["hash"].as_str() {
+        Some(hash) if valid_seed_id(hash) => Ok(format!("{}{}", SEED_URL, hash)),
+        _ => Err(anyhow!("alttpr.com didn't send back a seed")),
     }
+}
 
-    Ok(code_vec)
+fn matches_json(seed: &Value) -> bool {
+    // alttpr patch data has the patches and spoiler at the top level
+    seed.get("patch").is_some() && seed.get("spoiler").is_some()
+}
+
+fn from_json(seed: Value, url: Option<String>) -> Result<BoxedGame, MurahdahlaError> {
+    Ok(Box::new(Z3rGame::new_from_json(seed, url)?))
 }
 
 pub fn game_info<'a>(
     submission: &'a mut NewSubmission,
-    msg: &Vec<&str>,
-) -> Result<&'a mut NewSubmission, BoxedError> {
+    msg: &[&str],
+) -> Result<&'a mut NewSubmission, MurahdahlaError> {
     // for alttpr we just use the collection rate by default. we could also set one of
     // the optional values here if we wanted to take some other input. suppose we
     // wanted a bonk counter for example
@@ -280,9 +650,113 @@ pub fn game_info<'a>(
         return Err(anyhow!("ALTTPR submission did not include collection rate.").into());
     }
 
-    let number = u16::from_str(msg[0])?;
+    let number = u16::from_str(msg[0]).map_err(MurahdahlaError::user)?;
     let collection = Z3rCollectionRate::try_from(number)?;
     submission.set_collection(Some(collection));
 
     Ok(submission)
 }
+
+fn format_submission(s: &Submission, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "{} - {} - {}/216",
+        s.runner_name,
+        s.runner_time.unwrap(),
+        s.runner_collection.unwrap()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_game(fixture: &str) -> Z3rGame {
+        let json: Value = serde_json::from_str(fixture).unwrap();
+        Z3rGame::new_from_json(json, None).unwrap()
+    }
+
+    #[test]
+    fn code_at_exact_offset() {
+        let game = load_game(include_str!("../../tests/data/z3r/exact_offset.json"));
+        assert_eq!(
+            game.file_select_code().unwrap(),
+            ["Bow", "Ether", "Boots", "Key", "Powder"]
+        );
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "Open Defeat Ganon 7/7 (Bow/Ether/Boots/Key/Powder)"
+        );
+    }
+
+    #[test]
+    fn code_inside_larger_patch() {
+        let game = load_game(include_str!("../../tests/data/z3r/spanning_patch.json"));
+        assert_eq!(
+            game.file_select_code().unwrap(),
+            ["Bombs", "Mushroom", "Hammer", "Somaria", "Heart"]
+        );
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "Mystery (Bombs/Mushroom/Hammer/Somaria/Heart)"
+        );
+    }
+
+    #[test]
+    fn short_patch_is_an_error() {
+        let game = load_game(include_str!("../../tests/data/z3r/short_patch.json"));
+        assert!(game.file_select_code().is_err());
+        assert!(game.settings_str().is_err());
+    }
+
+    #[test]
+    fn crosskeys_with_enemizer() {
+        let game = load_game(include_str!("../../tests/data/z3r/crosskeys.json"));
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "Open Fast Ganon 7/7 Crosskeys Hard Item Pool Full Boss Shuffle Shuffled Enemies \
+            (Bow/Ether/Boots/Key/Powder)"
+        );
+    }
+
+    #[test]
+    fn customizer_seed_falls_back_to_name() {
+        let game = load_game(include_str!("../../tests/data/z3r/customizer.json"));
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "Customizer \"Weekly 7\" (Bow/Ether/Boots/Key/Powder)"
+        );
+    }
+
+    #[test]
+    fn door_rando_spoiler_settings() {
+        let url = Url::parse(
+            "https://alttprpatch.synack.live/patcher.html?patch=https://sahasrahbot.s3.amazonaws.com/patch/DR_Q3K9ZP.bps",
+        )
+        .unwrap();
+        assert!(matches_url(&url));
+        let patch_url = door_patch_url(&url).unwrap();
+        assert_eq!(
+            spoiler_url(&patch_url, "DR_Q3K9ZP").unwrap(),
+            "https://sahasrahbot.s3.amazonaws.com/spoiler/DR_Q3K9ZP_Spoiler.txt"
+        );
+        let game = DoorRandoGame {
+            patch_name: "DR_Q3K9ZP".to_owned(),
+            settings: Some(spoiler_settings(include_str!(
+                "../../tests/data/z3r/doors_spoiler.txt"
+            ))),
+            url: url.to_string(),
+        };
+        assert_eq!(
+            game.settings_str().unwrap(),
+            "Door Randomizer Open Ganon Crossed Doors Keydrop"
+        );
+    }
+
+    #[test]
+    fn missing_code_patch_is_an_error() {
+        // the first key here sorts after the code address which used to underflow
+        let game = load_game(include_str!("../../tests/data/z3r/no_code_patch.json"));
+        assert!(game.file_select_code().is_err());
+    }
+}
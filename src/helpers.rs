@@ -1,22 +1,41 @@
 use std::{
     collections::{HashMap, HashSet},
-    error::Error,
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::Result;
 use diesel::{
     mysql::MysqlConnection,
     r2d2::{ConnectionManager, Pool, PooledConnection},
+    result::{DatabaseErrorKind, Error as DieselError},
 };
-use serenity::{client::Context, model::id::GuildId, prelude::TypeMapKey};
+use serenity::{
+    client::Context,
+    http::HttpError,
+    model::{
+        guild::Guild,
+        id::{ChannelId, GuildId},
+    },
+    prelude::TypeMapKey,
+};
+use tokio::time::sleep;
 use uuid::Uuid;
 
-use crate::discord::{channel_groups::ChannelGroup, servers::DiscordServer};
+use crate::{
+    discord::{channel_groups::ChannelGroup, servers::DiscordServer},
+    error::{is_transient, MurahdahlaError},
+};
 
-pub type BoxedError = Box<dyn Error + Send + Sync>;
 pub type MysqlPool = Pool<ConnectionManager<MysqlConnection>>;
 pub type PooledConn = PooledConnection<ConnectionManager<MysqlConnection>>;
 
+// serenity waits out rate limits it knows about but a burst of edits on a big
+// leaderboard can still get a 429 or a 5xx back, so we give those a few more tries
+const DISCORD_ATTEMPTS: u32 = 4;
+const DISCORD_BACKOFF_MS: u64 = 1000;
+const DISCORD_JITTER_MS: u64 = 500;
+
 pub struct GroupContainer;
 
 // submission channels map to groups 1:1
@@ -55,12 +74,20 @@ pub async fn get_connection(ctx: &Context) -> PooledConn {
     conn
 }
 
+#[inline]
+pub async fn get_db_pool(ctx: &Context) -> MysqlPool {
+    // for code that needs connections at more than one point but can't hold one across
+    // an await. the pool is cheap to clone.
+    let data = ctx.data.read().await;
+    data.get::<DBPool>()
+        .expect("Expected DB pool in ShareMap")
+        .clone()
+}
+
 #[inline]
 pub fn get_pool(database_url: &str) -> Result<MysqlPool> {
     let manager = ConnectionManager::<MysqlConnection>::new(database_url);
-    let pool = Pool::builder()
-        .build(manager)
-        .expect("Failed to create pool.");
+    let pool = Pool::builder().build(manager)?;
 
     Ok(pool)
 }
@@ -71,3 +98,77 @@ pub fn new_uuid() -> Vec<u8> {
 
     new_uuid
 }
+
+pub async fn discord_with_retry<T, F, Fut>(mut request: F) -> serenity::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = serenity::Result<T>>,
+{
+    // retries a discord api call with exponential backoff when it was rate limited or
+    // discord had a problem on its end. anything else is returned immediately.
+    let mut attempt: u32 = 1;
+    loop {
+        match request().await {
+            Err(e) if attempt < DISCORD_ATTEMPTS && is_transient(&e) => {
+                let delay =
+                    DISCORD_BACKOFF_MS * 2u64.pow(attempt - 1) + jitter_ms(DISCORD_JITTER_MS);
+                warn!(
+                    "Discord request failed (attempt {}/{}), retrying in {}ms: {}",
+                    attempt, DISCORD_ATTEMPTS, delay, e
+                );
+                sleep(Duration::from_millis(delay)).await;
+                attempt += 1;
+            }
+            r => return r,
+        }
+    }
+}
+
+pub fn is_not_found(e: &MurahdahlaError) -> bool {
+    // discord says something we tried to change doesn't exist (anymore)
+    match e {
+        MurahdahlaError::Discord(e) => match e.as_ref() {
+            serenity::Error::Http(h) => matches!(
+                h,
+                HttpError::UnsuccessfulRequest(r) if r.status_code.as_u16() == 404
+            ),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub fn cached_guild(ctx: &Context, guild_id: GuildId) -> Option<Guild> {
+    // the cache hands out a lock guard that can't be held across an await
+    guild_id.to_guild_cached(&ctx.cache).map(|g| g.clone())
+}
+
+pub fn channel_id_by_name(guild: &Guild, name: &str) -> Option<ChannelId> {
+    guild
+        .channels
+        .values()
+        .find(|c| c.name == name)
+        .map(|c| c.id)
+}
+
+pub fn is_duplicate_key(e: &DieselError, index: &str) -> bool {
+    // mysql doesn't tell diesel which constraint failed so we look for the index in the
+    // message, e.g. "Duplicate entry '1' for key 'submissions.submissions_source_message'"
+    match e {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+            info.message().contains(&format!("{}'", index))
+        }
+        _ => false,
+    }
+}
+
+#[inline]
+pub fn jitter_ms(max_ms: u64) -> u64 {
+    // we don't need real randomness here, just enough to keep retries from lining up
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+
+    nanos as u64 % max_ms
+}
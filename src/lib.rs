@@ -0,0 +1,20 @@
+#![allow(clippy::extra_unused_lifetimes)] // Diesel Insertable derive macro
+use std::sync::OnceLock;
+
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate log;
+
+pub mod audit;
+pub mod config;
+pub mod discord;
+pub mod error;
+pub mod export;
+pub mod games;
+pub mod helpers;
+pub mod schema;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub static MAINTENANCE_USER: OnceLock<u64> = OnceLock::new();
@@ -1,79 +1,312 @@
-#![allow(clippy::extra_unused_lifetimes)] // Diesel Insertable derive macro
-use std::{env, sync::OnceLock};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{atomic::Ordering, Arc},
+};
 
 #[macro_use]
-extern crate diesel;
+extern crate diesel_migrations;
 #[macro_use]
 extern crate log;
 
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use serenity::{framework::standard::StandardFramework, prelude::*};
-
-pub mod discord;
-pub mod games;
-pub mod helpers;
-pub mod schema;
+use poise::{Framework, FrameworkOptions, PrefixFrameworkOptions};
+use serenity::{
+    builder::CreateCommand,
+    model::{application::Command, id::GuildId},
+    prelude::*,
+};
 
-use crate::{
+use murahdahla::{
+    audit,
+    config::{load_startup_config, read_env, BotConfig, ConfigContainer, Environment},
     discord::{
-        channel_groups::{get_groups, get_submission_channels},
-        commands::{after_hook, before_hook, GENERAL_GROUP},
+        aliases::{get_aliases, AliasContainer, AliasFramework},
+        channel_groups::{get_groups, get_submission_channels, ChannelGroup},
+        command_permissions::{get_grants, GrantContainer},
+        commands::{command_check, commands, on_error, post_command, pre_command},
+        context_menus::context_menu_commands,
+        error_report::{ErrorReporter, ErrorReporterContainer},
+        events::{new_event_bus, EventBusContainer},
+        ingest::{SubmissionQueue, SubmissionQueueContainer},
         intents,
-        messages::{normal_message_hook, Handler},
-        servers::get_servers,
+        messages::Handler,
+        secrets::{
+            take_restart_request, EnvironmentContainer, ShardManagerContainer, CLIENT_GENERATION,
+        },
+        server_settings::{get_all_settings, server_prefix, SettingsContainer},
+        servers::{get_servers, DiscordServer},
+        shared_cache::{CacheSnapshot, SharedCache, SharedCacheContainer},
+        submissions::{LeaderboardQueue, LeaderboardQueueContainer},
+        throttle::{
+            StartLimiterContainer, StartRaceLimiter, SubmissionThrottle, ThrottleContainer,
+        },
+    },
+    export::export_server,
+    games::{
+        api_health::{ApiHealth, ApiHealthContainer},
+        registry::{builtin_games, register_games},
+        z3r,
     },
     helpers::*,
+    MAINTENANCE_USER,
 };
 
-static MAINTENANCE_USER: OnceLock<u64> = OnceLock::new();
+#[cfg(unix)]
+use murahdahla::discord::secrets::rotate_on_sighup;
+
+embed_migrations!();
+
+#[derive(Parser)]
+#[command(version, about = "An async race bot for Discord")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Connect to Discord and run the bot. This is the default
+    Serve,
+    /// Run any database migrations that haven't been run yet
+    Migrate,
+    /// Print a server's groups, races and submissions as JSON
+    Export {
+        #[arg(long)]
+        server: u64,
+    },
+    /// Check config.toml and the environment without connecting to anything
+    VerifyConfig,
+    /// Print the file select code we'd post for an ALTTPR seed url or patch file
+    VerifyCode { source: String },
+    /// List a server's submissions that the current parser would read differently
+    ReparseAudit {
+        #[arg(long)]
+        server: u64,
+    },
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    dotenv().expect("Failed to load .env file");
+    // GameName looks names up in the registry when it reads them from the database, so
+    // the games are registered before any subcommand runs
+    register_games(builtin_games())?;
+    match Cli::parse().command.unwrap_or(CliCommand::Serve) {
+        CliCommand::Serve => serve().await,
+        CliCommand::Migrate => migrate(),
+        CliCommand::Export { server } => export(server),
+        CliCommand::VerifyConfig => verify_config(),
+        CliCommand::VerifyCode { source } => verify_code(&source).await,
+        CliCommand::ReparseAudit { server } => reparse_audit(server),
+    }
+}
+
+async fn verify_code(source: &str) -> anyhow::Result<()> {
+    // maintenance check that prints the file select code we'd post for an ALTTPR seed
+    // without connecting to discord
+    let code = z3r::verify_code(source)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+    println!("{}", code);
+
+    Ok(())
+}
+
+fn database_connection() -> anyhow::Result<PooledConn> {
+    // the subcommands other than serve only need the database
+    dotenv().ok();
+    let database_url = read_env("DATABASE_URL")?
+        .ok_or_else(|| anyhow::anyhow!("Expected DATABASE_URL in the environment"))?;
+
+    Ok(get_pool(&database_url)?.get()?)
+}
+
+fn migrate() -> anyhow::Result<()> {
+    let conn = database_connection()?;
+    embedded_migrations::run_with_output(&*conn, &mut io::stdout())?;
+
+    Ok(())
+}
+
+fn export(server_id: u64) -> anyhow::Result<()> {
+    let conn = database_connection()?;
+    let export = export_server(&conn, server_id)?;
+    println!("{}", serde_json::to_string_pretty(&export)?);
+
+    Ok(())
+}
+
+fn reparse_audit(server_id: u64) -> anyhow::Result<()> {
+    let conn = database_connection()?;
+    let report = audit::reparse_audit(&conn, server_id)?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+fn verify_config() -> anyhow::Result<()> {
+    dotenv().ok();
+    load_startup_config()?;
+    println!("Config OK");
+
+    Ok(())
+}
+
+async fn serve() -> anyhow::Result<()> {
+    // a .env file is optional. containers usually set the environment directly.
+    dotenv().ok();
     env_logger::init();
 
-    let token = env::var("MURAHDAHLA_DISCORD_TOKEN")
-        .expect("Expected MURAHDAHLA_DISCORD_TOKEN in the environment.");
-    let database_url = env::var("DATABASE_URL").expect("Expected DATABASE_URL in the environment");
-    let maintenance_user: u64 = env::var("MAINTENANCE_USER")
-        .expect("Expected MAINTENANCE_USER in the environment")
-        .parse::<u64>()
-        .expect("Expected MAINTENANCE_USER to be parsable to 64-bit integer");
-    MAINTENANCE_USER.set(maintenance_user).unwrap();
-    let framework = StandardFramework::new()
-        .configure(|c| c.prefix("!").allow_dm(false))
-        .group(&GENERAL_GROUP)
-        .before(before_hook)
-        .after(after_hook)
-        .normal_message(normal_message_hook);
-
-    let mut client = Client::builder(&token, intents())
-        .framework(framework)
-        .cache_settings(|c| c.max_messages(50))
+    let (environment, config) = load_startup_config()?;
+    MAINTENANCE_USER.set(environment.maintenance_user).unwrap();
+    let current_data: Arc<Mutex<Option<Arc<RwLock<TypeMap>>>>> = Arc::new(Mutex::new(None));
+    #[cfg(unix)]
+    tokio::spawn(rotate_on_sighup(current_data.clone()));
+
+    let mut client = build_client(environment, config).await?;
+    loop {
+        *current_data.lock().await = Some(client.data.clone());
+        if let Err(e) = client.start().await {
+            error!("Client error: {:?}", e);
+        }
+        if !take_restart_request() {
+            return Ok(());
+        }
+        // the token was rotated. everything is loaded again since the new client
+        // starts with an empty share map.
+        CLIENT_GENERATION.fetch_add(1, Ordering::SeqCst);
+        info!("Restarting client with rotated token");
+        let (environment, config) = load_startup_config()?;
+        client = build_client(environment, config).await?;
+    }
+}
+
+async fn build_client(environment: Environment, config: BotConfig) -> anyhow::Result<Client> {
+    let framework = Framework::builder()
+        .options(FrameworkOptions {
+            commands: commands(),
+            prefix_options: PrefixFrameworkOptions {
+                prefix: Some("!".into()),
+                dynamic_prefix: Some(server_prefix),
+                mention_as_prefix: false,
+                ..Default::default()
+            },
+            on_error,
+            pre_command,
+            post_command,
+            command_check: Some(command_check),
+            // the maintenance user is checked by the commands that need it
+            initialize_owners: false,
+            ..Default::default()
+        })
+        .setup(|ctx, _ready, framework| {
+            Box::pin(async move {
+                // setting the global commands replaces whatever was registered before so
+                // this is safe to run every time a client starts
+                let mut app_commands: Vec<CreateCommand> =
+                    poise::builtins::create_application_commands(&framework.options().commands);
+                app_commands.extend(context_menu_commands());
+                if let Err(e) = Command::set_global_commands(&ctx.http, app_commands).await {
+                    warn!("Error registering application commands: {}", e);
+                }
+
+                Ok(())
+            })
+        })
+        .build();
+
+    let client = Client::builder(&environment.discord_token, intents())
+        .framework(AliasFramework(framework))
+        .cache_settings({
+            let mut settings = serenity::cache::Settings::default();
+            settings.max_messages = 50;
+            settings
+        })
         .event_handler(Handler)
-        .await
-        .expect("Error creating client");
+        .await?;
 
     {
         let mut data = client.data.write().await;
-        let db_pool = get_pool(&database_url)?;
-        let conn = db_pool
-            .get()
-            .expect("Error retrieving database connection from pool");
+        let db_pool = get_pool(&environment.database_url)?;
+        let conn = db_pool.get()?;
 
-        let submission_channel_set = get_submission_channels(&conn)?;
-        let servers = get_servers(&conn)?;
-        let groups = get_groups(&conn)?;
+        let shared_cache = match &environment.redis_url {
+            Some(u) => Some(SharedCache::new(u)?),
+            None => None,
+        };
+        let error_reporter = match &environment.error_webhook_url {
+            Some(u) => Some(ErrorReporter::new(u)?),
+            None => None,
+        };
+        let (groups, servers, submission_channel_set) =
+            load_shared_maps(&conn, shared_cache.as_ref()).await?;
+        let settings = get_all_settings(&conn)?;
+        let grants = get_grants(&conn)?;
+        let aliases = get_aliases(&conn)?;
+        let lb_queue = LeaderboardQueue::new(config.max_concurrent_leaderboard_builds);
+        let submission_queue = SubmissionQueue::new(config.submission_workers);
 
+        data.insert::<ConfigContainer>(config);
         data.insert::<DBPool>(db_pool);
         data.insert::<SubmissionSet>(submission_channel_set);
         data.insert::<ServerContainer>(servers);
         data.insert::<GroupContainer>(groups);
+        data.insert::<SettingsContainer>(settings);
+        data.insert::<GrantContainer>(grants);
+        data.insert::<AliasContainer>(aliases);
+        data.insert::<LeaderboardQueueContainer>(lb_queue);
+        data.insert::<SubmissionQueueContainer>(submission_queue);
+        data.insert::<EventBusContainer>(new_event_bus());
+        data.insert::<ThrottleContainer>(SubmissionThrottle::default());
+        data.insert::<StartLimiterContainer>(StartRaceLimiter::default());
+        data.insert::<ApiHealthContainer>(ApiHealth::default());
+        data.insert::<ShardManagerContainer>(client.shard_manager.clone());
+        data.insert::<EnvironmentContainer>(environment);
+        if let Some(c) = shared_cache {
+            data.insert::<SharedCacheContainer>(c);
+        }
+        if let Some(r) = error_reporter {
+            data.insert::<ErrorReporterContainer>(r);
+        }
     }
 
-    if let Err(e) = client.start().await {
-        error!("Client error: {:?}", e);
+    Ok(client)
+}
+
+async fn load_shared_maps(
+    conn: &PooledConn,
+    shared_cache: Option<&SharedCache>,
+) -> anyhow::Result<(
+    HashMap<u64, ChannelGroup>,
+    HashMap<GuildId, DiscordServer>,
+    HashSet<u64>,
+)> {
+    // with a shared cache, whatever the other instances (or this one before a restart)
+    // left there is used instead of the database
+    let cache = match shared_cache {
+        Some(c) => c,
+        None => {
+            return Ok((
+                get_groups(conn)?,
+                get_servers(conn)?,
+                get_submission_channels(conn)?,
+            ))
+        }
+    };
+    match cache.load().await {
+        Ok(Some(snapshot)) => {
+            info!("Loaded groups and servers from shared cache");
+            return Ok(snapshot.into_maps());
+        }
+        Ok(None) => (),
+        Err(e) => warn!("Error reading shared cache, loading from database: {}", e),
+    };
+    let groups = get_groups(conn)?;
+    let servers = get_servers(conn)?;
+    if let Err(e) = cache.store(&CacheSnapshot::new(&groups, &servers)).await {
+        warn!("Error filling shared cache: {}", e);
     }
 
-    Ok(())
+    Ok((groups, servers, get_submission_channels(conn)?))
 }
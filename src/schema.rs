@@ -8,6 +8,28 @@ table! {
         race_type -> Tinytext,
         race_info -> Text,
         race_url -> Nullable<Tinytext>,
+        race_role_id -> Nullable<Unsigned<Bigint>>,
+        stop_pending -> Nullable<Tinytext>,
+        race_deadline -> Nullable<Datetime>,
+        race_sort -> Tinytext,
+        race_fields -> Nullable<Tinytext>,
+        race_blind -> Bool,
+        source_message_id -> Nullable<Unsigned<Bigint>>,
+        race_reminders -> Bool,
+        last_reminder_at -> Nullable<Datetime>,
+        race_claim -> Bool,
+        race_live_at -> Nullable<Datetime>,
+        live_countdown_sent -> Bool,
+        race_announcement -> Nullable<Text>,
+        race_preset -> Nullable<Tinytext>,
+        race_shuffle -> Bool,
+    }
+}
+
+table! {
+    banned_runners (channel_group_id, runner_id) {
+        channel_group_id -> Binary,
+        runner_id -> Unsigned<Bigint>,
     }
 }
 
@@ -19,7 +41,55 @@ table! {
         submission -> Unsigned<Bigint>,
         leaderboard -> Unsigned<Bigint>,
         spoiler -> Unsigned<Bigint>,
-        spoiler_role_id -> Unsigned<Bigint>,
+        spoiler_role_id -> Nullable<Unsigned<Bigint>>,
+        ascii_names -> Bool,
+        max_name_length -> Nullable<Unsigned<Smallint>>,
+        spoiler_access -> Tinytext,
+        verify_under_ms -> Nullable<Unsigned<Bigint>>,
+        verification_channel -> Nullable<Unsigned<Bigint>>,
+        show_flair -> Bool,
+        forfeit_words -> Nullable<Text>,
+        archive_channel -> Nullable<Unsigned<Bigint>>,
+        embed_color -> Nullable<Unsigned<Integer>>,
+        embed_thumbnail -> Nullable<Text>,
+        embed_banner -> Nullable<Text>,
+        embed_footer -> Nullable<Text>,
+        guard_spoiler -> Bool,
+        purge_spoiler -> Bool,
+        max_settings_length -> Nullable<Unsigned<Smallint>>,
+    }
+}
+
+table! {
+    command_aliases (server_id, alias) {
+        server_id -> Unsigned<Bigint>,
+        alias -> Varchar,
+        command_name -> Varchar,
+    }
+}
+
+table! {
+    command_permissions (server_id, command_name, role_id) {
+        server_id -> Unsigned<Bigint>,
+        command_name -> Varchar,
+        role_id -> Unsigned<Bigint>,
+    }
+}
+
+table! {
+    custom_games (server_id, game_name) {
+        server_id -> Unsigned<Bigint>,
+        game_name -> Varchar,
+        game_definition -> Text,
+    }
+}
+
+table! {
+    game_templates (server_id, template_name) {
+        server_id -> Unsigned<Bigint>,
+        template_name -> Varchar,
+        template_fields -> Tinytext,
+        template_format -> Text,
     }
 }
 
@@ -43,6 +113,66 @@ table! {
     }
 }
 
+table! {
+    presets (channel_group_id, preset_name) {
+        channel_group_id -> Binary,
+        preset_name -> Varchar,
+        preset_game -> Tinytext,
+        preset_settings -> Text,
+    }
+}
+
+table! {
+    result_channels (channel_group_id, channel_id) {
+        channel_group_id -> Binary,
+        channel_id -> Unsigned<Bigint>,
+        top_places -> Nullable<Unsigned<Smallint>>,
+    }
+}
+
+table! {
+    seed_claims (race_id, runner_id) {
+        race_id -> Unsigned<Integer>,
+        runner_id -> Unsigned<Bigint>,
+        claimed_at -> Datetime,
+    }
+}
+
+table! {
+    server_settings (server_id, setting_key) {
+        server_id -> Unsigned<Bigint>,
+        setting_key -> Varchar,
+        setting_value -> Text,
+    }
+}
+
+table! {
+    spoiler_roles (channel_group_id, role_id) {
+        channel_group_id -> Binary,
+        role_id -> Unsigned<Bigint>,
+        race_game -> Nullable<Tinytext>,
+        division_role_id -> Nullable<Unsigned<Bigint>>,
+    }
+}
+
+table! {
+    submission_cursors (channel_group_id) {
+        channel_group_id -> Binary,
+        last_message_id -> Unsigned<Bigint>,
+    }
+}
+
+table! {
+    submission_notes (note_id) {
+        note_id -> Unsigned<Integer>,
+        submission_id -> Unsigned<Integer>,
+        author_id -> Unsigned<Bigint>,
+        author_name -> Varchar,
+        note_datetime -> Datetime,
+        note_text -> Varchar,
+    }
+}
+
 table! {
     submissions (submission_id) {
         submission_id -> Unsigned<Integer>,
@@ -51,23 +181,59 @@ table! {
         race_game -> Tinytext,
         submission_datetime -> Datetime,
         runner_name -> Varchar,
-        runner_time -> Nullable<Time>,
         runner_collection -> Nullable<Unsigned<Smallint>>,
         option_number -> Nullable<Unsigned<Integer>>,
         option_text -> Nullable<Tinytext>,
         runner_forfeit -> Bool,
+        pending -> Bool,
+        unranked -> Bool,
+        runner_time_ms -> Nullable<Unsigned<Bigint>>,
+        source_message_id -> Nullable<Unsigned<Bigint>>,
+        submission_text -> Nullable<Text>,
+    }
+}
+
+table! {
+    users (user_id) {
+        user_id -> Unsigned<Bigint>,
+        flair -> Varchar,
     }
 }
 
 joinable!(async_races -> channels (channel_group_id));
+joinable!(banned_runners -> channels (channel_group_id));
 joinable!(channels -> servers (server_id));
+joinable!(command_aliases -> servers (server_id));
+joinable!(command_permissions -> servers (server_id));
+joinable!(custom_games -> servers (server_id));
+joinable!(game_templates -> servers (server_id));
 joinable!(messages -> async_races (race_id));
+joinable!(presets -> channels (channel_group_id));
+joinable!(result_channels -> channels (channel_group_id));
+joinable!(seed_claims -> async_races (race_id));
+joinable!(server_settings -> servers (server_id));
+joinable!(spoiler_roles -> channels (channel_group_id));
+joinable!(submission_cursors -> channels (channel_group_id));
+joinable!(submission_notes -> submissions (submission_id));
 joinable!(submissions -> async_races (race_id));
 
 allow_tables_to_appear_in_same_query!(
     async_races,
+    banned_runners,
     channels,
+    command_aliases,
+    command_permissions,
+    custom_games,
+    game_templates,
     messages,
+    presets,
+    result_channels,
+    seed_claims,
+    server_settings,
     servers,
+    spoiler_roles,
+    submission_cursors,
+    submission_notes,
     submissions,
+    users,
 );
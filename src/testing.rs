@@ -0,0 +1,382 @@
+// fakes of what serenity hands command handlers and hooks, for testing them without a
+// gateway connection. nothing here talks to discord so anything a handler sends through
+// ctx.http fails, but everything up to that can be checked. built with the "testing"
+// feature so forks can use it in their own tests.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use diesel::{mysql::MysqlConnection, r2d2::ConnectionManager};
+use serde_json::json;
+use serenity::{
+    cache::Cache,
+    client::Context,
+    gateway::{
+        Shard, ShardManager, ShardManagerOptions, ShardMessenger, ShardRunner, ShardRunnerOptions,
+    },
+    http::Http,
+    model::{
+        channel::Message,
+        gateway::{GatewayIntents, ShardInfo},
+        id::{GuildId, ShardId},
+    },
+    prelude::{Mutex, RwLock, TypeMap},
+};
+use tokio::net::TcpListener;
+
+use crate::{
+    config::{BotConfig, ConfigContainer},
+    discord::{
+        aliases::AliasContainer,
+        channel_groups::ChannelGroup,
+        command_permissions::GrantContainer,
+        events::{new_event_bus, EventBusContainer},
+        ingest::{SubmissionQueue, SubmissionQueueContainer},
+        server_settings::SettingsContainer,
+        servers::DiscordServer,
+        spoiler_roles::SpoilerAccess,
+        submissions::{LeaderboardQueue, LeaderboardQueueContainer, RunTime, Submission},
+        throttle::{
+            StartLimiterContainer, StartRaceLimiter, SubmissionThrottle, ThrottleContainer,
+        },
+    },
+    games::{
+        api_health::{ApiHealth, ApiHealthContainer},
+        AsyncRaceData, GameName, LeaderboardSort, NewAsyncRaceData, RaceType,
+    },
+    helpers::*,
+};
+
+// a message as the gateway would deliver it. ids default to 1 and the message is sent
+// in a guild by a user who isn't a bot.
+#[derive(Debug, Clone)]
+pub struct FakeMessage {
+    id: u64,
+    channel_id: u64,
+    guild_id: Option<u64>,
+    author_id: u64,
+    author_name: String,
+    bot: bool,
+    content: String,
+}
+
+impl FakeMessage {
+    pub fn new(content: &str) -> Self {
+        FakeMessage {
+            id: 1,
+            channel_id: 1,
+            guild_id: Some(1),
+            author_id: 1,
+            author_name: String::from("runner"),
+            bot: false,
+            content: content.to_owned(),
+        }
+    }
+
+    pub fn id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn channel(mut self, channel_id: u64) -> Self {
+        self.channel_id = channel_id;
+        self
+    }
+
+    pub fn guild(mut self, guild_id: Option<u64>) -> Self {
+        self.guild_id = guild_id;
+        self
+    }
+
+    pub fn author(mut self, author_id: u64, name: &str) -> Self {
+        self.author_id = author_id;
+        self.author_name = name.to_owned();
+        self
+    }
+
+    pub fn bot(mut self, bot: bool) -> Self {
+        self.bot = bot;
+        self
+    }
+
+    pub fn build(&self) -> Message {
+        // serenity's models can't be built directly outside of serenity, so we go
+        // through the same json the gateway sends
+        let message = json!({
+            "id": self.id.to_string(),
+            "channel_id": self.channel_id.to_string(),
+            "guild_id": self.guild_id.map(|g| g.to_string()),
+            "author": {
+                "id": self.author_id.to_string(),
+                "username": self.author_name,
+                "discriminator": "0001",
+                "avatar": null,
+                "bot": self.bot,
+            },
+            "content": self.content,
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        });
+
+        serde_json::from_value(message).expect("Fake message doesn't match serenity's model")
+    }
+}
+
+// a pool that doesn't connect until a connection is asked for, so handlers that never
+// touch the database can be tested without one
+pub fn lazy_pool(database_url: &str) -> MysqlPool {
+    MysqlPool::builder().build_unchecked(ConnectionManager::<MysqlConnection>::new(database_url))
+}
+
+// the share map the bot builds on startup, with no servers or groups. this starts the
+// submission queue's workers so it has to be called inside a tokio runtime.
+pub fn fake_data(pool: MysqlPool) -> TypeMap {
+    let config = BotConfig::default();
+    let mut data = TypeMap::new();
+    data.insert::<LeaderboardQueueContainer>(LeaderboardQueue::new(
+        config.max_concurrent_leaderboard_builds,
+    ));
+    data.insert::<SubmissionQueueContainer>(SubmissionQueue::new(config.submission_workers));
+    data.insert::<ConfigContainer>(config);
+    data.insert::<DBPool>(pool);
+    data.insert::<SubmissionSet>(HashSet::new());
+    data.insert::<ServerContainer>(HashMap::new());
+    data.insert::<GroupContainer>(HashMap::new());
+    data.insert::<SettingsContainer>(HashMap::new());
+    data.insert::<GrantContainer>(HashMap::new());
+    data.insert::<AliasContainer>(HashMap::new());
+    data.insert::<EventBusContainer>(new_event_bus());
+    data.insert::<ThrottleContainer>(SubmissionThrottle::default());
+    data.insert::<StartLimiterContainer>(StartRaceLimiter::default());
+    data.insert::<ApiHealthContainer>(ApiHealth::default());
+
+    data
+}
+
+pub fn add_server(data: &mut TypeMap, server: DiscordServer) {
+    data.get_mut::<ServerContainer>()
+        .expect("No server container in share map")
+        .insert(GuildId::from(server.server_id), server);
+}
+
+pub fn add_group(data: &mut TypeMap, group: ChannelGroup) {
+    // groups are looked up by their submission channel
+    data.get_mut::<SubmissionSet>()
+        .expect("No submission channel set in share map")
+        .insert(group.submission);
+    data.get_mut::<GroupContainer>()
+        .expect("No group container in share map")
+        .insert(group.submission, group);
+}
+
+pub async fn fake_context(data: TypeMap) -> Context {
+    // serenity only hands out a shard messenger for a connected shard, so the shard
+    // connects to a gateway on localhost that accepts it and never says anything
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Couldn't start the fake gateway");
+    let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        if let Ok((stream, _)) = listener.accept().await {
+            let _ = tokio_tungstenite::accept_async(stream).await;
+        }
+    });
+
+    let data = Arc::new(RwLock::new(data));
+    let http = Arc::new(Http::new(""));
+    let cache = Arc::new(Cache::new());
+    let ws_url = Arc::new(Mutex::new(ws_url));
+    let (manager, _) = ShardManager::new(ShardManagerOptions {
+        data: data.clone(),
+        event_handlers: Vec::new(),
+        raw_event_handlers: Vec::new(),
+        framework: Arc::default(),
+        shard_index: 0,
+        shard_init: 0,
+        shard_total: 1,
+        ws_url: ws_url.clone(),
+        cache: cache.clone(),
+        http: http.clone(),
+        intents: GatewayIntents::empty(),
+        presence: None,
+    });
+    let shard_info = ShardInfo {
+        id: ShardId(0),
+        total: 1,
+    };
+    let shard = Shard::new(ws_url, "", shard_info, GatewayIntents::empty(), None)
+        .await
+        .expect("Couldn't connect to the fake gateway");
+    let runner = ShardRunner::new(ShardRunnerOptions {
+        data: data.clone(),
+        event_handlers: Vec::new(),
+        raw_event_handlers: Vec::new(),
+        framework: None,
+        manager,
+        shard,
+        cache: cache.clone(),
+        http: http.clone(),
+    });
+
+    Context {
+        data,
+        shard: ShardMessenger::new(&runner),
+        shard_id: ShardId(0),
+        http,
+        cache,
+    }
+}
+
+// rows as they'd come out of the database, for tests that only care about a few fields.
+// set those with struct update syntax, e.g.
+// Submission { runner_name: "a".to_owned(), ..test_submission() }
+
+pub fn test_group() -> ChannelGroup {
+    ChannelGroup {
+        channel_group_id: vec![0; 16],
+        server_id: 1,
+        group_name: String::from("test"),
+        submission: 1000,
+        leaderboard: 1001,
+        spoiler: 1002,
+        spoiler_role_id: Some(1003),
+        ascii_names: false,
+        max_name_length: None,
+        spoiler_access: SpoilerAccess::Role,
+        verify_under: None,
+        verification_channel: None,
+        show_flair: false,
+        forfeit_words: None,
+        archive_channel: None,
+        embed_color: None,
+        embed_thumbnail: None,
+        embed_banner: None,
+        embed_footer: None,
+        guard_spoiler: false,
+        purge_spoiler: false,
+        max_settings_length: None,
+    }
+}
+
+// a stopped IGT race in test_group from 2026-01-01
+pub fn test_race() -> AsyncRaceData {
+    AsyncRaceData {
+        race_id: 1,
+        channel_group_id: vec![0; 16],
+        race_active: false,
+        race_date: NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+        race_game: GameName::ALTTPR,
+        race_type: RaceType::IGT,
+        race_info: String::from("Open"),
+        race_url: None,
+        race_role_id: None,
+        stop_pending: None,
+        race_deadline: None,
+        race_sort: LeaderboardSort::Time,
+        race_fields: None,
+        race_blind: false,
+        source_message_id: None,
+        race_reminders: true,
+        last_reminder_at: None,
+        race_claim: false,
+        race_live_at: None,
+        live_countdown_sent: false,
+        race_announcement: None,
+        race_preset: None,
+        race_shuffle: false,
+    }
+}
+
+// an RTA race in test_group that hasn't been inserted yet, dated today
+pub fn test_new_race() -> NewAsyncRaceData {
+    NewAsyncRaceData {
+        channel_group_id: vec![0; 16],
+        race_active: true,
+        race_date: Utc::now().date_naive(),
+        race_game: GameName::Other,
+        race_type: RaceType::RTA,
+        race_info: String::from("Test Seed"),
+        race_url: None,
+        race_deadline: None,
+        race_sort: LeaderboardSort::Time,
+        race_fields: None,
+        race_blind: false,
+        source_message_id: None,
+        race_reminders: true,
+        last_reminder_at: None,
+        race_claim: false,
+        race_live_at: None,
+        live_countdown_sent: false,
+        race_announcement: None,
+        race_preset: None,
+        race_shuffle: false,
+    }
+}
+
+// a 1:23:45 finish in test_race, submitted when the race started
+pub fn test_submission() -> Submission {
+    Submission {
+        submission_id: 0,
+        runner_id: 0,
+        race_id: 1,
+        race_game: GameName::ALTTPR,
+        submission_datetime: NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap(),
+        runner_name: String::from("runner"),
+        runner_collection: None,
+        option_number: None,
+        option_text: None,
+        runner_forfeit: false,
+        pending: false,
+        unranked: false,
+        runner_time: Some(RunTime::from_millis(5_025_000)),
+        source_message_id: None,
+        submission_text: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        discord::channel_groups::{get_group, in_submission_channel},
+        games::next_word,
+    };
+
+    #[tokio::test]
+    async fn handlers_see_fake_state() {
+        let mut data = fake_data(lazy_pool("mysql://localhost/unused"));
+        add_group(&mut data, test_group());
+        let ctx = fake_context(data).await;
+
+        let msg = FakeMessage::new("1:23:45 216")
+            .channel(1000)
+            .author(5, "someone")
+            .build();
+        assert_eq!(msg.content, "1:23:45 216");
+        assert_eq!(msg.author.id.get(), 5);
+        assert!(in_submission_channel(&ctx, msg.channel_id).await);
+        assert_eq!(get_group(&ctx, msg.channel_id).await.group_name, "test");
+
+        let elsewhere = FakeMessage::new("hi").channel(1).build();
+        assert!(!in_submission_channel(&ctx, elsewhere.channel_id).await);
+
+        assert_eq!(
+            next_word("igt --blind https://alttpr.com/h/abc"),
+            Some(("igt", "--blind https://alttpr.com/h/abc"))
+        );
+    }
+}
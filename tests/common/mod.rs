@@ -0,0 +1,261 @@
+// shared pieces for tests that run the bot's logic against a mock discord and, for the
+// tests that need one, the mysql fixture in tests/docker-compose.yml
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::Utc;
+use diesel::prelude::*;
+use serenity::{
+    async_trait,
+    model::{
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        permissions::Permissions,
+    },
+};
+
+use murahdahla::{
+    discord::{
+        channel_groups::ChannelGroup,
+        ops::{DiscordOps, PostedMessage},
+        servers::DiscordServer,
+        spoiler_roles::SpoilerAccess,
+    },
+    error::MurahdahlaError,
+    games::{AsyncRaceData, GameName, NewAsyncRaceData},
+    helpers::*,
+    schema::{channels, servers},
+    testing,
+};
+
+pub const TEST_DATABASE_VAR: &str = "MURAHDAHLA_TEST_DATABASE_URL";
+pub const SERVER: u64 = 10;
+pub const SUBMISSION: u64 = 1000;
+pub const LEADERBOARD: u64 = 1001;
+pub const SPOILER: u64 = 1002;
+pub const SPOILER_ROLE: u64 = 2000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    Say(ChannelId, String),
+    Edit(ChannelId, MessageId, String),
+    Delete(ChannelId, MessageId),
+    AddRole(UserId, RoleId),
+    RemoveRole(UserId, RoleId),
+    AllowMember(ChannelId, UserId),
+    RemoveOverwrite(ChannelId, UserId),
+}
+
+// records everything the bot asks discord to do and keeps track of members' roles so
+// role changes can be checked afterwards
+pub struct MockDiscord {
+    next_id: AtomicU64,
+    pub calls: Mutex<Vec<Call>>,
+    pub members: Mutex<HashMap<UserId, Vec<RoleId>>>,
+}
+
+impl MockDiscord {
+    pub fn new() -> Self {
+        // message ids are primary keys so tests sharing a database need their own range
+        let offset = u64::from(u32::from_le_bytes(new_uuid()[..4].try_into().unwrap()));
+        MockDiscord {
+            next_id: AtomicU64::new((1 << 48) + (offset << 12)),
+            calls: Mutex::new(Vec::new()),
+            members: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_member(self, user_id: u64, roles: &[u64]) -> Self {
+        self.members.lock().unwrap().insert(
+            UserId::from(user_id),
+            roles.iter().map(|r| RoleId::from(*r)).collect(),
+        );
+        self
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn roles_of(&self, user_id: u64) -> Vec<RoleId> {
+        self.members
+            .lock()
+            .unwrap()
+            .get(&UserId::from(user_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn record(&self, call: Call) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+#[async_trait]
+impl DiscordOps for MockDiscord {
+    async fn say(
+        &self,
+        channel_id: ChannelId,
+        content: &str,
+    ) -> Result<PostedMessage, MurahdahlaError> {
+        self.record(Call::Say(channel_id, content.to_owned()));
+        let message_id = MessageId::from(self.next_id.fetch_add(1, Ordering::SeqCst));
+
+        Ok(PostedMessage {
+            message_id,
+            channel_id,
+            timestamp: Utc::now().naive_utc(),
+        })
+    }
+
+    async fn edit(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: &str,
+    ) -> Result<(), MurahdahlaError> {
+        self.record(Call::Edit(channel_id, message_id, content.to_owned()));
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), MurahdahlaError> {
+        self.record(Call::Delete(channel_id, message_id));
+        Ok(())
+    }
+
+    async fn member_roles(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Vec<RoleId>, MurahdahlaError> {
+        Ok(self.roles_of(user_id.get()))
+    }
+
+    async fn add_role(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<(), MurahdahlaError> {
+        self.record(Call::AddRole(user_id, role_id));
+        let mut members = self.members.lock().unwrap();
+        let roles = members.entry(user_id).or_default();
+        if !roles.contains(&role_id) {
+            roles.push(role_id);
+        }
+        Ok(())
+    }
+
+    async fn remove_role(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<(), MurahdahlaError> {
+        self.record(Call::RemoveRole(user_id, role_id));
+        if let Some(roles) = self.members.lock().unwrap().get_mut(&user_id) {
+            roles.retain(|r| *r != role_id);
+        }
+        Ok(())
+    }
+
+    async fn allow_member(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+        _allow: Permissions,
+    ) -> Result<(), MurahdahlaError> {
+        self.record(Call::AllowMember(channel_id, user_id));
+        Ok(())
+    }
+
+    async fn remove_member_overwrite(
+        &self,
+        channel_id: ChannelId,
+        user_id: UserId,
+    ) -> Result<(), MurahdahlaError> {
+        self.record(Call::RemoveOverwrite(channel_id, user_id));
+        Ok(())
+    }
+}
+
+// the rows from murahdahla::testing with this file's ids and a fresh group id so tests
+// can share a database
+
+pub fn test_group(spoiler_access: SpoilerAccess) -> ChannelGroup {
+    let spoiler_role_id = match spoiler_access {
+        SpoilerAccess::Role => Some(SPOILER_ROLE),
+        _ => None,
+    };
+    ChannelGroup {
+        channel_group_id: new_uuid(),
+        server_id: SERVER,
+        submission: SUBMISSION,
+        leaderboard: LEADERBOARD,
+        spoiler: SPOILER,
+        spoiler_role_id,
+        spoiler_access,
+        ..testing::test_group()
+    }
+}
+
+pub fn test_race(group: &ChannelGroup, game: GameName) -> AsyncRaceData {
+    AsyncRaceData {
+        channel_group_id: group.channel_group_id.clone(),
+        race_active: true,
+        race_game: game,
+        race_info: String::from("Test Seed"),
+        ..testing::test_race()
+    }
+}
+
+pub fn new_test_race(group: &ChannelGroup) -> NewAsyncRaceData {
+    NewAsyncRaceData {
+        channel_group_id: group.channel_group_id.clone(),
+        ..testing::test_new_race()
+    }
+}
+
+pub fn test_pool() -> MysqlPool {
+    let url = env::var(TEST_DATABASE_VAR).unwrap_or_else(|_| {
+        panic!(
+            "{} needs to point at the database from tests/docker-compose.yml",
+            TEST_DATABASE_VAR
+        )
+    });
+    get_pool(&url).expect("Error connecting to test database")
+}
+
+pub fn insert_group(pool: &MysqlPool, spoiler_access: SpoilerAccess) -> ChannelGroup {
+    // every test gets its own server and group so they can share a database
+    let mut group = test_group(spoiler_access);
+    group.server_id = u64::from_le_bytes(group.channel_group_id[..8].try_into().unwrap());
+    let server = DiscordServer {
+        server_id: group.server_id,
+        owner_id: 1,
+        admin_role_id: None,
+        mod_role_id: None,
+    };
+    let conn = pool.get().unwrap();
+    diesel::insert_into(servers::table)
+        .values(&server)
+        .execute(&conn)
+        .unwrap();
+    diesel::insert_into(channels::table)
+        .values(&group)
+        .execute(&conn)
+        .unwrap();
+
+    group
+}
@@ -0,0 +1,155 @@
+// runs the role management commands against a server the same way the command
+// handlers do, minus discord. the tests marked #[ignore] also write to the mysql
+// fixture; see tests/race_flow.rs for how to run them.
+
+mod common;
+
+use diesel::prelude::*;
+use serenity::model::id::RoleId;
+
+use common::*;
+use murahdahla::{
+    discord::{
+        messages::build_roles_message,
+        servers::{write_server_role, DiscordServer, Permission, RoleCommand},
+        spoiler_roles::SpoilerAccess,
+    },
+    schema::servers,
+};
+
+const OWNER: u64 = 1;
+const MEMBER: u64 = 2;
+const ADMIN_ROLE: u64 = 100;
+const MOD_ROLE: u64 = 200;
+const NEW_ROLE: u64 = 300;
+
+fn test_server() -> DiscordServer {
+    DiscordServer {
+        server_id: 10,
+        owner_id: OWNER,
+        admin_role_id: Some(ADMIN_ROLE),
+        mod_role_id: Some(MOD_ROLE),
+    }
+}
+
+fn permissions_with_role(server: &DiscordServer, role: u64) -> Permission {
+    server.determine_user_permissions(MEMBER, &[RoleId::from(role)])
+}
+
+#[test]
+fn setmodrole_only_changes_mod_role() {
+    let mut server = test_server();
+    server.apply_role_command(RoleCommand::SetMod, Some(NEW_ROLE));
+
+    assert_eq!(server.mod_role_id, Some(NEW_ROLE));
+    assert_eq!(server.admin_role_id, Some(ADMIN_ROLE));
+    assert_eq!(permissions_with_role(&server, NEW_ROLE), Permission::Mod);
+    assert_eq!(
+        permissions_with_role(&server, ADMIN_ROLE),
+        Permission::Admin
+    );
+    assert_eq!(permissions_with_role(&server, MOD_ROLE), Permission::None);
+}
+
+#[test]
+fn setadminrole_only_changes_admin_role() {
+    let mut server = test_server();
+    server.apply_role_command(RoleCommand::SetAdmin, Some(NEW_ROLE));
+
+    assert_eq!(server.admin_role_id, Some(NEW_ROLE));
+    assert_eq!(server.mod_role_id, Some(MOD_ROLE));
+    assert_eq!(permissions_with_role(&server, NEW_ROLE), Permission::Admin);
+    assert_eq!(permissions_with_role(&server, MOD_ROLE), Permission::Mod);
+    assert_eq!(permissions_with_role(&server, ADMIN_ROLE), Permission::None);
+}
+
+#[test]
+fn removemodrole_only_clears_mod_role() {
+    let mut server = test_server();
+    // the role is ignored when removing
+    server.apply_role_command(RoleCommand::RemoveMod, Some(NEW_ROLE));
+
+    assert_eq!(server.mod_role_id, None);
+    assert_eq!(server.admin_role_id, Some(ADMIN_ROLE));
+    assert_eq!(permissions_with_role(&server, MOD_ROLE), Permission::None);
+    assert_eq!(
+        permissions_with_role(&server, ADMIN_ROLE),
+        Permission::Admin
+    );
+}
+
+#[test]
+fn removeadminrole_only_clears_admin_role() {
+    let mut server = test_server();
+    server.apply_role_command(RoleCommand::RemoveAdmin, None);
+
+    assert_eq!(server.admin_role_id, None);
+    assert_eq!(server.mod_role_id, Some(MOD_ROLE));
+    assert_eq!(permissions_with_role(&server, ADMIN_ROLE), Permission::None);
+    assert_eq!(permissions_with_role(&server, MOD_ROLE), Permission::Mod);
+}
+
+#[test]
+fn owner_keeps_admin_without_roles() {
+    let mut server = test_server();
+    server.apply_role_command(RoleCommand::RemoveAdmin, None);
+    server.apply_role_command(RoleCommand::RemoveMod, None);
+
+    assert_eq!(
+        server.determine_user_permissions(OWNER, &[]),
+        Permission::Admin
+    );
+    assert_eq!(permissions_with_role(&server, ADMIN_ROLE), Permission::None);
+}
+
+#[test]
+fn roles_message_lists_both_roles() {
+    let text = build_roles_message(Some(String::from("Admins")), None);
+
+    assert!(text.contains("admin: Admins\n"));
+    assert!(text.contains("mod: (none)\n"));
+}
+
+fn stored_roles(conn: &MysqlConnection, server_id: u64) -> (Option<u64>, Option<u64>) {
+    servers::table
+        .find(server_id)
+        .select((servers::admin_role_id, servers::mod_role_id))
+        .first(conn)
+        .unwrap()
+}
+
+#[test]
+#[ignore]
+fn mod_role_commands_write_mod_role_column() {
+    let pool = test_pool();
+    let server_id = insert_group(&pool, SpoilerAccess::Role).server_id;
+    let conn = pool.get().unwrap();
+    write_server_role(&conn, server_id, RoleCommand::SetAdmin, Some(ADMIN_ROLE)).unwrap();
+
+    write_server_role(&conn, server_id, RoleCommand::SetMod, Some(MOD_ROLE)).unwrap();
+    assert_eq!(
+        stored_roles(&conn, server_id),
+        (Some(ADMIN_ROLE), Some(MOD_ROLE))
+    );
+
+    write_server_role(&conn, server_id, RoleCommand::RemoveMod, None).unwrap();
+    assert_eq!(stored_roles(&conn, server_id), (Some(ADMIN_ROLE), None));
+}
+
+#[test]
+#[ignore]
+fn admin_role_commands_write_admin_role_column() {
+    let pool = test_pool();
+    let server_id = insert_group(&pool, SpoilerAccess::Role).server_id;
+    let conn = pool.get().unwrap();
+    write_server_role(&conn, server_id, RoleCommand::SetMod, Some(MOD_ROLE)).unwrap();
+
+    write_server_role(&conn, server_id, RoleCommand::SetAdmin, Some(NEW_ROLE)).unwrap();
+    assert_eq!(
+        stored_roles(&conn, server_id),
+        (Some(NEW_ROLE), Some(MOD_ROLE))
+    );
+
+    write_server_role(&conn, server_id, RoleCommand::RemoveAdmin, None).unwrap();
+    assert_eq!(stored_roles(&conn, server_id), (None, Some(MOD_ROLE)));
+}
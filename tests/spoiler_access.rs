@@ -0,0 +1,119 @@
+// grants and takes away spoiler access through a mock discord for each of the group
+// access modes
+
+mod common;
+
+use serenity::model::id::{ChannelId, RoleId, UserId};
+
+use common::*;
+use murahdahla::{
+    discord::spoiler_roles::{
+        grant_spoiler_access, revoke_spoiler_access, SpoilerAccess, SpoilerRole,
+    },
+    games::GameName,
+};
+
+const RUNNER: u64 = 5;
+const DIVISION_ROLE: u64 = 3000;
+const DIVISION_SPOILER_ROLE: u64 = 3001;
+const OTHER_ROLE: u64 = 4000;
+
+fn division_rule(group_id: &[u8]) -> SpoilerRole {
+    SpoilerRole {
+        channel_group_id: group_id.to_vec(),
+        role_id: DIVISION_SPOILER_ROLE,
+        race_game: None,
+        division_role_id: Some(DIVISION_ROLE),
+    }
+}
+
+#[tokio::test]
+async fn role_access_gives_group_role() {
+    let discord = MockDiscord::new().with_member(RUNNER, &[]);
+    let group = test_group(SpoilerAccess::Role);
+    let race = test_race(&group, GameName::ALTTPR);
+
+    grant_spoiler_access(&discord, &group, &[], &race, UserId::from(RUNNER))
+        .await
+        .unwrap();
+
+    assert_eq!(discord.roles_of(RUNNER), vec![RoleId::from(SPOILER_ROLE)]);
+}
+
+#[tokio::test]
+async fn role_access_uses_matching_division() {
+    let discord = MockDiscord::new().with_member(RUNNER, &[DIVISION_ROLE]);
+    let group = test_group(SpoilerAccess::Role);
+    let race = test_race(&group, GameName::ALTTPR);
+    let rules = vec![division_rule(&group.channel_group_id)];
+
+    grant_spoiler_access(&discord, &group, &rules, &race, UserId::from(RUNNER))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        discord.roles_of(RUNNER),
+        vec![
+            RoleId::from(DIVISION_ROLE),
+            RoleId::from(DIVISION_SPOILER_ROLE)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn role_revoke_leaves_other_roles() {
+    let discord =
+        MockDiscord::new().with_member(RUNNER, &[OTHER_ROLE, SPOILER_ROLE, DIVISION_SPOILER_ROLE]);
+    let group = test_group(SpoilerAccess::Role);
+    let race = test_race(&group, GameName::ALTTPR);
+    let rules = vec![division_rule(&group.channel_group_id)];
+
+    revoke_spoiler_access(&discord, &group, &rules, &race, UserId::from(RUNNER))
+        .await
+        .unwrap();
+
+    assert_eq!(discord.roles_of(RUNNER), vec![RoleId::from(OTHER_ROLE)]);
+}
+
+#[tokio::test]
+async fn overwrite_access_covers_both_channels() {
+    let discord = MockDiscord::new();
+    let group = test_group(SpoilerAccess::Overwrite);
+    let race = test_race(&group, GameName::ALTTPR);
+    let runner = UserId::from(RUNNER);
+
+    grant_spoiler_access(&discord, &group, &[], &race, runner)
+        .await
+        .unwrap();
+    revoke_spoiler_access(&discord, &group, &[], &race, runner)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        discord.calls(),
+        vec![
+            Call::AllowMember(ChannelId::from(SPOILER), runner),
+            Call::AllowMember(ChannelId::from(LEADERBOARD), runner),
+            Call::RemoveOverwrite(ChannelId::from(SPOILER), runner),
+            Call::RemoveOverwrite(ChannelId::from(LEADERBOARD), runner),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn race_role_access_needs_race_role() {
+    let discord = MockDiscord::new();
+    let group = test_group(SpoilerAccess::RaceRole);
+    let mut race = test_race(&group, GameName::ALTTPR);
+    let runner = UserId::from(RUNNER);
+
+    assert!(grant_spoiler_access(&discord, &group, &[], &race, runner)
+        .await
+        .is_err());
+    race.race_role_id = Some(OTHER_ROLE);
+    grant_spoiler_access(&discord, &group, &[], &race, runner)
+        .await
+        .unwrap();
+
+    assert_eq!(discord.roles_of(RUNNER), vec![RoleId::from(OTHER_ROLE)]);
+}